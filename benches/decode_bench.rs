@@ -1,11 +1,31 @@
 use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
 use std::hint::black_box;
-use yenc;
 
 fn create_test_data(size: usize) -> Vec<u8> {
     (0..size).map(|i| (i % 256) as u8).collect()
 }
 
+/// Bytes that encode to a critical character (NUL, LF, CR, '=')
+const ESCAPED_BYTES: [u8; 4] = [0xD6, 0xE0, 0xE3, 0x13];
+
+/// Create data where roughly `percent`% of the bytes need escaping
+fn create_escape_density_data(size: usize, percent: u32) -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+    (0..size)
+        .map(|i| {
+            // xorshift keeps the pattern irregular so branch prediction can't learn it
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            if state % 100 < percent {
+                ESCAPED_BYTES[i % ESCAPED_BYTES.len()]
+            } else {
+                0x41 + (state % 26) as u8
+            }
+        })
+        .collect()
+}
+
 fn bench_decode_various_sizes(c: &mut Criterion) {
     let mut group = c.benchmark_group("decode");
 
@@ -55,10 +75,110 @@ fn bench_decode_best_case(c: &mut Criterion) {
     });
 }
 
+fn bench_decode_crc_overhead(c: &mut Criterion) {
+    // CRC validation should cost little on top of decoding
+    let size = 1_024_000;
+    let original = create_test_data(size);
+    let mut encoded = Vec::new();
+    yenc::encode(&original[..], &mut encoded, "crc.bin").unwrap();
+
+    let mut group = c.benchmark_group("decode_crc");
+    group.throughput(Throughput::Bytes(size as u64));
+
+    group.bench_function("with_crc", |b| {
+        b.iter(|| {
+            let mut output = Vec::with_capacity(size);
+            yenc::decode(black_box(&encoded[..]), &mut output).unwrap();
+        });
+    });
+
+    group.bench_function("without_crc", |b| {
+        b.iter(|| {
+            let mut output = Vec::with_capacity(size);
+            yenc::Decoder::new()
+                .no_crc_check()
+                .decode(black_box(&encoded[..]), &mut output)
+                .unwrap();
+        });
+    });
+
+    group.finish();
+}
+
+fn bench_decode_escape_density(c: &mut Criterion) {
+    // Sweep the escape slow path between the best and worst cases
+    let size = 102_400;
+    let mut group = c.benchmark_group("decode_escape_density");
+    group.throughput(Throughput::Bytes(size as u64));
+
+    for line_length in [128, 997] {
+        for percent in [0, 2, 10, 25, 50, 100] {
+            let original = create_escape_density_data(size, percent);
+            let mut encoded = Vec::new();
+            yenc::Encoder::new()
+                .line_length(line_length)
+                .encode(&original[..], &mut encoded, "density.bin")
+                .unwrap();
+
+            // The scalar code also decodes the tails of every vector kernel
+            for &backend in yenc::Backend::available() {
+                let decoder = yenc::Decoder::new().backend(backend);
+                let id =
+                    BenchmarkId::new(format!("{}-line{}", backend.name(), line_length), percent);
+                group.bench_with_input(id, &encoded, |b, encoded| {
+                    b.iter(|| {
+                        let mut output = Vec::with_capacity(size);
+                        decoder
+                            .decode(black_box(&encoded[..]), &mut output)
+                            .unwrap();
+                    });
+                });
+            }
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_decode_tiny(c: &mut Criterion) {
+    // Short lines, where the vector setup is hardest to pay back; used to
+    // tune the size below which the scalar code is picked automatically
+    let size = 10_240;
+    let mut group = c.benchmark_group("decode_tiny");
+    group.throughput(Throughput::Bytes(size as u64));
+
+    for line_length in [16, 24, 32, 48, 64] {
+        let original = create_escape_density_data(size, 2);
+        let mut encoded = Vec::new();
+        yenc::Encoder::new()
+            .line_length(line_length)
+            .encode(&original[..], &mut encoded, "tiny.bin")
+            .unwrap();
+
+        for backend in [yenc::Backend::Scalar, yenc::Backend::best()] {
+            let decoder = yenc::Decoder::new().backend(backend);
+            let id = BenchmarkId::new(backend.name(), line_length);
+            group.bench_with_input(id, &encoded, |b, encoded| {
+                b.iter(|| {
+                    let mut output = Vec::with_capacity(size);
+                    decoder
+                        .decode(black_box(&encoded[..]), &mut output)
+                        .unwrap();
+                });
+            });
+        }
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_decode_various_sizes,
     bench_decode_worst_case,
-    bench_decode_best_case
+    bench_decode_best_case,
+    bench_decode_crc_overhead,
+    bench_decode_escape_density,
+    bench_decode_tiny
 );
 criterion_main!(benches);
@@ -1,11 +1,31 @@
 use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
 use std::hint::black_box;
-use yenc;
 
 fn create_test_data(size: usize) -> Vec<u8> {
     (0..size).map(|i| (i % 256) as u8).collect()
 }
 
+/// Bytes that encode to a critical character (NUL, LF, CR, '=')
+const ESCAPED_BYTES: [u8; 4] = [0xD6, 0xE0, 0xE3, 0x13];
+
+/// Create data where roughly `percent`% of the bytes need escaping
+fn create_escape_density_data(size: usize, percent: u32) -> Vec<u8> {
+    let mut state = 0x2545_f491_u32;
+    (0..size)
+        .map(|i| {
+            // xorshift keeps the pattern irregular so branch prediction can't learn it
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            if state % 100 < percent {
+                ESCAPED_BYTES[i % ESCAPED_BYTES.len()]
+            } else {
+                0x41 + (state % 26) as u8
+            }
+        })
+        .collect()
+}
+
 fn bench_encode_various_sizes(c: &mut Criterion) {
     let mut group = c.benchmark_group("encode");
 
@@ -48,10 +68,69 @@ fn bench_encode_best_case(c: &mut Criterion) {
     });
 }
 
+fn bench_encode_escape_density(c: &mut Criterion) {
+    // Sweep the escape slow path between the best and worst cases
+    let size = 102_400;
+    let mut group = c.benchmark_group("encode_escape_density");
+    group.throughput(Throughput::Bytes(size as u64));
+
+    for &backend in yenc::Backend::available() {
+        for line_length in [128, 997] {
+            for percent in [0, 2, 10, 25, 50, 100] {
+                let data = create_escape_density_data(size, percent);
+                let encoder = yenc::Encoder::new()
+                    .line_length(line_length)
+                    .backend(backend);
+
+                let id =
+                    BenchmarkId::new(format!("{}-line{}", backend.name(), line_length), percent);
+                group.bench_with_input(id, &data, |b, data| {
+                    b.iter(|| {
+                        let mut output = Vec::new();
+                        encoder
+                            .encode(black_box(&data[..]), &mut output, "density.bin")
+                            .unwrap();
+                    });
+                });
+            }
+        }
+    }
+
+    group.finish();
+}
+
+fn bench_encode_tiny(c: &mut Criterion) {
+    // Tiny articles, where the vector setup is hardest to pay back; used to
+    // tune the size below which the scalar code is picked automatically
+    let mut group = c.benchmark_group("encode_tiny");
+
+    for size in [16, 24, 32, 48, 64, 128] {
+        let data = create_escape_density_data(size, 2);
+        group.throughput(Throughput::Bytes(size as u64));
+
+        for backend in [yenc::Backend::Scalar, yenc::Backend::best()] {
+            let encoder = yenc::Encoder::new().backend(backend);
+            let id = BenchmarkId::new(backend.name(), size);
+            group.bench_with_input(id, &data, |b, data| {
+                b.iter(|| {
+                    let mut output = Vec::with_capacity(256);
+                    encoder
+                        .encode(black_box(&data[..]), &mut output, "tiny.bin")
+                        .unwrap();
+                });
+            });
+        }
+    }
+
+    group.finish();
+}
+
 criterion_group!(
     benches,
     bench_encode_various_sizes,
     bench_encode_worst_case,
-    bench_encode_best_case
+    bench_encode_best_case,
+    bench_encode_escape_density,
+    bench_encode_tiny
 );
 criterion_main!(benches);
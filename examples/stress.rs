@@ -0,0 +1,303 @@
+//! Generate a corpus of valid and deliberately broken yEnc articles
+//!
+//! Downstream clients can run their decoders over the corpus to exercise
+//! their error paths against realistic garbage:
+//!
+//! ```text
+//! cargo run --example stress -- --output DIR
+//! ```
+//!
+//! Every article is written to `DIR/<name>.yenc`, and `DIR/MANIFEST` lists
+//! each file with whether a conforming decoder should accept it.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+use yenc::{Encoder, MultiPartInfo};
+
+/// One article of the corpus
+struct Case {
+    name: &'static str,
+    valid: bool,
+    description: &'static str,
+    article: Vec<u8>,
+}
+
+/// Deterministic pseudo-random payload
+fn payload(len: usize, seed: u32) -> Vec<u8> {
+    let mut state = seed.max(1);
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+fn encode(data: &[u8], name: &str) -> Vec<u8> {
+    let mut article = Vec::new();
+    yenc::encode(data, &mut article, name).expect("encoding to memory cannot fail");
+    article
+}
+
+fn encode_part(data: &[u8], name: &str, info: &MultiPartInfo) -> Vec<u8> {
+    let mut article = Vec::new();
+    yenc::encode_part(
+        &data[info.begin as usize - 1..info.end as usize],
+        &mut article,
+        name,
+        info,
+    )
+    .expect("encoding to memory cannot fail");
+    article
+}
+
+/// Replace the first occurrence of `from` with `to`
+fn replace(article: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+    let pos = article
+        .windows(from.len())
+        .position(|w| w == from)
+        .expect("pattern present in article");
+    [&article[..pos], to, &article[pos + from.len()..]].concat()
+}
+
+/// Offset of the `=yend` line
+fn trailer_offset(article: &[u8]) -> usize {
+    article
+        .windows(6)
+        .rposition(|w| w == b"=yend ")
+        .expect("article has a trailer")
+}
+
+fn corpus() -> Vec<Case> {
+    let data = payload(10_000, 0x9e37_79b9);
+    let all_bytes: Vec<u8> = (0..=255).cycle().take(1024).collect();
+    let single = encode(&data, "payload.bin");
+
+    let part1_info = MultiPartInfo::new(1, 2, 1, 5000, data.len() as u64);
+    let part2_info = MultiPartInfo::new(2, 2, 5001, data.len() as u64, data.len() as u64);
+    let part1 = encode_part(&data, "payload.bin", &part1_info);
+    let part2 = encode_part(&data, "payload.bin", &part2_info);
+
+    let mut long_lines = Vec::new();
+    Encoder::new()
+        .line_length(997)
+        .encode(&data[..], &mut long_lines, "payload.bin")
+        .expect("encoding to memory cannot fail");
+
+    let body_start = single.iter().position(|&b| b == b'\n').unwrap() + 1;
+    let trailer = trailer_offset(&single);
+
+    vec![
+        Case {
+            name: "valid_single",
+            valid: true,
+            description: "single-part article",
+            article: single.clone(),
+        },
+        Case {
+            name: "valid_part1",
+            valid: true,
+            description: "first part of a two-part post",
+            article: part1.clone(),
+        },
+        Case {
+            name: "valid_part2",
+            valid: true,
+            description: "second part of a two-part post",
+            article: part2.clone(),
+        },
+        Case {
+            name: "valid_all_bytes",
+            valid: true,
+            description: "every byte value, exercising all escapes",
+            article: encode(&all_bytes, "all_bytes.bin"),
+        },
+        Case {
+            name: "valid_empty",
+            valid: true,
+            description: "zero-byte file",
+            article: encode(&[], "empty.bin"),
+        },
+        Case {
+            name: "valid_crlf",
+            valid: true,
+            description: "CRLF line endings",
+            article: replace_all_lf(&single),
+        },
+        Case {
+            name: "bad_crc",
+            valid: false,
+            description: "crc32 does not match the data",
+            article: tamper_crc(&single, b" crc32="),
+        },
+        Case {
+            name: "bad_pcrc",
+            valid: false,
+            description: "pcrc32 does not match the part data",
+            article: tamper_crc(&part1, b" pcrc32="),
+        },
+        Case {
+            name: "truncated_body",
+            valid: false,
+            description: "article cut off in the middle of the data",
+            article: single[..body_start + (trailer - body_start) / 2].to_vec(),
+        },
+        Case {
+            name: "missing_trailer",
+            valid: false,
+            description: "all data present but no =yend line",
+            article: single[..trailer].to_vec(),
+        },
+        Case {
+            name: "missing_header",
+            valid: false,
+            description: "no =ybegin line",
+            article: single[body_start..].to_vec(),
+        },
+        Case {
+            name: "missing_part_line",
+            valid: false,
+            description: "multi-part header without =ypart",
+            article: remove_line(&part2, b"=ypart "),
+        },
+        Case {
+            name: "size_mismatch",
+            valid: false,
+            description: "trailer size disagrees with the part range",
+            article: replace(&part1, b"=yend size=5000", b"=yend size=4999"),
+        },
+        Case {
+            name: "overlong_lines",
+            valid: false,
+            description: "header announces line=128 but lines are 997 long",
+            article: replace(&long_lines, b"line=997", b"line=128"),
+        },
+        Case {
+            name: "dangling_escape",
+            valid: false,
+            description: "data ends with a lone escape character",
+            article: [&single[..trailer], b"=\n", &single[trailer..]].concat(),
+        },
+        Case {
+            name: "glued_trailer",
+            valid: false,
+            description: "=yend joined to the last data line",
+            article: [&single[..trailer - 1], &single[trailer..]].concat(),
+        },
+        Case {
+            name: "name_spaces",
+            valid: true,
+            description: "file name containing spaces",
+            article: encode(&data[..100], "my holiday photo (1).jpg"),
+        },
+        Case {
+            name: "name_unicode",
+            valid: true,
+            description: "non-ASCII file name",
+            article: encode(&data[..100], "résumé 日本語.txt"),
+        },
+        Case {
+            name: "name_traversal",
+            valid: true,
+            description: "file name attempting path traversal",
+            article: encode(&data[..100], "../../../etc/passwd"),
+        },
+        Case {
+            name: "name_long",
+            valid: true,
+            description: "file name of 900 characters",
+            article: encode(&data[..100], &"x".repeat(900)),
+        },
+        Case {
+            name: "name_empty",
+            valid: false,
+            description: "empty file name",
+            article: encode(&data[..100], ""),
+        },
+    ]
+}
+
+fn replace_all_lf(article: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(article.len() + article.len() / 64);
+    for &byte in article {
+        if byte == b'\n' {
+            converted.push(b'\r');
+        }
+        converted.push(byte);
+    }
+    converted
+}
+
+/// Flip the bits of the CRC following `key` in the trailer
+fn tamper_crc(article: &[u8], key: &[u8]) -> Vec<u8> {
+    let pos = article
+        .windows(key.len())
+        .rposition(|w| w == key)
+        .expect("article has the CRC field")
+        + key.len();
+    let mut tampered = article.to_vec();
+    for digit in &mut tampered[pos..pos + 8] {
+        *digit = if *digit == b'0' { b'f' } else { b'0' };
+    }
+    tampered
+}
+
+fn remove_line(article: &[u8], prefix: &[u8]) -> Vec<u8> {
+    let start = article
+        .windows(prefix.len())
+        .position(|w| w == prefix)
+        .expect("line present in article");
+    let end = start + article[start..].iter().position(|&b| b == b'\n').unwrap() + 1;
+    [&article[..start], &article[end..]].concat()
+}
+
+fn usage() -> ! {
+    eprintln!("usage: stress --output DIR");
+    process::exit(2);
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let output = match (args.next().as_deref(), args.next(), args.next()) {
+        (Some("--output" | "-o"), Some(dir), None) => PathBuf::from(dir),
+        _ => usage(),
+    };
+
+    let result = fs::create_dir_all(&output).and_then(|()| {
+        let mut manifest = String::new();
+        for case in corpus() {
+            fs::write(output.join(format!("{}.yenc", case.name)), &case.article)?;
+            manifest.push_str(&format!(
+                "{}.yenc\t{}\t{}\n",
+                case.name,
+                if case.valid { "valid" } else { "invalid" },
+                case.description
+            ));
+        }
+        fs::write(output.join("MANIFEST"), manifest)
+    });
+
+    if let Err(err) = result {
+        eprintln!("stress: {}: {}", output.display(), err);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_manifest_matches_strict_decoder() {
+        for case in corpus() {
+            let result = yenc::Decoder::new()
+                .strict()
+                .decode(&case.article[..], &mut Vec::new());
+            assert_eq!(result.is_ok(), case.valid, "{}: {:?}", case.name, result);
+        }
+    }
+}
@@ -0,0 +1,1275 @@
+//! Assembly of multi-part files, in memory or in an output file
+
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::crc32;
+use crate::decode::{DecodeOutcome, Decoder};
+use crate::error::{Result, YencError};
+use crate::header::{YencHeader, YencPart};
+use crate::sink::SinkWindowWriter;
+
+/// Writer passing decoded bytes on to a window of a seekable output
+///
+/// The output has already been positioned at the start of the window.
+struct SeekWindowWriter<'a, W> {
+    inner: &'a mut W,
+    len: u64,
+    written: u64,
+    overflow: bool,
+}
+
+impl<W: Write> Write for SeekWindowWriter<'_, W> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = (data.len() as u64).min(self.len - self.written) as usize;
+        self.inner.write_all(&data[..n])?;
+        self.written += n as u64;
+        self.overflow |= n < data.len();
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// 0-based byte range of a file of `full_size` bytes that an article covers
+///
+/// Single-part articles cover the whole file.
+pub(crate) fn part_range(
+    header: &YencHeader,
+    part: Option<&YencPart>,
+    full_size: u64,
+) -> Result<Range<u64>> {
+    if header.size != full_size {
+        return Err(YencError::InvalidData(format!(
+            "File size mismatch: header says {}, assembler holds {}",
+            header.size, full_size
+        )));
+    }
+    match part {
+        Some(part) => {
+            if part.begin == 0 || part.begin > part.end.saturating_add(1) || part.end > full_size {
+                return Err(YencError::InvalidData(format!(
+                    "Part range begin={} end={} is outside a {} byte file",
+                    part.begin, part.end, full_size
+                )));
+            }
+            Ok(part.begin - 1..part.end)
+        }
+        None => Ok(0..full_size),
+    }
+}
+
+/// Check that articles agree on the number of parts
+///
+/// `numbers` holds the `part=` and `total=` of every article with a
+/// `total=`.
+fn check_totals(numbers: &[(Option<usize>, usize)]) -> Result<()> {
+    let Some(&(_, first)) = numbers.first() else {
+        return Ok(());
+    };
+    for &(part, total) in numbers {
+        if total != first {
+            return Err(YencError::InvalidData(format!(
+                "Parts disagree on the number of parts: total={} and total={}",
+                first, total
+            )));
+        }
+        if let Some(part) = part.filter(|&part| part > total) {
+            return Err(YencError::InvalidData(format!(
+                "Part {} of a post of {} parts",
+                part, total
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Check the CRC32 of the whole file against the `crc32=` of a part
+///
+/// `parts` must exactly tile the file, as `validate_coverage` checks; their
+/// CRC32s are combined in file order. Nothing is checked when the expected
+/// CRC32 or that of any part is unknown.
+fn check_full_crc(parts: &[(YencPart, Option<u32>)], expected: Option<u32>) -> Result<()> {
+    let Some(expected) = expected else {
+        return Ok(());
+    };
+    let mut sorted: Vec<_> = parts
+        .iter()
+        .filter(|(part, _)| part.begin <= part.end)
+        .collect();
+    sorted.sort_by_key(|(part, _)| part.begin);
+    let crcs = sorted
+        .iter()
+        .map(|(part, crc)| crc.map(|crc| (crc, part.size())))
+        .collect::<Option<Vec<_>>>();
+    match crcs.map(crc32::combine_parts) {
+        Some(actual) if actual != expected => Err(YencError::CrcMismatch { expected, actual }),
+        _ => Ok(()),
+    }
+}
+
+/// 0-based byte range taken by `len` bytes at a 1-based `begin` offset
+fn data_range(begin: u64, len: usize, full_size: u64) -> Result<Range<u64>> {
+    let len = len as u64;
+    let start = begin
+        .checked_sub(1)
+        .filter(|&start| start.checked_add(len).is_some_and(|end| end <= full_size));
+    match start {
+        Some(start) => Ok(start..start + len),
+        None => Err(YencError::InvalidData(format!(
+            "{} bytes at begin={} do not fit in a {} byte file",
+            len, begin, full_size
+        ))),
+    }
+}
+
+/// What decoding the articles of a multi-part file produced
+///
+/// Returned by `decode_parts`, `decode_part_paths` and `decode_part_files`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeSummary {
+    /// Header of the first article
+    pub header: YencHeader,
+    /// Outcome of every article, in the order given
+    pub parts: Vec<DecodeOutcome>,
+    /// Bytes written to the output file
+    pub bytes_written: u64,
+}
+
+/// Check that `parts` exactly tile bytes `1..=size` of a file
+///
+/// Parts may be given in any order. Empty parts (`end = begin - 1`) are
+/// accepted anywhere inside the file, since they cover nothing.
+///
+/// # Errors
+/// Returns `YencError::InvalidData` describing the first gap, overlap or
+/// out-of-range part found.
+///
+/// # Example
+/// ```
+/// use yenc::{YencPart, validate_coverage};
+///
+/// let parts = [YencPart { begin: 6, end: 10 }, YencPart { begin: 1, end: 5 }];
+/// assert!(validate_coverage(10, &parts).is_ok());
+/// assert!(validate_coverage(12, &parts).is_err());
+/// ```
+pub fn validate_coverage(size: u64, parts: &[YencPart]) -> Result<()> {
+    let mut sorted: Vec<&YencPart> = parts.iter().collect();
+    sorted.sort_by_key(|part| (part.begin, part.end));
+
+    let mut next = 1;
+    for part in sorted {
+        if part.begin == 0 || part.begin > part.end.saturating_add(1) || part.end > size {
+            return Err(YencError::InvalidData(format!(
+                "Part range begin={} end={} is outside a {} byte file",
+                part.begin, part.end, size
+            )));
+        }
+        if part.begin > part.end {
+            continue;
+        }
+        if part.begin > next {
+            return Err(YencError::InvalidData(format!(
+                "Gap in coverage: bytes {} to {} are missing",
+                next,
+                part.begin - 1
+            )));
+        }
+        if part.begin < next {
+            return Err(YencError::InvalidData(format!(
+                "Part begin={} end={} overlaps bytes already covered up to {}",
+                part.begin,
+                part.end,
+                next - 1
+            )));
+        }
+        next = part.end.saturating_add(1);
+    }
+
+    if next <= size {
+        return Err(YencError::InvalidData(format!(
+            "Gap in coverage: bytes {} to {} are missing",
+            next, size
+        )));
+    }
+    Ok(())
+}
+
+/// Check that decoded articles make up one whole file
+///
+/// The articles must agree on the file size and the number of parts, and
+/// their ranges must exactly tile the file (see `validate_coverage`). If a
+/// part carries the `crc32=` of the whole file, as the last one usually
+/// does, the CRC32s of all parts combined must match it.
+///
+/// # Errors
+/// Returns `YencError::InvalidData` if no articles are given, or they
+/// disagree, leave gaps or overlap; and `YencError::CrcMismatch` if the
+/// file as a whole does not match its `crc32=`.
+///
+/// # Example
+/// ```
+/// use yenc::{Decoder, MultiPartInfo, validate_parts};
+///
+/// let data = b"Hello, World";
+/// let info1 = MultiPartInfo::new(1, 2, 1, 6, 12);
+/// let info2 = MultiPartInfo::new(2, 2, 7, 12, 12).with_full_crc(crc32fast::hash(data));
+/// let mut outcomes = Vec::new();
+/// for (range, info) in [(0..6, &info1), (6..12, &info2)] {
+///     let mut part = Vec::new();
+///     yenc::encode_part(&data[range], &mut part, "hello.txt", info).unwrap();
+///     outcomes.push(Decoder::new().decode_to_vec(&part).unwrap().0);
+/// }
+/// assert!(validate_parts(&outcomes).is_ok());
+/// assert!(validate_parts(&outcomes[1..]).is_err());
+/// ```
+pub fn validate_parts(parts: &[DecodeOutcome]) -> Result<()> {
+    let Some(first) = parts.first() else {
+        return Err(YencError::InvalidData("No parts given".to_string()));
+    };
+    let size = first.header.size;
+
+    let mut numbers = Vec::new();
+    let mut full_crc = None;
+    let mut ranges = Vec::with_capacity(parts.len());
+    for outcome in parts {
+        if outcome.header.size != size {
+            return Err(YencError::InvalidData(format!(
+                "Parts describe different files: size={} and size={}",
+                size, outcome.header.size
+            )));
+        }
+        if let Some(total) = outcome.header.total {
+            numbers.push((outcome.header.part, total));
+        }
+        match &outcome.part {
+            Some(part) => {
+                let trailer_crc = outcome.trailer.as_ref().and_then(|trailer| trailer.crc32);
+                full_crc = trailer_crc.or(full_crc);
+                ranges.push((part.clone(), outcome.crc32));
+            }
+            None => ranges.push((
+                YencPart {
+                    begin: 1,
+                    end: size,
+                },
+                outcome.crc32,
+            )),
+        }
+    }
+
+    check_totals(&numbers)?;
+    let coverage: Vec<YencPart> = ranges.iter().map(|(part, _)| part.clone()).collect();
+    validate_coverage(size, &coverage)?;
+    check_full_crc(&ranges, full_crc)
+}
+
+/// Decode bytes `range` (0-based) of a multi-part file from its part files
+///
+/// Only the keyword lines of every part file are read to find the parts
+/// covering the range; just those are then decoded, with the same checks as
+/// `decode`. This is enough to preview the start of a media file before the
+/// rest has been downloaded. Parts may be given in any order and may
+/// overlap; they must agree on the file size.
+///
+/// # Errors
+/// Returns `YencError::InvalidConfig` if `range` is reversed, runs past
+/// the end of the file or is too large to hold in memory, and
+/// `YencError::InvalidData` if no parts are given or they leave part of the
+/// range uncovered.
+///
+/// # Example
+/// ```no_run
+/// let parts = ["movie.mkv.part001.yenc", "movie.mkv.part002.yenc"];
+/// let start = yenc::extract_range(parts, 0..64 * 1024)?;
+/// # Ok::<(), yenc::YencError>(())
+/// ```
+pub fn extract_range<P, I>(part_paths: I, range: Range<u64>) -> Result<Vec<u8>>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = P>,
+{
+    let decoder = Decoder::new();
+    let (full_size, parts) = index_parts(&decoder, part_paths)?;
+    if range.start > range.end || range.end > full_size {
+        return Err(YencError::InvalidConfig(format!(
+            "Range {}..{} is outside a {} byte file",
+            range.start, range.end, full_size
+        )));
+    }
+    let Ok(len) = usize::try_from(range.end - range.start) else {
+        return Err(YencError::InvalidConfig(format!(
+            "Range {}..{} is too large to hold in memory",
+            range.start, range.end
+        )));
+    };
+
+    // Take each byte from the first part to cover it, skipping parts that
+    // add nothing new
+    let wanted = parts
+        .into_iter()
+        .filter(|(_, part)| part.start < range.end && range.start < part.end);
+    let mut data = Vec::with_capacity(len);
+    for (path, part) in wanted {
+        let next = range.start + data.len() as u64;
+        if part.start > next {
+            return Err(YencError::InvalidData(format!(
+                "Gap in coverage: bytes {} to {} are missing",
+                next + 1,
+                part.start
+            )));
+        }
+        if part.end <= next {
+            continue;
+        }
+
+        let decoded = decode_part_file(&decoder, path.as_ref(), &part)?;
+        let end = part.end.min(range.end);
+        let window = (next - part.start) as usize..(end - part.start) as usize;
+        data.extend_from_slice(&decoded[window]);
+    }
+
+    if data.len() < len {
+        return Err(YencError::InvalidData(format!(
+            "Gap in coverage: bytes {} to {} are missing",
+            range.start + data.len() as u64 + 1,
+            range.end
+        )));
+    }
+    Ok(data)
+}
+
+/// File size and part files with the 0-based byte range of each
+pub(crate) type IndexedParts<P> = (u64, Vec<(P, Range<u64>)>);
+
+/// Find the byte range each part file covers from its keyword lines alone
+///
+/// Returns the file size the parts agree on and the parts with their 0-based
+/// ranges, sorted by where they start.
+pub(crate) fn index_parts<P, I>(decoder: &Decoder, part_paths: I) -> Result<IndexedParts<P>>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = P>,
+{
+    let mut full_size = None;
+    let mut parts = Vec::new();
+    for path in part_paths {
+        let metadata = decoder.read_metadata(BufReader::new(File::open(&path)?))?;
+        let full_size = *full_size.get_or_insert(metadata.header.size);
+        let part = part_range(&metadata.header, metadata.part.as_ref(), full_size)?;
+        parts.push((path, part));
+    }
+
+    let Some(full_size) = full_size else {
+        return Err(YencError::InvalidData("No parts given".to_string()));
+    };
+    parts.sort_by_key(|(_, part)| part.start);
+    Ok((full_size, parts))
+}
+
+/// Decode the part file at `path`, whose data has to fill `part` exactly
+pub(crate) fn decode_part_file(
+    decoder: &Decoder,
+    path: &Path,
+    part: &Range<u64>,
+) -> Result<Vec<u8>> {
+    let len = part.end - part.start;
+    let mut decoded = Vec::with_capacity(usize::try_from(len).unwrap_or_default());
+    decoder.decode(BufReader::new(File::open(path)?), &mut decoded)?;
+    if decoded.len() as u64 != len {
+        return Err(YencError::InvalidData(format!(
+            "Part size mismatch: part range implies {}, but data decodes to {}",
+            len,
+            decoded.len()
+        )));
+    }
+    Ok(decoded)
+}
+
+/// Byte ranges and parts an assembler has written so far
+#[derive(Debug, Default)]
+struct Coverage {
+    /// Sorted, non-overlapping, non-adjacent 0-based byte ranges written so far
+    covered: Vec<Range<u64>>,
+    /// Distinct part ranges added so far, in the order they arrived
+    parts: Vec<YencPart>,
+    /// Times each of `parts` was added
+    received: Vec<usize>,
+    /// CRC32 of the data each of `parts` was added with last, if known
+    crcs: Vec<Option<u32>>,
+    /// `part=` and `total=` of the articles added with a `total=`
+    numbers: Vec<(Option<usize>, usize)>,
+    /// `crc32=` of the whole file, from the trailer of a part
+    full_crc: Option<u32>,
+}
+
+impl Coverage {
+    /// Record `range` as written, as part of the 0-based part range `part`
+    /// whose data has CRC32 `crc`
+    fn add(&mut self, range: Range<u64>, part: Range<u64>, crc: Option<u32>) {
+        self.mark(range);
+        let part = YencPart {
+            begin: part.start + 1,
+            end: part.end,
+        };
+        match self.parts.iter().position(|seen| *seen == part) {
+            Some(index) => {
+                self.received[index] += 1;
+                self.crcs[index] = crc;
+            }
+            None => {
+                self.parts.push(part);
+                self.received.push(1);
+                self.crcs.push(crc);
+            }
+        }
+    }
+
+    /// Record the whole-file fields of a decoded article's keyword lines
+    fn add_outcome(&mut self, outcome: &DecodeOutcome) {
+        if let Some(total) = outcome.header.total {
+            self.numbers.push((outcome.header.part, total));
+        }
+        if outcome.part.is_some() {
+            let trailer_crc = outcome.trailer.as_ref().and_then(|trailer| trailer.crc32);
+            self.full_crc = trailer_crc.or(self.full_crc);
+        }
+    }
+
+    /// Check that the parts added make up the whole file
+    fn validate(&self, full_size: u64) -> Result<()> {
+        check_totals(&self.numbers)?;
+        validate_coverage(full_size, &self.parts)?;
+        if let Some(gap) = self.missing(full_size).first() {
+            return Err(YencError::InvalidData(format!(
+                "Gap in coverage: bytes {} to {} are missing",
+                gap.start + 1,
+                gap.end
+            )));
+        }
+        let parts: Vec<_> = self
+            .parts
+            .iter()
+            .cloned()
+            .zip(self.crcs.iter().copied())
+            .collect();
+        check_full_crc(&parts, self.full_crc)
+    }
+
+    /// Record `range` as written, merging it with touching ranges
+    fn mark(&mut self, range: Range<u64>) {
+        if range.is_empty() {
+            return;
+        }
+        let first = self.covered.partition_point(|r| r.end < range.start);
+        let last = self.covered.partition_point(|r| r.start <= range.end);
+        let mut merged = range;
+        if first < last {
+            merged.start = merged.start.min(self.covered[first].start);
+            merged.end = merged.end.max(self.covered[last - 1].end);
+        }
+        self.covered.splice(first..last, [merged]);
+    }
+
+    fn covered_bytes(&self) -> u64 {
+        self.covered
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum()
+    }
+
+    fn missing(&self, full_size: u64) -> Vec<Range<u64>> {
+        let mut missing = Vec::new();
+        let mut pos = 0;
+        for range in &self.covered {
+            if range.start > pos {
+                missing.push(pos..range.start);
+            }
+            pos = range.end;
+        }
+        if pos < full_size {
+            missing.push(pos..full_size);
+        }
+        missing
+    }
+
+    fn report(&self, full_size: u64) -> CoverageReport {
+        let duplicates = self
+            .parts
+            .iter()
+            .zip(&self.received)
+            .filter(|&(_, &times)| times > 1)
+            .map(|(part, &times)| (part.clone(), times))
+            .collect();
+
+        // Empty parts cover nothing, so they overlap nothing either
+        let mut sorted: Vec<&YencPart> = self
+            .parts
+            .iter()
+            .filter(|part| part.begin <= part.end)
+            .collect();
+        sorted.sort_by_key(|part| (part.begin, part.end));
+        let mut overlaps = Vec::new();
+        for (i, first) in sorted.iter().enumerate() {
+            for second in sorted[i + 1..]
+                .iter()
+                .take_while(|second| second.begin <= first.end)
+            {
+                overlaps.push(((*first).clone(), (*second).clone()));
+            }
+        }
+
+        CoverageReport {
+            missing: self.missing(full_size),
+            duplicates,
+            overlaps,
+        }
+    }
+}
+
+/// How completely the parts added to an assembler cover the file
+///
+/// Lets a downloader decide which segments to fetch again before declaring
+/// the file complete.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CoverageReport {
+    /// 0-based byte ranges no part has written
+    pub missing: Vec<Range<u64>>,
+    /// Parts added more than once, with the number of times
+    ///
+    /// Harmless by themselves: the same range simply got written again.
+    pub duplicates: Vec<(YencPart, usize)>,
+    /// Pairs of different part ranges that share bytes, ordered by `begin`
+    ///
+    /// Well-formed posts never have these, so one of each pair is likely
+    /// from another post or mislabeled.
+    pub overlaps: Vec<(YencPart, YencPart)>,
+}
+
+impl CoverageReport {
+    /// Whether every byte was written by parts that tile the file exactly
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty() && self.overlaps.is_empty()
+    }
+}
+
+/// Assembles a multi-part file in memory, decoding each part at its offset
+///
+/// The assembler owns a buffer of the full file size (or borrows one from
+/// the caller) and decodes every part straight into `begin - 1`, without an
+/// intermediate copy. Parts may arrive in any order; the byte ranges they
+/// cover are tracked so the caller can tell when the file is complete.
+/// `FileAssembler` does the same in an output file.
+///
+/// # Example
+/// ```
+/// use yenc::{Assembler, MultiPartInfo};
+///
+/// let data = b"Hello, World";
+/// let (mut part1, mut part2) = (Vec::new(), Vec::new());
+/// let info1 = MultiPartInfo::new(1, 2, 1, 6, 12);
+/// let info2 = MultiPartInfo::new(2, 2, 7, 12, 12);
+/// yenc::encode_part(&data[..6], &mut part1, "hello.txt", &info1).unwrap();
+/// yenc::encode_part(&data[6..], &mut part2, "hello.txt", &info2).unwrap();
+///
+/// let mut assembler = Assembler::new(data.len());
+/// assembler.add_part(&part2[..]).unwrap();
+/// assert!(!assembler.is_complete());
+/// assembler.add_part(&part1[..]).unwrap();
+/// assert!(assembler.is_complete());
+/// assert_eq!(assembler.into_inner(), data);
+/// ```
+#[derive(Debug)]
+pub struct Assembler<B = Vec<u8>> {
+    buffer: B,
+    decoder: Decoder,
+    coverage: Coverage,
+}
+
+impl Assembler<Vec<u8>> {
+    /// Create an assembler with a zero-filled buffer of `full_size` bytes
+    pub fn new(full_size: usize) -> Self {
+        Self::with_buffer(vec![0; full_size])
+    }
+}
+
+impl<B: AsRef<[u8]> + AsMut<[u8]>> Assembler<B> {
+    /// Create an assembler writing into a caller-provided buffer
+    ///
+    /// The buffer length is taken as the full file size.
+    pub fn with_buffer(buffer: B) -> Self {
+        Self {
+            buffer,
+            decoder: Decoder::new(),
+            coverage: Coverage::default(),
+        }
+    }
+
+    /// Use `decoder` to decode parts instead of the default decoder
+    ///
+    /// Parts are always placed byte for byte, so a decoder in text mode
+    /// makes `add_part` fail with `YencError::InvalidConfig`.
+    pub fn decoder(mut self, decoder: Decoder) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Size of the file being assembled
+    pub fn full_size(&self) -> usize {
+        self.buffer.as_ref().len()
+    }
+
+    /// Decode one article and place its data at the offset given by `=ypart`
+    ///
+    /// Single-part articles cover the whole file. Parts may be added more than
+    /// once; later data overwrites earlier data.
+    ///
+    /// # Returns
+    /// The same outcome as `Decoder::decode`
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidData` if the article describes a different
+    /// file size, its range lies outside the file, or it decodes to more bytes
+    /// than its range holds. Decoding errors are passed through; in that case
+    /// the part is not marked as covered.
+    pub fn add_part<R: Read>(&mut self, reader: R) -> Result<DecodeOutcome> {
+        let full_size = self.full_size() as u64;
+        let buffer = self.buffer.as_mut();
+        let mut range = 0..0;
+        let mut slot = None;
+        let (range_ref, slot_ref) = (&mut range, &mut slot);
+
+        let result = self.decoder.decode_with(reader, move |header, part| {
+            *range_ref = part_range(header, part, full_size)?;
+            Ok(slot_ref.insert(SinkWindowWriter::new(buffer, range_ref.clone())))
+        })?;
+
+        if let Some(writer) = slot {
+            let written = range.start..range.start + writer.finish()?;
+            self.coverage.add(written, range, result.crc32);
+            self.coverage.add_outcome(&result);
+        }
+        Ok(result)
+    }
+
+    /// Place already-decoded data at a 1-based `begin` offset
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidData` if the data does not fit in the file.
+    pub fn write_at(&mut self, begin: u64, data: &[u8]) -> Result<()> {
+        let range = data_range(begin, data.len(), self.full_size() as u64)?;
+        self.buffer.as_mut()[range.start as usize..range.end as usize].copy_from_slice(data);
+        self.coverage
+            .add(range.clone(), range, Some(crc32fast::hash(data)));
+        Ok(())
+    }
+
+    /// Number of bytes written so far
+    pub fn covered_bytes(&self) -> u64 {
+        self.coverage.covered_bytes()
+    }
+
+    /// Whether every byte of the file has been written
+    pub fn is_complete(&self) -> bool {
+        self.covered_bytes() == self.full_size() as u64
+    }
+
+    /// 0-based byte ranges that have not been written yet
+    pub fn missing(&self) -> Vec<Range<u64>> {
+        self.coverage.missing(self.full_size() as u64)
+    }
+
+    /// Part ranges added so far, without duplicates
+    ///
+    /// Single-part articles are listed as a part covering the whole file.
+    pub fn parts(&self) -> &[YencPart] {
+        &self.coverage.parts
+    }
+
+    /// Missing ranges, duplicate parts and overlapping parts so far
+    pub fn coverage_report(&self) -> CoverageReport {
+        self.coverage.report(self.full_size() as u64)
+    }
+
+    /// Check that the parts added so far exactly tile the file
+    ///
+    /// Stricter than `is_complete`: overlapping parts are reported even if
+    /// together they cover every byte. See `validate_coverage`.
+    pub fn validate_coverage(&self) -> Result<()> {
+        validate_coverage(self.full_size() as u64, self.parts())
+    }
+
+    /// Check that the parts added so far make up the whole file
+    ///
+    /// Besides `validate_coverage`, every byte must have been written, the
+    /// articles must agree on `total=`, and the CRC32 of the assembled file
+    /// must match the `crc32=` a part carried, if any did. File sizes are
+    /// checked as parts are added.
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidData` for gaps, overlaps and differing
+    /// totals, and `YencError::CrcMismatch` if the file does not match its
+    /// `crc32=`.
+    pub fn validate(&self) -> Result<()> {
+        self.coverage.validate(self.full_size() as u64)
+    }
+
+    /// The assembled data, including any zero-filled gaps
+    pub fn as_slice(&self) -> &[u8] {
+        self.buffer.as_ref()
+    }
+
+    /// Return the underlying buffer
+    pub fn into_inner(self) -> B {
+        self.buffer
+    }
+}
+
+/// Assembles a multi-part file in an output file, decoding each part at its offset
+///
+/// Like `Assembler`, but for files too large to hold in memory: every part
+/// is decoded straight into the output at `begin - 1`, seeking there first,
+/// so parts can be added in whatever order they arrive. Any `Write + Seek`
+/// output works; `create` and `create_unsized` make a file of the full
+/// size, which is sparse on file systems that support it, so the regions no
+/// part has reached yet take no disk space.
+///
+/// # Example
+/// ```no_run
+/// use yenc::FileAssembler;
+///
+/// let mut assembler = FileAssembler::create("big.iso", 2_000_000)?;
+/// for path in ["big.iso.002.yenc", "big.iso.001.yenc"] {
+///     assembler.add_part(std::io::BufReader::new(std::fs::File::open(path)?))?;
+/// }
+/// assembler.validate_coverage()?;
+/// # Ok::<(), yenc::YencError>(())
+/// ```
+#[derive(Debug)]
+pub struct FileAssembler<W = File> {
+    output: W,
+    /// Unknown until the first part for assemblers from `create_unsized`
+    full_size: Option<u64>,
+    /// Sets the length of the output once the size is learned from a part
+    resize: Option<fn(&mut W, u64) -> io::Result<()>>,
+    decoder: Decoder,
+    coverage: Coverage,
+}
+
+impl FileAssembler<File> {
+    /// Create the output file at `path`, `full_size` bytes long
+    ///
+    /// Bytes no part has covered yet read as zeros.
+    pub fn create<P: AsRef<Path>>(path: P, full_size: u64) -> Result<Self> {
+        let file = File::create(path)?;
+        file.set_len(full_size)?;
+        Ok(Self::new(file, full_size))
+    }
+
+    /// Create the output file at `path`, sized by the first part added
+    ///
+    /// For parts fetched over the network, where the file size is only
+    /// known once the first one arrives, whichever part that is. The file
+    /// is then set to its full size right away. Until then, `full_size` is
+    /// 0 and `write_at` fails.
+    pub fn create_unsized<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self {
+            output: File::create(path)?,
+            full_size: None,
+            resize: Some(|file, len| file.set_len(len)),
+            decoder: Decoder::new(),
+            coverage: Coverage::default(),
+        })
+    }
+}
+
+impl<W: Write + Seek> FileAssembler<W> {
+    /// Create an assembler writing a file of `full_size` bytes into `output`
+    pub fn new(output: W, full_size: u64) -> Self {
+        Self {
+            output,
+            full_size: Some(full_size),
+            resize: None,
+            decoder: Decoder::new(),
+            coverage: Coverage::default(),
+        }
+    }
+
+    /// Use `decoder` to decode parts instead of the default decoder
+    ///
+    /// Parts are always placed byte for byte, so a decoder in text mode
+    /// makes `add_part` fail with `YencError::InvalidConfig`.
+    pub fn decoder(mut self, decoder: Decoder) -> Self {
+        self.decoder = decoder;
+        self
+    }
+
+    /// Size of the file being assembled
+    pub fn full_size(&self) -> u64 {
+        self.full_size.unwrap_or_default()
+    }
+
+    /// Decode one article and write its data at the offset given by `=ypart`
+    ///
+    /// Behaves like `Assembler::add_part`. When decoding fails, the data
+    /// decoded up to that point has already been written, but the part is
+    /// not marked as covered.
+    pub fn add_part<R: Read>(&mut self, reader: R) -> Result<DecodeOutcome> {
+        let full_size = &mut self.full_size;
+        let output = &mut self.output;
+        let resize = self.resize;
+        let mut range = 0..0;
+        let mut slot = None;
+        let (range_ref, slot_ref) = (&mut range, &mut slot);
+
+        let result = self.decoder.decode_with(reader, move |header, part| {
+            let full_size = match *full_size {
+                Some(size) => size,
+                None => {
+                    if let Some(resize) = resize {
+                        resize(output, header.size)?;
+                    }
+                    *full_size.insert(header.size)
+                }
+            };
+            *range_ref = part_range(header, part, full_size)?;
+            output.seek(SeekFrom::Start(range_ref.start))?;
+            Ok(slot_ref.insert(SeekWindowWriter {
+                inner: output,
+                len: range_ref.end - range_ref.start,
+                written: 0,
+                overflow: false,
+            }))
+        })?;
+
+        if let Some((written, overflow)) = slot.map(|writer| (writer.written, writer.overflow)) {
+            if overflow {
+                return Err(YencError::InvalidData(format!(
+                    "Part decodes to more than the {} bytes of its range",
+                    range.end - range.start
+                )));
+            }
+            let written = range.start..range.start + written;
+            self.coverage.add(written, range, result.crc32);
+            self.coverage.add_outcome(&result);
+        }
+        Ok(result)
+    }
+
+    /// Write already-decoded data at a 1-based `begin` offset
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidData` if the data does not fit in the file.
+    pub fn write_at(&mut self, begin: u64, data: &[u8]) -> Result<()> {
+        let range = data_range(begin, data.len(), self.full_size())?;
+        self.output.seek(SeekFrom::Start(range.start))?;
+        self.output.write_all(data)?;
+        self.coverage
+            .add(range.clone(), range, Some(crc32fast::hash(data)));
+        Ok(())
+    }
+
+    /// Number of bytes written so far
+    pub fn covered_bytes(&self) -> u64 {
+        self.coverage.covered_bytes()
+    }
+
+    /// Whether every byte of the file has been written
+    pub fn is_complete(&self) -> bool {
+        self.covered_bytes() == self.full_size()
+    }
+
+    /// 0-based byte ranges that have not been written yet
+    pub fn missing(&self) -> Vec<Range<u64>> {
+        self.coverage.missing(self.full_size())
+    }
+
+    /// Part ranges added so far, without duplicates
+    pub fn parts(&self) -> &[YencPart] {
+        &self.coverage.parts
+    }
+
+    /// Missing ranges, duplicate parts and overlapping parts so far
+    pub fn coverage_report(&self) -> CoverageReport {
+        self.coverage.report(self.full_size())
+    }
+
+    /// Check that the parts added so far exactly tile the file
+    ///
+    /// See `Assembler::validate_coverage`.
+    pub fn validate_coverage(&self) -> Result<()> {
+        validate_coverage(self.full_size(), self.parts())
+    }
+
+    /// Check that the parts added so far make up the whole file
+    ///
+    /// See `Assembler::validate`.
+    pub fn validate(&self) -> Result<()> {
+        self.coverage.validate(self.full_size())
+    }
+
+    /// Flush the output and return it
+    pub fn into_inner(mut self) -> Result<W> {
+        self.output.flush()?;
+        Ok(self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::{Encoder, MultiPartInfo};
+
+    fn encode_part(data: &[u8], info: &MultiPartInfo) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        Encoder::new()
+            .encode_part(
+                &data[(info.begin - 1) as usize..info.end as usize],
+                &mut encoded,
+                "test.bin",
+                info,
+            )
+            .unwrap();
+        encoded
+    }
+
+    #[test]
+    fn test_assemble_out_of_order() {
+        let data: Vec<u8> = (0..=255).collect();
+        let parts = [
+            MultiPartInfo::new(1, 3, 1, 100, 256),
+            MultiPartInfo::new(2, 3, 101, 200, 256),
+            MultiPartInfo::new(3, 3, 201, 256, 256),
+        ];
+
+        let mut assembler = Assembler::new(data.len());
+        for info in parts.iter().rev() {
+            assembler.add_part(&encode_part(&data, info)[..]).unwrap();
+        }
+
+        assert!(assembler.is_complete());
+        assert!(assembler.missing().is_empty());
+        assert_eq!(assembler.as_slice(), &data[..]);
+    }
+
+    #[test]
+    fn test_assemble_tracks_missing_ranges() {
+        let data = vec![7u8; 30];
+        let mut assembler = Assembler::new(data.len());
+
+        let info = MultiPartInfo::new(2, 3, 11, 20, 30);
+        assembler.add_part(&encode_part(&data, &info)[..]).unwrap();
+        // Adding the same part again does not count twice
+        assembler.add_part(&encode_part(&data, &info)[..]).unwrap();
+
+        assert_eq!(assembler.covered_bytes(), 10);
+        assert_eq!(assembler.missing(), vec![0..10, 20..30]);
+        assert!(!assembler.is_complete());
+
+        assembler.write_at(1, &data[..10]).unwrap();
+        assembler.write_at(21, &data[20..]).unwrap();
+        assert!(assembler.is_complete());
+    }
+
+    #[test]
+    fn test_assemble_into_caller_buffer() {
+        let data = b"Hello, World".to_vec();
+        let mut buffer = [0u8; 12];
+
+        let mut assembler = Assembler::with_buffer(&mut buffer[..]);
+        let mut encoded = Vec::new();
+        crate::encode(&data[..], &mut encoded, "hello.txt").unwrap();
+        assembler.add_part(&encoded[..]).unwrap();
+        assert!(assembler.is_complete());
+
+        assert_eq!(&buffer[..], &data[..]);
+    }
+
+    #[test]
+    fn test_assemble_validate_coverage() {
+        let data = vec![3u8; 30];
+        let mut assembler = Assembler::new(data.len());
+
+        let info = MultiPartInfo::new(1, 2, 1, 20, 30);
+        assembler.add_part(&encode_part(&data, &info)[..]).unwrap();
+        assembler.add_part(&encode_part(&data, &info)[..]).unwrap();
+        assert_eq!(assembler.parts(), &[YencPart { begin: 1, end: 20 }]);
+        assert!(assembler.validate_coverage().is_err());
+
+        // Complete, but the second range overlaps the first
+        assembler.write_at(11, &data[10..]).unwrap();
+        assert!(assembler.is_complete());
+        assert!(assembler.validate_coverage().is_err());
+    }
+
+    #[test]
+    fn test_assemble_validate() {
+        let data: Vec<u8> = (0..30).collect();
+        let full_crc = crc32fast::hash(&data);
+        let first = MultiPartInfo::new(1, 3, 1, 10, 30);
+        let second = MultiPartInfo::new(2, 3, 11, 20, 30);
+        let last = MultiPartInfo::new(3, 3, 21, 30, 30).with_full_crc(full_crc);
+        let message = |result: Result<()>| match result {
+            Err(YencError::InvalidData(msg)) => msg,
+            other => panic!("expected InvalidData, got {:?}", other),
+        };
+
+        let mut assembler = Assembler::new(data.len());
+        for info in [&last, &first] {
+            assembler.add_part(&encode_part(&data, info)[..]).unwrap();
+        }
+        assert!(message(assembler.validate()).contains("bytes 11 to 20"));
+        assembler
+            .add_part(&encode_part(&data, &second)[..])
+            .unwrap();
+        assert!(assembler.validate().is_ok());
+
+        // Data that matches its pcrc32= but not the crc32= of the file
+        let mut other = data.clone();
+        other[15] ^= 1;
+        assembler
+            .add_part(&encode_part(&other, &second)[..])
+            .unwrap();
+        assert!(matches!(
+            assembler.validate(),
+            Err(YencError::CrcMismatch { expected, .. }) if expected == full_crc
+        ));
+        assembler.write_at(11, &data[10..20]).unwrap();
+        assert!(assembler.validate().is_ok());
+
+        // A part of another split of the same file, with a different total=
+        let foreign = encode_part(&data, &MultiPartInfo::new(2, 4, 11, 20, 30));
+        // And one numbered past the total
+        let numbered = String::from_utf8_lossy(&encode_part(&data, &second))
+            .replace("part=2", "part=4")
+            .into_bytes();
+        for (part, error) in [
+            (foreign, "total=3 and total=4"),
+            (numbered, "Part 4 of a post of 3"),
+        ] {
+            let mut assembler = Assembler::new(data.len());
+            assembler.add_part(&encode_part(&data, &first)[..]).unwrap();
+            assembler.add_part(&part[..]).unwrap();
+            assembler.add_part(&encode_part(&data, &last)[..]).unwrap();
+            assert!(message(assembler.validate()).contains(error));
+        }
+    }
+
+    #[test]
+    fn test_validate_parts() {
+        let data: Vec<u8> = (0..30).collect();
+        let infos = [
+            MultiPartInfo::new(1, 2, 1, 15, 30),
+            MultiPartInfo::new(2, 2, 16, 30, 30).with_full_crc(crc32fast::hash(&data)),
+        ];
+        let decode = |data: &[u8], info| {
+            let (outcome, _) = Decoder::new()
+                .decode_to_vec(&encode_part(data, info))
+                .unwrap();
+            outcome
+        };
+        let mut outcomes: Vec<_> = infos.iter().map(|info| decode(&data, info)).collect();
+        assert!(validate_parts(&outcomes).is_ok());
+        outcomes.reverse();
+        assert!(validate_parts(&outcomes).is_ok());
+
+        assert!(matches!(
+            validate_parts(&[]),
+            Err(YencError::InvalidData(_))
+        ));
+        assert!(matches!(
+            validate_parts(&outcomes[..1]),
+            Err(YencError::InvalidData(_))
+        ));
+
+        let mut other = data.clone();
+        other[0] ^= 1;
+        outcomes[1] = decode(&other, &infos[0]);
+        assert!(matches!(
+            validate_parts(&outcomes),
+            Err(YencError::CrcMismatch { .. })
+        ));
+
+        outcomes[1] = decode(&data, &MultiPartInfo::new(1, 3, 1, 15, 30));
+        assert!(matches!(
+            validate_parts(&outcomes),
+            Err(YencError::InvalidData(_))
+        ));
+    }
+
+    #[test]
+    fn test_coverage_report() {
+        let data = vec![5u8; 40];
+        let mut assembler = Assembler::new(data.len());
+        let part = |begin, end| YencPart { begin, end };
+
+        let first = MultiPartInfo::new(1, 4, 1, 10, 40);
+        assembler.add_part(&encode_part(&data, &first)[..]).unwrap();
+        let report = assembler.coverage_report();
+        assert_eq!(report.missing, vec![10..40]);
+        assert!(report.duplicates.is_empty() && report.overlaps.is_empty());
+        assert!(!report.is_complete());
+
+        assembler.add_part(&encode_part(&data, &first)[..]).unwrap();
+        assembler.add_part(&encode_part(&data, &first)[..]).unwrap();
+        // A part from another split of the same file
+        let other = MultiPartInfo::new(2, 3, 8, 30, 40);
+        assembler.add_part(&encode_part(&data, &other)[..]).unwrap();
+        assembler.write_at(31, &data[30..]).unwrap();
+
+        let report = assembler.coverage_report();
+        assert!(report.missing.is_empty());
+        assert_eq!(report.duplicates, vec![(part(1, 10), 3)]);
+        assert_eq!(report.overlaps, vec![(part(1, 10), part(8, 30))]);
+        assert!(assembler.is_complete());
+        assert!(!report.is_complete());
+    }
+
+    #[test]
+    fn test_file_assembler() {
+        let data: Vec<u8> = (0..=255).collect();
+        let parts = [
+            MultiPartInfo::new(1, 3, 1, 100, 256),
+            MultiPartInfo::new(2, 3, 101, 200, 256),
+            MultiPartInfo::new(3, 3, 201, 256, 256),
+        ];
+
+        let mut assembler = FileAssembler::new(io::Cursor::new(Vec::new()), data.len() as u64);
+        for info in parts.iter().rev() {
+            assembler.add_part(&encode_part(&data, info)[..]).unwrap();
+        }
+        assert!(assembler.is_complete());
+        assert!(assembler.validate_coverage().is_ok());
+        assert_eq!(assembler.into_inner().unwrap().into_inner(), data);
+
+        // The file has its full size even with the last part missing
+        let path = std::env::temp_dir().join(format!("yenc-assemble-{}", std::process::id()));
+        let mut assembler = FileAssembler::create(&path, data.len() as u64).unwrap();
+        assembler
+            .add_part(&encode_part(&data, &parts[0])[..])
+            .unwrap();
+        assembler.write_at(101, &data[100..200]).unwrap();
+        assert_eq!(assembler.missing(), vec![200..256]);
+        assert!(matches!(
+            assembler.write_at(250, &data[..10]),
+            Err(YencError::InvalidData(_))
+        ));
+        assembler.into_inner().unwrap();
+
+        let written = std::fs::read(&path).unwrap();
+        assert_eq!(written[..200], data[..200]);
+        assert_eq!(written[200..], [0; 56]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_file_assembler_unsized() {
+        let data: Vec<u8> = (0..=255).collect();
+        let parts = [
+            MultiPartInfo::new(1, 3, 1, 100, 256),
+            MultiPartInfo::new(2, 3, 101, 200, 256),
+            MultiPartInfo::new(3, 3, 201, 256, 256),
+        ];
+        let path = std::env::temp_dir().join(format!("yenc-unsized-{}", std::process::id()));
+
+        // The first part to arrive sizes the whole file
+        let mut assembler = FileAssembler::create_unsized(&path).unwrap();
+        assert_eq!(assembler.full_size(), 0);
+        assembler
+            .add_part(&encode_part(&data, &parts[1])[..])
+            .unwrap();
+        assert_eq!(assembler.full_size(), 256);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 256);
+
+        for info in [&parts[2], &parts[0]] {
+            assembler.add_part(&encode_part(&data, info)[..]).unwrap();
+        }
+        assert!(assembler.is_complete());
+        assembler.into_inner().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_validate_coverage() {
+        let part = |begin, end| YencPart { begin, end };
+
+        assert!(validate_coverage(10, &[part(1, 10)]).is_ok());
+        assert!(validate_coverage(10, &[part(6, 10), part(1, 5)]).is_ok());
+        assert!(validate_coverage(0, &[]).is_ok());
+        assert!(validate_coverage(0, &[part(1, 0)]).is_ok());
+        // Empty parts cover nothing, wherever they are
+        assert!(validate_coverage(10, &[part(1, 10), part(4, 3)]).is_ok());
+
+        let message = |result: Result<()>| match result {
+            Err(YencError::InvalidData(msg)) => msg,
+            other => panic!("expected InvalidData, got {:?}", other),
+        };
+        assert!(
+            message(validate_coverage(10, &[part(1, 4), part(6, 10)])).contains("bytes 5 to 5")
+        );
+        assert!(message(validate_coverage(10, &[part(1, 5)])).contains("bytes 6 to 10"));
+        assert!(message(validate_coverage(10, &[part(2, 10)])).contains("bytes 1 to 1"));
+        assert!(message(validate_coverage(10, &[part(1, 6), part(5, 10)])).contains("overlaps"));
+        assert!(message(validate_coverage(10, &[part(1, 11)])).contains("outside"));
+        assert!(message(validate_coverage(10, &[part(0, 10)])).contains("outside"));
+        assert!(message(validate_coverage(10, &[part(1, u64::MAX)])).contains("outside"));
+        assert!(message(validate_coverage(10, &[part(1, 10), part(1, 10)])).contains("overlaps"));
+    }
+
+    #[test]
+    fn test_assemble_rejects_foreign_parts() {
+        let data = vec![1u8; 20];
+        let mut assembler = Assembler::new(10);
+
+        // Different file size
+        let info = MultiPartInfo::new(1, 2, 1, 10, 20);
+        let result = assembler.add_part(&encode_part(&data, &info)[..]);
+        assert!(matches!(result, Err(YencError::InvalidData(_))));
+
+        // Range outside the file
+        let encoded = b"=ybegin part=2 total=2 line=128 size=10 name=test.bin\n\
+                        =ypart begin=6 end=12\n\
+                        +++++++\n\
+                        =yend size=7 part=2\n";
+        let result = assembler.add_part(&encoded[..]);
+        assert!(matches!(result, Err(YencError::InvalidData(_))));
+
+        assert!(matches!(
+            assembler.write_at(8, &data[..5]),
+            Err(YencError::InvalidData(_))
+        ));
+        assert!(matches!(
+            assembler.write_at(0, &data[..1]),
+            Err(YencError::InvalidData(_))
+        ));
+        assert_eq!(assembler.covered_bytes(), 0);
+    }
+
+    #[test]
+    fn test_assemble_rejects_overlong_part() {
+        // Range holds 2 bytes but the data decodes to 5
+        let encoded = b"=ybegin part=1 total=1 line=128 size=10 name=test.bin\n\
+                        =ypart begin=1 end=2\n\
+                        +++++\n\
+                        =yend size=2 part=1\n";
+        let mut assembler = Assembler::new(10);
+        let result = assembler.add_part(&encoded[..]);
+        assert!(matches!(result, Err(YencError::InvalidData(_))));
+        assert_eq!(assembler.covered_bytes(), 0);
+    }
+
+    #[test]
+    fn test_assemble_rejects_text_mode() {
+        let mut encoded = Vec::new();
+        crate::encode(&b"a\r\nb"[..], &mut encoded, "test.txt").unwrap();
+        let mut assembler = Assembler::new(4).decoder(Decoder::new().text_mode());
+        let result = assembler.add_part(&encoded[..]);
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+        assert_eq!(assembler.covered_bytes(), 0);
+    }
+}
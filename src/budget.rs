@@ -0,0 +1,201 @@
+//! Memory budget shared by every call made through one encoder or decoder
+//!
+//! `max_memory` creates a budget that the encoder or decoder and all its
+//! clones draw from. Each call reserves its buffers before allocating them
+//! and gives them back when it returns, so calls running at once, such as
+//! the parts of a parallel encode or decode, wait for memory to free up
+//! instead of together going past the cap.
+//!
+//! A call takes what it needs to start in one go and only ever grows that
+//! without waiting. No call waits while holding memory, so waiting cannot
+//! deadlock: it ends as soon as enough other calls have finished.
+
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+
+use crate::error::{Result, YencError};
+
+/// Cap on the bytes reserved at any one time
+#[derive(Debug)]
+pub(crate) struct MemoryBudget {
+    limit: usize,
+    used: Mutex<usize>,
+    released: Condvar,
+}
+
+impl MemoryBudget {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            used: Mutex::new(0),
+            released: Condvar::new(),
+        }
+    }
+
+    pub(crate) fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Reserve `bytes`, waiting for other reservations to be released if need be
+    ///
+    /// Fails with `YencError::MemoryLimitExceeded` straight away if `bytes`
+    /// is more than the whole budget.
+    pub(crate) fn reserve(self: &Arc<Self>, bytes: usize) -> Result<Reservation> {
+        if bytes > self.limit {
+            return Err(YencError::MemoryLimitExceeded { limit: self.limit });
+        }
+        let mut used = self.used();
+        while self.limit - *used < bytes {
+            used = self
+                .released
+                .wait(used)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+        *used += bytes;
+        Ok(Reservation {
+            budget: Some(Arc::clone(self)),
+            bytes,
+        })
+    }
+
+    /// Bytes reserved so far; a panic elsewhere leaves the count intact
+    fn used(&self) -> MutexGuard<'_, usize> {
+        self.used.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Bytes held from a `MemoryBudget`, given back when dropped
+#[derive(Debug)]
+pub(crate) struct Reservation {
+    /// `None` when there is no budget to draw from
+    budget: Option<Arc<MemoryBudget>>,
+    bytes: usize,
+}
+
+impl Reservation {
+    /// Reservation that can grow without bound
+    pub(crate) fn unlimited() -> Self {
+        Self {
+            budget: None,
+            bytes: 0,
+        }
+    }
+
+    /// Reserve `bytes` from `budget`, or nothing without one
+    pub(crate) fn take(budget: Option<&Arc<MemoryBudget>>, bytes: usize) -> Result<Self> {
+        match budget {
+            Some(budget) => budget.reserve(bytes),
+            None => Ok(Self::unlimited()),
+        }
+    }
+
+    /// Grow to at least `min` bytes and as far as `max` if they are free
+    ///
+    /// Never waits: if `min` bytes cannot be had right away, fails with
+    /// `YencError::MemoryLimitExceeded`. Returns the size reached, which is
+    /// never below what was held before.
+    pub(crate) fn grow(&mut self, min: usize, max: usize) -> Result<usize> {
+        let Some(budget) = &self.budget else {
+            return Ok(max);
+        };
+        if self.bytes >= max {
+            return Ok(self.bytes);
+        }
+        let mut used = budget.used();
+        let target = max.min(self.bytes + (budget.limit - *used));
+        if target < min {
+            return Err(YencError::MemoryLimitExceeded {
+                limit: budget.limit,
+            });
+        }
+        if target > self.bytes {
+            *used += target - self.bytes;
+            self.bytes = target;
+        }
+        Ok(self.bytes)
+    }
+
+    /// Grow by exactly `bytes`, without waiting
+    pub(crate) fn add(&mut self, bytes: usize) -> Result<()> {
+        let target = self.bytes + bytes;
+        self.grow(target, target).map(drop)
+    }
+
+    /// Move `bytes` of this reservation into a new one
+    pub(crate) fn split_off(&mut self, bytes: usize) -> Self {
+        let bytes = match self.budget {
+            Some(_) => bytes.min(self.bytes),
+            None => 0,
+        };
+        self.bytes -= bytes;
+        Self {
+            budget: self.budget.clone(),
+            bytes,
+        }
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        if let Some(budget) = &self.budget {
+            if self.bytes > 0 {
+                *budget.used() -= self.bytes;
+                budget.released.notify_all();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_reserve_waits_for_release() {
+        let budget = Arc::new(MemoryBudget::new(100));
+        let held = budget.reserve(60).unwrap();
+
+        thread::scope(|scope| {
+            let waiting = scope.spawn(|| budget.reserve(50).unwrap());
+            thread::sleep(Duration::from_millis(50));
+            assert!(!waiting.is_finished());
+            drop(held);
+            assert_eq!(waiting.join().unwrap().bytes, 50);
+        });
+        assert_eq!(*budget.used(), 0);
+
+        assert!(matches!(
+            budget.reserve(101),
+            Err(YencError::MemoryLimitExceeded { limit: 100 })
+        ));
+    }
+
+    #[test]
+    fn test_grow_and_split() {
+        let budget = Arc::new(MemoryBudget::new(100));
+        let mut first = budget.reserve(30).unwrap();
+        let other = budget.reserve(40).unwrap();
+
+        // Grows only into what is free, without waiting
+        assert_eq!(first.grow(40, 80).unwrap(), 60);
+        assert!(matches!(
+            first.grow(61, 61),
+            Err(YencError::MemoryLimitExceeded { limit: 100 })
+        ));
+        assert_eq!(first.grow(1, 10).unwrap(), 60);
+        assert!(first.add(1).is_err());
+
+        let part = first.split_off(20);
+        assert_eq!((first.bytes, part.bytes), (40, 20));
+        drop(other);
+        drop(part);
+        assert_eq!(*budget.used(), 40);
+        drop(first);
+        assert_eq!(*budget.used(), 0);
+
+        let mut unlimited = Reservation::unlimited();
+        assert_eq!(unlimited.grow(1, usize::MAX).unwrap(), usize::MAX);
+        assert_eq!(unlimited.split_off(10).bytes, 0);
+    }
+}
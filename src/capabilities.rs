@@ -0,0 +1,111 @@
+//! Build and runtime capability introspection
+
+use crate::consts::{BUFFER_SIZE, LINE_LENGTH};
+use crate::dispatch::Backend;
+
+/// Version of the yEnc draft this crate implements
+pub(crate) const SPEC_VERSION: &str = "1.3";
+
+/// What this build of the crate supports on the current machine
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Version of this crate
+    pub crate_version: &'static str,
+    /// Version of the yEnc draft implemented
+    pub spec_version: &'static str,
+    /// SIMD backends usable on this CPU, best first (empty when only the
+    /// scalar kernels are available)
+    pub simd_backends: Vec<&'static str>,
+    /// Cargo features enabled in this build
+    pub features: Vec<&'static str>,
+    /// Default line length used by the encoder
+    pub default_line_length: usize,
+    /// Default read chunk size used by the encoder and decoder
+    pub default_chunk_size: usize,
+    /// Default memory budget (`None` means unbounded)
+    pub default_max_memory: Option<usize>,
+}
+
+/// SIMD kernels usable on this CPU, best first
+fn simd_backends() -> Vec<&'static str> {
+    Backend::available()
+        .iter()
+        .filter(|&&backend| backend != Backend::Scalar)
+        .map(|backend| backend.name())
+        .collect()
+}
+
+/// Report the spec version, backends, features and defaults of this build
+///
+/// Useful for diagnostics and for applications (or FFI consumers) that need
+/// to negotiate behavior with the library.
+///
+/// # Example
+/// ```
+/// let caps = yenc::capabilities();
+/// assert_eq!(caps.spec_version, "1.3");
+/// println!("yenc {} on {:?}", caps.crate_version, caps.simd_backends);
+/// ```
+pub fn capabilities() -> Capabilities {
+    let mut features = Vec::new();
+    if cfg!(feature = "simd") {
+        features.push("simd");
+    }
+    if cfg!(feature = "portable-simd") {
+        features.push("portable-simd");
+    }
+    if cfg!(feature = "mmap") {
+        features.push("mmap");
+    }
+    if cfg!(feature = "io-uring") {
+        features.push("io-uring");
+    }
+    if cfg!(feature = "rayon") {
+        features.push("rayon");
+    }
+    if cfg!(feature = "test-support") {
+        features.push("test-support");
+    }
+
+    Capabilities {
+        crate_version: env!("CARGO_PKG_VERSION"),
+        spec_version: SPEC_VERSION,
+        simd_backends: simd_backends(),
+        features,
+        default_line_length: LINE_LENGTH,
+        default_chunk_size: BUFFER_SIZE,
+        default_max_memory: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let caps = capabilities();
+
+        assert_eq!(caps.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(caps.spec_version, "1.3");
+        assert_eq!(caps.default_line_length, 128);
+        assert_eq!(caps.default_chunk_size, 8 * 1024);
+        assert_eq!(caps.features.contains(&"simd"), cfg!(feature = "simd"));
+        assert_eq!(caps.features.contains(&"mmap"), cfg!(feature = "mmap"));
+        assert_eq!(
+            caps.features.contains(&"io-uring"),
+            cfg!(feature = "io-uring")
+        );
+        assert_eq!(caps.features.contains(&"rayon"), cfg!(feature = "rayon"));
+        if cfg!(all(feature = "simd", target_arch = "x86_64")) {
+            assert!(caps.simd_backends.contains(&"sse2"));
+        }
+        if cfg!(all(feature = "simd", target_arch = "aarch64")) {
+            assert_eq!(caps.simd_backends[0], "neon");
+        }
+        if cfg!(feature = "portable-simd") {
+            assert_eq!(caps.simd_backends.last(), Some(&"portable"));
+            assert!(caps.features.contains(&"portable-simd"));
+        }
+    }
+}
@@ -12,6 +12,13 @@ pub(crate) const ESCAPE_CHAR: u8 = b'=';
 /// Default line length for encoded output
 pub(crate) const LINE_LENGTH: usize = 128;
 
+/// Longest accepted line length, so that a line with a trailing escape pair
+/// stays within the 998-character NNTP line limit
+pub(crate) const MAX_LINE_LENGTH: usize = 997;
+
+/// Default read chunk size for encoding and decoding
+pub(crate) const BUFFER_SIZE: usize = 8 * 1024;
+
 /// Characters that are valid to escape according to yEnc spec
 pub(crate) const ESCAPING_CHARS: [u8; 7] = [
     0x00, // NULL
@@ -22,3 +29,45 @@ pub(crate) const ESCAPING_CHARS: [u8; 7] = [
     0x2E, // DOT
     0x3D, // EQUAL - escape character itself
 ];
+
+/// Whether a byte is valid after an escape character, once decoded
+pub(crate) const IS_ESCAPING_CHAR: [bool; 256] = {
+    let mut table = [false; 256];
+    let mut i = 0;
+    while i < ESCAPING_CHARS.len() {
+        table[ESCAPING_CHARS[i] as usize] = true;
+        i += 1;
+    }
+    table
+};
+
+/// Whether an input byte must be escaped when encoded
+pub(crate) const NEEDS_ESCAPE: [bool; 256] = {
+    let mut table = [false; 256];
+    let mut byte = 0;
+    while byte < table.len() {
+        let encoded = (byte as u8).wrapping_add(OFFSET);
+        table[byte] = IS_ESCAPING_CHAR[encoded as usize] || byte as u8 == ESCAPE_CHAR;
+        byte += 1;
+    }
+    table
+};
+
+/// Characters that have to be escaped for the data to survive at all
+pub(crate) const REQUIRED_ESCAPING_CHARS: [u8; 4] = [
+    0x00, // NULL
+    0x0A, // LF
+    0x0D, // CR
+    0x3D, // EQUAL - escape character itself
+];
+
+/// Whether an input byte has to be escaped when encoded
+pub(crate) const NEEDS_REQUIRED_ESCAPE: [bool; 256] = {
+    let mut table = [false; 256];
+    let mut i = 0;
+    while i < REQUIRED_ESCAPING_CHARS.len() {
+        table[REQUIRED_ESCAPING_CHARS[i].wrapping_sub(OFFSET) as usize] = true;
+        i += 1;
+    }
+    table
+};
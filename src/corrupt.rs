@@ -0,0 +1,191 @@
+//! Corruption injection for testing yEnc consumers
+//!
+//! Available with the `test-support` feature. The helpers take valid encoded
+//! output and damage it in controlled ways, so test suites can check how
+//! their code handles broken articles without hand-crafting each one.
+
+/// A single way of damaging an encoded article
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Corruption {
+    /// Flip bit `bit` (0-7) of the byte at `offset`
+    FlipBit { offset: usize, bit: u8 },
+    /// Remove a line, terminator included (1-based line number)
+    DropLine(usize),
+    /// Keep only the first `len` bytes
+    Truncate(usize),
+    /// Replace every `crc32=` and `pcrc32=` value in the trailer with a wrong one
+    TamperCrc,
+}
+
+/// Apply `corruptions` to `article` in order and return the damaged copy
+///
+/// Each corruption sees the result of the previous ones, so offsets and line
+/// numbers refer to the article as modified so far.
+///
+/// # Panics
+/// Panics if an offset, bit or line number is out of range, or if
+/// `TamperCrc` is applied to an article without a CRC. A corruption that
+/// silently does nothing would make the calling test pass for the wrong
+/// reason.
+///
+/// # Example
+/// ```
+/// use yenc::corrupt::{Corruption, corrupt};
+///
+/// let mut article = Vec::new();
+/// yenc::encode(&b"hello"[..], &mut article, "hello.txt").unwrap();
+///
+/// let damaged = corrupt(&article, &[Corruption::TamperCrc]);
+/// assert!(yenc::decode(&damaged[..], std::io::sink()).is_err());
+/// ```
+pub fn corrupt(article: &[u8], corruptions: &[Corruption]) -> Vec<u8> {
+    let mut data = article.to_vec();
+    for corruption in corruptions {
+        match *corruption {
+            Corruption::FlipBit { offset, bit } => {
+                assert!(bit < 8, "bit {} out of range", bit);
+                assert!(
+                    offset < data.len(),
+                    "offset {} beyond article of {} bytes",
+                    offset,
+                    data.len()
+                );
+                data[offset] ^= 1 << bit;
+            }
+            Corruption::DropLine(line) => {
+                let range = line_range(&data, line)
+                    .unwrap_or_else(|| panic!("article has no line {}", line));
+                data.drain(range);
+            }
+            Corruption::Truncate(len) => {
+                assert!(
+                    len <= data.len(),
+                    "cannot truncate {} bytes to {}",
+                    data.len(),
+                    len
+                );
+                data.truncate(len);
+            }
+            Corruption::TamperCrc => tamper_crc(&mut data),
+        }
+    }
+    data
+}
+
+/// Byte range of 1-based line `line`, including its terminator
+fn line_range(data: &[u8], line: usize) -> Option<std::ops::Range<usize>> {
+    let mut start = 0;
+    for _ in 1..line.max(1) {
+        start += data[start..].iter().position(|&b| b == b'\n')? + 1;
+    }
+    if line == 0 || start >= data.len() {
+        return None;
+    }
+    let end = data[start..]
+        .iter()
+        .position(|&b| b == b'\n')
+        .map_or(data.len(), |pos| start + pos + 1);
+    Some(start..end)
+}
+
+/// Invert every hex digit of the CRC values on the last `=yend` line
+fn tamper_crc(data: &mut [u8]) {
+    let trailer = data
+        .windows(6)
+        .rposition(|w| w == b"=yend ")
+        .expect("article has no =yend line");
+    let end = data[trailer..]
+        .iter()
+        .position(|&b| b == b'\r' || b == b'\n')
+        .map_or(data.len(), |pos| trailer + pos);
+
+    let mut tampered = false;
+    let mut pos = trailer;
+    while let Some(found) = data[pos..end].windows(6).position(|w| w == b"crc32=") {
+        let digits = pos + found + 6;
+        for digit in data[digits..end]
+            .iter_mut()
+            .take_while(|b| b.is_ascii_hexdigit())
+        {
+            let value = (*digit as char).to_digit(16).unwrap() as u8;
+            *digit = b"0123456789abcdef"[usize::from(value ^ 0xf)];
+            tampered = true;
+        }
+        pos = digits;
+    }
+    assert!(tampered, "article has no CRC to tamper with");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::YencError;
+
+    const ARTICLE: &[u8] =
+        b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5 crc32=515ad3cc\n";
+
+    #[test]
+    fn test_flip_bit() {
+        let damaged = corrupt(ARTICLE, &[Corruption::FlipBit { offset: 38, bit: 0 }]);
+        assert_eq!(damaged[38], b'*' ^ 1);
+        assert_eq!(damaged.len(), ARTICLE.len());
+
+        let result = crate::decode(&damaged[..], std::io::sink());
+        assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+    }
+
+    #[test]
+    fn test_drop_line() {
+        let damaged = corrupt(ARTICLE, &[Corruption::DropLine(3)]);
+        assert_eq!(damaged, &ARTICLE[..45]);
+
+        let damaged = corrupt(ARTICLE, &[Corruption::DropLine(1)]);
+        assert!(damaged.starts_with(b"*+,-=n\n"));
+    }
+
+    #[test]
+    fn test_truncate() {
+        let damaged = corrupt(ARTICLE, &[Corruption::Truncate(41)]);
+        assert_eq!(damaged, &ARTICLE[..41]);
+    }
+
+    #[test]
+    fn test_tamper_crc() {
+        let damaged = corrupt(ARTICLE, &[Corruption::TamperCrc]);
+        assert!(damaged.ends_with(b"crc32=aea52c33\n"));
+
+        let part = b"=ybegin part=1 total=1 line=128 size=5 name=test.bin\n\
+                     =ypart begin=1 end=5\n\
+                     *+,-=n\n\
+                     =yend size=5 part=1 pcrc32=515ad3cc crc32=515ad3cc\n";
+        let damaged = corrupt(part, &[Corruption::TamperCrc]);
+        assert!(damaged.ends_with(b"pcrc32=aea52c33 crc32=aea52c33\n"));
+    }
+
+    #[test]
+    fn test_corruptions_apply_in_order() {
+        let damaged = corrupt(
+            ARTICLE,
+            &[
+                Corruption::DropLine(2),
+                Corruption::FlipBit { offset: 38, bit: 1 },
+            ],
+        );
+        assert_eq!(damaged[38], b'=' ^ 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "article has no line 4")]
+    fn test_drop_missing_line_panics() {
+        corrupt(ARTICLE, &[Corruption::DropLine(4)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "no CRC")]
+    fn test_tamper_without_crc_panics() {
+        corrupt(
+            b"=ybegin line=128 size=0 name=a\n=yend size=0\n",
+            &[Corruption::TamperCrc],
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! CRC32 arithmetic for multi-part files
+
+use crc32fast::Hasher;
+
+/// CRC32 of two pieces of data one after the other
+///
+/// `crc_a` and `crc_b` are the CRC32s of the two pieces and `len_b` is the
+/// length of the second one in bytes. This lets the `crc32=` of a whole
+/// file be worked out from the `pcrc32=` of its parts without reading the
+/// data again.
+///
+/// # Example
+/// ```
+/// use yenc::crc32;
+///
+/// let crc = crc32::combine(crc32fast::hash(b"Hello, "), crc32fast::hash(b"World!"), 6);
+/// assert_eq!(crc, crc32fast::hash(b"Hello, World!"));
+/// ```
+pub fn combine(crc_a: u32, crc_b: u32, len_b: u64) -> u32 {
+    let mut hasher = Hasher::new_with_initial(crc_a);
+    hasher.combine(&Hasher::new_with_initial_len(crc_b, len_b));
+    hasher.finalize()
+}
+
+/// CRC32 of a whole file from the CRC32 and length of each of its parts
+///
+/// Parts must be given in file order. No parts make an empty file, whose
+/// CRC32 is 0.
+///
+/// # Example
+/// ```
+/// use yenc::crc32;
+///
+/// let parts = [&b"Hello"[..], b", ", b"World!"];
+/// let crc = crc32::combine_parts(parts.iter().map(|part| (crc32fast::hash(part), part.len() as u64)));
+/// assert_eq!(crc, crc32fast::hash(b"Hello, World!"));
+/// ```
+pub fn combine_parts<I: IntoIterator<Item = (u32, u64)>>(parts: I) -> u32 {
+    parts
+        .into_iter()
+        .fold(0, |crc, (part_crc, len)| combine(crc, part_crc, len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combine() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i * 31 + i / 7) as u8).collect();
+        for split in [0, 1, 4999, 10_000] {
+            let (a, b) = data.split_at(split);
+            let crc = combine(crc32fast::hash(a), crc32fast::hash(b), b.len() as u64);
+            assert_eq!(crc, crc32fast::hash(&data));
+        }
+
+        let parts = data
+            .chunks(777)
+            .map(|part| (crc32fast::hash(part), part.len() as u64));
+        assert_eq!(combine_parts(parts), crc32fast::hash(&data));
+        assert_eq!(combine_parts([]), 0);
+    }
+}
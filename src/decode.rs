@@ -1,12 +1,177 @@
 //! yEnc decoding functionality
 
-use std::io::{BufRead, BufReader, Read, Write};
+use std::borrow::Cow;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::ops::{Deref, Range};
+use std::sync::Arc;
 
 use crc32fast::Hasher;
+use memchr::{memchr, memmem};
 
-use crate::consts::{ESCAPE_CHAR, ESCAPE_OFFSET, ESCAPING_CHARS, OFFSET};
+use crate::budget::{MemoryBudget, Reservation};
+use crate::consts::{BUFFER_SIZE, ESCAPE_CHAR, ESCAPE_OFFSET, NEEDS_ESCAPE, OFFSET};
+use crate::dispatch::{self, Backend};
 use crate::error::{Result, YencError};
-use crate::header::{YencHeader, YencPart, YencTrailer};
+use crate::header::{
+    YencHeader, YencPart, YencTrailer, YencVersion, formatting_issue, unknown_keys,
+};
+use crate::scratch::Scratch;
+
+#[cfg(all(feature = "simd", target_arch = "aarch64"))]
+mod neon;
+#[cfg(feature = "portable-simd")]
+mod portable;
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod x86;
+
+/// Decoded bytes collected before they are written out
+const OUTPUT_BLOCK: usize = 32 * 1024;
+
+/// Outcome of decoding an article together with its decoded data
+type DecodedArticle = (DecodeOutcome, Vec<u8>);
+
+/// Outcome of decoding an article together with its decoded lines
+type DecodedSegments<'a> = (DecodeOutcome, Vec<Cow<'a, [u8]>>);
+
+/// What decoding an article found and produced
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeOutcome {
+    /// Parsed `=ybegin` fields
+    ///
+    /// If the header has no `line=` value (or `line=0`), `line_len` is set
+    /// to the longest data line observed.
+    pub header: YencHeader,
+    /// Parsed `=ypart` fields (multi-part articles only)
+    pub part: Option<YencPart>,
+    /// Parsed `=yend` fields, if the trailer was found
+    pub trailer: Option<YencTrailer>,
+    /// Number of decoded bytes written
+    pub bytes_written: u64,
+    /// CRC32 of the decoded data, unless CRC checking is disabled
+    pub crc32: Option<u32>,
+    /// Damage that lenient decoding let pass; strict decoding fails instead
+    pub warnings: Vec<DecodeWarning>,
+    /// Data lines that repair mode replaced with zeros
+    pub damage: DamageMap,
+    /// Bytes after the trailer skipped on the way to the next article, such
+    /// as signatures or tag lines
+    ///
+    /// Only `decode_all` and `decode_all_resync` read past a trailer; every
+    /// other way of decoding stops at the `=yend` line and leaves this 0.
+    pub trailing_bytes: u64,
+}
+
+impl DecodeOutcome {
+    /// Whether the data matched every CRC32 it was checked against
+    ///
+    /// Only ever false with `Decoder::allow_crc_mismatch`; otherwise a
+    /// mismatch fails the decode. Also true when there was nothing to check.
+    pub fn crc_ok(&self) -> bool {
+        !self
+            .warnings
+            .iter()
+            .any(|warning| matches!(warning, DecodeWarning::CrcMismatch { .. }))
+    }
+}
+
+/// Something wrong with an article that did not stop it from decoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DecodeWarning {
+    /// The data decodes to a different number of bytes than the article
+    /// declares: the size of the part range for parts, else the trailer or
+    /// header size
+    SizeMismatch {
+        /// Bytes the keyword lines announce
+        declared: u64,
+        /// Bytes the data decoded to
+        decoded: u64,
+    },
+    /// The CRC32 of the data differs from the trailer's or the one set by
+    /// `Decoder::expected_crc`; only with `Decoder::allow_crc_mismatch`
+    CrcMismatch {
+        /// CRC32 the data should have
+        expected: u32,
+        /// CRC32 of the data as decoded
+        actual: u32,
+    },
+    /// Input ended before the `=yend` line, so data may be missing; a
+    /// downloader will usually want to fetch the article again
+    Truncated {
+        /// Bytes decoded before the input ended
+        decoded: u64,
+    },
+    /// Lines of NUL bytes, as some caching servers pad article bodies with,
+    /// were dropped from the end of the data
+    NulPadding {
+        /// NUL bytes dropped
+        bytes: u64,
+    },
+}
+
+/// Byte ranges of decoded data that repair mode filled with zeros
+///
+/// Offsets are 0-based within the data of the article; for a part, add
+/// `begin - 1` to get offsets in the file. Ranges are sorted, and ranges of
+/// consecutive lines are merged. See `Decoder::repair`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DamageMap {
+    ranges: Vec<Range<u64>>,
+    lines: usize,
+}
+
+impl DamageMap {
+    /// Byte ranges of the data that were replaced
+    pub fn ranges(&self) -> &[Range<u64>] {
+        &self.ranges
+    }
+
+    /// Number of data lines that were replaced
+    ///
+    /// A replaced line that would have decoded to nothing adds no range,
+    /// but still counts here.
+    pub fn lines(&self) -> usize {
+        self.lines
+    }
+
+    /// Whether no line was replaced
+    pub fn is_empty(&self) -> bool {
+        self.lines == 0
+    }
+
+    /// Total number of bytes replaced
+    pub fn damaged_bytes(&self) -> u64 {
+        self.ranges
+            .iter()
+            .map(|range| range.end - range.start)
+            .sum()
+    }
+
+    /// Record a replaced line that held `range` of the data
+    fn add(&mut self, range: Range<u64>) {
+        self.lines += 1;
+        if range.is_empty() {
+            return;
+        }
+        match self.ranges.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ => self.ranges.push(range),
+        }
+    }
+}
+
+/// Keyword lines of an article, read without decoding its data
+#[derive(Debug, Clone, PartialEq)]
+pub struct YencMetadata {
+    /// Parsed `=ybegin` fields, with `line_len` as declared
+    pub header: YencHeader,
+    /// Parsed `=ypart` fields (multi-part articles only)
+    pub part: Option<YencPart>,
+    /// Parsed `=yend` fields, if the trailer was found
+    pub trailer: Option<YencTrailer>,
+}
 
 /// Decode a single yEnc-encoded byte
 #[inline]
@@ -14,129 +179,2062 @@ fn decode_byte(byte: u8) -> u8 {
     byte.wrapping_sub(OFFSET)
 }
 
+/// Decode the byte following an escape character
+///
+/// In strict mode only the bytes the encoder escapes are accepted.
+#[inline]
+fn decode_escaped(byte: u8, strict: bool) -> Result<u8> {
+    let result = decode_byte(byte.wrapping_sub(ESCAPE_OFFSET));
+
+    if strict && !NEEDS_ESCAPE[result as usize] {
+        return Err(YencError::InvalidData(format!(
+            "Invalid escape sequence: ={:02x}",
+            byte
+        )));
+    }
+    Ok(result)
+}
+
+/// Bytes checked one by one for an escape character before `memchr` takes over
+const SHORT_RUN: usize = 16;
+
+/// Scalar decoding, used on its own and for the tails of SIMD kernels
+///
+/// `escaped` carries a pending escape character across calls. `memchr`
+/// finds the escape characters, so the runs between them are decoded as
+/// whole slices rather than tested byte by byte.
+#[inline]
+fn decode_scalar(
+    mut data: &[u8],
+    out: &mut Vec<u8>,
+    escaped: &mut bool,
+    strict: bool,
+) -> Result<()> {
+    loop {
+        if *escaped {
+            let Some((&byte, rest)) = data.split_first() else {
+                return Ok(());
+            };
+            *escaped = false;
+            out.push(decode_escaped(byte, strict)?);
+            data = rest;
+        }
+
+        // Back-to-back escapes are common in escape-heavy data
+        if data.first() == Some(&ESCAPE_CHAR) {
+            *escaped = true;
+            data = &data[1..];
+            continue;
+        }
+
+        // Between dense escapes a short walk beats setting up a search
+        let window = &data[..data.len().min(SHORT_RUN)];
+        let run = match window.iter().position(|&byte| byte == ESCAPE_CHAR) {
+            Some(pos) => pos,
+            None => memchr(ESCAPE_CHAR, &data[window.len()..])
+                .map_or(data.len(), |pos| window.len() + pos),
+        };
+        out.extend(data[..run].iter().map(|&byte| decode_byte(byte)));
+        if run == data.len() {
+            return Ok(());
+        }
+        *escaped = true;
+        data = &data[run + 1..];
+    }
+}
+
+/// Decode an escape-heavy chunk byte by byte
+///
+/// In lenient mode this avoids branching on every escape character, which
+/// the predictor cannot follow at high escape densities.
+///
+/// # Safety
+/// `out` must have at least `chunk.len()` bytes of spare capacity.
+#[cfg(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64")))]
+#[inline(always)]
+unsafe fn decode_dense(
+    chunk: &[u8],
+    out: &mut Vec<u8>,
+    escaped: &mut bool,
+    strict: bool,
+) -> Result<()> {
+    if strict {
+        return decode_scalar(chunk, out, escaped, strict);
+    }
+
+    let ptr = out.as_mut_ptr();
+    let mut len = out.len();
+    let mut pending = *escaped;
+    for &byte in chunk {
+        let is_escape = (byte == ESCAPE_CHAR) & !pending;
+        let offset = if pending {
+            OFFSET.wrapping_add(ESCAPE_OFFSET)
+        } else {
+            OFFSET
+        };
+        // SAFETY: at most one byte is written per input byte, and the
+        // caller reserved `chunk.len()` bytes
+        unsafe { ptr.add(len).write(byte.wrapping_sub(offset)) };
+        len += usize::from(!is_escape);
+        pending = is_escape;
+    }
+    // SAFETY: bytes up to `len` were initialized above
+    unsafe { out.set_len(len) };
+    *escaped = pending;
+    Ok(())
+}
+
 /// Trim whitespaces at the beginning and end of a byte slice
 #[inline]
-fn trim_bytes(line: &[u8]) -> &[u8] {
+pub(crate) fn trim_bytes(line: &[u8]) -> &[u8] {
+    &line[trim_range(line)]
+}
+
+/// Range of `line` left after trimming whitespaces at both ends
+#[inline]
+fn trim_range(line: &[u8]) -> Range<usize> {
     let is_ws = |b: &u8| b" \t\r\n".contains(b);
     let start = line.iter().position(|b| !is_ws(b)).unwrap_or(line.len());
     let end = line
         .iter()
         .rposition(|b| !is_ws(b))
         .map(|i| i + 1)
-        .unwrap_or(0);
-    &line[start..end]
+        .unwrap_or(start);
+    start..end
+}
+
+/// Line ending written by `Decoder::text_mode`
+const NATIVE_LINE_ENDING: &[u8] = if cfg!(windows) { b"\r\n" } else { b"\n" };
+
+/// Writer adapter converting CRLF line endings to the platform's native ones
+struct TextWriter<W> {
+    inner: W,
+    pending_cr: bool,
+}
+
+impl<W: Write> TextWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            pending_cr: false,
+        }
+    }
+
+    /// Write out a CR that was held back waiting for a possible LF
+    fn finish(&mut self) -> io::Result<()> {
+        if self.pending_cr {
+            self.pending_cr = false;
+            self.inner.write_all(b"\r")?;
+        }
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for TextWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+        if self.pending_cr && !rest.is_empty() {
+            self.pending_cr = false;
+            if rest[0] == b'\n' {
+                self.inner.write_all(NATIVE_LINE_ENDING)?;
+                rest = &rest[1..];
+            } else {
+                self.inner.write_all(b"\r")?;
+            }
+        }
+
+        // Runs between CRs go out whole
+        while let Some(pos) = memchr(b'\r', rest) {
+            self.inner.write_all(&rest[..pos])?;
+            match rest.get(pos + 1) {
+                Some(b'\n') => {
+                    self.inner.write_all(NATIVE_LINE_ENDING)?;
+                    rest = &rest[pos + 2..];
+                }
+                Some(_) => {
+                    self.inner.write_all(b"\r")?;
+                    rest = &rest[pos + 1..];
+                }
+                None => {
+                    self.pending_cr = true;
+                    rest = &[];
+                }
+            }
+        }
+        self.inner.write_all(rest)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writer passing every write on to each of several writers in turn
+struct TeeWriter<'a, 'b> {
+    writers: &'a mut [&'b mut dyn Write],
+}
+
+impl Write for TeeWriter<'_, '_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for writer in self.writers.iter_mut() {
+            writer.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for writer in self.writers.iter_mut() {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Source of input lines for the decoder
+trait LineSource {
+    /// The line found by the last `next_line`, including its line ending
+    fn line(&self) -> &[u8];
+
+    /// Advance to the next line, returning its length (0 at the end of input)
+    ///
+    /// Lines the memory budget has no room for fail with
+    /// `YencError::MemoryLimitExceeded`.
+    fn next_line(&mut self) -> Result<usize>;
+}
+
+/// Lines of an article that is already in memory, such as a mapped file
+struct SliceLines<'a> {
+    data: &'a [u8],
+    line: Range<usize>,
+    limit: Option<usize>,
+}
+
+impl<'a> SliceLines<'a> {
+    fn new(data: &'a [u8], limit: Option<usize>) -> Self {
+        Self {
+            data,
+            line: 0..0,
+            limit,
+        }
+    }
+}
+
+impl LineSource for SliceLines<'_> {
+    fn line(&self) -> &[u8] {
+        &self.data[self.line.clone()]
+    }
+
+    fn next_line(&mut self) -> Result<usize> {
+        let start = self.line.end;
+        let rest = &self.data[start..];
+        let len = memchr(b'\n', rest).map_or(rest.len(), |pos| pos + 1);
+        if let Some(limit) = self.limit {
+            if len > limit {
+                return Err(YencError::MemoryLimitExceeded { limit });
+            }
+        }
+        self.line = start..start + len;
+        Ok(len)
+    }
+}
+
+/// Input buffer that hands out whole lines, refilled a block at a time
+///
+/// Lines are borrowed from the buffer, so many lines are parsed per read
+/// call and none is copied. Only a line cut off at the end of the buffer is
+/// moved to the front before the next read.
+struct LineReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    /// Current line
+    line: Range<usize>,
+    /// End of the data read so far
+    end: usize,
+    block: usize,
+    /// Memory for `buf`, grown for lines longer than a block
+    reservation: Reservation,
+    /// Memory for the output block of the call, held as long as the lines
+    _output: Reservation,
+    eof: bool,
+}
+
+impl<R: Read> LineReader<R> {
+    fn new(
+        inner: R,
+        buf: Vec<u8>,
+        block: usize,
+        reservation: Reservation,
+        output: Reservation,
+    ) -> Self {
+        Self {
+            inner,
+            buf,
+            line: 0..0,
+            end: 0,
+            block,
+            reservation,
+            _output: output,
+            eof: false,
+        }
+    }
+
+    /// Hand back the read buffer for reuse
+    fn into_buffer(self) -> Vec<u8> {
+        self.buf
+    }
 }
 
-/// Decoder with configurable options
-#[derive(Debug, Clone)]
-pub struct Decoder {
-    strict: bool,
-    validate_crc: bool,
-}
+impl<R: Read> LineSource for LineReader<R> {
+    fn line(&self) -> &[u8] {
+        &self.buf[self.line.clone()]
+    }
+
+    fn next_line(&mut self) -> Result<usize> {
+        let mut start = self.line.end;
+        let mut scanned = start;
+        loop {
+            let found = memchr(b'\n', &self.buf[scanned..self.end]).map(|pos| scanned + pos + 1);
+            let line_end = found.unwrap_or(self.end);
+            if found.is_some() || self.eof {
+                self.line = start..line_end;
+                return Ok(line_end - start);
+            }
+            scanned = self.end;
+
+            // Move the partial line to the front and read after it
+            if start > 0 {
+                self.buf.copy_within(start..self.end, 0);
+                scanned -= start;
+                self.end -= start;
+                start = 0;
+                self.line = 0..0;
+            }
+            // A whole block, or as much of one as the budget has room for
+            let room = self.reservation.grow(self.end + 1, self.end + self.block)?;
+            let want = self.block.min(room - self.end);
+            if self.buf.len() < self.end + want {
+                self.buf.resize(self.end + want, 0);
+            }
+            match self.inner.read(&mut self.buf[self.end..self.end + want]) {
+                Ok(0) => self.eof = true,
+                Ok(n) => self.end += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+}
+
+/// Find the start of a `=yend ` keyword within a line
+///
+/// `=y` never results from escaping a critical character, so it can only
+/// mark a keyword, even when a broken client joined it to the last data line.
+#[inline]
+pub(crate) fn find_trailer(line: &[u8]) -> Option<usize> {
+    memmem::find(line, b"=yend ")
+}
+
+/// Per-operation decoding state: pending escape, running CRC32 and scratch block
+///
+/// Kept apart from `Decoder` so the configuration stays immutable and can be
+/// shared between threads while every operation owns its own state.
+pub(crate) struct DecodeState {
+    escaped: bool,
+    hasher: Option<Hasher>,
+    block: Vec<u8>,
+    /// Pending bytes that make a block worth writing out
+    block_size: usize,
+    /// Bytes at the start of `block` already hashed
+    hashed: usize,
+    bytes_written: u64,
+    longest_line: usize,
+    damage: DamageMap,
+}
+
+impl DecodeState {
+    pub(crate) fn new(validate_crc: bool) -> Self {
+        Self {
+            escaped: false,
+            hasher: validate_crc.then(Hasher::new),
+            block: Vec::new(),
+            block_size: OUTPUT_BLOCK,
+            hashed: 0,
+            bytes_written: 0,
+            longest_line: 0,
+            damage: DamageMap::default(),
+        }
+    }
+
+    /// Collect output in `block` rather than a fresh allocation
+    pub(crate) fn with_block(mut self, mut block: Vec<u8>) -> Self {
+        block.clear();
+        self.block = block;
+        self
+    }
+
+    /// Write out blocks of `block_size` bytes rather than `OUTPUT_BLOCK`
+    pub(crate) fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Hand back the output block for reuse
+    pub(crate) fn into_block(self) -> Vec<u8> {
+        self.block
+    }
+
+    /// Decode one data line with `decoder`, writing to `writer` in blocks
+    ///
+    /// Lines are collected until a block's worth of bytes is pending, so even an
+    /// unbuffered writer sees few, large writes, and the hasher whole blocks
+    /// rather than a call per line. Call `flush` once the data ends. A line
+    /// that fails to decode is dropped as a whole.
+    pub(crate) fn write_line<W: Write>(
+        &mut self,
+        decoder: &Decoder,
+        data: &[u8],
+        writer: &mut W,
+    ) -> Result<()> {
+        self.decode_line(decoder, data)?;
+        if self.block.len() >= self.block_size {
+            self.flush(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Decode one data line with `decoder` onto the pending lines
+    ///
+    /// A line that fails to decode is dropped as a whole.
+    fn decode_line(&mut self, decoder: &Decoder, data: &[u8]) -> Result<()> {
+        self.longest_line = self.longest_line.max(data.len());
+
+        let start = self.block.len();
+        let repaired = match decoder.repair_line(data, &mut self.block, &mut self.escaped) {
+            Ok(repaired) => repaired,
+            Err(err) => {
+                self.block.truncate(start);
+                return Err(err);
+            }
+        };
+        let len = (self.block.len() - start) as u64;
+        if repaired {
+            self.damage
+                .add(self.bytes_written..self.bytes_written + len);
+        }
+        self.bytes_written += len;
+        Ok(())
+    }
+
+    /// Decode one data line for `Decoder::decode_segments`, adding it to
+    /// `segments`
+    ///
+    /// An escape-free line decodes to as many bytes, so it is decoded in
+    /// place and borrowed. Other lines are decoded onto the pending lines,
+    /// which then become an owned segment.
+    fn push_segment<'a>(
+        &mut self,
+        decoder: &Decoder,
+        data: &'a mut [u8],
+        segments: &mut Vec<Cow<'a, [u8]>>,
+    ) -> Result<()> {
+        if self.escaped || memchr(ESCAPE_CHAR, data).is_some() {
+            self.decode_line(decoder, data)?;
+            self.take_segment(segments);
+            return Ok(());
+        }
+
+        self.take_segment(segments);
+        self.longest_line = self.longest_line.max(data.len());
+        for byte in data.iter_mut() {
+            *byte = decode_byte(*byte);
+        }
+        if let Some(ref mut hasher) = self.hasher {
+            hasher.update(data);
+        }
+        self.bytes_written += data.len() as u64;
+        if !data.is_empty() {
+            segments.push(Cow::Borrowed(data));
+        }
+        Ok(())
+    }
+
+    /// Move the pending lines into an owned segment
+    fn take_segment(&mut self, segments: &mut Vec<Cow<'_, [u8]>>) {
+        self.hash_pending();
+        self.hashed = 0;
+        if !self.block.is_empty() {
+            segments.push(Cow::Owned(mem::take(&mut self.block)));
+        }
+    }
+
+    /// Lines decoded since the last `flush` or `consume`
+    fn pending(&self) -> &[u8] {
+        &self.block
+    }
+
+    /// Drop the pending lines once the caller has taken them from `pending`
+    fn consume(&mut self) {
+        self.hash_pending();
+        self.block.clear();
+        self.hashed = 0;
+    }
+
+    /// Write out the lines still pending
+    pub(crate) fn flush<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.hash_pending();
+        writer.write_all(&self.block)?;
+        self.block.clear();
+        self.hashed = 0;
+        Ok(())
+    }
+
+    /// Hash the pending lines in one go while the block is still in cache;
+    /// crc32fast uses PCLMULQDQ / ARMv8 CRC instructions where available,
+    /// which only reach full speed on long slices
+    fn hash_pending(&mut self) {
+        if let Some(ref mut hasher) = self.hasher {
+            hasher.update(&self.block[self.hashed..]);
+        }
+        self.hashed = self.block.len();
+    }
+
+    /// CRC32 of everything decoded so far, if validation is enabled
+    pub(crate) fn crc(&mut self) -> Option<u32> {
+        self.hash_pending();
+        self.hasher.clone().map(Hasher::finalize)
+    }
+}
+
+/// Keyword lines of an article and what `Decoder::data_line` keeps track
+/// of between its data lines
+#[derive(Debug, Clone)]
+struct ArticleBody {
+    header: YencHeader,
+    part: Option<YencPart>,
+    declared_line_len: Option<usize>,
+    /// NUL bytes held back in case they pad the end of the data
+    padding: usize,
+}
+
+impl ArticleBody {
+    fn new(header: YencHeader) -> Self {
+        Self {
+            declared_line_len: header.line_len.filter(|&len| len > 0),
+            header,
+            part: None,
+            padding: 0,
+        }
+    }
+}
+
+/// Line of the data section as `Decoder::data_line` takes it apart
+///
+/// Implemented for shared lines and, so escape-free lines can be decoded in
+/// place, for mutable ones.
+trait DataLine: Deref<Target = [u8]> + Sized {
+    /// Cut the line down to `range`, split at `trailer` within it if given
+    fn split(self, range: Range<usize>, trailer: Option<usize>) -> (Self, Option<Self>);
+}
+
+impl DataLine for &[u8] {
+    fn split(self, range: Range<usize>, trailer: Option<usize>) -> (Self, Option<Self>) {
+        let line = &self[range];
+        match trailer {
+            Some(pos) => (&line[..pos], Some(&line[pos..])),
+            None => (line, None),
+        }
+    }
+}
+
+impl DataLine for &mut [u8] {
+    fn split(self, range: Range<usize>, trailer: Option<usize>) -> (Self, Option<Self>) {
+        let line = &mut self[range];
+        match trailer {
+            Some(pos) => {
+                let (data, trailer) = line.split_at_mut(pos);
+                (data, Some(trailer))
+            }
+            None => (line, None),
+        }
+    }
+}
+
+/// Decoder with configurable options
+///
+/// A `Decoder` only holds configuration; all per-operation state lives on
+/// the stack of the call. It is `Send + Sync`, so one configured decoder can
+/// be shared by reference (or in an `Arc`) across a whole thread pool.
+///
+/// Instances are cheap to create and to reuse for any number of calls. To
+/// also reuse the buffers of each call, pass a `Scratch` to `decode_with_scratch`.
+#[derive(Debug, Clone)]
+pub struct Decoder {
+    strict: bool,
+    pub(crate) validate_crc: bool,
+    budget: Option<Arc<MemoryBudget>>,
+    pub(crate) text_mode: bool,
+    chunk_size: Option<usize>,
+    max_name_length: Option<usize>,
+    name_chars: Option<fn(char) -> bool>,
+    reject_unknown_keywords: bool,
+    trim_whitespace: bool,
+    backend: Option<Backend>,
+    expected_crc: Option<u32>,
+    expected_pcrc: Option<u32>,
+    pub(crate) repair: bool,
+    pub(crate) allow_crc_mismatch: bool,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Self {
+            strict: false,
+            validate_crc: true,
+            budget: None,
+            text_mode: false,
+            chunk_size: None,
+            max_name_length: None,
+            name_chars: None,
+            reject_unknown_keywords: false,
+            trim_whitespace: false,
+            backend: None,
+            expected_crc: None,
+            expected_pcrc: None,
+            repair: false,
+            allow_crc_mismatch: false,
+        }
+    }
+}
+
+impl Decoder {
+    /// Create a new decoder with default settings
+    ///
+    /// Default settings:
+    /// - Lenient mode (accepts any escaped character)
+    /// - CRC validation enabled
+    /// - No memory budget
+    /// - Binary mode (output is written as decoded)
+    /// - Input read in 8 KiB chunks
+    /// - No limits on the header file name
+    /// - Whitespace at the ends of data lines kept as data
+    /// - Kernels picked automatically for the CPU and line length
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable strict validation of escape sequences
+    ///
+    /// When enabled, only characters that should be escaped according to
+    /// the yEnc spec are accepted. Invalid escape sequences will cause an error.
+    ///
+    /// Articles are also held to the rules of the yEnc version their header
+    /// follows (see `YencHeader::version`): 1.0 articles may have no
+    /// `=ypart` line and no `part=` on the trailer, part numbers start at 1
+    /// and stay within `total=`, and 1.2 parts need a `pcrc32=` on the
+    /// trailer. A `total=` without `part=` fits no version.
+    ///
+    /// The header must name a file: an empty `name=` fails with
+    /// `YencError::InvalidName`.
+    ///
+    /// A data line ending in a lone `=` fails in strict mode. Otherwise the
+    /// escape applies to the first byte of the next data line, which is what
+    /// broken encoders splitting an escape pair across lines mean. Either
+    /// way, data ending on an escape, right before the trailer or the end of
+    /// input, fails with `YencError::InvalidData`.
+    ///
+    /// Keyword lines must be formatted as the spec has them: tokens
+    /// separated by single spaces, no whitespace around `=` and no key given
+    /// twice. Lenient decoding reads `size =123` as `size=123` and takes
+    /// the last of repeated keys.
+    ///
+    /// Data lines longer than the header's `line=` value allows (one more,
+    /// for an escape pair wrapped at the end) fail, as gateways that join
+    /// or reflow lines produce them.
+    ///
+    /// Input that ends before the `=yend` line fails with
+    /// `YencError::Truncated`, which says how much was decoded. Lenient
+    /// decoding hands over what was there with `DecodeWarning::Truncated`.
+    ///
+    /// Data that decodes to a different size than declared (the part range
+    /// for parts, else the trailer and header sizes) fails too. Lenient
+    /// decoding reports it as `DecodeWarning::SizeMismatch` instead, which
+    /// matters most for articles without a CRC32 to catch the damage.
+    ///
+    /// Lines of nothing but NUL bytes between the last data line and the
+    /// trailer or the end of input are decoded as data. Lenient decoding
+    /// drops them as padding with `DecodeWarning::NulPadding`.
+    ///
+    /// Strict decoding does not go together with `repair` or
+    /// `allow_crc_mismatch`, which hand over damaged data it would reject;
+    /// such a decoder fails with `YencError::InvalidConfig`.
+    pub fn strict(mut self) -> Self {
+        self.strict = true;
+        self
+    }
+
+    /// Disable CRC validation
+    ///
+    /// By default, CRC32 checksums are validated if present in the trailer.
+    pub fn no_crc_check(mut self) -> Self {
+        self.validate_crc = false;
+        self
+    }
+
+    /// Check single-part articles against a CRC32 known from elsewhere
+    ///
+    /// SFV files and some NZB indexers carry the checksum of a file even
+    /// when its article has no `crc32=` on the trailer. The data is checked
+    /// against `crc` as well as against the trailer, and a mismatch fails
+    /// with `YencError::CrcMismatch`. Parts are checked against
+    /// `expected_pcrc` instead. Combined with `no_crc_check`, which would
+    /// skip the check, it fails with `YencError::InvalidConfig`.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::{Decoder, YencError};
+    ///
+    /// let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5\n";
+    /// let decoder = Decoder::new().expected_crc(0x515ad3cc);
+    /// assert!(decoder.decode_to_vec(input).is_ok());
+    /// let result = Decoder::new().expected_crc(0).decode_to_vec(input);
+    /// assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+    /// ```
+    pub fn expected_crc(mut self, crc: u32) -> Self {
+        self.expected_crc = Some(crc);
+        self
+    }
+
+    /// Check parts against a part CRC32 known from elsewhere
+    ///
+    /// Like `expected_crc`, for articles with an `=ypart` line, whose data
+    /// is checked against `crc` as well as against the trailer's `pcrc32=`.
+    /// Does not go together with `no_crc_check` either.
+    pub fn expected_pcrc(mut self, crc: u32) -> Self {
+        self.expected_pcrc = Some(crc);
+        self
+    }
+
+    /// Hand over the data of articles whose CRC32 does not match
+    ///
+    /// A mismatch normally fails with `YencError::CrcMismatch` once all the
+    /// data has been decoded and written. With this option decoding
+    /// completes instead, and the outcome carries a
+    /// `DecodeWarning::CrcMismatch` with both values (see
+    /// `DecodeOutcome::crc_ok`), so the data can still go to PAR2 repair.
+    /// Pulling decoded data with `reader` or `events`, the warning shows up
+    /// in their `warnings` once the article has ended.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::{DecodeWarning, Decoder};
+    ///
+    /// let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5 crc32=ffffffff\n";
+    /// let (outcome, data) = Decoder::new().allow_crc_mismatch().decode_to_vec(input).unwrap();
+    /// assert_eq!(data, [0, 1, 2, 3, 4]);
+    /// assert!(!outcome.crc_ok());
+    /// assert_eq!(
+    ///     outcome.warnings,
+    ///     [DecodeWarning::CrcMismatch { expected: 0xffffffff, actual: 0x515ad3cc }]
+    /// );
+    /// ```
+    pub fn allow_crc_mismatch(mut self) -> Self {
+        self.allow_crc_mismatch = true;
+        self
+    }
+
+    /// Replace corrupt data lines with zeros instead of failing
+    ///
+    /// A data line with an escape sequence no encoder would write is taken
+    /// as damaged. Its bytes are written as zeros, as many as it decodes to
+    /// anyway, so everything after it stays at the right offset, and
+    /// decoding carries on. Strict decoding, which fails on such lines,
+    /// cannot be combined with it. The outcome's
+    /// `damage` lists the replaced ranges, which is what recovery with PAR2
+    /// needs to know.
+    ///
+    /// Replaced lines make the CRC32 of the data differ from the trailer's,
+    /// so that check is skipped once a line has been replaced. Everything
+    /// else is checked as usual.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Decoder;
+    ///
+    /// // `=K` is not an escape any encoder writes
+    /// let input = b"=ybegin line=4 size=8 name=a\nKLMN\nK=KMN\n=yend size=8\n";
+    /// let (outcome, data) = Decoder::new().repair().decode_to_vec(input).unwrap();
+    /// assert_eq!(data, b"!\"#$\0\0\0\0");
+    /// assert_eq!(outcome.damage.ranges(), vec![4..8]);
+    /// ```
+    pub fn repair(mut self) -> Self {
+        self.repair = true;
+        self
+    }
+
+    /// Cap the memory used for buffering, in total across concurrent calls
+    ///
+    /// The budget is shared by this decoder, its clones and every call made
+    /// through them, such as the parts `decode_parts_parallel` decodes at
+    /// once and the blocks `decode_pipelined` keeps in flight. Each call
+    /// reserves its read buffer and output block before it starts, waiting
+    /// while other calls hold the rest of the budget. Both buffers are cut
+    /// down to a quarter of the budget at most, whatever `chunk_size` says.
+    ///
+    /// The read buffer grows for lines longer than a chunk as far as the
+    /// budget has room at the time; lines that do not fit are rejected with
+    /// `YencError::MemoryLimitExceeded`, as are lines longer than the whole
+    /// budget in an article already held in memory.
+    ///
+    /// Calling `max_memory` again starts a new budget.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::{Decoder, YencError};
+    ///
+    /// let decoder = Decoder::new().max_memory(256);
+    /// let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
+    /// assert!(decoder.decode_to_vec(input).is_ok());
+    ///
+    /// // The header line alone is longer than the whole budget
+    /// let name = "x".repeat(300);
+    /// let input = format!("=ybegin line=128 size=5 name={name}\nKLMNO\n=yend size=5\n");
+    /// assert!(matches!(
+    ///     decoder.decode(input.as_bytes(), Vec::new()),
+    ///     Err(YencError::MemoryLimitExceeded { limit: 256 })
+    /// ));
+    /// ```
+    pub fn max_memory(mut self, bytes: usize) -> Self {
+        self.budget = Some(Arc::new(MemoryBudget::new(bytes)));
+        self
+    }
+
+    /// Decode as text, converting CRLF line endings to the platform's native ones
+    ///
+    /// CRC32 validation still applies to the data as it was encoded. Decoding
+    /// into place, as `decode_into` and `decode_parts_parallel` do, needs the
+    /// decoded bytes to match the part ranges, so those reject text mode with
+    /// `YencError::InvalidConfig`.
+    pub fn text_mode(mut self) -> Self {
+        self.text_mode = true;
+        self
+    }
+
+    /// Set the size of the chunks the input is read in
+    ///
+    /// Larger chunks mean fewer read calls, which helps with high-latency
+    /// sources; smaller chunks keep the working set in cache. Defaults to
+    /// 8 KiB, or the memory budget if that is smaller. A chunk size over the
+    /// memory budget fails with `YencError::InvalidConfig`.
+    pub fn chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk_size = Some(bytes.max(1));
+        self
+    }
+
+    /// Reject headers whose `name=` value is longer than `bytes`
+    ///
+    /// Names come straight from the article and may be posted by anyone;
+    /// this keeps kilobyte-long names out of logs and file systems. Such
+    /// headers fail with `YencError::InvalidName`.
+    pub fn max_name_length(mut self, bytes: usize) -> Self {
+        self.max_name_length = Some(bytes);
+        self
+    }
+
+    /// Reject headers whose `name=` value contains a character for which
+    /// `allowed` returns false
+    ///
+    /// Bytes of the name that are not valid UTF-8 are checked as U+FFFD.
+    /// Such headers fail with `YencError::InvalidName`.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::{Decoder, YencError};
+    ///
+    /// let input = b"=ybegin line=128 size=0 name=bell\x07.txt\n=yend size=0\n";
+    /// let result = Decoder::new()
+    ///     .name_chars(|c| !c.is_control())
+    ///     .decode(&input[..], std::io::sink());
+    /// assert!(matches!(result, Err(YencError::InvalidName(_))));
+    /// ```
+    pub fn name_chars(mut self, allowed: fn(char) -> bool) -> Self {
+        self.name_chars = Some(allowed);
+        self
+    }
+
+    /// Reject keyword lines containing keys the spec does not define
+    ///
+    /// By default unknown keys on `=ybegin`, `=ypart` and `=yend` lines are
+    /// ignored. Combined with `strict`, this is meant for conformance testing
+    /// of encoders, where stray fields point at a bug. Such lines fail with
+    /// `YencError::InvalidHeader`.
+    pub fn reject_unknown_keywords(mut self) -> Self {
+        self.reject_unknown_keywords = true;
+        self
+    }
+
+    /// Strip spaces and tabs from both ends of data lines
+    ///
+    /// By default only the line ending is dropped, as a space or tab at
+    /// either end of a data line is payload from encoders that do not escape
+    /// them. This brings back the old behavior of trimming all whitespace,
+    /// for articles padded with whitespace on their way through a mail or
+    /// news gateway. Keyword lines are trimmed either way.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Decoder;
+    ///
+    /// // An unescaped tab encodes 0xdf, a space 0xf6
+    /// let input = b"=ybegin line=128 size=3 name=a\n\tK \n=yend size=3\n";
+    /// let (_, data) = Decoder::new().decode_to_vec(input).unwrap();
+    /// assert_eq!(data, b"\xdf!\xf6");
+    /// let (_, data) = Decoder::new().trim_whitespace().decode_to_vec(input).unwrap();
+    /// assert_eq!(data, b"!");
+    /// ```
+    pub fn trim_whitespace(mut self) -> Self {
+        self.trim_whitespace = true;
+        self
+    }
+
+    /// Always decode with the kernels of `backend`
+    ///
+    /// By default each line goes to the fastest kernels the CPU supports,
+    /// with short lines left to the scalar code. Pinning a backend is mainly
+    /// useful for benchmarking and for ruling out a kernel when chasing a
+    /// bug. A backend the CPU does not support is ignored.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Validate the configuration
+    ///
+    /// Decoding performs the same checks, so calling this is optional; it
+    /// lets invalid settings be reported when the decoder is configured
+    /// rather than when it is first used.
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` for a zero memory budget, a chunk
+    /// size over the memory budget, and options that contradict each other:
+    /// `strict` with `repair` or `allow_crc_mismatch`, and `no_crc_check`
+    /// with `expected_crc` or `expected_pcrc`.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::{Decoder, YencError};
+    ///
+    /// assert!(Decoder::new().strict().try_build().is_ok());
+    /// assert!(matches!(
+    ///     Decoder::new().strict().repair().try_build(),
+    ///     Err(YencError::InvalidConfig(_))
+    /// ));
+    /// ```
+    pub fn try_build(self) -> Result<Self> {
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Check that the options are within bounds and consistent
+    fn validate(&self) -> Result<()> {
+        let max_memory = self.memory_limit();
+        if max_memory == Some(0) {
+            return Err(YencError::InvalidConfig(
+                "memory budget must be greater than zero".to_string(),
+            ));
+        }
+        if let (Some(chunk_size), Some(max_memory)) = (self.chunk_size, max_memory) {
+            if chunk_size > max_memory {
+                return Err(YencError::InvalidConfig(format!(
+                    "chunk size of {} bytes exceeds the memory budget of {} bytes",
+                    chunk_size, max_memory
+                )));
+            }
+        }
+        if self.strict && self.repair {
+            return Err(YencError::InvalidConfig(
+                "strict decoding rejects the lines repair would replace".to_string(),
+            ));
+        }
+        if self.strict && self.allow_crc_mismatch {
+            return Err(YencError::InvalidConfig(
+                "strict decoding rejects the CRC32 mismatches it would allow".to_string(),
+            ));
+        }
+        if !self.validate_crc && (self.expected_crc.is_some() || self.expected_pcrc.is_some()) {
+            return Err(YencError::InvalidConfig(
+                "an expected CRC32 cannot be checked with CRC validation disabled".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Size of the memory budget, if one is set
+    pub(crate) fn memory_limit(&self) -> Option<usize> {
+        self.budget.as_ref().map(|budget| budget.limit())
+    }
+
+    /// Sizes of the read buffer and of the output block of one call
+    ///
+    /// With a memory budget, each is at most a quarter of it, leaving the
+    /// rest for long lines and for other calls.
+    fn buffer_sizes(&self) -> (usize, usize) {
+        let chunk_size = self.chunk_size.unwrap_or(BUFFER_SIZE);
+        match &self.budget {
+            Some(budget) => {
+                let quarter = (budget.limit() / 4).max(1);
+                (chunk_size.min(quarter), OUTPUT_BLOCK.min(quarter))
+            }
+            None => (chunk_size, OUTPUT_BLOCK),
+        }
+    }
+
+    /// Reserve the read buffer and output block of one call, plus `extra` bytes
+    ///
+    /// Waits while other calls hold too much of the memory budget.
+    pub(crate) fn reserve(&self, extra: usize) -> Result<Reservation> {
+        let (read, block) = self.buffer_sizes();
+        Reservation::take(self.budget.as_ref(), read + block + extra)
+    }
+
+    /// Lines of `reader`, read into `buf` within the memory in `reservation`
+    fn line_reader<R: Read>(
+        &self,
+        reader: R,
+        buf: Vec<u8>,
+        mut reservation: Reservation,
+    ) -> LineReader<R> {
+        let (read, block) = self.buffer_sizes();
+        let output = reservation.split_off(block);
+        LineReader::new(reader, buf, read, reservation, output)
+    }
+
+    /// Fresh per-call state with this decoder's settings
+    pub(crate) fn state(&self) -> DecodeState {
+        DecodeState::new(self.validate_crc).with_block_size(self.buffer_sizes().1)
+    }
+
+    /// Decode one line of yEnc data, appending the decoded bytes to `out`
+    ///
+    /// `escaped` carries a pending escape character across calls. When a
+    /// vector backend is selected, runs without escapes are decoded 16 or 32
+    /// bytes at a time and only escape pairs and the tail go byte by byte.
+    #[inline]
+    fn decode_line(&self, data: &[u8], out: &mut Vec<u8>, escaped: &mut bool) -> Result<()> {
+        self.decode_line_as(data, out, escaped, self.strict)
+    }
+
+    /// Decode one line like `decode_line`, checking escape sequences if
+    /// `strict`
+    #[inline]
+    fn decode_line_as(
+        &self,
+        data: &[u8],
+        out: &mut Vec<u8>,
+        escaped: &mut bool,
+        strict: bool,
+    ) -> Result<()> {
+        out.reserve(data.len());
+
+        let consumed = match dispatch::select(self.backend, data.len()) {
+            #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+            backend @ (Backend::Sse2 | Backend::Avx2) => {
+                x86::decode(backend, data, out, escaped, strict)?
+            }
+            #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+            Backend::Neon => neon::decode(data, out, escaped, strict)?,
+            #[cfg(feature = "portable-simd")]
+            Backend::Portable => portable::decode(data, out, escaped, strict)?,
+            _ => 0,
+        };
+
+        decode_scalar(&data[consumed..], out, escaped, strict)?;
+        if self.strict && *escaped {
+            return Err(YencError::InvalidData(
+                "Escape character at end of line".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Decode one line like `decode_line`, replacing it with zeros in repair
+    /// mode if it does not pass the strict checks
+    ///
+    /// Returns whether the line was replaced.
+    #[inline]
+    fn repair_line(&self, data: &[u8], out: &mut Vec<u8>, escaped: &mut bool) -> Result<bool> {
+        if !self.repair {
+            self.decode_line(data, out, escaped)?;
+            return Ok(false);
+        }
+
+        let (start, was_escaped) = (out.len(), *escaped);
+        if self.decode_line_as(data, out, escaped, true).is_ok() {
+            return Ok(false);
+        }
+        // Lenient decoding tells how many bytes the line was meant to hold;
+        // repair mode is never strict, so it cannot fail
+        out.truncate(start);
+        *escaped = was_escaped;
+        let _ = self.decode_line_as(data, out, escaped, false);
+        out[start..].fill(0);
+        Ok(true)
+    }
+
+    /// Check the header file name against the configured limits
+    ///
+    /// The name itself is left out of error messages, since it is exactly the
+    /// kind of input that should not reach logs unchecked.
+    fn check_name(&self, name: &[u8]) -> Result<()> {
+        if let Some(limit) = self.max_name_length {
+            if name.len() > limit {
+                return Err(YencError::InvalidName(format!(
+                    "name is {} bytes, limit is {}",
+                    name.len(),
+                    limit
+                )));
+            }
+        }
+        if let Some(allowed) = self.name_chars {
+            // Bytes that are not UTF-8 are checked as U+FFFD
+            let mut pos = 0;
+            for chunk in name.utf8_chunks() {
+                let chars = chunk.valid().char_indices().map(|(i, c)| (pos + i, c));
+                let invalid = (!chunk.invalid().is_empty())
+                    .then_some((pos + chunk.valid().len(), char::REPLACEMENT_CHARACTER));
+                if let Some((pos, c)) = chars.chain(invalid).find(|&(_, c)| !allowed(c)) {
+                    return Err(YencError::InvalidName(format!(
+                        "disallowed character U+{:04X} at byte {}",
+                        c as u32, pos
+                    )));
+                }
+                pos += chunk.valid().len() + chunk.invalid().len();
+            }
+        }
+        Ok(())
+    }
+
+    /// Check a keyword line for unknown keys if they are to be rejected,
+    /// and in strict mode for formatting the parser merely tolerates
+    fn check_keywords(&self, line: &[u8]) -> Result<()> {
+        let keyword = || {
+            let keyword = line
+                .split(u8::is_ascii_whitespace)
+                .next()
+                .unwrap_or_default();
+            String::from_utf8_lossy(keyword)
+        };
+        if self.reject_unknown_keywords {
+            if let Some(key) = unknown_keys(line).first() {
+                return Err(YencError::InvalidHeader(format!(
+                    "Unknown keyword '{}' on {} line",
+                    String::from_utf8_lossy(key),
+                    keyword()
+                )));
+            }
+        }
+        if self.strict {
+            if let Some(issue) = formatting_issue(line) {
+                return Err(YencError::InvalidHeader(format!(
+                    "{} on {} line",
+                    issue,
+                    keyword()
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parse and check a `=ybegin` line
+    fn parse_header(&self, line: &[u8]) -> Result<YencHeader> {
+        self.check_keywords(line)?;
+        let header = YencHeader::parse_bytes(line)?;
+
+        self.check_name(&header.name)?;
+        if self.strict && header.name.is_empty() {
+            return Err(YencError::InvalidName("name is empty".to_string()));
+        }
+
+        // line= is optional in practice, but strict mode insists on a usable value
+        if self.strict && header.line_len.filter(|&len| len > 0).is_none() {
+            return Err(YencError::MissingField("line".to_string()));
+        }
+        if self.strict {
+            Self::check_version(&header)?;
+        }
+        Ok(header)
+    }
+
+    /// Check that the part fields of a header fit some yEnc version
+    fn check_version(header: &YencHeader) -> Result<()> {
+        match (header.part, header.total) {
+            (None, Some(_)) => Err(YencError::InvalidHeader(
+                "total= without part= fits no yEnc version".to_string(),
+            )),
+            (Some(0), _) => Err(YencError::InvalidHeader(
+                "Part numbers start at 1".to_string(),
+            )),
+            (Some(part), Some(total)) if part > total => Err(YencError::InvalidHeader(format!(
+                "Part {} is past the total of {}",
+                part, total
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Parse and check a `=ypart` line
+    fn parse_part(&self, line: &[u8]) -> Result<YencPart> {
+        self.check_keywords(line)?;
+        YencPart::parse_bytes(line)
+    }
+
+    /// Check that a multi-part header was followed by a `=ypart` line
+    ///
+    /// In strict mode, single-part articles must not have one either.
+    fn check_part_line(&self, header: &YencHeader, part_info: Option<&YencPart>) -> Result<()> {
+        if header.part.is_some() && part_info.is_none() {
+            return Err(YencError::InvalidData(
+                "Header indicates multi-part but no =ypart line found".to_string(),
+            ));
+        }
+        if self.strict && header.part.is_none() && part_info.is_some() {
+            return Err(YencError::InvalidData(
+                "=ypart line in a yEnc 1.0 article".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Parse a `=yend` line and check it against the article it ends
+    ///
+    /// The CRC32 is checked once the article is complete, by `check_crc`.
+    fn parse_trailer(
+        &self,
+        line: &[u8],
+        header: &YencHeader,
+        part_info: Option<&YencPart>,
+    ) -> Result<YencTrailer> {
+        self.check_keywords(line)?;
+        let trailer = YencTrailer::parse_bytes(line)?;
+
+        if self.strict {
+            match header.version() {
+                YencVersion::V1_0 if trailer.part.is_some() => {
+                    return Err(YencError::InvalidData(
+                        "part= on the trailer of a yEnc 1.0 article".to_string(),
+                    ));
+                }
+                YencVersion::V1_2 if trailer.pcrc32.is_none() => {
+                    return Err(YencError::MissingField("pcrc32".to_string()));
+                }
+                _ => {}
+            }
+        }
+
+        // Validate part size if multi-part
+        if let Some(part) = part_info {
+            let expected_size = part.size();
+            if trailer.size != expected_size {
+                return Err(YencError::InvalidData(format!(
+                    "Part size mismatch: trailer says {}, but part range implies {}",
+                    trailer.size, expected_size
+                )));
+            }
+
+            // For multi-part, also validate part number matches
+            if let Some(header_part) = header.part {
+                if trailer.part != Some(header_part) {
+                    return Err(YencError::InvalidData(format!(
+                        "Part number mismatch: header says {}, trailer says {:?}",
+                        header_part, trailer.part
+                    )));
+                }
+            }
+        }
+
+        Ok(trailer)
+    }
+
+    /// Check the CRC32 of the decoded data against the trailer and against
+    /// one set by `expected_crc` or `expected_pcrc`
+    ///
+    /// `crc` is the CRC32 of the decoded data, if validation is enabled. A
+    /// mismatch fails, or is a warning with `allow_crc_mismatch`.
+    fn check_crc(
+        &self,
+        part: Option<&YencPart>,
+        trailer: Option<&YencTrailer>,
+        crc: Option<u32>,
+    ) -> Result<Vec<DecodeWarning>> {
+        let Some(actual) = crc else {
+            return Ok(Vec::new());
+        };
+        // Parts are checked against pcrc32, single-part files against crc32
+        let (declared, external) = match part {
+            Some(_) => (trailer.and_then(|t| t.pcrc32), self.expected_pcrc),
+            None => (trailer.and_then(|t| t.crc32), self.expected_crc),
+        };
+
+        let mut warnings = Vec::new();
+        for expected in [declared, external].into_iter().flatten() {
+            if expected == actual {
+                continue;
+            }
+            if !self.allow_crc_mismatch {
+                return Err(YencError::CrcMismatch { expected, actual });
+            }
+            let warning = DecodeWarning::CrcMismatch { expected, actual };
+            if !warnings.contains(&warning) {
+                warnings.push(warning);
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Whether a data line is nothing but NUL bytes that lenient decoding
+    /// holds back as possible padding
+    ///
+    /// NUL is always escaped, so such a line is never valid data. It is
+    /// decoded after all if more data follows it.
+    fn is_padding(&self, data: &[u8]) -> bool {
+        !self.strict && !data.is_empty() && data.iter().all(|&b| b == 0)
+    }
+
+    /// Warning for NUL padding dropped from the end of the data, if any
+    fn padding_warnings(padding: usize) -> Vec<DecodeWarning> {
+        if padding == 0 {
+            return Vec::new();
+        }
+        vec![DecodeWarning::NulPadding {
+            bytes: padding as u64,
+        }]
+    }
+
+    /// In strict mode, check a data line against the declared `line=` length
+    ///
+    /// An escape pair starting in the last column makes a line one longer,
+    /// so that much is allowed.
+    fn check_line_length(&self, data: &[u8], declared: Option<usize>) -> Result<()> {
+        match declared {
+            Some(limit) if self.strict && data.len() > limit + 1 => {
+                Err(YencError::InvalidData(format!(
+                    "Data line of {} characters is longer than line={} allows",
+                    data.len(),
+                    limit
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Check that the data decoded to the size the keyword lines declare
+    ///
+    /// The part range is checked against the trailer when parsing it, so
+    /// only one of them needs checking against the data.
+    fn check_size(
+        &self,
+        header: &YencHeader,
+        part: Option<&YencPart>,
+        trailer: Option<&YencTrailer>,
+        decoded: u64,
+    ) -> Result<Option<DecodeWarning>> {
+        let declared = match (part, trailer) {
+            (Some(part), _) => part.size(),
+            (None, Some(trailer)) if trailer.size != decoded => trailer.size,
+            (None, _) => header.size,
+        };
+        if declared == decoded {
+            return Ok(None);
+        }
+        if self.strict {
+            return Err(YencError::InvalidData(format!(
+                "Size mismatch: declared {} bytes, but data decodes to {}",
+                declared, decoded
+            )));
+        }
+        Ok(Some(DecodeWarning::SizeMismatch { declared, decoded }))
+    }
+
+    /// Check for a missing `=yend` line, which fails in strict mode
+    fn check_truncated(&self, trailer_found: bool, decoded: u64) -> Result<Option<DecodeWarning>> {
+        match trailer_found {
+            true => Ok(None),
+            false if self.strict => Err(YencError::Truncated { recovered: decoded }),
+            false => Ok(Some(DecodeWarning::Truncated { decoded })),
+        }
+    }
+
+    /// Run the checks on a decoded article that need all of it
+    fn finish_outcome(&self, mut outcome: DecodeOutcome) -> Result<DecodeOutcome> {
+        let truncated = self.check_truncated(outcome.trailer.is_some(), outcome.bytes_written)?;
+        outcome.warnings.extend(truncated);
+        // Replaced lines cannot match the CRC32
+        if outcome.damage.is_empty() {
+            let crc = self.check_crc(
+                outcome.part.as_ref(),
+                outcome.trailer.as_ref(),
+                outcome.crc32,
+            )?;
+            outcome.warnings.extend(crc);
+        }
+        let size = self.check_size(
+            &outcome.header,
+            outcome.part.as_ref(),
+            outcome.trailer.as_ref(),
+            outcome.bytes_written,
+        )?;
+        outcome.warnings.extend(size);
+        Ok(outcome)
+    }
+
+    /// Take one line of the data section of an article
+    ///
+    /// Splits off a trailer, which may have been glued to the last data
+    /// line, holds back lines that may be NUL padding, and hands the data to
+    /// `decode`, after any padding held back that turned out to be data.
+    /// Returns the parsed trailer if the line holds one, which ends the data.
+    fn data_line<D: DataLine>(
+        &self,
+        body: &mut ArticleBody,
+        state: &mut DecodeState,
+        line: D,
+        decode: impl FnOnce(&mut DecodeState, D) -> Result<()>,
+    ) -> Result<Option<YencTrailer>> {
+        let range = self.data_range(&line);
+        let trailer = self.split_trailer(&line[range.clone()])?;
+        let (data, trailer_line) = line.split(range, trailer);
+        if self.is_padding(&data) {
+            body.padding += data.len();
+        } else {
+            if body.padding > 0 && !data.is_empty() {
+                state.decode_line(self, &vec![0; mem::take(&mut body.padding)])?;
+            }
+            self.check_line_length(&data, body.declared_line_len)?;
+            decode(state, data)?;
+        }
+
+        if body.declared_line_len.is_none() && state.longest_line > 0 {
+            body.header.line_len = Some(state.longest_line);
+        }
+
+        let Some(trailer_line) = trailer_line else {
+            return Ok(None);
+        };
+        if state.escaped {
+            return Err(YencError::InvalidData(
+                "Trailer follows an incomplete escape sequence".to_string(),
+            ));
+        }
+        self.parse_trailer(&trailer_line, &body.header, body.part.as_ref())
+            .map(Some)
+    }
+
+    /// End the data section of an article at `trailer`, or at the end of the
+    /// input without one, and run the checks that need all of it
+    fn end_data(
+        &self,
+        body: ArticleBody,
+        state: &mut DecodeState,
+        trailer: Option<YencTrailer>,
+    ) -> Result<DecodeOutcome> {
+        if trailer.is_none() && state.escaped {
+            return Err(YencError::InvalidData(
+                "File ended with incomplete escape sequence".to_string(),
+            ));
+        }
+        self.finish_outcome(DecodeOutcome {
+            header: body.header,
+            part: body.part,
+            trailer,
+            bytes_written: state.bytes_written,
+            crc32: state.crc(),
+            warnings: Self::padding_warnings(body.padding),
+            damage: mem::take(&mut state.damage),
+            trailing_bytes: 0,
+        })
+    }
+
+    /// Range of a line of the data section that holds data or a trailer
+    ///
+    /// Only the line ending is dropped unless `trim_whitespace` is set. A
+    /// line that is a trailer apart from whitespace around it is trimmed like
+    /// any keyword line.
+    pub(crate) fn data_range(&self, line: &[u8]) -> Range<usize> {
+        let trimmed = trim_range(line);
+        if self.trim_whitespace || line[trimmed.clone()].starts_with(b"=yend ") {
+            return trimmed;
+        }
+        let end = line
+            .iter()
+            .rposition(|&b| b != b'\r' && b != b'\n')
+            .map_or(0, |i| i + 1);
+        0..end
+    }
+
+    /// Split a trailer off a data line it was glued to, if there is one
+    pub(crate) fn split_trailer(&self, line: &[u8]) -> Result<Option<usize>> {
+        match find_trailer(line) {
+            Some(pos) if pos > 0 && self.strict => Err(YencError::InvalidData(
+                "Trailer does not start on its own line".to_string(),
+            )),
+            pos => Ok(pos),
+        }
+    }
+
+    /// Read the next line after the header, copying it to `raw` if requested
+    fn read_body_line<L: LineSource>(
+        &self,
+        lines: &mut L,
+        raw: &mut Option<&mut dyn Write>,
+    ) -> Result<usize> {
+        let bytes_read = lines.next_line()?;
+        if let Some(raw) = raw {
+            raw.write_all(lines.line())?;
+        }
+        Ok(bytes_read)
+    }
+
+    /// Decode yEnc data from a reader and write to a writer
+    ///
+    /// # Arguments
+    /// * `reader` - Input reader containing yEnc-encoded data
+    /// * `writer` - Output writer for decoded data
+    ///
+    /// # Returns
+    /// The parsed header, part and trailer, the number of bytes written and
+    /// the CRC32 of the decoded data; see `DecodeOutcome`
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Decoder;
+    ///
+    /// let input = b"=ybegin line=128 size=5 name=test.bin\nABCDE\n=yend size=5\n";
+    /// let mut output = Vec::new();
+    ///
+    /// let outcome = Decoder::new()
+    ///     .strict()
+    ///     .decode(&input[..], &mut output)
+    ///     .unwrap();
+    /// assert_eq!(outcome.header.name, b"test.bin");
+    /// assert_eq!(outcome.bytes_written, 5);
+    /// ```
+    pub fn decode<R: Read, W: Write>(&self, reader: R, writer: W) -> Result<DecodeOutcome> {
+        self.decode_to(reader, writer, None, &mut Scratch::new())
+    }
+
+    /// Decode like `decode`, reusing the buffers in `scratch`
+    ///
+    /// Once `scratch` has grown to fit the articles at hand, decoding one
+    /// after another allocates nothing for input or output buffering. Keep
+    /// one `Scratch` per thread and share the `Decoder` itself.
+    pub fn decode_with_scratch<R: Read, W: Write>(
+        &self,
+        reader: R,
+        writer: W,
+        scratch: &mut Scratch,
+    ) -> Result<DecodeOutcome> {
+        self.decode_to(reader, writer, None, scratch)
+    }
+
+    /// Decode an encoded article held in memory into a new vector
+    ///
+    /// Returns the same outcome as `decode` together with the decoded data.
+    /// The decoded data is never longer
+    /// than its encoding, so the output is allocated once up front, and lines
+    /// are parsed straight out of `encoded`.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Decoder;
+    ///
+    /// let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
+    /// let (outcome, data) = Decoder::new().decode_to_vec(input).unwrap();
+    /// assert_eq!(outcome.header.name, b"test.bin");
+    /// assert_eq!(data, b"!\"#$%");
+    /// ```
+    pub fn decode_to_vec(&self, encoded: &[u8]) -> Result<DecodedArticle> {
+        let mut output = Vec::with_capacity(encoded.len());
+        let outcome = self.decode_slice(encoded, &mut output)?;
+        Ok((outcome, output))
+    }
+
+    /// Decode an article held in memory, borrowing from it where possible
+    ///
+    /// A data line without escape characters decodes to the same number of
+    /// bytes, so it is decoded in place inside `encoded` and returned as a
+    /// borrowed segment. Lines with escapes are decoded into owned segments.
+    /// Concatenated, the segments hold the data `decode` would write, and the
+    /// same checks apply. On return `encoded` is partly decoded and should
+    /// not be used as yEnc data again.
+    ///
+    /// Text mode changes the decoded data and is not supported here.
+    ///
+    /// # Example
+    /// ```
+    /// use std::borrow::Cow;
+    /// use yenc::Decoder;
+    ///
+    /// let mut input = b"=ybegin line=128 size=5 name=test.bin\nKLMN\n=}\n=yend size=5\n".to_vec();
+    /// let (_, segments) = Decoder::new().decode_segments(&mut input).unwrap();
+    ///
+    /// assert!(matches!(segments[0], Cow::Borrowed(b"!\"#$")));
+    /// assert!(matches!(segments[1], Cow::Owned(_)));
+    /// assert_eq!(segments.concat(), b"!\"#$\x13");
+    /// ```
+    pub fn decode_segments<'a>(&self, encoded: &'a mut [u8]) -> Result<DecodedSegments<'a>> {
+        self.validate()?;
+        if self.text_mode {
+            return Err(YencError::InvalidConfig(
+                "text mode is not supported when decoding segments".to_string(),
+            ));
+        }
+
+        let mut rest = encoded;
+        let mut lines = std::iter::from_fn(move || {
+            if rest.is_empty() {
+                return None;
+            }
+            let end = memchr(b'\n', rest).map_or(rest.len(), |pos| pos + 1);
+            let (line, tail) = std::mem::take(&mut rest).split_at_mut(end);
+            rest = tail;
+            Some(line)
+        });
+
+        let header = loop {
+            let Some(line) = lines.next() else {
+                return Err(YencError::InvalidHeader("No header found".to_string()));
+            };
+            let trimmed = trim_bytes(line);
+            if trimmed.starts_with(b"=ybegin ") {
+                break self.parse_header(trimmed)?;
+            }
+        };
+        let mut body = ArticleBody::new(header);
+
+        let Some(mut line) = lines.next() else {
+            return Err(YencError::InvalidData("No data found".to_string()));
+        };
+        if trim_bytes(line).starts_with(b"=ypart ") {
+            body.part = Some(self.parse_part(trim_bytes(line))?);
+            let Some(next) = lines.next() else {
+                return Err(YencError::InvalidData(
+                    "No data found after part line".to_string(),
+                ));
+            };
+            line = next;
+        }
+        self.check_part_line(&body.header, body.part.as_ref())?;
 
-impl Default for Decoder {
-    fn default() -> Self {
-        Self {
-            strict: false,
-            validate_crc: true,
+        let mut state = self.state();
+        let mut segments = Vec::new();
+        loop {
+            let trailer = self.data_line(&mut body, &mut state, line, |state, data| {
+                state.push_segment(self, data, &mut segments)
+            })?;
+            if trailer.is_some() {
+                let outcome = self.end_data(body, &mut state, trailer)?;
+                return Ok((outcome, segments));
+            }
+
+            match lines.next() {
+                Some(next) => line = next,
+                None => {
+                    let outcome = self.end_data(body, &mut state, None)?;
+                    return Ok((outcome, segments));
+                }
+            }
         }
     }
-}
 
-impl Decoder {
-    /// Create a new decoder with default settings
+    /// Decode like `decode`, also copying the article verbatim to `raw`
     ///
-    /// Default settings:
-    /// - Lenient mode (accepts any escaped character)
-    /// - CRC validation enabled
-    pub fn new() -> Self {
-        Self::default()
+    /// Every line from `=ybegin` through `=yend` is written to `raw` exactly
+    /// as read, line endings included, so archival tools can store the
+    /// original article while producing the decoded file in the same pass.
+    /// Anything before the header (such as NNTP headers) is not copied.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Decoder;
+    ///
+    /// let input = b"Subject: test\r\n=ybegin line=128 size=5 name=test.bin\r\nKLMNO\r\n=yend size=5\r\n";
+    /// let (mut output, mut raw) = (Vec::new(), Vec::new());
+    ///
+    /// Decoder::new()
+    ///     .decode_keep_raw(&input[..], &mut output, &mut raw)
+    ///     .unwrap();
+    /// assert_eq!(output, b"!\"#$%");
+    /// assert_eq!(raw, &input[15..]);
+    /// ```
+    pub fn decode_keep_raw<R: Read, W: Write, V: Write>(
+        &self,
+        reader: R,
+        writer: W,
+        mut raw: V,
+    ) -> Result<DecodeOutcome> {
+        self.decode_to(reader, writer, Some(&mut raw), &mut Scratch::new())
     }
 
-    /// Enable strict validation of escape sequences
+    /// Decode an article into several writers at once
     ///
-    /// When enabled, only characters that should be escaped according to
-    /// the yEnc spec are accepted. Invalid escape sequences will cause an error.
-    pub fn strict(mut self) -> Self {
-        self.strict = true;
-        self
+    /// Every block of decoded data goes to each writer in order, so a file
+    /// can be written while another digest is computed over the same bytes
+    /// without decoding twice. Text mode applies to all writers alike.
+    ///
+    /// # Errors
+    /// Besides decoding errors, the first write error of any writer stops
+    /// decoding; writers before it in `writers` may have received more data
+    /// than the ones after.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    /// use yenc::Decoder;
+    ///
+    /// let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
+    /// let (mut file, mut copy) = (Vec::new(), Vec::new());
+    ///
+    /// Decoder::new()
+    ///     .decode_tee(&input[..], &mut [&mut file as &mut dyn Write, &mut copy])
+    ///     .unwrap();
+    /// assert_eq!(file, b"!\"#$%");
+    /// assert_eq!(copy, file);
+    /// ```
+    pub fn decode_tee<R: Read>(
+        &self,
+        reader: R,
+        writers: &mut [&mut dyn Write],
+    ) -> Result<DecodeOutcome> {
+        self.decode(reader, TeeWriter { writers })
     }
 
-    /// Disable CRC validation
+    /// Read the keyword lines of an article, skipping over its data
     ///
-    /// By default, CRC32 checksums are validated if present in the trailer.
-    pub fn no_crc_check(mut self) -> Self {
-        self.validate_crc = false;
-        self
+    /// Parses the `=ybegin` and `=ypart` lines and the trailer with the
+    /// same checks as `decode`, but data lines are neither decoded nor
+    /// hashed, so the data is not checked against the sizes and CRC32s
+    /// announced. For tools that only need to know what a file contains.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Decoder;
+    ///
+    /// let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5 crc32=00000000\n";
+    /// let metadata = Decoder::new().read_metadata(&input[..]).unwrap();
+    /// assert_eq!(metadata.header.name, b"test.bin");
+    /// assert_eq!(metadata.trailer.unwrap().crc32, Some(0));
+    /// ```
+    pub fn read_metadata<R: Read>(&self, reader: R) -> Result<YencMetadata> {
+        self.validate()?;
+        let mut lines = self.line_reader(reader, Vec::new(), self.reserve(0)?);
+        let raw = &mut None;
+        let header = self.read_header(&mut lines, raw)?;
+        let part = self.read_part(&mut lines, raw, &header)?;
+
+        // Starting at the first data line, look for the trailer only
+        let trailer = loop {
+            let line = &lines.line()[self.data_range(lines.line())];
+            if let Some(pos) = self.split_trailer(line)? {
+                break Some(self.parse_trailer(&line[pos..], &header, part.as_ref())?);
+            }
+            if lines.next_line()? == 0 {
+                break None;
+            }
+        };
+        Ok(YencMetadata {
+            header,
+            part,
+            trailer,
+        })
     }
 
-    /// Decode yEnc data from a reader and write to a writer
+    /// Decode every article in a stream holding several back to back
     ///
-    /// # Arguments
-    /// * `reader` - Input reader containing yEnc-encoded data
-    /// * `writer` - Output writer for decoded data
+    /// Cached article dumps and mbox-like files often hold several
+    /// `=ybegin`…`=yend` blocks, with other text between them. Each block is
+    /// decoded in turn with the same checks as `decode`, its data written to
+    /// `writer` right after that of the block before; the `bytes_written` of
+    /// each outcome tell where one ends. An article without a trailer ends
+    /// at the next `=ybegin` line. Whatever follows a trailer up to the next
+    /// `=ybegin` line is skipped unread, however malformed, and counted in
+    /// the outcome's `trailing_bytes`.
     ///
     /// # Returns
-    /// A tuple of (header, part, trailer, bytes_written)
-    /// - For single-part files: part will be None
-    /// - For multi-part files: part contains begin/end byte positions
+    /// The outcome of every article, in order; empty if there is none
+    ///
+    /// # Errors
+    /// Stops at the first article that fails to decode; see
+    /// `decode_all_resync` to carry on instead. Returns
+    /// `YencError::InvalidConfig` for text mode, which is not supported here.
     ///
     /// # Example
     /// ```
     /// use yenc::Decoder;
     ///
-    /// let input = b"=ybegin line=128 size=5 name=test.bin\nABCDE\n=yend size=5\n";
+    /// let input = b"From: poster\n\
+    ///               =ybegin line=128 size=2 name=a.txt\nKL\n=yend size=2\n\
+    ///               From: poster\n\
+    ///               =ybegin line=128 size=3 name=b.txt\nMNO\n=yend size=3\n";
+    ///
     /// let mut output = Vec::new();
+    /// let outcomes = Decoder::new().decode_all(&input[..], &mut output).unwrap();
+    /// assert_eq!(outcomes.len(), 2);
+    /// assert_eq!(outcomes[1].header.name, b"b.txt");
+    /// assert_eq!(output, b"!\"#$%");
+    /// ```
+    pub fn decode_all<R: Read, W: Write>(
+        &self,
+        reader: R,
+        writer: W,
+    ) -> Result<Vec<DecodeOutcome>> {
+        self.decode_blocks(reader, writer, false)?
+            .into_iter()
+            .collect()
+    }
+
+    /// Decode every article in a stream like `decode_all`, carrying on past
+    /// articles that fail
     ///
-    /// let (header, part, trailer, size) = Decoder::new()
-    ///     .strict()
-    ///     .decode(&input[..], &mut output)
-    ///     .unwrap();
+    /// A mangled article ends up as an error in the results, and decoding
+    /// resumes at the next `=ybegin` line, so one bad block does not cost
+    /// the rest of the stream. Data the failed article decoded to before
+    /// the error has already been written to `writer`.
+    ///
+    /// # Returns
+    /// The result of every article, in order; empty if there is none
+    ///
+    /// # Errors
+    /// I/O errors, and `YencError::InvalidConfig` as for `decode_all`,
+    /// stop decoding altogether.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::{Decoder, YencError};
+    ///
+    /// let input = b"=ybegin line=128 size=2 name=a.txt\nKL\n=yend size=2 crc32=00000000\n\
+    ///               =ybegin line=128 size=3 name=b.txt\nMNO\n=yend size=3\n";
+    ///
+    /// let results = Decoder::new().decode_all_resync(&input[..], std::io::sink()).unwrap();
+    /// assert!(matches!(results[0], Err(YencError::CrcMismatch { .. })));
+    /// assert_eq!(results[1].as_ref().unwrap().header.name, b"b.txt");
     /// ```
-    pub fn decode<R: Read, W: Write>(
+    pub fn decode_all_resync<R: Read, W: Write>(
+        &self,
+        reader: R,
+        writer: W,
+    ) -> Result<Vec<Result<DecodeOutcome>>> {
+        self.decode_blocks(reader, writer, true)
+    }
+
+    /// Decode the articles of a stream for `decode_all` and
+    /// `decode_all_resync`
+    ///
+    /// Without `resync`, the first failing article ends decoding with its
+    /// error.
+    fn decode_blocks<R: Read, W: Write>(
         &self,
-        mut reader: R,
+        reader: R,
         mut writer: W,
-    ) -> Result<(YencHeader, Option<YencPart>, Option<YencTrailer>, usize)> {
-        let mut buf_reader = BufReader::new(&mut reader);
-        let mut line = Vec::new();
+        resync: bool,
+    ) -> Result<Vec<Result<DecodeOutcome>>> {
+        self.validate()?;
+        if self.text_mode {
+            return Err(YencError::InvalidConfig(
+                "text mode is not supported when decoding several articles".to_string(),
+            ));
+        }
 
-        let header = loop {
-            line.clear();
-            let bytes_read = buf_reader.read_until(b'\n', &mut line)?;
+        let mut lines = self.line_reader(reader, Vec::new(), self.reserve(0)?);
+        let mut output = Vec::new();
+        let mut results: Vec<Result<DecodeOutcome>> = Vec::new();
+        // Whether the current line is the header of the next article
+        let mut at_header = false;
+        loop {
+            if !at_header {
+                let mut skipped = 0;
+                let found = Self::skip_to_header(&mut lines, &mut skipped)?;
+                if let Some(Ok(outcome)) = results.last_mut() {
+                    outcome.trailing_bytes = skipped;
+                }
+                if !found {
+                    break;
+                }
+            }
+            let sink = &mut writer;
+            let open = |_: &YencHeader, _: Option<&YencPart>| Ok(sink);
+            let header = self.parse_header(trim_bytes(lines.line()));
+            // A bad header is still the current line, so skip past it
+            let started = header.is_ok();
+            let result = header.and_then(|header| {
+                self.decode_body(&mut lines, None, &mut output, header, true, open)
+            });
+
+            // An article cut short by the next one, whether it decoded or
+            // failed, leaves that article's header current
+            at_header = started && trim_bytes(lines.line()).starts_with(b"=ybegin ");
+            match result {
+                Err(err) if !resync || matches!(err, YencError::Io(_)) => return Err(err),
+                result => results.push(result),
+            }
+        }
+        Ok(results)
+    }
+
+    /// Read up to the next `=ybegin` line, leaving it current
+    ///
+    /// Adds the length of the lines passed over to `skipped`. Returns false
+    /// if the input ends first.
+    fn skip_to_header<L: LineSource>(lines: &mut L, skipped: &mut u64) -> Result<bool> {
+        loop {
+            let bytes_read = lines.next_line()?;
             if bytes_read == 0 {
-                return Err(YencError::InvalidHeader("No header found".to_string()));
+                return Ok(false);
+            }
+            if trim_bytes(lines.line()).starts_with(b"=ybegin ") {
+                return Ok(true);
+            }
+            *skipped += bytes_read as u64;
+        }
+    }
+
+    fn decode_to<R: Read, W: Write>(
+        &self,
+        reader: R,
+        writer: W,
+        raw: Option<&mut dyn Write>,
+        scratch: &mut Scratch,
+    ) -> Result<DecodeOutcome> {
+        self.validate()?;
+        let reservation = self.reserve(0)?;
+        let mut lines = self.line_reader(reader, mem::take(&mut scratch.input), reservation);
+        let result = self.decode_lines(&mut lines, writer, raw, &mut scratch.output);
+        scratch.input = lines.into_buffer();
+        result
+    }
+
+    /// Decode an article held in memory straight from the slice
+    ///
+    /// Same as `decode`, minus the copy of every line into a read buffer.
+    pub(crate) fn decode_slice<W: Write>(
+        &self,
+        encoded: &[u8],
+        writer: W,
+    ) -> Result<DecodeOutcome> {
+        self.validate()?;
+        // Lines are borrowed from `encoded`, so only the output block is held
+        let _output = Reservation::take(self.budget.as_ref(), self.buffer_sizes().1)?;
+        let mut lines = SliceLines::new(encoded, self.memory_limit());
+        self.decode_lines(&mut lines, writer, None, &mut Vec::new())
+    }
+
+    /// Decode like `decode`, with the buffers held in `reservation`
+    ///
+    /// For callers that reserve more than the decode itself in one go, as
+    /// waiting for the rest while holding part of the budget could deadlock.
+    pub(crate) fn decode_reserved<R: Read, W: Write>(
+        &self,
+        reader: R,
+        writer: W,
+        reservation: Reservation,
+    ) -> Result<DecodeOutcome> {
+        self.validate()?;
+        let mut lines = self.line_reader(reader, Vec::new(), reservation);
+        self.decode_lines(&mut lines, writer, None, &mut Vec::new())
+    }
+
+    fn decode_lines<L: LineSource, W: Write>(
+        &self,
+        lines: &mut L,
+        writer: W,
+        raw: Option<&mut dyn Write>,
+        output: &mut Vec<u8>,
+    ) -> Result<DecodeOutcome> {
+        if self.text_mode {
+            let mut text_writer = TextWriter::new(writer);
+            let result = self.decode_stream(lines, raw, output, |_, _| Ok(&mut text_writer))?;
+            text_writer.finish()?;
+            Ok(result)
+        } else {
+            self.decode_stream(lines, raw, output, |_, _| Ok(writer))
+        }
+    }
+
+    /// Decode in binary mode, choosing the writer once the header is known
+    ///
+    /// `open` is called with the parsed `=ybegin` and `=ypart` fields before
+    /// any data is written, so callers can place the output based on them.
+    pub(crate) fn decode_with<R, W, F>(&self, reader: R, open: F) -> Result<DecodeOutcome>
+    where
+        R: Read,
+        W: Write,
+        F: FnOnce(&YencHeader, Option<&YencPart>) -> Result<W>,
+    {
+        self.validate()?;
+        if self.text_mode {
+            return Err(YencError::InvalidConfig(
+                "text mode is not supported when decoding into place".to_string(),
+            ));
+        }
+        let mut lines = self.line_reader(reader, Vec::new(), self.reserve(0)?);
+        self.decode_stream(&mut lines, None, &mut Vec::new(), open)
+    }
+
+    /// Skip to the `=ybegin` line and parse it
+    fn read_header<L: LineSource>(
+        &self,
+        lines: &mut L,
+        raw: &mut Option<&mut dyn Write>,
+    ) -> Result<YencHeader> {
+        self.next_header(lines, raw)?
+            .ok_or_else(|| YencError::InvalidHeader("No header found".to_string()))
+    }
+
+    /// Skip to the next `=ybegin` line and parse it, or `None` at the end
+    fn next_header<L: LineSource>(
+        &self,
+        lines: &mut L,
+        raw: &mut Option<&mut dyn Write>,
+    ) -> Result<Option<YencHeader>> {
+        loop {
+            let bytes_read = lines.next_line()?;
+            if bytes_read == 0 {
+                return Ok(None);
             }
 
-            let trimmed = trim_bytes(&line);
+            let trimmed = trim_bytes(lines.line());
             if trimmed.starts_with(b"=ybegin ") {
-                if let Ok(header_text) = std::str::from_utf8(trimmed) {
-                    break YencHeader::parse(header_text)?;
-                } else {
-                    return Err(YencError::InvalidHeader("Invalid header".to_string()));
+                if let Some(raw) = raw.as_mut() {
+                    raw.write_all(lines.line())?;
                 }
+                return self.parse_header(trimmed).map(Some);
             }
-        };
+        }
+    }
 
-        line.clear();
-        let bytes_read = buf_reader.read_until(b'\n', &mut line)?;
+    /// Parse the `=ypart` line following the header, if there is one
+    ///
+    /// Leaves `lines` on the first data line.
+    fn read_part<L: LineSource>(
+        &self,
+        lines: &mut L,
+        raw: &mut Option<&mut dyn Write>,
+        header: &YencHeader,
+    ) -> Result<Option<YencPart>> {
+        let bytes_read = self.read_body_line(lines, raw)?;
         if bytes_read == 0 {
             return Err(YencError::InvalidData("No data found".to_string()));
         }
 
-        let trimmed = trim_bytes(&line);
+        let trimmed = trim_bytes(lines.line());
         let part_info = if trimmed.starts_with(b"=ypart ") {
-            let part = if let Ok(part_text) = std::str::from_utf8(trimmed) {
-                YencPart::parse(part_text)?
-            } else {
-                return Err(YencError::InvalidData("Invalid part line".to_string()));
-            };
+            let part = self.parse_part(trimmed)?;
 
             // Read the next line (first data line)
-            line.clear();
-            let bytes_read = buf_reader.read_until(b'\n', &mut line)?;
+            let bytes_read = self.read_body_line(lines, raw)?;
             if bytes_read == 0 {
-                return Err(YencError::InvalidData("No data found after part line".to_string()));
+                return Err(YencError::InvalidData(
+                    "No data found after part line".to_string(),
+                ));
             }
 
             Some(part)
@@ -145,121 +2243,261 @@ impl Decoder {
             None
         };
 
-        // Check if header indicates multi-part but no part line found
-        if header.part.is_some() && part_info.is_none() {
-            return Err(YencError::InvalidData(
-                "Header indicates multi-part but no =ypart line found".to_string(),
+        self.check_part_line(header, part_info.as_ref())?;
+        Ok(part_info)
+    }
+
+    fn decode_stream<L, W, F>(
+        &self,
+        lines: &mut L,
+        mut raw: Option<&mut dyn Write>,
+        output: &mut Vec<u8>,
+        open: F,
+    ) -> Result<DecodeOutcome>
+    where
+        L: LineSource,
+        W: Write,
+        F: FnOnce(&YencHeader, Option<&YencPart>) -> Result<W>,
+    {
+        let header = self.read_header(lines, &mut raw)?;
+        self.decode_body(lines, raw, output, header, false, open)
+    }
+
+    /// Decode the article after its `=ybegin` line
+    ///
+    /// With `until_header`, an article without a trailer also ends at the
+    /// next `=ybegin` line, which is left as the current line.
+    fn decode_body<L, W, F>(
+        &self,
+        lines: &mut L,
+        mut raw: Option<&mut dyn Write>,
+        output: &mut Vec<u8>,
+        header: YencHeader,
+        until_header: bool,
+        open: F,
+    ) -> Result<DecodeOutcome>
+    where
+        L: LineSource,
+        W: Write,
+        F: FnOnce(&YencHeader, Option<&YencPart>) -> Result<W>,
+    {
+        let mut body = ArticleBody::new(header);
+        body.part = self.read_part(lines, &mut raw, &body.header)?;
+
+        let mut writer = open(&body.header, body.part.as_ref())?;
+
+        let mut state = self.state().with_block(mem::take(output));
+        let result = (|| loop {
+            let trailer = self.data_line(&mut body, &mut state, lines.line(), |state, data| {
+                state.write_line(self, data, &mut writer)
+            })?;
+            if trailer.is_some() {
+                return self.end_data(body, &mut state, trailer);
+            }
+
+            let bytes_read = self.read_body_line(lines, &mut raw)?;
+            let next_header = until_header && trim_bytes(lines.line()).starts_with(b"=ybegin ");
+            if bytes_read == 0 || next_header {
+                return self.end_data(body, &mut state, None);
+            }
+        })();
+
+        // Everything decoded before an error is still handed over, as it
+        // would have been had each line been written on its own
+        let flushed = state.flush(&mut writer);
+        *output = state.into_block();
+        let result = result?;
+        flushed?;
+        Ok(result)
+    }
+}
+
+/// Where an `ArticleStream` is within its article
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    /// Looking for the `=ybegin` line
+    Header,
+    /// After the header, with the `=ypart` line not looked for yet
+    Part,
+    /// Decoding data lines, starting at the current line
+    Data,
+    /// Data done, with the end not yet reported
+    End,
+    /// Finished, or failed
+    Done,
+}
+
+/// Point of the article reached by `ArticleStream::advance`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Event {
+    /// The `=ybegin` line was parsed
+    Begin,
+    /// The `=ypart` line was parsed
+    Part,
+    /// A block of data lines was decoded
+    Data,
+    /// The article ended, with or without a trailer
+    End,
+}
+
+/// Article decoded a step at a time, for callers that pull the data
+///
+/// Each `advance` gets past the header, the part line or a block of data
+/// lines, reading no further than that. Decoded data is collected in blocks
+/// of about `OUTPUT_BLOCK` bytes, less under a small memory budget, and hashed a block at a time, as when
+/// decoding into a writer. The same checks as `Decoder::decode` apply, and
+/// the warnings it would report are collected once the article ends.
+pub(crate) struct ArticleStream<R> {
+    decoder: Decoder,
+    lines: LineReader<R>,
+    stage: Stage,
+    /// Keyword lines so far, from `Event::Begin` on
+    body: Option<ArticleBody>,
+    trailer: Option<YencTrailer>,
+    warnings: Vec<DecodeWarning>,
+    state: DecodeState,
+    /// Error hit partway through a block, held back until the lines decoded
+    /// before it have been handed out
+    error: Option<YencError>,
+}
+
+impl<R: Read> ArticleStream<R> {
+    /// Start on an article from `reader`, reading nothing yet
+    ///
+    /// Text mode is not supported.
+    pub(crate) fn new(decoder: &Decoder, reader: R) -> Result<Self> {
+        decoder.validate()?;
+        if decoder.text_mode {
+            return Err(YencError::InvalidConfig(
+                "text mode is not supported when pulling decoded data".to_string(),
             ));
         }
 
-        // Initialize CRC32 hasher if validation is enabled
-        let mut crc_hasher = if self.validate_crc {
-            Some(Hasher::new())
-        } else {
-            None
-        };
+        Ok(Self {
+            decoder: decoder.clone(),
+            lines: decoder.line_reader(reader, Vec::new(), decoder.reserve(0)?),
+            stage: Stage::Header,
+            body: None,
+            trailer: None,
+            warnings: Vec::new(),
+            state: decoder.state(),
+            error: None,
+        })
+    }
 
-        let mut bytes_written = 0;
-        let mut escaped = false;
-        loop {
-            let trimmed = trim_bytes(&line);
-            if trimmed.starts_with(b"=yend ") {
-                if let Ok(trailer_text) = std::str::from_utf8(trimmed) {
-                    let trailer = YencTrailer::parse(trailer_text)?;
-
-                    // Validate part size if multi-part
-                    if let Some(ref part) = part_info {
-                        let expected_size = part.size();
-                        if trailer.size != expected_size {
-                            return Err(YencError::InvalidData(format!(
-                                "Part size mismatch: trailer says {}, but part range implies {}",
-                                trailer.size, expected_size
-                            )));
-                        }
-
-                        // For multi-part, also validate part number matches
-                        if let Some(header_part) = header.part {
-                            if trailer.part != Some(header_part) {
-                                return Err(YencError::InvalidData(format!(
-                                    "Part number mismatch: header says {}, trailer says {:?}",
-                                    header_part, trailer.part
-                                )));
-                            }
-                        }
-                    }
+    /// The parsed header, once `Event::Begin` has been reached
+    ///
+    /// Without a `line=` value, the line length is filled in from the data
+    /// decoded so far.
+    pub(crate) fn header(&self) -> Option<&YencHeader> {
+        self.body.as_ref().map(|body| &body.header)
+    }
 
-                    if let Some(hasher) = crc_hasher {
-                        let computed_crc = hasher.finalize();
-
-                        // For multi-part files, validate against pcrc32 (part CRC)
-                        // For single-part files, validate against crc32 (file CRC)
-                        let expected_crc = if part_info.is_some() {
-                            trailer.pcrc32 // Multi-part: use part CRC
-                        } else {
-                            trailer.crc32 // Single-part: use file CRC
-                        };
-
-                        if let Some(expected) = expected_crc {
-                            if computed_crc != expected {
-                                return Err(YencError::CrcMismatch {
-                                    expected,
-                                    actual: computed_crc,
-                                });
-                            }
-                        }
-                        // Note: CRC is optional, so if not present we don't fail
-                    }
+    /// The parsed part line, once `Event::Part` has been reached
+    pub(crate) fn part(&self) -> Option<&YencPart> {
+        self.body.as_ref()?.part.as_ref()
+    }
 
-                    return Ok((header, part_info, Some(trailer), bytes_written));
-                } else {
-                    return Err(YencError::InvalidData("Invalid trailer".to_string()));
-                }
-            }
+    /// The parsed trailer, once `Event::End` has been reached
+    pub(crate) fn trailer(&self) -> Option<&YencTrailer> {
+        self.trailer.as_ref()
+    }
 
-            for &byte in trimmed {
-                if byte == ESCAPE_CHAR {
-                    escaped = true;
-                    continue;
-                }
+    /// What `Decoder::decode` would report as `DecodeOutcome::warnings`,
+    /// once `Event::End` has been reached
+    pub(crate) fn warnings(&self) -> &[DecodeWarning] {
+        &self.warnings
+    }
 
-                let decoded = if escaped {
-                    escaped = false;
-                    let result = decode_byte(byte.wrapping_sub(ESCAPE_OFFSET));
+    /// Data decoded by the last `advance`, if it returned `Event::Data`
+    pub(crate) fn data(&self) -> &[u8] {
+        self.state.pending()
+    }
 
-                    if self.strict && !ESCAPING_CHARS.contains(&result) {
-                        return Err(YencError::InvalidData(format!(
-                            "Invalid escape sequence: ={:02x}",
-                            byte
-                        )));
-                    }
-                    result
+    /// Move on to the next point of the article, or `None` past its end
+    ///
+    /// After an error the stream is finished and returns `None`.
+    pub(crate) fn advance(&mut self) -> Result<Option<Event>> {
+        self.state.consume();
+        if let Some(err) = self.error.take() {
+            self.stage = Stage::Done;
+            return Err(err);
+        }
+        let result = self.step();
+        if result.is_err() {
+            self.stage = Stage::Done;
+        }
+        result
+    }
+
+    fn step(&mut self) -> Result<Option<Event>> {
+        let raw = &mut None;
+        match self.stage {
+            Stage::Header => {
+                let header = self.decoder.read_header(&mut self.lines, raw)?;
+                self.body = Some(ArticleBody::new(header));
+                self.stage = Stage::Part;
+                Ok(Some(Event::Begin))
+            }
+            Stage::Part => {
+                let body = self
+                    .body
+                    .as_mut()
+                    .expect("header parsed before the part line");
+                body.part = self.decoder.read_part(&mut self.lines, raw, &body.header)?;
+                self.stage = Stage::Data;
+                if body.part.is_some() {
+                    Ok(Some(Event::Part))
                 } else {
-                    decode_byte(byte)
+                    self.step()
+                }
+            }
+            Stage::Data => {
+                let ended = match self.decode_block() {
+                    Ok(ended) => ended,
+                    Err(err) if !self.state.pending().is_empty() => {
+                        self.error = Some(err);
+                        return Ok(Some(Event::Data));
+                    }
+                    Err(err) => return Err(err),
                 };
-
-                // Update CRC if validation is enabled
-                if let Some(ref mut hasher) = crc_hasher {
-                    hasher.update(&[decoded]);
+                if ended {
+                    self.stage = Stage::End;
+                }
+                if self.state.pending().is_empty() {
+                    self.step()
+                } else {
+                    Ok(Some(Event::Data))
                 }
-
-                writer.write_all(&[decoded])?;
-                bytes_written += 1;
             }
-
-            line.clear();
-            let bytes_read = buf_reader.read_until(b'\n', &mut line)?;
-            if bytes_read == 0 {
-                break;
+            Stage::End => {
+                self.stage = Stage::Done;
+                Ok(Some(Event::End))
             }
+            Stage::Done => Ok(None),
         }
+    }
 
-        if escaped {
-            return Err(YencError::InvalidData(
-                "File ended with incomplete escape sequence".to_string(),
-            ));
+    /// Decode data lines until a block is full, returning whether the data ended
+    fn decode_block(&mut self) -> Result<bool> {
+        loop {
+            let body = self.body.as_mut().expect("header parsed before the data");
+            let decoder = &self.decoder;
+            let trailer =
+                decoder.data_line(body, &mut self.state, self.lines.line(), |state, data| {
+                    state.decode_line(decoder, data)
+                })?;
+            if trailer.is_some() || self.lines.next_line()? == 0 {
+                // The body stays behind for `header` and `part`
+                let outcome = decoder.end_data(body.clone(), &mut self.state, trailer)?;
+                self.trailer = outcome.trailer;
+                self.warnings = outcome.warnings;
+                return Ok(true);
+            }
+            if self.state.pending().len() >= self.state.block_size {
+                return Ok(false);
+            }
         }
-
-        Ok((header, part_info, None, bytes_written))
     }
 }
 
@@ -274,38 +2512,232 @@ impl Decoder {
 /// let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
 /// let mut output = Vec::new();
 ///
-/// let (header, part, trailer, size) = decode(&input[..], &mut output).unwrap();
+/// let outcome = decode(&input[..], &mut output).unwrap();
+/// assert_eq!(outcome.bytes_written, 5);
 /// ```
-pub fn decode<R: Read, W: Write>(
-    reader: R,
-    writer: W,
-) -> Result<(YencHeader, Option<YencPart>, Option<YencTrailer>, usize)> {
+pub fn decode<R: Read, W: Write>(reader: R, writer: W) -> Result<DecodeOutcome> {
     Decoder::default().decode(reader, writer)
 }
 
+/// Decode an encoded article held in memory with default settings
+///
+/// This is a convenience function equivalent to `Decoder::new().decode_to_vec(encoded)`
+pub fn decode_to_vec(encoded: &[u8]) -> Result<DecodedArticle> {
+    Decoder::default().decode_to_vec(encoded)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
-    fn test_decode_byte() {
-        assert_eq!(decode_byte(b'*'), 0);
-        assert_eq!(decode_byte(b'+'), 1);
-        assert_eq!(decode_byte(b','), 2);
+    fn test_decode_byte() {
+        assert_eq!(decode_byte(b'*'), 0);
+        assert_eq!(decode_byte(b'+'), 1);
+        assert_eq!(decode_byte(b','), 2);
+    }
+
+    #[test]
+    fn test_trim_bytes() {
+        assert_eq!(trim_bytes(b"  abc \r\n"), b"abc");
+        assert_eq!(trim_bytes(b"\r\n"), b"");
+        assert_eq!(trim_bytes(b""), b"");
+    }
+
+    #[test]
+    fn test_decode_escape_at_line_end() {
+        // The pair =} split across lines decodes to 0x13
+        let input = b"=ybegin line=128 size=5 name=a\nKL=\n}MN\n=yend size=5\n";
+        let (_, data) = Decoder::new().decode_to_vec(input).unwrap();
+        assert_eq!(data, b"!\"\x13#$");
+        let mut copy = input.to_vec();
+        let (_, segments) = Decoder::new().decode_segments(&mut copy).unwrap();
+        assert_eq!(segments.concat(), b"!\"\x13#$");
+
+        let result = Decoder::new().strict().decode_to_vec(input);
+        match result {
+            Err(YencError::InvalidData(msg)) => assert!(msg.contains("end of line")),
+            other => panic!("expected InvalidData, got {:?}", other),
+        }
+
+        // Nothing left for the escape to apply to
+        let cases: [&[u8]; 3] = [
+            b"=ybegin line=128 size=2 name=a\nKL=\n=yend size=2\n",
+            b"=ybegin line=128 size=2 name=a\nKL==yend size=2\n",
+            b"=ybegin line=128 size=2 name=a\nKL=\n",
+        ];
+        for input in cases {
+            let result = Decoder::new().decode_to_vec(input);
+            assert!(
+                matches!(result, Err(YencError::InvalidData(_))),
+                "{:?}",
+                result
+            );
+            let mut copy = input.to_vec();
+            let result = Decoder::new().decode_segments(&mut copy);
+            assert!(
+                matches!(result, Err(YencError::InvalidData(_))),
+                "{:?}",
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn test_decode_whitespace_payload() {
+        // Unescaped spaces and tabs at the ends of data lines, and a
+        // trailer indented by a gateway
+        let input = b"=ybegin line=128 size=7 name=a\r\n KL\t\r\n\tM \r\n  =yend size=7 \r\n";
+        let expected = b"\xf6!\"\xdf\xdf#\xf6";
+
+        let (_, data) = Decoder::new().decode_to_vec(input).unwrap();
+        assert_eq!(data, expected);
+        let mut copy = input.to_vec();
+        let (_, segments) = Decoder::new().decode_segments(&mut copy).unwrap();
+        assert_eq!(segments.concat(), expected);
+        let metadata = Decoder::new().read_metadata(&input[..]).unwrap();
+        assert_eq!(metadata.trailer.unwrap().size, 7);
+
+        let (_, data) = Decoder::new()
+            .trim_whitespace()
+            .decode_to_vec(input)
+            .unwrap();
+        assert_eq!(data, b"!\"#");
+    }
+
+    #[test]
+    fn test_decode_escaped_boundary_whitespace() {
+        // Escaped TABs and SPACEs starting and ending lines
+        let input = b"=ybegin line=6 size=8 name=a\n=IKL=`\n=`MN=I\n=yend size=8\n";
+        let expected = b"\xdf!\"\xf6\xf6#$\xdf";
+
+        for decoder in [
+            Decoder::new(),
+            Decoder::new().strict(),
+            Decoder::new().trim_whitespace(),
+        ] {
+            let (_, data) = decoder.decode_to_vec(input).unwrap();
+            assert_eq!(data, expected);
+            let mut copy = input.to_vec();
+            let (_, segments) = decoder.decode_segments(&mut copy).unwrap();
+            assert_eq!(segments.concat(), expected);
+        }
+    }
+
+    #[test]
+    fn test_decode_simple() {
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
+        let mut output = Vec::new();
+
+        let outcome = decode(&input[..], &mut output).unwrap();
+
+        assert_eq!(outcome.header.name, b"test.bin");
+        assert_eq!(outcome.header.size, 5);
+        assert_eq!(outcome.bytes_written, 5);
+        assert_eq!(output, vec![33, 34, 35, 36, 37]);
+        assert!(outcome.part.is_none());
+    }
+
+    #[test]
+    fn test_decode_to_vec() {
+        let input = b"=ybegin part=1 total=1 line=128 size=5 name=test.bin\n\
+                      =ypart begin=1 end=5\n\
+                      KLMNO\n\
+                      =yend size=5 part=1 pcrc32=b05f5b44\n";
+
+        let (outcome, data) = decode_to_vec(input).unwrap();
+        assert_eq!(outcome.header.name, b"test.bin");
+        assert_eq!(outcome.part.map(|p| (p.begin, p.end)), Some((1, 5)));
+        assert_eq!(outcome.trailer.map(|t| t.size), Some(5));
+        assert_eq!(outcome.bytes_written, 5);
+        assert_eq!(data, vec![33, 34, 35, 36, 37]);
+
+        let result = Decoder::new()
+            .strict()
+            .decode_to_vec(b"=ybegin size=5 name=x\n");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_decode_segments() {
+        let data: Vec<u8> = (0..=255).cycle().take(3000).collect();
+        let mut encoded = Vec::new();
+        crate::encode(&data[..], &mut encoded, "test.bin").unwrap();
+        let lines = encoded
+            .split(|&b| b == b'\n')
+            .filter(|l| !l.is_empty())
+            .count();
+
+        let (outcome, segments) = Decoder::new().decode_segments(&mut encoded).unwrap();
+        assert_eq!(outcome.header.name, b"test.bin");
+        assert_eq!(outcome.trailer.map(|t| t.size), Some(3000));
+        assert_eq!(outcome.bytes_written, 3000);
+        assert_eq!(outcome.crc32, Some(crc32fast::hash(&data)));
+        assert_eq!(segments.len(), lines - 2);
+        assert_eq!(segments.concat(), data);
+
+        // Escape-free lines are borrowed, the rest owned
+        let mut encoded =
+            b"=ybegin line=4 size=6 name=a\r\nKLMN\r\n=}=}\r\n=yend size=6\r\n".to_vec();
+        let (outcome, segments) = Decoder::new().decode_segments(&mut encoded).unwrap();
+        assert_eq!(outcome.header.line_len, Some(4));
+        assert!(matches!(segments[0], Cow::Borrowed(b"!\"#$")));
+        assert!(matches!(segments[1], Cow::Owned(ref s) if s == b"\x13\x13"));
     }
 
     #[test]
-    fn test_decode_simple() {
-        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
-        let mut output = Vec::new();
+    fn test_decode_segments_checks() {
+        let run = |decoder: Decoder, input: &[u8]| {
+            decoder.decode_segments(&mut input.to_vec()).map(|_| ())
+        };
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5 crc32=00000000\n";
 
-        let (header, part, _, size) = decode(&input[..], &mut output).unwrap();
+        let result = run(Decoder::new(), input);
+        assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+        assert!(run(Decoder::new().no_crc_check(), input).is_ok());
 
-        assert_eq!(header.name, "test.bin");
-        assert_eq!(header.size, 5);
-        assert_eq!(size, 5);
-        assert_eq!(output, vec![33, 34, 35, 36, 37]);
-        assert!(part.is_none());
+        let result = run(Decoder::new().text_mode(), input);
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+
+        let result = run(Decoder::new(), b"KLMNO\n");
+        assert!(matches!(result, Err(YencError::InvalidHeader(_))));
+
+        let result = run(
+            Decoder::new(),
+            b"=ybegin line=128 size=5 name=test.bin\nKLMN=",
+        );
+        assert!(matches!(result, Err(YencError::InvalidData(msg)) if msg.contains("escape")));
+    }
+
+    #[test]
+    fn test_decode_segments_like_decode() {
+        let inputs: [&[u8]; 4] = [
+            // NUL lines inside the data and padding at the end
+            b"=ybegin line=4 size=7 name=a\nKL\n\0\0\nMN\n\0\n=yend size=7\n",
+            // An escape pending across NUL lines
+            b"=ybegin line=4 size=3 name=a\nKL=\n\0\n=yend size=3\n",
+            // A trailer glued to the last data line, and a size mismatch
+            b"=ybegin line=4 size=5 name=a\nKLMN=yend size=5\n",
+            // A line repair mode replaces
+            b"=ybegin line=4 size=8 name=a\nKLMN\nK=KMN\n=yend size=8\n",
+        ];
+        for decoder in [Decoder::new(), Decoder::new().repair()] {
+            for input in inputs {
+                let decoded = decoder.decode_to_vec(input);
+                let mut copy = input.to_vec();
+                let segments = decoder.decode_segments(&mut copy);
+                match (decoded, segments) {
+                    (Ok((outcome, data)), Ok((segment_outcome, segments))) => {
+                        assert_eq!(segment_outcome, outcome);
+                        assert_eq!(segments.concat(), data);
+                    }
+                    (Err(err), Err(segment_err)) => {
+                        assert_eq!(segment_err.to_string(), err.to_string());
+                    }
+                    (decoded, segments) => panic!("{:?} but {:?}", decoded, segments),
+                }
+            }
+        }
     }
 
     #[test]
@@ -314,13 +2746,14 @@ mod tests {
         let mut output = Vec::new();
 
         // Using builder
-        let (header, _, _, _) = Decoder::new()
+        let outcome = Decoder::new()
             .strict()
             .no_crc_check()
             .decode(&input[..], &mut output)
             .unwrap();
 
-        assert_eq!(header.name, "test.bin");
+        assert_eq!(outcome.header.name, b"test.bin");
+        assert_eq!(outcome.crc32, None);
     }
 
     #[test]
@@ -337,6 +2770,14 @@ mod tests {
             }
             other => panic!("Expected InvalidData, got {:?}", other),
         }
+
+        // Every escape the encoder writes is a valid one
+        let data: Vec<u8> = (0..=255).collect();
+        let encoded = crate::Encoder::new()
+            .encode_to_vec(&data, "test.bin")
+            .unwrap();
+        let (_, decoded) = Decoder::new().strict().decode_to_vec(&encoded).unwrap();
+        assert_eq!(decoded, data);
     }
 
     #[test]
@@ -345,13 +2786,14 @@ mod tests {
         let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5 crc32=515ad3cc\n";
         let mut output = Vec::new();
 
-        let (header, part, trailer, size) = decode(&input[..], &mut output).unwrap();
+        let outcome = decode(&input[..], &mut output).unwrap();
 
-        assert_eq!(header.name, "test.bin");
-        assert_eq!(size, 5);
+        assert_eq!(outcome.header.name, b"test.bin");
+        assert_eq!(outcome.bytes_written, 5);
         assert_eq!(output, vec![0, 1, 2, 3, 4]);
-        assert!(part.is_none());
-        assert_eq!(trailer.unwrap().crc32, Some(0x515ad3cc));
+        assert!(outcome.part.is_none());
+        assert_eq!(outcome.trailer.unwrap().crc32, Some(0x515ad3cc));
+        assert_eq!(outcome.crc32, Some(0x515ad3cc));
     }
 
     #[test]
@@ -372,6 +2814,22 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_decode_crc_across_blocks() {
+        // Hashed partly when full blocks are written out, and the rest
+        // when the trailer is reached
+        let data: Vec<u8> = (0..OUTPUT_BLOCK * 3 + 77).map(|i| (i * 13) as u8).collect();
+        let mut encoded = Vec::new();
+        crate::encode(&data[..], &mut encoded, "test.bin").unwrap();
+        let mut output = Vec::new();
+
+        let outcome = decode(&encoded[..], &mut output).unwrap();
+
+        assert_eq!(outcome.bytes_written, data.len() as u64);
+        assert_eq!(output, data);
+        assert_eq!(outcome.trailer.unwrap().crc32, Some(crc32fast::hash(&data)));
+    }
+
     #[test]
     fn test_decode_no_crc_check() {
         // Even with wrong CRC, should pass when validation is disabled
@@ -398,6 +2856,349 @@ mod tests {
         assert_eq!(output, vec![0, 1, 2, 3, 4]);
     }
 
+    #[test]
+    fn test_decode_max_memory() {
+        let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5\n";
+        let mut output = Vec::new();
+
+        let result = Decoder::new()
+            .max_memory(64)
+            .decode(&input[..], &mut output);
+        assert!(result.is_ok());
+        assert_eq!(output, vec![0, 1, 2, 3, 4]);
+
+        // Header line alone is longer than the budget
+        let result = Decoder::new()
+            .max_memory(16)
+            .decode(&input[..], &mut output);
+        assert!(matches!(
+            result,
+            Err(YencError::MemoryLimitExceeded { limit: 16 })
+        ));
+
+        // Clones share the budget: while an article being pulled holds its
+        // buffers, another decode has no room left for the header line
+        let decoder = Decoder::new().max_memory(64);
+        let pulled = ArticleStream::new(&decoder.clone(), &input[..]).unwrap();
+        let result = decoder.decode(&input[..], &mut output);
+        assert!(matches!(
+            result,
+            Err(YencError::MemoryLimitExceeded { limit: 64 })
+        ));
+        drop(pulled);
+        assert!(decoder.decode(&input[..], &mut output).is_ok());
+    }
+
+    #[test]
+    fn test_decode_with_scratch() {
+        let decoder = Decoder::new();
+        let mut scratch = Scratch::new();
+
+        // A large article first, so later ones land in used buffers
+        for len in [100_000, 5, 300] {
+            let data: Vec<u8> = (0..len).map(|i| (i % 256) as u8).collect();
+            let mut encoded = Vec::new();
+            crate::encode(&data[..], &mut encoded, "test.bin").unwrap();
+
+            let mut output = Vec::new();
+            let outcome = decoder
+                .decode_with_scratch(&encoded[..], &mut output, &mut scratch)
+                .unwrap();
+            assert!(outcome.trailer.is_some());
+            assert_eq!(outcome.bytes_written, len);
+            assert_eq!(output, data);
+        }
+    }
+
+    #[test]
+    fn test_decode_lines_across_reads() {
+        let data: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+        let mut encoded = Vec::new();
+        crate::encode(&data[..], &mut encoded, "test.bin").unwrap();
+
+        // Reads ending mid-line, and lines longer than a read
+        for chunk_size in [1, 7, 100, 200] {
+            let mut output = Vec::new();
+            let outcome = Decoder::new()
+                .chunk_size(chunk_size)
+                .max_memory(200)
+                .decode(&encoded[..], &mut output)
+                .unwrap();
+            assert!(outcome.trailer.is_some());
+            assert_eq!(outcome.bytes_written, 1000);
+            assert_eq!(output, data);
+        }
+    }
+
+    #[test]
+    fn test_decoder_try_build() {
+        assert!(Decoder::new().strict().try_build().is_ok());
+
+        let result = Decoder::new().max_memory(0).try_build();
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+
+        // Options that contradict each other
+        let invalid = [
+            Decoder::new().strict().allow_crc_mismatch(),
+            Decoder::new().repair().strict(),
+            Decoder::new().no_crc_check().expected_crc(0x1234_5678),
+            Decoder::new().expected_pcrc(0x1234_5678).no_crc_check(),
+            Decoder::new().chunk_size(1 << 20).max_memory(1024),
+        ];
+        for decoder in invalid {
+            let result = decoder.try_build();
+            assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+        }
+        assert!(
+            Decoder::new()
+                .chunk_size(1024)
+                .max_memory(1024)
+                .try_build()
+                .is_ok()
+        );
+        assert!(
+            Decoder::new()
+                .repair()
+                .allow_crc_mismatch()
+                .try_build()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_decode_chunk_size() {
+        let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5 crc32=515ad3cc\n";
+
+        for chunk_size in [1, 3, 64 * 1024] {
+            let mut output = Vec::new();
+            Decoder::new()
+                .chunk_size(chunk_size)
+                .decode(&input[..], &mut output)
+                .unwrap();
+            assert_eq!(output, vec![0, 1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn test_decode_text_mode() {
+        let mut encoded = Vec::new();
+        crate::Encoder::new()
+            .text_mode()
+            .encode(&b"one\ntwo\rthree\r"[..], &mut encoded, "test.txt")
+            .unwrap();
+
+        let mut output = Vec::new();
+        let outcome = Decoder::new()
+            .text_mode()
+            .decode(&encoded[..], &mut output)
+            .unwrap();
+
+        // Lone CRs are kept, CRLF becomes the native line ending
+        let mut expected = b"one".to_vec();
+        expected.extend_from_slice(NATIVE_LINE_ENDING);
+        expected.extend_from_slice(b"two\rthree\r");
+        assert_eq!(output, expected);
+        assert_eq!(outcome.bytes_written, 15);
+
+        // CRLF split between writes, and CRs in a row
+        let mut writer = TextWriter::new(Vec::new());
+        for chunk in [&b"a\r"[..], b"\nb\r\r", b"\r\nc", b"\r"] {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.finish().unwrap();
+        let native = String::from_utf8(NATIVE_LINE_ENDING.to_vec()).unwrap();
+        let expected = format!("a{native}b\r\r{native}c\r");
+        assert_eq!(writer.inner, expected.as_bytes());
+    }
+
+    #[test]
+    fn test_decode_missing_line_length() {
+        let input = b"=ybegin size=5 name=test.bin\n*+,\n-=n\n=yend size=5\n";
+        let mut output = Vec::new();
+
+        let outcome = decode(&input[..], &mut output).unwrap();
+        assert_eq!(output, vec![0, 1, 2, 3, 4]);
+        assert_eq!(outcome.header.line_len, Some(3)); // Observed, not declared
+
+        let input = b"=ybegin line=0 size=5 name=test.bin\n*+,-=n\n=yend size=5\n";
+        let outcome = decode(&input[..], &mut output).unwrap();
+        assert_eq!(outcome.header.line_len, Some(6));
+    }
+
+    #[test]
+    fn test_decode_missing_line_length_strict() {
+        let input = b"=ybegin size=5 name=test.bin\n*+,-=n\n=yend size=5\n";
+        let mut output = Vec::new();
+
+        let result = Decoder::new().strict().decode(&input[..], &mut output);
+        assert!(matches!(result, Err(YencError::MissingField(field)) if field == "line"));
+    }
+
+    #[test]
+    fn test_decode_strict_line_length() {
+        // One character over for the escape pair wrapped at the end is fine
+        let input = b"=ybegin line=4 size=8 name=a\nKLMN\nKLM=}\n=yend size=8\n";
+        assert!(Decoder::new().strict().decode_to_vec(input).is_ok());
+
+        // Two lines joined by a gateway
+        let input = b"=ybegin line=4 size=8 name=a\nKLMNKLMN\n=yend size=8\n";
+        let (_, data) = Decoder::new().decode_to_vec(input).unwrap();
+        assert_eq!(data, b"!\"#$!\"#$");
+        let strict = Decoder::new().strict();
+        match strict.decode_to_vec(input) {
+            Err(YencError::InvalidData(msg)) => assert!(msg.contains("line=4"), "{}", msg),
+            other => panic!("expected InvalidData, got {:?}", other),
+        }
+        let mut copy = input.to_vec();
+        assert!(strict.decode_segments(&mut copy).is_err());
+        let mut events = strict.events(&input[..]).unwrap();
+        let failed = std::iter::from_fn(|| events.next_event().map(|event| event.is_err()))
+            .any(|failed| failed);
+        assert!(failed);
+
+        // Nothing to check against without a line length
+        let input = b"=ybegin size=8 name=a\nKLMNKLMN\n=yend size=8\n";
+        Decoder::new().decode_to_vec(input).unwrap();
+    }
+
+    #[test]
+    fn test_decode_repair() {
+        // `=K` decodes to 0xff, which no encoder escapes
+        let input = b"=ybegin line=4 size=14 name=a\nKLMN\nK=KM\n=KMN\nOPQR\n=yend size=14 crc32=00000000\n";
+        let repair = Decoder::new().repair();
+        let (outcome, data) = repair.decode_to_vec(input).unwrap();
+        assert_eq!(data, b"!\"#$\0\0\0\0\0\0%&'(");
+        assert_eq!(outcome.damage.ranges(), vec![4..10]);
+        assert_eq!(outcome.damage.lines(), 2);
+        assert_eq!(outcome.damage.damaged_bytes(), 6);
+        let mut copy = input.to_vec();
+        let (outcome, segments) = repair.decode_segments(&mut copy).unwrap();
+        assert_eq!(segments.concat(), data);
+        assert_eq!(outcome.damage.ranges(), vec![4..10]);
+
+        // Without repair, the CRC32 catches what lenient decoding let through
+        let result = Decoder::new().decode_to_vec(input);
+        assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+
+        // Undamaged data is still checked against the trailer
+        let input = b"=ybegin line=4 size=4 name=a\nKLMN\n=yend size=4 crc32=00000000\n";
+        let result = repair.decode_to_vec(input);
+        assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+
+        // A lone `=` at the end carries over, though strictly its line counts
+        // as damaged
+        let input = b"=ybegin line=4 size=6 name=a\nKLM=\n}NO\n=yend size=6\n";
+        let (outcome, data) = repair.decode_to_vec(input).unwrap();
+        assert_eq!(data, b"!\"#\x13$%");
+        assert!(outcome.damage.is_empty());
+        let result = repair.clone().strict().decode_to_vec(input);
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_decode_truncated() {
+        let input = b"=ybegin line=128 size=5 name=a\nKLM\n";
+        let (outcome, data) = Decoder::new().decode_to_vec(input).unwrap();
+        assert_eq!(data, b"!\"#");
+        let truncated = DecodeWarning::Truncated { decoded: 3 };
+        assert_eq!(outcome.warnings[0], truncated);
+        let mut copy = input.to_vec();
+        let (outcome, _) = Decoder::new().decode_segments(&mut copy).unwrap();
+        assert_eq!(outcome.warnings[0], truncated);
+
+        let strict = Decoder::new().strict();
+        let result = strict.decode_to_vec(input);
+        assert!(matches!(result, Err(YencError::Truncated { recovered: 3 })));
+        let mut copy = input.to_vec();
+        assert!(strict.decode_segments(&mut copy).is_err());
+        let mut events = strict.events(&input[..]).unwrap();
+        let failed = std::iter::from_fn(|| events.next_event().map(|event| event.is_err()))
+            .any(|failed| failed);
+        assert!(failed);
+
+        // Complete data without a trailer is still flagged
+        let input = b"=ybegin line=128 size=3 name=a\nKLM\n";
+        let (outcome, _) = Decoder::new().decode_to_vec(input).unwrap();
+        assert_eq!(outcome.warnings, [truncated]);
+    }
+
+    #[test]
+    fn test_decode_size_mismatch() {
+        // Bodies cut short, with no CRC32 to notice
+        let cases: [(&[u8], u64); 3] = [
+            (b"=ybegin line=128 size=5 name=a\nKLM\n=yend size=5\n", 5),
+            (b"=ybegin line=128 size=5 name=a\nKLM\n=yend size=3\n", 5),
+            (
+                b"=ybegin part=1 line=128 size=9 name=a\n=ypart begin=1 end=5\n\
+                  KLM\n=yend size=5 part=1\n",
+                5,
+            ),
+        ];
+        let warning = |declared| DecodeWarning::SizeMismatch {
+            declared,
+            decoded: 3,
+        };
+        for (input, declared) in cases {
+            let (outcome, data) = Decoder::new().decode_to_vec(input).unwrap();
+            assert_eq!(data, b"!\"#");
+            assert_eq!(outcome.warnings, [warning(declared)]);
+            let mut copy = input.to_vec();
+            let (outcome, _) = Decoder::new().decode_segments(&mut copy).unwrap();
+            assert_eq!(outcome.warnings, [warning(declared)]);
+
+            let strict = Decoder::new().strict();
+            let result = strict.decode_to_vec(input);
+            assert!(
+                matches!(result, Err(YencError::InvalidData(_))),
+                "{:?}",
+                result
+            );
+            let mut copy = input.to_vec();
+            assert!(strict.decode_segments(&mut copy).is_err());
+            let mut events = strict.events(input).unwrap();
+            let failed = std::iter::from_fn(|| events.next_event().map(|event| event.is_err()))
+                .any(|failed| failed);
+            assert!(failed);
+        }
+
+        let input = b"=ybegin line=128 size=3 name=a\nKLM\n=yend size=3\n";
+        let (outcome, _) = Decoder::new().strict().decode_to_vec(input).unwrap();
+        assert!(outcome.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_decode_strict_version() {
+        let accepted: [&[u8]; 3] = [
+            b"=ybegin line=128 size=5 name=a\nKLMNO\n=yend size=5\n",
+            b"=ybegin part=1 line=128 size=9 name=a\n=ypart begin=1 end=5\nKLMNO\n\
+              =yend size=5 part=1\n",
+            b"=ybegin part=2 total=2 line=128 size=9 name=a\n=ypart begin=5 end=9\nKLMNO\n\
+              =yend size=5 part=2 pcrc32=b05f5b44\n",
+        ];
+        for input in accepted {
+            Decoder::new().strict().decode_to_vec(input).unwrap();
+        }
+
+        let rejected: [&[u8]; 6] = [
+            // 1.0 with part line or trailer part number
+            b"=ybegin line=128 size=5 name=a\n=ypart begin=1 end=5\nKLMNO\n=yend size=5\n",
+            b"=ybegin line=128 size=5 name=a\nKLMNO\n=yend size=5 part=1\n",
+            // total without part, part 0, part past total
+            b"=ybegin total=2 line=128 size=5 name=a\nKLMNO\n=yend size=5\n",
+            b"=ybegin part=0 line=128 size=9 name=a\n=ypart begin=1 end=5\nKLMNO\n\
+              =yend size=5 part=0\n",
+            b"=ybegin part=3 total=2 line=128 size=9 name=a\n=ypart begin=1 end=5\nKLMNO\n\
+              =yend size=5 part=3\n",
+            // 1.2 without pcrc32
+            b"=ybegin part=2 total=2 line=128 size=9 name=a\n=ypart begin=5 end=9\nKLMNO\n\
+              =yend size=5 part=2\n",
+        ];
+        for input in rejected {
+            assert!(Decoder::new().strict().decode_to_vec(input).is_err());
+            Decoder::new().decode_to_vec(input).unwrap();
+        }
+    }
+
     #[test]
     fn test_decode_multipart() {
         let input = b"=ybegin part=1 total=2 line=128 size=10 name=test.bin\n\
@@ -406,9 +3207,15 @@ mod tests {
                       =yend size=5 part=1 pcrc32=515ad3cc\n";
         let mut output = Vec::new();
 
-        let (header, part, trailer, size) = decode(&input[..], &mut output).unwrap();
+        let DecodeOutcome {
+            header,
+            part,
+            trailer,
+            bytes_written: size,
+            ..
+        } = decode(&input[..], &mut output).unwrap();
 
-        assert_eq!(header.name, "test.bin");
+        assert_eq!(header.name, b"test.bin");
         assert_eq!(header.size, 10); // Full file size
         assert_eq!(header.part, Some(1));
         assert_eq!(header.total, Some(2));
@@ -485,6 +3292,94 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_decode_expected_crc() {
+        let single = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5\n";
+        let part = b"=ybegin part=1 line=128 size=10 name=test.bin\n\
+                     =ypart begin=1 end=5\n\
+                     *+,-=n\n\
+                     =yend size=5 part=1\n";
+
+        let (_, data) = Decoder::new()
+            .expected_crc(0x515ad3cc)
+            .decode_to_vec(single)
+            .unwrap();
+        assert_eq!(data, vec![0, 1, 2, 3, 4]);
+        Decoder::new()
+            .expected_pcrc(0x515ad3cc)
+            .decode_to_vec(part)
+            .unwrap();
+
+        let result = Decoder::new()
+            .expected_crc(0xffffffff)
+            .decode_to_vec(single);
+        match result.unwrap_err() {
+            YencError::CrcMismatch { expected, actual } => {
+                assert_eq!(expected, 0xffffffff);
+                assert_eq!(actual, 0x515ad3cc);
+            }
+            other => panic!("Expected CrcMismatch, got {:?}", other),
+        }
+        let result = Decoder::new().expected_pcrc(0xffffffff).decode_to_vec(part);
+        assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+
+        // Each applies only to its kind of article
+        Decoder::new()
+            .expected_pcrc(0xffffffff)
+            .decode_to_vec(single)
+            .unwrap();
+        Decoder::new()
+            .expected_crc(0xffffffff)
+            .decode_to_vec(part)
+            .unwrap();
+
+        // Checked even without a trailer, and cannot be combined with no_crc_check
+        let truncated = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n";
+        let result = Decoder::new()
+            .expected_crc(0xffffffff)
+            .decode_to_vec(truncated);
+        assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+        let decoder = Decoder::new().expected_crc(0xffffffff).no_crc_check();
+        let result = decoder.decode_to_vec(single);
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_decode_allow_crc_mismatch() {
+        let input = b"=ybegin part=1 line=128 size=10 name=test.bin\n\
+                      =ypart begin=1 end=5\n\
+                      *+,-=n\n\
+                      =yend size=5 part=1 pcrc32=ffffffff\n";
+        let decoder = Decoder::new().allow_crc_mismatch();
+        let mismatch = DecodeWarning::CrcMismatch {
+            expected: 0xffffffff,
+            actual: 0x515ad3cc,
+        };
+
+        let mut output = Vec::new();
+        let outcome = decoder.decode(&input[..], &mut output).unwrap();
+        assert_eq!(output, vec![0, 1, 2, 3, 4]);
+        assert!(!outcome.crc_ok());
+        assert_eq!(outcome.crc32, Some(0x515ad3cc));
+        assert_eq!(outcome.warnings, vec![mismatch.clone()]);
+        let mut copy = input.to_vec();
+        let (outcome, _) = decoder.decode_segments(&mut copy).unwrap();
+        assert_eq!(outcome.warnings, vec![mismatch.clone()]);
+
+        // An externally known CRC32 that differs as well is reported once more
+        let (outcome, _) = decoder
+            .clone()
+            .expected_pcrc(0)
+            .decode_to_vec(input)
+            .unwrap();
+        assert_eq!(outcome.warnings.len(), 2);
+
+        let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5 crc32=515ad3cc\n";
+        let (outcome, _) = decoder.decode_to_vec(input).unwrap();
+        assert!(outcome.crc_ok());
+        assert!(outcome.warnings.is_empty());
+    }
+
     #[test]
     fn test_decode_multipart_no_crc_check() {
         // Multi-part with wrong pcrc32, but validation disabled
@@ -511,7 +3406,13 @@ mod tests {
                       =yend size=5 part=2 pcrc32=515ad3cc\n";
         let mut output = Vec::new();
 
-        let (header, part, trailer, size) = decode(&input[..], &mut output).unwrap();
+        let DecodeOutcome {
+            header,
+            part,
+            trailer,
+            bytes_written: size,
+            ..
+        } = decode(&input[..], &mut output).unwrap();
 
         assert_eq!(header.size, 500000); // Full file size
         assert_eq!(header.part, Some(2));
@@ -527,5 +3428,408 @@ mod tests {
 
         assert_eq!(size, 5);
         assert_eq!(output, vec![0, 1, 2, 3, 4]);
+
+        // A part range running backwards is rejected, not taken as huge
+        let input = b"=ybegin part=2 total=5 line=128 size=500000 name=mybinary.dat\n\
+                      =ypart begin=10 end=5\n\
+                      *+,-=n\n\
+                      =yend size=5 part=2\n";
+        let result = decode(&input[..], &mut Vec::new());
+        assert!(matches!(result, Err(YencError::InvalidHeader(_))));
+    }
+
+    #[test]
+    fn test_decode_name_length_limit() {
+        let input = b"=ybegin line=128 size=5 name=abcdefgh.bin\nKLMNO\n=yend size=5\n";
+
+        let result = Decoder::new()
+            .max_name_length(8)
+            .decode(&input[..], &mut Vec::new());
+        match result {
+            Err(YencError::InvalidName(msg)) => {
+                assert!(msg.contains("12 bytes"));
+                assert!(!msg.contains("abcdefgh"));
+            }
+            other => panic!("expected InvalidName, got {:?}", other),
+        }
+
+        assert!(
+            Decoder::new()
+                .max_name_length(12)
+                .decode(&input[..], &mut Vec::new())
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_decode_name_chars() {
+        let input = b"=ybegin line=128 size=5 name=a\x1b[2Jb.bin\nKLMNO\n=yend size=5\n";
+        let mut output = Vec::new();
+
+        // Names are passed through unchecked by default
+        let outcome = decode(&input[..], &mut output).unwrap();
+        assert_eq!(outcome.header.name, b"a\x1b[2Jb.bin");
+
+        let result = Decoder::new()
+            .name_chars(|c| !c.is_control())
+            .decode(&input[..], &mut Vec::new());
+        match result {
+            Err(YencError::InvalidName(msg)) => assert!(msg.contains("U+001B at byte 1")),
+            other => panic!("expected InvalidName, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_strict_empty_name() {
+        let input = b"=ybegin line=128 size=5 name=\nKLMNO\n=yend size=5\n";
+
+        let outcome = decode(&input[..], &mut Vec::new()).unwrap();
+        assert!(outcome.header.name.is_empty());
+
+        let result = Decoder::new().strict().decode(&input[..], &mut Vec::new());
+        assert!(matches!(result, Err(YencError::InvalidName(_))));
+    }
+
+    #[test]
+    fn test_decode_latin1_name() {
+        let input = b"=ybegin line=128 size=5 name=caf\xe9 \xfc.bin\nKLMNO\n=yend size=5\n";
+        let mut output = Vec::new();
+
+        let outcome = decode(&input[..], &mut output).unwrap();
+        assert_eq!(outcome.header.name, b"caf\xe9 \xfc.bin");
+        assert_eq!(outcome.header.name_lossy(), "caf\u{fffd} \u{fffd}.bin");
+        assert_eq!(output, b"!\"#$%");
+
+        let result = Decoder::new()
+            .name_chars(|c| c != char::REPLACEMENT_CHARACTER)
+            .decode(&input[..], &mut Vec::new());
+        match result {
+            Err(YencError::InvalidName(msg)) => assert!(msg.contains("U+FFFD at byte 3")),
+            other => panic!("expected InvalidName, got {:?}", other),
+        }
+
+        // Mangled keywords are not recognized
+        let input = b"=ybegin line=128 size\xe9=5 name=a.bin\nKLMNO\n=yend size=5\n";
+        let result = decode(&input[..], &mut Vec::new());
+        assert!(matches!(result, Err(YencError::MissingField(_))));
+    }
+
+    #[test]
+    fn test_decode_keep_raw_multipart() {
+        let article = b"=ybegin part=1 total=2 line=128 size=10 name=test.bin\n\
+                        =ypart begin=1 end=5\n\
+                        *+,-=n\n\
+                        =yend size=5 part=1 pcrc32=515ad3cc\n";
+        let input = [
+            &b"From: poster\n\n"[..],
+            &article[..],
+            &b"-- \nsignature\n"[..],
+        ]
+        .concat();
+        let (mut output, mut raw) = (Vec::new(), Vec::new());
+
+        let outcome = Decoder::new()
+            .decode_keep_raw(&input[..], &mut output, &mut raw)
+            .unwrap();
+
+        assert!(outcome.part.is_some());
+        assert!(outcome.trailer.is_some());
+        assert_eq!(outcome.bytes_written, 5);
+        assert_eq!(output, vec![0, 1, 2, 3, 4]);
+        assert_eq!(raw, &article[..]);
+    }
+
+    #[test]
+    fn test_decode_tee() {
+        let data: Vec<u8> = (0..50_000u32).map(|i| (i * 13 + i / 101) as u8).collect();
+        let encoded = crate::Encoder::new()
+            .encode_to_vec(&data, "test.bin")
+            .unwrap();
+
+        let (mut output, mut copy) = (Vec::new(), Vec::new());
+        let outcome = Decoder::new()
+            .decode_tee(&encoded[..], &mut [&mut output, &mut copy])
+            .unwrap();
+        assert_eq!(outcome.bytes_written, data.len() as u64);
+        assert_eq!(output, data);
+        assert_eq!(copy, data);
+
+        // A failing writer stops decoding
+        let mut full = [0u8; 100];
+        let result = Decoder::new().decode_tee(&encoded[..], &mut [&mut &mut full[..]]);
+        assert!(matches!(result, Err(YencError::Io(_))));
+    }
+
+    #[test]
+    fn test_read_metadata() {
+        // Data that would fail the CRC32 check is not looked at
+        let input = b"=ybegin part=1 total=2 line=128 size=10 name=test.bin\n\
+                      =ypart begin=1 end=5\n\
+                      KLMNO\n\
+                      =yend size=5 part=1 pcrc32=515ad3cc\n";
+        assert!(Decoder::new().decode(&input[..], io::sink()).is_err());
+        let metadata = Decoder::new().read_metadata(&input[..]).unwrap();
+        assert_eq!(metadata.header.total, Some(2));
+        assert_eq!(metadata.part.unwrap().end, 5);
+        assert_eq!(metadata.trailer.unwrap().pcrc32, Some(0x515ad3cc));
+
+        // Keyword lines are still checked against each other
+        let input = b"=ybegin part=1 total=2 line=128 size=10 name=test.bin\n\
+                      =ypart begin=1 end=5\n\
+                      KLMNO\n\
+                      =yend size=4 part=1\n";
+        let result = Decoder::new().read_metadata(&input[..]);
+        assert!(matches!(result, Err(YencError::InvalidData(_))));
+
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO=yend size=5\n";
+        let metadata = Decoder::new().read_metadata(&input[..]).unwrap();
+        assert_eq!(metadata.trailer.unwrap().size, 5);
+
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n";
+        let metadata = Decoder::new().read_metadata(&input[..]).unwrap();
+        assert!(metadata.trailer.is_none());
+    }
+
+    #[test]
+    fn test_decode_all() {
+        let input = b"From: poster\n\n\
+                      =ybegin part=1 total=2 line=128 size=10 name=test.bin\n\
+                      =ypart begin=1 end=5\n\
+                      *+,-=n\n\
+                      =yend size=5 part=1 pcrc32=515ad3cc\n\
+                      -- \nsignature\n\
+                      =ybegin line=128 size=3 name=cut.bin\n\
+                      KLM\n\
+                      =ybegin line=128 size=2 name=last.bin\n\
+                      NO\n\
+                      =yend size=2\n";
+        let mut output = Vec::new();
+        let outcomes = Decoder::new().decode_all(&input[..], &mut output).unwrap();
+
+        let names: Vec<_> = outcomes.iter().map(|o| o.header.name_lossy()).collect();
+        assert_eq!(names, ["test.bin", "cut.bin", "last.bin"]);
+        let sizes: Vec<_> = outcomes.iter().map(|o| o.bytes_written).collect();
+        assert_eq!(sizes, [5, 3, 2]);
+        assert!(outcomes[0].part.is_some());
+        assert!(outcomes[1].trailer.is_none());
+        assert!(outcomes[2].trailer.is_some());
+        assert_eq!(output, [0, 1, 2, 3, 4, b'!', b'"', b'#', b'$', b'%']);
+
+        let outcomes = Decoder::new()
+            .decode_all(&b"no articles here\n"[..], io::sink())
+            .unwrap();
+        assert!(outcomes.is_empty());
+
+        let input = b"=ybegin line=128 size=1 name=a\nK\n=yend size=1\n\
+                      =ybegin line=128 size=1 name=b\nK\n=yend size=1 crc32=00000000\n";
+        let result = Decoder::new().decode_all(&input[..], io::sink());
+        assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+    }
+
+    #[test]
+    fn test_decode_nul_padding() {
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n\0\0\0\r\n\0\0\n=yend size=5\n";
+        let padding = DecodeWarning::NulPadding { bytes: 5 };
+
+        let mut output = Vec::new();
+        let outcome = Decoder::new().decode(&input[..], &mut output).unwrap();
+        assert_eq!(output, b"!\"#$%");
+        assert_eq!(outcome.warnings, vec![padding.clone()]);
+        let mut copy = input.to_vec();
+        let (outcome, segments) = Decoder::new().decode_segments(&mut copy).unwrap();
+        assert_eq!(segments.concat(), b"!\"#$%");
+        assert_eq!(outcome.bytes_written, 5);
+        assert_eq!(outcome.warnings, vec![padding]);
+
+        // Strict decoding takes them for data
+        let result = Decoder::new().strict().decode(&input[..], io::sink());
+        assert!(matches!(result, Err(YencError::InvalidData(_))));
+
+        // Padding up to the end of input, or glued to the trailer
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n\0\0\0";
+        let (outcome, data) = Decoder::new().decode_to_vec(input).unwrap();
+        assert_eq!(data, b"!\"#$%");
+        assert!(
+            outcome
+                .warnings
+                .contains(&DecodeWarning::NulPadding { bytes: 3 })
+        );
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n\0\0=yend size=5\n";
+        let (outcome, _) = Decoder::new().decode_to_vec(input).unwrap();
+        assert_eq!(
+            outcome.warnings,
+            vec![DecodeWarning::NulPadding { bytes: 2 }]
+        );
+
+        // NUL lines with data after them are not padding
+        let input = b"=ybegin line=128 size=4 name=test.bin\nKL\n\0\0\nMN\n=yend size=4\n";
+        let (outcome, data) = Decoder::new().decode_to_vec(input).unwrap();
+        assert_eq!(data, b"!\"\xd6\xd6#$");
+        let mut copy = input.to_vec();
+        let (_, segments) = Decoder::new().decode_segments(&mut copy).unwrap();
+        assert_eq!(segments.concat(), data);
+        assert_eq!(outcome.bytes_written, 6);
+    }
+
+    #[test]
+    fn test_decode_trailing_garbage() {
+        let input = b"=ybegin line=128 size=2 name=a\nKL\n=yend size=2\n\
+                      -- \nsig =y\xff\n=yend size=9\n\
+                      =ybegin line=128 size=1 name=b\nK\n=yend size=1\r\nPath: x\r\n";
+
+        // A single article ends at its trailer
+        for decoder in [Decoder::new(), Decoder::new().strict()] {
+            let mut output = Vec::new();
+            let outcome = decoder.decode(&input[..], &mut output).unwrap();
+            assert_eq!(output, b"!\"");
+            assert_eq!(outcome.trailing_bytes, 0);
+            let (outcome, _) = decoder.decode_to_vec(input).unwrap();
+            assert_eq!(outcome.header.name, b"a");
+        }
+
+        let outcomes = Decoder::new().decode_all(&input[..], io::sink()).unwrap();
+        let skipped: Vec<_> = outcomes.iter().map(|o| o.trailing_bytes).collect();
+        assert_eq!(skipped, [25, 9]);
+        let results = Decoder::new()
+            .decode_all_resync(&input[..], io::sink())
+            .unwrap();
+        assert_eq!(results[1].as_ref().unwrap().trailing_bytes, 9);
+    }
+
+    #[test]
+    fn test_decode_all_resync() {
+        let input = b"=ybegin line=128 size=oops name=a\nK\n=yend size=1\n\
+                      =ybegin line=128 size=1 name=b\nK\n=yend size=1 crc32=00000000\n\
+                      =ybegin line=128 size=2 name=c\nNO\n=yend size=2\n";
+        let mut output = Vec::new();
+        let results = Decoder::new()
+            .decode_all_resync(&input[..], &mut output)
+            .unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert!(matches!(results[0], Err(YencError::InvalidHeader(_))));
+        assert!(matches!(results[1], Err(YencError::CrcMismatch { .. })));
+        assert_eq!(results[2].as_ref().unwrap().header.name, b"c");
+        // The failed article's data was already written
+        assert_eq!(output, b"!$%");
+
+        // Without the next article there is nothing to resume at
+        let input = b"=ybegin line=128 size=1 name=a\nK\n=yend size=1 crc32=00000000\nK\n";
+        let results = Decoder::new()
+            .decode_all_resync(&input[..], io::sink())
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+
+        // An article failing where the next one starts does not take it along
+        let input = b"=ybegin line=128 size=1 name=a.txt\nK\n\
+                      =ybegin line=128 size=1 name=b.txt\nK\n=yend size=1\n";
+        let results = Decoder::new()
+            .strict()
+            .decode_all_resync(&input[..], io::sink())
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(matches!(results[0], Err(YencError::Truncated { .. })));
+        assert_eq!(results[1].as_ref().unwrap().header.name, b"b.txt");
+    }
+
+    #[test]
+    fn test_decode_unknown_keywords() {
+        let input = b"=ybegin line=128 size=5 foo=bar name=test.bin\n\
+                      KLMNO\n\
+                      =yend size=5\n";
+
+        // Ignored by default, even in strict mode
+        assert!(
+            Decoder::new()
+                .strict()
+                .decode(&input[..], &mut Vec::new())
+                .is_ok()
+        );
+
+        let result = Decoder::new()
+            .reject_unknown_keywords()
+            .decode(&input[..], &mut Vec::new());
+        match result {
+            Err(YencError::InvalidHeader(msg)) => assert!(msg.contains("'foo' on =ybegin")),
+            other => panic!("expected InvalidHeader, got {:?}", other),
+        }
+
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5 junk=1\n";
+        let result = Decoder::new()
+            .reject_unknown_keywords()
+            .decode(&input[..], &mut Vec::new());
+        assert!(matches!(result, Err(YencError::InvalidHeader(_))));
+    }
+
+    #[test]
+    fn test_decode_keyword_formatting() {
+        let inputs: [&[u8]; 3] = [
+            b"=ybegin line=128 size =5 name=test.bin\nKLMNO\n=yend size=5\n",
+            b"=ybegin line=128\tsize=5 name=test.bin\nKLMNO\n=yend size=5\n",
+            b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5 size=5\n",
+        ];
+        for input in inputs {
+            let (_, data) = Decoder::new().decode_to_vec(input).unwrap();
+            assert_eq!(data, b"!\"#$%");
+            let result = Decoder::new().strict().decode_to_vec(input);
+            assert!(matches!(result, Err(YencError::InvalidHeader(_))));
+        }
+
+        // A malformed number fails either way, naming the field
+        let input = b"=ybegin line=128 size=5x name=test.bin\nKLMNO\n=yend size=5\n";
+        match Decoder::new().decode_to_vec(input) {
+            Err(YencError::InvalidHeader(msg)) => assert!(msg.contains("size="), "{}", msg),
+            other => panic!("expected InvalidHeader, got {:?}", other),
+        }
+    }
+
+    /// Writer that records the size of every write call
+    struct CountingWriter(Vec<usize>, Vec<u8>);
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(buf.len());
+            self.1.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_decode_batches_writes() {
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7) as u8).collect();
+        let mut encoded = Vec::new();
+        crate::encode(&data[..], &mut encoded, "big.bin").unwrap();
+
+        let mut writer = CountingWriter(Vec::new(), Vec::new());
+        decode(&encoded[..], &mut writer).unwrap();
+        assert_eq!(writer.1, data);
+        // 100 kB in 32 KiB blocks rather than one write per 128-byte line
+        assert_eq!(writer.0.len(), 4);
+    }
+
+    #[test]
+    fn test_decode_writes_data_before_errors() {
+        // CRC mismatch: all data was decoded and still reaches the writer
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5 crc32=00000000\n";
+        let mut output = Vec::new();
+        let result = decode(&input[..], &mut output);
+        assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+        assert_eq!(output, b"!\"#$%");
+
+        // Invalid escape in strict mode: only the lines before it are written
+        let input = b"=ybegin line=128 size=6 name=test.bin\nKLM\nN=AO\n=yend size=6\n";
+        let mut output = Vec::new();
+        assert!(
+            Decoder::new()
+                .strict()
+                .decode(&input[..], &mut output)
+                .is_err()
+        );
+        assert_eq!(output, b"!\"#");
     }
 }
@@ -0,0 +1,182 @@
+//! NEON decoding kernel for aarch64
+//!
+//! Mirrors the SSE2 kernel: runs without an escape character are decoded
+//! with a single vector subtract and store, sparse escape pairs are handled
+//! one at a time, and escape-heavy chunks go through `decode_dense`. NEON is
+//! part of the aarch64 baseline, so no runtime detection is needed.
+
+use std::arch::aarch64::*;
+
+use super::{decode_dense, decode_escaped};
+use crate::consts::{ESCAPE_CHAR, OFFSET};
+use crate::error::Result;
+
+/// Escape characters per 16-byte chunk above which `decode_dense` is faster
+const DENSE_ESCAPES_PER_16: u32 = 3;
+
+/// Decode as much of `data` as the NEON kernel can handle
+///
+/// Returns the number of input bytes consumed; the caller decodes the rest
+/// with the scalar code. `out` must have at least `data.len()` bytes of
+/// spare capacity.
+#[inline]
+pub(super) fn decode(
+    data: &[u8],
+    out: &mut Vec<u8>,
+    escaped: &mut bool,
+    strict: bool,
+) -> Result<usize> {
+    debug_assert!(out.capacity() - out.len() >= data.len());
+
+    // SAFETY: NEON is part of the aarch64 baseline, and the caller reserved room
+    unsafe { decode_neon(data, out, escaped, strict) }
+}
+
+/// Narrow a byte-wise comparison result to 4 bits per byte, first byte lowest
+///
+/// NEON has no movemask; shifting each 16-bit lane right by 4 and narrowing
+/// keeps one nibble of every byte.
+#[inline(always)]
+unsafe fn nibble_mask(cmp: uint8x16_t) -> u64 {
+    // SAFETY: plain register operations
+    unsafe {
+        let narrowed = vshrn_n_u16::<4>(vreinterpretq_u16_u8(cmp));
+        vget_lane_u64::<0>(vreinterpret_u64_u8(narrowed))
+    }
+}
+
+/// Decode 16 bytes at a time
+///
+/// # Safety
+/// The CPU must support NEON, and `out` must have at least `data.len()`
+/// bytes of spare capacity.
+#[target_feature(enable = "neon")]
+unsafe fn decode_neon(
+    data: &[u8],
+    out: &mut Vec<u8>,
+    escaped: &mut bool,
+    strict: bool,
+) -> Result<usize> {
+    let mut i = 0;
+    // SAFETY: loads stay within `data`; every store lands in the spare
+    // capacity, since at most `i` bytes were appended for `i` consumed and
+    // `i + 16 <= data.len()`
+    unsafe {
+        let escape = vdupq_n_u8(ESCAPE_CHAR);
+        let offset = vdupq_n_u8(OFFSET);
+
+        while i + 16 <= data.len() {
+            if *escaped {
+                *escaped = false;
+                out.push(decode_escaped(data[i], strict)?);
+                i += 1;
+                continue;
+            }
+
+            let chunk = vld1q_u8(data.as_ptr().add(i));
+            let mask = nibble_mask(vceqq_u8(chunk, escape));
+
+            // Escape-heavy chunks are cheaper to decode byte by byte than
+            // one escape pair per iteration
+            if mask.count_ones() / 4 > DENSE_ESCAPES_PER_16 {
+                decode_dense(&data[i..i + 16], out, escaped, strict)?;
+                i += 16;
+                continue;
+            }
+
+            let len = out.len();
+            vst1q_u8(out.as_mut_ptr().add(len), vsubq_u8(chunk, offset));
+
+            // Keep the bytes before the first escape character
+            let run = if mask == 0 {
+                16
+            } else {
+                mask.trailing_zeros() as usize / 4
+            };
+            out.set_len(len + run);
+            i += run;
+
+            if mask != 0 {
+                *escaped = true;
+                i += 1;
+            }
+        }
+    }
+    Ok(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::decode_scalar;
+    use super::*;
+
+    /// Encoded-looking input with roughly `percent`% escape characters
+    fn sample(len: usize, percent: u32, seed: u32) -> Vec<u8> {
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                if state % 100 < percent {
+                    ESCAPE_CHAR
+                } else {
+                    (state >> 8) as u8
+                }
+            })
+            .collect()
+    }
+
+    /// Decode `data` with the NEON kernel followed by the scalar tail
+    fn run(data: &[u8], escaped: bool, strict: bool) -> Result<(Vec<u8>, bool)> {
+        let mut out = Vec::with_capacity(data.len());
+        let mut escaped = escaped;
+        let consumed = decode(data, &mut out, &mut escaped, strict)?;
+        decode_scalar(&data[consumed..], &mut out, &mut escaped, strict)?;
+        Ok((out, escaped))
+    }
+
+    fn scalar(data: &[u8], escaped: bool, strict: bool) -> Result<(Vec<u8>, bool)> {
+        let mut out = Vec::new();
+        let mut escaped = escaped;
+        decode_scalar(data, &mut out, &mut escaped, strict)?;
+        Ok((out, escaped))
+    }
+
+    #[test]
+    fn test_neon_matches_scalar() {
+        for percent in [0, 1, 10, 50, 100] {
+            for len in (0..100).chain([1000, 4096]) {
+                let data = sample(len, percent, len as u32 * 31 + percent);
+                for escaped in [false, true] {
+                    assert_eq!(
+                        run(&data, escaped, false).unwrap(),
+                        scalar(&data, escaped, false).unwrap(),
+                        "len={} percent={} escaped={}",
+                        len,
+                        percent,
+                        escaped
+                    );
+                    assert_eq!(
+                        run(&data, escaped, true).ok(),
+                        scalar(&data, escaped, true).ok(),
+                        "strict len={} percent={} escaped={}",
+                        len,
+                        percent,
+                        escaped
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_nibble_mask() {
+        let mut bytes = [0u8; 16];
+        bytes[0] = 0xff;
+        bytes[5] = 0xff;
+        bytes[15] = 0xff;
+        let mask = unsafe { nibble_mask(vld1q_u8(bytes.as_ptr())) };
+        assert_eq!(mask, 0xf000_0000_00f0_000f);
+    }
+}
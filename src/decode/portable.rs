@@ -0,0 +1,126 @@
+//! Portable `std::simd` decoding kernel
+//!
+//! Same structure as the hand-written kernels, expressed with `std::simd`
+//! so that targets without them (RISC-V, s390x, ...) still get vectorized
+//! decoding. Requires a nightly compiler and the `portable-simd` feature.
+
+use std::simd::prelude::*;
+
+use super::{decode_escaped, decode_scalar};
+use crate::consts::{ESCAPE_CHAR, OFFSET};
+use crate::error::Result;
+
+const LANES: usize = 16;
+
+/// Escape characters per chunk above which the byte loop is faster
+const DENSE_ESCAPES: u32 = 3;
+
+/// Decode as much of `data` as the portable kernel can handle
+///
+/// Returns the number of input bytes consumed; the caller decodes the rest
+/// with the scalar code.
+pub(super) fn decode(
+    data: &[u8],
+    out: &mut Vec<u8>,
+    escaped: &mut bool,
+    strict: bool,
+) -> Result<usize> {
+    let escape = Simd::splat(ESCAPE_CHAR);
+    let offset = Simd::splat(OFFSET);
+
+    let mut i = 0;
+    while i + LANES <= data.len() {
+        if *escaped {
+            *escaped = false;
+            out.push(decode_escaped(data[i], strict)?);
+            i += 1;
+            continue;
+        }
+
+        let chunk = u8x16::from_slice(&data[i..i + LANES]);
+        let mask = chunk.simd_eq(escape).to_bitmask();
+
+        if mask.count_ones() > DENSE_ESCAPES {
+            decode_scalar(&data[i..i + LANES], out, escaped, strict)?;
+            i += LANES;
+            continue;
+        }
+
+        // Keep the bytes before the first escape character
+        let run = if mask == 0 {
+            LANES
+        } else {
+            mask.trailing_zeros() as usize
+        };
+        out.extend_from_slice(&(chunk - offset).to_array()[..run]);
+        i += run;
+
+        if mask != 0 {
+            *escaped = true;
+            i += 1;
+        }
+    }
+    Ok(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encoded-looking input with roughly `percent`% escape characters
+    fn sample(len: usize, percent: u32, seed: u32) -> Vec<u8> {
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                if state % 100 < percent {
+                    ESCAPE_CHAR
+                } else {
+                    (state >> 8) as u8
+                }
+            })
+            .collect()
+    }
+
+    /// Decode `data` with `decode` (or the scalar code alone) plus the tail
+    fn run(data: &[u8], escaped: bool, strict: bool, vector: bool) -> Result<(Vec<u8>, bool)> {
+        let mut out = Vec::new();
+        let mut escaped = escaped;
+        let consumed = if vector {
+            decode(data, &mut out, &mut escaped, strict)?
+        } else {
+            0
+        };
+        decode_scalar(&data[consumed..], &mut out, &mut escaped, strict)?;
+        Ok((out, escaped))
+    }
+
+    #[test]
+    fn test_portable_matches_scalar() {
+        for percent in [0, 1, 10, 50, 100] {
+            for len in (0..100).chain([1000, 4096]) {
+                let data = sample(len, percent, len as u32 * 31 + percent);
+                for escaped in [false, true] {
+                    assert_eq!(
+                        run(&data, escaped, false, true).unwrap(),
+                        run(&data, escaped, false, false).unwrap(),
+                        "len={} percent={} escaped={}",
+                        len,
+                        percent,
+                        escaped
+                    );
+                    assert_eq!(
+                        run(&data, escaped, true, true).ok(),
+                        run(&data, escaped, true, false).ok(),
+                        "strict len={} percent={} escaped={}",
+                        len,
+                        percent,
+                        escaped
+                    );
+                }
+            }
+        }
+    }
+}
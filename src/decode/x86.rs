@@ -0,0 +1,263 @@
+//! SSE2 and AVX2 decoding kernels for x86_64
+//!
+//! Both kernels look for `=` a whole register at a time. Runs without an
+//! escape character are decoded with a single vector subtract and store,
+//! sparse escape pairs are handled one at a time, and escape-heavy chunks go
+//! through a branch-free byte loop. Lines are split on LF and trimmed before
+//! they get here, so `=` is the only byte that needs attention.
+
+use std::arch::x86_64::*;
+
+use super::{decode_dense, decode_escaped};
+use crate::consts::{ESCAPE_CHAR, OFFSET};
+use crate::dispatch::Backend;
+use crate::error::Result;
+
+/// Escape characters per 16-byte chunk above which `decode_dense` is faster
+const DENSE_ESCAPES_PER_16: u32 = 3;
+/// Escape characters per 32-byte chunk above which `decode_dense` is faster
+const DENSE_ESCAPES_PER_32: u32 = 6;
+
+/// Decode as much of `data` as the kernels of `backend` can handle
+///
+/// `backend` is `Sse2` or `Avx2` and must be available on this CPU. Returns
+/// the number of input bytes consumed; the caller decodes the rest with the
+/// scalar code. `out` must have at least `data.len()` bytes of spare
+/// capacity.
+#[inline]
+pub(super) fn decode(
+    backend: Backend,
+    data: &[u8],
+    out: &mut Vec<u8>,
+    escaped: &mut bool,
+    strict: bool,
+) -> Result<usize> {
+    debug_assert!(out.capacity() - out.len() >= data.len());
+
+    debug_assert!(backend.is_available());
+
+    let mut consumed = 0;
+    if backend == Backend::Avx2 {
+        // SAFETY: the dispatcher only picks available backends, and the
+        // caller reserved room
+        consumed = unsafe { decode_avx2(data, out, escaped, strict)? };
+    }
+    // SAFETY: SSE2 is part of the x86_64 baseline, and the caller reserved room
+    consumed += unsafe { decode_sse2(&data[consumed..], out, escaped, strict)? };
+    Ok(consumed)
+}
+
+/// Decode 16 bytes at a time
+///
+/// # Safety
+/// The CPU must support SSE2, and `out` must have at least `data.len()`
+/// bytes of spare capacity.
+#[target_feature(enable = "sse2")]
+unsafe fn decode_sse2(
+    data: &[u8],
+    out: &mut Vec<u8>,
+    escaped: &mut bool,
+    strict: bool,
+) -> Result<usize> {
+    let mut i = 0;
+    // SAFETY: loads stay within `data`; every store lands in the spare
+    // capacity, since at most `i` bytes were appended for `i` consumed and
+    // `i + 16 <= data.len()`
+    unsafe {
+        let escape = _mm_set1_epi8(ESCAPE_CHAR as i8);
+        let offset = _mm_set1_epi8(OFFSET as i8);
+
+        while i + 16 <= data.len() {
+            if *escaped {
+                *escaped = false;
+                out.push(decode_escaped(data[i], strict)?);
+                i += 1;
+                continue;
+            }
+
+            let chunk = _mm_loadu_si128(data.as_ptr().add(i).cast());
+            let mask = _mm_movemask_epi8(_mm_cmpeq_epi8(chunk, escape)) as u32;
+
+            // Escape-heavy chunks are cheaper to decode byte by byte than
+            // one escape pair per iteration
+            if mask.count_ones() > DENSE_ESCAPES_PER_16 {
+                decode_dense(&data[i..i + 16], out, escaped, strict)?;
+                i += 16;
+                continue;
+            }
+
+            let len = out.len();
+            _mm_storeu_si128(
+                out.as_mut_ptr().add(len).cast(),
+                _mm_sub_epi8(chunk, offset),
+            );
+
+            // Keep the bytes before the first escape character
+            let run = if mask == 0 {
+                16
+            } else {
+                mask.trailing_zeros() as usize
+            };
+            out.set_len(len + run);
+            i += run;
+
+            if mask != 0 {
+                *escaped = true;
+                i += 1;
+            }
+        }
+    }
+    Ok(i)
+}
+
+/// Decode 32 bytes at a time
+///
+/// # Safety
+/// The CPU must support AVX2, and `out` must have at least `data.len()`
+/// bytes of spare capacity.
+#[target_feature(enable = "avx2")]
+unsafe fn decode_avx2(
+    data: &[u8],
+    out: &mut Vec<u8>,
+    escaped: &mut bool,
+    strict: bool,
+) -> Result<usize> {
+    let mut i = 0;
+    // SAFETY: as for `decode_sse2`, with 32-byte registers
+    unsafe {
+        let escape = _mm256_set1_epi8(ESCAPE_CHAR as i8);
+        let offset = _mm256_set1_epi8(OFFSET as i8);
+
+        while i + 32 <= data.len() {
+            if *escaped {
+                *escaped = false;
+                out.push(decode_escaped(data[i], strict)?);
+                i += 1;
+                continue;
+            }
+
+            let chunk = _mm256_loadu_si256(data.as_ptr().add(i).cast());
+            let mask = _mm256_movemask_epi8(_mm256_cmpeq_epi8(chunk, escape)) as u32;
+
+            // Escape-heavy chunks are cheaper to decode byte by byte than
+            // one escape pair per iteration
+            if mask.count_ones() > DENSE_ESCAPES_PER_32 {
+                decode_dense(&data[i..i + 32], out, escaped, strict)?;
+                i += 32;
+                continue;
+            }
+
+            let len = out.len();
+            _mm256_storeu_si256(
+                out.as_mut_ptr().add(len).cast(),
+                _mm256_sub_epi8(chunk, offset),
+            );
+
+            let run = if mask == 0 {
+                32
+            } else {
+                mask.trailing_zeros() as usize
+            };
+            out.set_len(len + run);
+            i += run;
+
+            if mask != 0 {
+                *escaped = true;
+                i += 1;
+            }
+        }
+    }
+    Ok(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::decode_scalar;
+    use super::*;
+
+    /// Encoded-looking input with roughly `percent`% escape characters
+    fn sample(len: usize, percent: u32, seed: u32) -> Vec<u8> {
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                if state % 100 < percent {
+                    ESCAPE_CHAR
+                } else {
+                    (state >> 8) as u8
+                }
+            })
+            .collect()
+    }
+
+    type Kernel = unsafe fn(&[u8], &mut Vec<u8>, &mut bool, bool) -> Result<usize>;
+
+    /// Decode `data` with `kernel` followed by the scalar tail
+    fn run(kernel: Kernel, data: &[u8], escaped: bool, strict: bool) -> Result<(Vec<u8>, bool)> {
+        let mut out = Vec::with_capacity(data.len());
+        let mut escaped = escaped;
+        let consumed = unsafe { kernel(data, &mut out, &mut escaped, strict)? };
+        decode_scalar(&data[consumed..], &mut out, &mut escaped, strict)?;
+        Ok((out, escaped))
+    }
+
+    fn scalar(data: &[u8], escaped: bool, strict: bool) -> Result<(Vec<u8>, bool)> {
+        let mut out = Vec::new();
+        let mut escaped = escaped;
+        decode_scalar(data, &mut out, &mut escaped, strict)?;
+        Ok((out, escaped))
+    }
+
+    fn check_kernel(kernel: Kernel) {
+        for percent in [0, 1, 10, 50, 100] {
+            for len in (0..100).chain([1000, 4096]) {
+                let data = sample(len, percent, len as u32 * 31 + percent);
+                for escaped in [false, true] {
+                    assert_eq!(
+                        run(kernel, &data, escaped, false).unwrap(),
+                        scalar(&data, escaped, false).unwrap(),
+                        "len={} percent={} escaped={}",
+                        len,
+                        percent,
+                        escaped
+                    );
+                    assert_eq!(
+                        run(kernel, &data, escaped, true).ok(),
+                        scalar(&data, escaped, true).ok(),
+                        "strict len={} percent={} escaped={}",
+                        len,
+                        percent,
+                        escaped
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sse2_matches_scalar() {
+        check_kernel(decode_sse2);
+    }
+
+    #[test]
+    fn test_avx2_matches_scalar() {
+        if Backend::Avx2.is_available() {
+            check_kernel(decode_avx2);
+        }
+    }
+
+    #[test]
+    fn test_kernels_strict_escape_errors() {
+        // "=A" decodes to a byte that never needs escaping
+        let mut data = vec![b'a'; 40];
+        data[20] = ESCAPE_CHAR;
+        data[21] = b'A';
+
+        assert!(run(decode_sse2, &data, false, true).is_err());
+        if Backend::Avx2.is_available() {
+            assert!(run(decode_avx2, &data, false, true).is_err());
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Comparison of decoded yEnc data against a known original
+
+use std::io::{self, Read, Write};
+
+use crc32fast::Hasher;
+
+use crate::decode::{DecodeOutcome, Decoder};
+use crate::error::Result;
+use crate::header::{YencHeader, YencTrailer};
+
+/// Result of comparing a decoded article with the original data
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffReport {
+    /// Parsed `=ybegin` fields of the article
+    pub header: YencHeader,
+    /// Parsed `=yend` fields, if the trailer was found
+    pub trailer: Option<YencTrailer>,
+    /// Number of bytes the article decoded to
+    pub decoded_size: u64,
+    /// Number of bytes in the original
+    pub original_size: u64,
+    /// Offset (0-based) of the first differing byte, if any
+    ///
+    /// When one side is a prefix of the other, this is the length of the
+    /// shorter side.
+    pub first_difference: Option<u64>,
+    /// CRC32 of the decoded data
+    pub decoded_crc: u32,
+    /// CRC32 of the original data
+    pub original_crc: u32,
+}
+
+impl DiffReport {
+    /// Whether the decoded data is identical to the original
+    pub fn is_match(&self) -> bool {
+        self.first_difference.is_none()
+    }
+
+    /// CRC32 announced by the trailer (`pcrc32` for parts, `crc32` otherwise)
+    pub fn trailer_crc(&self) -> Option<u32> {
+        let trailer = self.trailer.as_ref()?;
+        if self.header.part.is_some() {
+            trailer.pcrc32
+        } else {
+            trailer.crc32
+        }
+    }
+}
+
+/// Writer that compares everything written to it against a reader
+struct Comparer<R> {
+    original: R,
+    buf: Vec<u8>,
+    offset: u64,
+    first_difference: Option<u64>,
+    decoded_hasher: Hasher,
+    original_hasher: Hasher,
+    original_size: u64,
+}
+
+impl<R: Read> Comparer<R> {
+    /// Read up to `len` bytes of the original into the scratch buffer
+    fn read_original(&mut self, len: usize) -> io::Result<usize> {
+        self.buf.clear();
+        let n = self
+            .original
+            .by_ref()
+            .take(len as u64)
+            .read_to_end(&mut self.buf)?;
+        self.original_hasher.update(&self.buf);
+        self.original_size += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read> Write for Comparer<R> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.decoded_hasher.update(data);
+
+        let n = self.read_original(data.len())?;
+        if self.first_difference.is_none() {
+            if let Some(i) = data[..n].iter().zip(&self.buf).position(|(a, b)| a != b) {
+                self.first_difference = Some(self.offset + i as u64);
+            } else if n < data.len() {
+                self.first_difference = Some(self.offset + n as u64);
+            }
+        }
+
+        self.offset += data.len() as u64;
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Decode an article and compare the result with the original data
+///
+/// The article is decoded without CRC validation so that corrupt articles
+/// can still be compared; the CRCs are reported instead. `original` must
+/// contain exactly the bytes the article is expected to decode to (for a
+/// multi-part article, just that part).
+///
+/// # Example
+/// ```
+/// let encoded = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5\n";
+/// let original = [0u8, 1, 2, 3, 4];
+///
+/// let report = yenc::diff(&encoded[..], &original[..]).unwrap();
+/// assert!(report.is_match());
+/// ```
+pub fn diff<E: Read, O: Read>(encoded: E, original: O) -> Result<DiffReport> {
+    let mut comparer = Comparer {
+        original,
+        buf: Vec::new(),
+        offset: 0,
+        first_difference: None,
+        decoded_hasher: Hasher::new(),
+        original_hasher: Hasher::new(),
+        original_size: 0,
+    };
+
+    let DecodeOutcome {
+        header,
+        trailer,
+        bytes_written: decoded_size,
+        ..
+    } = Decoder::new()
+        .no_crc_check()
+        .decode(encoded, &mut comparer)?;
+
+    // Account for any original data beyond the decoded output
+    let mut rest = [0u8; 8192];
+    loop {
+        let n = comparer.original.read(&mut rest)?;
+        if n == 0 {
+            break;
+        }
+        comparer.original_hasher.update(&rest[..n]);
+        comparer.original_size += n as u64;
+    }
+    if comparer.first_difference.is_none() && comparer.original_size != decoded_size {
+        comparer.first_difference = Some(decoded_size);
+    }
+
+    Ok(DiffReport {
+        header,
+        trailer,
+        decoded_size,
+        original_size: comparer.original_size,
+        first_difference: comparer.first_difference,
+        decoded_crc: comparer.decoded_hasher.finalize(),
+        original_crc: comparer.original_hasher.finalize(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENCODED: &[u8] =
+        b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5 crc32=515ad3cc\n";
+
+    #[test]
+    fn test_diff_match() {
+        let report = diff(ENCODED, &[0u8, 1, 2, 3, 4][..]).unwrap();
+
+        assert!(report.is_match());
+        assert_eq!(report.decoded_size, 5);
+        assert_eq!(report.original_size, 5);
+        assert_eq!(report.decoded_crc, 0x515ad3cc);
+        assert_eq!(report.original_crc, 0x515ad3cc);
+        assert_eq!(report.trailer_crc(), Some(0x515ad3cc));
+    }
+
+    #[test]
+    fn test_diff_first_difference() {
+        let report = diff(ENCODED, &[0u8, 1, 9, 3, 4][..]).unwrap();
+
+        assert!(!report.is_match());
+        assert_eq!(report.first_difference, Some(2));
+        assert_ne!(report.decoded_crc, report.original_crc);
+    }
+
+    #[test]
+    fn test_diff_length_mismatch() {
+        // Original is longer than the decoded data
+        let report = diff(ENCODED, &[0u8, 1, 2, 3, 4, 5][..]).unwrap();
+        assert_eq!(report.first_difference, Some(5));
+        assert_eq!(report.original_size, 6);
+
+        // Original is shorter than the decoded data
+        let report = diff(ENCODED, &[0u8, 1, 2][..]).unwrap();
+        assert_eq!(report.first_difference, Some(3));
+        assert_eq!(report.original_size, 3);
+    }
+
+    #[test]
+    fn test_diff_corrupt_crc_still_compares() {
+        let encoded =
+            b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5 crc32=ffffffff\n";
+        let report = diff(&encoded[..], &[0u8, 1, 2, 3, 4][..]).unwrap();
+
+        assert!(report.is_match());
+        assert_eq!(report.trailer_crc(), Some(0xffffffff));
+    }
+}
@@ -0,0 +1,169 @@
+//! Runtime selection of the encoding and decoding kernels
+//!
+//! CPU features are probed once per process. Each encode block or decoded
+//! line then goes to the best kernel the CPU supports, unless it is too short
+//! to make up for the vector setup or the caller asked for a specific
+//! backend with `Encoder::backend` / `Decoder::backend`.
+
+use std::sync::OnceLock;
+
+/// Inputs shorter than this go straight to the scalar code
+///
+/// Shorter inputs do not fill a single register, so the kernel call would be
+/// pure overhead. The `decode_tiny` and `encode_tiny` benchmarks show the
+/// vector kernels already ahead at 16-byte lines and articles.
+pub(crate) const MIN_SIMD_LEN: usize = 16;
+
+/// A set of encoding and decoding kernels
+///
+/// Which backends exist depends on the target and on the `simd` feature;
+/// [`Backend::available`] lists the ones usable on the running CPU.
+///
+/// # Example
+/// ```
+/// use yenc::{Backend, Decoder};
+///
+/// // Pin the scalar kernels, e.g. to compare against the vector ones
+/// let decoder = Decoder::new().backend(Backend::Scalar);
+/// assert!(Backend::available().contains(&Backend::Scalar));
+/// # let _ = decoder;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Backend {
+    /// Byte-at-a-time code, available everywhere
+    Scalar,
+    /// 16-byte SSE2 kernels (x86_64)
+    Sse2,
+    /// 32-byte AVX2 kernels (x86_64)
+    Avx2,
+    /// 16-byte NEON kernels (aarch64)
+    Neon,
+    /// 16-byte `std::simd` kernels for any target (`portable-simd` feature)
+    Portable,
+}
+
+impl Backend {
+    /// Short lowercase name, as reported by `capabilities()`
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Scalar => "scalar",
+            Backend::Sse2 => "sse2",
+            Backend::Avx2 => "avx2",
+            Backend::Neon => "neon",
+            Backend::Portable => "portable",
+        }
+    }
+
+    /// Fastest backend usable on this CPU
+    pub fn best() -> Backend {
+        Backend::available()[0]
+    }
+
+    /// Backends usable on this CPU, best first, always ending with `Scalar`
+    pub fn available() -> &'static [Backend] {
+        static AVAILABLE: OnceLock<Vec<Backend>> = OnceLock::new();
+        AVAILABLE.get_or_init(detect)
+    }
+
+    /// Whether this backend can run on this CPU
+    pub fn is_available(self) -> bool {
+        Backend::available().contains(&self)
+    }
+}
+
+/// Probe the CPU for the vector extensions the kernels use
+///
+/// The portable kernels rank below the hand-written ones, so they are only
+/// picked automatically on targets that have none.
+fn detect() -> Vec<Backend> {
+    let mut backends = native();
+    #[cfg(feature = "portable-simd")]
+    backends.push(Backend::Portable);
+    backends.push(Backend::Scalar);
+    backends
+}
+
+/// Hand-written kernels usable on this CPU, best first
+fn native() -> Vec<Backend> {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    {
+        // SSE2 is part of the x86_64 baseline
+        if is_x86_feature_detected!("avx2") {
+            vec![Backend::Avx2, Backend::Sse2]
+        } else {
+            vec![Backend::Sse2]
+        }
+    }
+    #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+    {
+        // NEON is part of the aarch64 baseline
+        vec![Backend::Neon]
+    }
+    #[cfg(not(all(feature = "simd", any(target_arch = "x86_64", target_arch = "aarch64"))))]
+    {
+        Vec::new()
+    }
+}
+
+/// Backend to use for `len` bytes of input
+///
+/// A `requested` backend is honored when the CPU supports it; otherwise, or
+/// when nothing was requested, short inputs use the scalar code and the rest
+/// the best available kernels. The result is always safe to run.
+#[inline]
+pub(crate) fn select(requested: Option<Backend>, len: usize) -> Backend {
+    match requested {
+        Some(backend) if backend.is_available() => backend,
+        _ if len < MIN_SIMD_LEN => Backend::Scalar,
+        _ => Backend::best(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_available() {
+        let available = Backend::available();
+        assert_eq!(available.last(), Some(&Backend::Scalar));
+        assert_eq!(Backend::best(), available[0]);
+        assert!(Backend::Scalar.is_available());
+
+        if cfg!(all(feature = "simd", target_arch = "x86_64")) {
+            assert!(Backend::Sse2.is_available());
+            assert!(!Backend::Neon.is_available());
+        }
+        if cfg!(feature = "portable-simd") {
+            assert_eq!(available[available.len() - 2], Backend::Portable);
+        }
+        if !cfg!(any(feature = "simd", feature = "portable-simd")) {
+            assert_eq!(available, [Backend::Scalar]);
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        assert_eq!(select(None, 0), Backend::Scalar);
+        assert_eq!(select(None, MIN_SIMD_LEN - 1), Backend::Scalar);
+        assert_eq!(select(None, MIN_SIMD_LEN), Backend::best());
+
+        // Explicit requests win over the size heuristic
+        assert_eq!(select(Some(Backend::Scalar), 1 << 20), Backend::Scalar);
+        assert_eq!(select(Some(Backend::best()), 1), Backend::best());
+
+        // Unsupported requests fall back to automatic selection
+        let missing = [
+            Backend::Sse2,
+            Backend::Avx2,
+            Backend::Neon,
+            Backend::Portable,
+        ]
+        .into_iter()
+        .find(|b| !b.is_available());
+        if let Some(missing) = missing {
+            assert_eq!(select(Some(missing), 1 << 20), Backend::best());
+        }
+    }
+}
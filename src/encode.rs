@@ -1,16 +1,32 @@
 //! yEnc encoding functionality
 
-use std::io::{Read, Write};
+use std::borrow::Cow;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::sync::Arc;
 
 use crc32fast::Hasher;
-
-use crate::consts::{ESCAPE_CHAR, ESCAPE_OFFSET, ESCAPING_CHARS, LINE_LENGTH, OFFSET};
+use memchr::memchr_iter;
+
+use crate::budget::{MemoryBudget, Reservation};
+use crate::consts::{
+    BUFFER_SIZE, ESCAPE_CHAR, ESCAPE_OFFSET, LINE_LENGTH, MAX_LINE_LENGTH, NEEDS_ESCAPE,
+    NEEDS_REQUIRED_ESCAPE, OFFSET,
+};
+use crate::dispatch::{self, Backend};
 use crate::error::{Result, YencError};
+use crate::scratch::Scratch;
 
-#[inline]
-fn needs_escape(byte: u8, encoded: u8) -> bool {
-    ESCAPING_CHARS.contains(&encoded) || byte == ESCAPE_CHAR
-}
+#[cfg(all(feature = "simd", target_arch = "aarch64"))]
+mod neon;
+#[cfg(feature = "portable-simd")]
+mod portable;
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+mod x86;
+
+/// Input bytes encoded into the output buffer at a time; the buffer is
+/// written out whenever it holds at least this much
+const OUTPUT_BLOCK: usize = 32 * 1024;
 
 /// Encode a single byte
 #[inline]
@@ -18,6 +34,305 @@ fn encode_byte(byte: u8) -> u8 {
     byte.wrapping_add(OFFSET)
 }
 
+/// Which bytes the encoder escapes
+///
+/// `table` holds the input bytes escaped wherever they appear; the other
+/// rules depend on where a byte lands in the line. The vector kernels know
+/// only the default set, so anything else goes through the scalar code.
+#[derive(Clone, PartialEq, Eq)]
+pub(crate) struct EscapeRules {
+    policy: EscapePolicy,
+    /// Output characters escaped on top of the policy
+    extra: Vec<u8>,
+    /// Input bytes escaped anywhere in a line, by the policy or as extras
+    table: [bool; 256],
+    /// Escape a `.` in the first column
+    leading_dot: bool,
+    /// Escape a TAB or SPACE in the first or last column
+    boundary_whitespace: bool,
+}
+
+impl EscapeRules {
+    /// The escapes of the default policy, which the vector kernels implement
+    const DEFAULT: Self = Self {
+        policy: EscapePolicy::Paranoid,
+        extra: Vec::new(),
+        table: NEEDS_ESCAPE,
+        leading_dot: false,
+        boundary_whitespace: false,
+    };
+
+    /// Escape what `policy` calls for, keeping the extra characters
+    fn set_policy(&mut self, policy: EscapePolicy) {
+        self.policy = policy;
+        self.table = match policy {
+            EscapePolicy::Minimal | EscapePolicy::Spec => NEEDS_REQUIRED_ESCAPE,
+            EscapePolicy::Paranoid => NEEDS_ESCAPE,
+        };
+        for &encoded in &self.extra {
+            self.table[encoded.wrapping_sub(OFFSET) as usize] = true;
+        }
+    }
+
+    /// Escape the output character `encoded` wherever it appears
+    fn escape(&mut self, encoded: u8) {
+        self.extra.push(encoded);
+        self.table[encoded.wrapping_sub(OFFSET) as usize] = true;
+    }
+
+    /// Whether a `.` in the first column is escaped
+    fn leading_dot(&self) -> bool {
+        self.leading_dot || self.policy == EscapePolicy::Spec
+    }
+
+    /// Whether a TAB or SPACE in the first or last column is escaped
+    fn boundary_whitespace(&self) -> bool {
+        self.boundary_whitespace || self.policy == EscapePolicy::Spec
+    }
+
+    /// Check that every escape pair can be written on a line
+    ///
+    /// Besides the line-breaking characters, the pair may not be `=y`, which
+    /// decoders take for the start of a keyword line.
+    fn validate(&self) -> Result<()> {
+        for byte in 0..=u8::MAX {
+            let pair = encode_byte(byte).wrapping_add(ESCAPE_OFFSET);
+            if self.escapes(byte) && matches!(pair, b'\0' | b'\n' | b'\r' | b'y') {
+                return Err(YencError::InvalidConfig(format!(
+                    "cannot escape 0x{:02x}: its escape pair would hold 0x{:02x}",
+                    encode_byte(byte),
+                    pair
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `byte` is escaped wherever it lands
+    #[inline]
+    fn escapes(&self, byte: u8) -> bool {
+        self.table[byte as usize]
+    }
+
+    /// Whether `byte` is escaped when it lands in `column`
+    ///
+    /// `at_end` says the byte is the last of the data, so it ends the last
+    /// line whatever the column.
+    #[inline]
+    fn needs_escape(&self, byte: u8, column: usize, line_length: usize, at_end: bool) -> bool {
+        self.escapes(byte)
+            || match encode_byte(byte) {
+                b'.' => self.leading_dot() && column == 0,
+                b'\t' | b' ' => {
+                    self.boundary_whitespace()
+                        && (column == 0 || column + 1 >= line_length || at_end)
+                }
+                _ => false,
+            }
+    }
+
+    /// Whether the rules escape anything the vector kernels would not
+    fn is_active(&self) -> bool {
+        let escaped = |encoded: u8| self.escapes(encoded.wrapping_sub(OFFSET));
+        self.table != NEEDS_ESCAPE
+            || (self.leading_dot() && !escaped(b'.'))
+            || (self.boundary_whitespace() && !(escaped(b'\t') && escaped(b' ')))
+    }
+}
+
+impl Default for EscapeRules {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+impl std::fmt::Debug for EscapeRules {
+    /// Lists the escaped output characters rather than the whole table
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let escaped: Vec<u8> = (0..=u8::MAX)
+            .filter(|&byte| self.escapes(byte))
+            .map(encode_byte)
+            .collect();
+        f.debug_struct("EscapeRules")
+            .field("policy", &self.policy)
+            .field("escaped", &escaped)
+            .field("leading_dot", &self.leading_dot)
+            .field("boundary_whitespace", &self.boundary_whitespace)
+            .finish()
+    }
+}
+
+/// Which characters the encoder escapes
+///
+/// Every policy escapes what yEnc cannot do without: NUL, LF, CR and the
+/// escape character `=`. The others differ in what they do about gateways
+/// that strip whitespace at the ends of lines or mangle lines starting with
+/// a dot. Output of any policy decodes with any decoder.
+///
+/// # Example
+/// ```
+/// use yenc::{Encoder, EscapePolicy};
+///
+/// // 0xdf encodes to a TAB, which the spec's rules escape only at line ends
+/// let encoder = Encoder::new().line_length(4).escape_policy(EscapePolicy::Spec);
+/// let output = encoder.encode_to_vec(&[0xdf; 3], "a").unwrap();
+/// assert!(output.windows(7).any(|w| w == b"\n=I\t=I\n"));
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EscapePolicy {
+    /// Only the characters that have to be escaped, for the smallest output
+    /// over transports known to be clean
+    Minimal,
+    /// The minimal set plus the positional rules of the yEnc spec: a TAB or
+    /// SPACE starting or ending a line and a `.` starting one are escaped
+    Spec,
+    /// The minimal set plus TAB, SPACE and `.` wherever they appear, so no
+    /// gateway finds anything to strip
+    ///
+    /// This is the default, and the only policy the vector backends encode;
+    /// the others go through the scalar code.
+    #[default]
+    Paranoid,
+}
+
+/// Line terminator written after every line of an article
+///
+/// NNTP and mail transport lines end in CRLF, so articles handed to a
+/// posting library as-is need `CrLf`; local files usually use `Lf`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`, the default
+    #[default]
+    Lf,
+    /// `\r\n`
+    CrLf,
+}
+
+impl LineEnding {
+    /// The terminator itself
+    pub fn as_bytes(self) -> &'static [u8] {
+        match self {
+            LineEnding::Lf => b"\n",
+            LineEnding::CrLf => b"\r\n",
+        }
+    }
+}
+
+/// Append the encoding of `byte` to `out`, ending the line once it is full
+///
+/// This is the wrapping rule of the yEnc spec, which every kernel follows:
+/// the line ends as soon as it holds `line_length` characters, and an
+/// escape pair is never split, so a pair starting in the last column makes
+/// the line one character longer instead.
+#[inline]
+fn encode_into(byte: u8, out: &mut Vec<u8>, column: &mut usize, line_length: usize) {
+    encode_into_with(byte, out, column, line_length, &EscapeRules::DEFAULT, false);
+}
+
+/// `encode_into`, also applying the position-dependent `rules`
+#[inline]
+fn encode_into_with(
+    byte: u8,
+    out: &mut Vec<u8>,
+    column: &mut usize,
+    line_length: usize,
+    rules: &EscapeRules,
+    at_end: bool,
+) {
+    let encoded = encode_byte(byte);
+
+    if rules.needs_escape(byte, *column, line_length, at_end) {
+        out.extend_from_slice(&[ESCAPE_CHAR, encoded.wrapping_add(ESCAPE_OFFSET)]);
+        *column += 2;
+    } else {
+        out.push(encoded);
+        *column += 1;
+    }
+
+    if *column >= line_length {
+        out.push(b'\n');
+        *column = 0;
+    }
+}
+
+/// Upper bound on the keyword lines of a single-part article, framed as
+/// part 1 of 1 or not, not counting the name
+const KEYWORD_LINES_LEN: usize = 200;
+
+/// Upper bound on the encoded size of `len` input bytes, line breaks included
+#[inline]
+fn max_encoded_len(len: usize, line_length: usize) -> usize {
+    2 * len + 2 * len / line_length + 1
+}
+
+/// Read everything from `reader` into `buf`, at most `chunk_size` bytes per read call
+///
+/// `reservation` grows to hold `buf`; once the memory budget is full and the
+/// input goes on, fails with `YencError::MemoryLimitExceeded`.
+fn read_chunked<R: Read>(
+    mut reader: R,
+    buf: &mut Vec<u8>,
+    chunk_size: usize,
+    reservation: &mut Reservation,
+) -> Result<()> {
+    loop {
+        let len = buf.len();
+        let room = match reservation.grow(len + 1, len + chunk_size) {
+            Ok(room) => room.min(len + chunk_size),
+            Err(_) if at_end(&mut reader)? => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        buf.resize(room, 0);
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => {
+                buf.truncate(len);
+                return Ok(());
+            }
+            Ok(n) => buf.truncate(len + n),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => buf.truncate(len),
+            Err(err) => {
+                buf.truncate(len);
+                return Err(err.into());
+            }
+        }
+    }
+}
+
+/// Whether `reader` is done, reading at most one byte to find out
+fn at_end<R: Read>(reader: &mut R) -> io::Result<bool> {
+    let mut byte = [0u8; 1];
+    loop {
+        match reader.read(&mut byte) {
+            Ok(n) => return Ok(n == 0),
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Convert bare LF line endings to CRLF, leaving existing CRLF pairs intact
+fn to_crlf(data: &[u8]) -> Vec<u8> {
+    let mut converted = Vec::with_capacity(data.len());
+    let mut prev = 0;
+    for &byte in data {
+        if byte == b'\n' && prev != b'\r' {
+            converted.push(b'\r');
+        }
+        converted.push(byte);
+        prev = byte;
+    }
+    converted
+}
+
+/// Describe a byte count reported by `Encoder::stream_body` for errors
+fn describe_read(read: u64, size: u64) -> String {
+    if read > size {
+        format!("more than {} bytes", size)
+    } else {
+        format!("{} bytes", read)
+    }
+}
+
 /// Multi-part encoding configuration
 #[derive(Debug, Clone)]
 pub struct MultiPartInfo {
@@ -26,11 +341,11 @@ pub struct MultiPartInfo {
     /// Total number of parts
     pub total: usize,
     /// Starting byte position in original file (1-based, inclusive)
-    pub begin: usize,
+    pub begin: u64,
     /// Ending byte position in original file (1-based, inclusive)
-    pub end: usize,
+    pub end: u64,
     /// Full file size (not just this part)
-    pub full_size: usize,
+    pub full_size: u64,
     /// Optional: Full file CRC32 (typically included in last part only)
     pub full_crc: Option<u32>,
 }
@@ -44,7 +359,7 @@ impl MultiPartInfo {
     /// * `begin` - Starting byte position (1-based, inclusive)
     /// * `end` - Ending byte position (1-based, inclusive)
     /// * `full_size` - Total file size
-    pub fn new(part: usize, total: usize, begin: usize, end: usize, full_size: usize) -> Self {
+    pub fn new(part: usize, total: usize, begin: u64, end: u64, full_size: u64) -> Self {
         Self {
             part,
             total,
@@ -55,6 +370,69 @@ impl MultiPartInfo {
         }
     }
 
+    /// Create a multi-part configuration, checking that it describes a
+    /// possible part
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` for the cases listed under
+    /// `validate`.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::{MultiPartInfo, YencError};
+    ///
+    /// assert!(MultiPartInfo::try_new(2, 2, 6, 10, 10).is_ok());
+    /// let result = MultiPartInfo::try_new(1, 2, 10, 5, 100);
+    /// assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+    /// ```
+    pub fn try_new(
+        part: usize,
+        total: usize,
+        begin: u64,
+        end: u64,
+        full_size: u64,
+    ) -> Result<Self> {
+        let part_info = Self::new(part, total, begin, end, full_size);
+        part_info.validate()?;
+        Ok(part_info)
+    }
+
+    /// Check that this describes a part that can exist
+    ///
+    /// The part number has to be between 1 and `total`, and the range has
+    /// to lie within the file: `1 <= begin`, `end <= full_size`, and `begin
+    /// <= end` unless the part is empty (`end = begin - 1`). The encoding
+    /// methods check this before writing anything.
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` naming the first broken rule.
+    pub fn validate(&self) -> Result<()> {
+        if self.part == 0 || self.part > self.total {
+            return Err(YencError::InvalidConfig(format!(
+                "part number must be between 1 and the total of {}, got {}",
+                self.total, self.part
+            )));
+        }
+        if self.begin == 0 {
+            return Err(YencError::InvalidConfig(
+                "part range starts at byte 1, got begin=0".to_string(),
+            ));
+        }
+        if self.end < self.begin - 1 {
+            return Err(YencError::InvalidConfig(format!(
+                "part range ends before it begins: begin={} end={}",
+                self.begin, self.end
+            )));
+        }
+        if self.end > self.full_size {
+            return Err(YencError::InvalidConfig(format!(
+                "part range ends at byte {}, past the file size of {}",
+                self.end, self.full_size
+            )));
+        }
+        Ok(())
+    }
+
     /// Set the full file CRC32 (typically for last part)
     pub fn with_full_crc(mut self, crc: u32) -> Self {
         self.full_crc = Some(crc);
@@ -62,16 +440,325 @@ impl MultiPartInfo {
     }
 
     /// Calculate expected part size (end - begin + 1)
-    pub fn expected_size(&self) -> usize {
-        self.end - self.begin + 1
+    ///
+    /// An empty part is described by `end = begin - 1` and has size 0, as
+    /// has a range that ends before it begins.
+    pub fn expected_size(&self) -> u64 {
+        self.end
+            .checked_sub(self.begin)
+            .map_or(0, |span| span.saturating_add(1))
+    }
+}
+
+/// What encoding an article produced
+///
+/// Carries everything a poster needs for the NZB or subject line without
+/// parsing the encoded output again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EncodeReport {
+    /// Bytes of data encoded, as announced by `size=` (for parts, the part
+    /// size); in text mode, counted after line ending conversion
+    pub input_bytes: u64,
+    /// Bytes written, keyword lines included
+    pub output_bytes: usize,
+    /// Number of encoded data lines
+    pub lines: usize,
+    /// CRC32 in the trailer: of the data for single-part articles, of the
+    /// whole file for parts given `MultiPartInfo::with_full_crc`
+    pub crc32: Option<u32>,
+    /// CRC32 of the part data (multi-part articles only)
+    pub pcrc32: Option<u32>,
+}
+
+/// Escape statistics measured on a data sample, used to predict encoded size
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverheadEstimate {
+    /// Number of input bytes sampled
+    pub sampled_bytes: u64,
+    /// Number of sampled bytes that need escaping where they fall on a line
+    pub escaped_bytes: u64,
+    /// Number of CRs text mode adds to the sample (0 in binary mode)
+    pub inserted_bytes: u64,
+    /// Line length the estimate applies to
+    pub line_length: usize,
+    /// Length of the line ending written after every line (2 for CRLF)
+    pub line_ending_len: usize,
+}
+
+impl OverheadEstimate {
+    /// Fraction of sampled bytes that need escaping (0.0 to 1.0)
+    pub fn escape_ratio(&self) -> f64 {
+        if self.sampled_bytes == 0 {
+            return 0.0;
+        }
+        self.escaped_bytes as f64 / self.sampled_bytes as f64
+    }
+
+    /// Predicted size of the encoded data lines (including line breaks) for
+    /// `input_len` bytes with the same escape frequency as the sample
+    ///
+    /// Header and trailer lines are not included. Text mode conversion is
+    /// predicted from the sample too.
+    pub fn predict_encoded_len(&self, input_len: u64) -> u64 {
+        let expansion = if self.sampled_bytes == 0 {
+            1.0
+        } else {
+            (self.sampled_bytes + self.inserted_bytes + self.escaped_bytes) as f64
+                / self.sampled_bytes as f64
+        };
+        let encoded = (input_len as f64 * expansion).ceil() as u64;
+        let lines = encoded.div_ceil(self.line_length.max(1) as u64);
+        encoded + lines * self.line_ending_len as u64
+    }
+
+    /// Predicted overhead of the encoded data over the input, in percent
+    pub fn overhead_percent(&self) -> f64 {
+        if self.sampled_bytes == 0 {
+            return 0.0;
+        }
+        let encoded = self.predict_encoded_len(self.sampled_bytes);
+        (encoded - self.sampled_bytes) as f64 * 100.0 / self.sampled_bytes as f64
+    }
+}
+
+/// Per-operation encoding state: output column, running CRC32 and totals
+///
+/// Kept apart from `Encoder` so the configuration stays immutable and can be
+/// shared between threads while every operation owns its own state.
+pub(crate) struct EncodeState {
+    line_length: usize,
+    rules: EscapeRules,
+    column: usize,
+    /// Last byte of the data so far, held back under position-dependent
+    /// rules until it is known whether it ends the data
+    pending: Option<u8>,
+    hasher: Option<Hasher>,
+    buf: Vec<u8>,
+    /// Input bytes encoded into `buf` at a time; `buf` is written out
+    /// whenever it holds at least this much
+    block_size: usize,
+    /// Memory for the buffers of the call, held as long as the state
+    _reservation: Reservation,
+    backend: Option<Backend>,
+    /// Lines are queued ending in LF and get this terminator on the way out
+    line_ending: LineEnding,
+    /// Output with CRLF line endings, kept for reuse
+    converted: Vec<u8>,
+    /// Bytes handed to the writer so far
+    written: usize,
+    /// Line breaks among them
+    newlines: usize,
+}
+
+impl EncodeState {
+    pub(crate) fn new(line_length: usize, compute_crc: bool, backend: Option<Backend>) -> Self {
+        Self {
+            line_length,
+            rules: EscapeRules::default(),
+            column: 0,
+            pending: None,
+            hasher: compute_crc.then(Hasher::new),
+            buf: Vec::new(),
+            block_size: OUTPUT_BLOCK,
+            _reservation: Reservation::unlimited(),
+            backend,
+            line_ending: LineEnding::Lf,
+            converted: Vec::new(),
+            written: 0,
+            newlines: 0,
+        }
+    }
+
+    /// Apply the position-dependent escaping `rules`
+    pub(crate) fn with_rules(mut self, rules: EscapeRules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    /// End lines with `line_ending` in the output
+    pub(crate) fn with_line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Encode `block_size` input bytes at a time rather than `OUTPUT_BLOCK`
+    pub(crate) fn with_block_size(mut self, block_size: usize) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    /// Hold `reservation` for as long as the state is in use
+    pub(crate) fn with_reservation(mut self, reservation: Reservation) -> Self {
+        self._reservation = reservation;
+        self
+    }
+
+    /// Queue output in `buf` rather than a fresh allocation
+    pub(crate) fn with_buffer(mut self, mut buf: Vec<u8>) -> Self {
+        buf.clear();
+        self.buf = buf;
+        self
+    }
+
+    /// Hand back the output buffer for reuse
+    pub(crate) fn into_buffer(self) -> Vec<u8> {
+        self.buf
+    }
+
+    /// Output queued for the writer
+    ///
+    /// Keyword lines are formatted in here, so that they go out in the same
+    /// write as the data next to them.
+    pub(crate) fn output(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+
+    /// Encode `data`, wrapping lines and updating the CRC32
+    ///
+    /// Output is queued and handed to `writer` with one `write_all` per
+    /// block or more; call `flush` at the end. When a vector backend
+    /// is selected, runs of bytes that need no escaping are encoded 16 or 32
+    /// at a time, unless position-dependent rules call for the scalar code;
+    /// those also hold back the last byte until `finish`.
+    pub(crate) fn write_data<W: Write>(&mut self, writer: &mut W, data: &[u8]) -> io::Result<()> {
+        if let Some(hasher) = &mut self.hasher {
+            hasher.update(data);
+        }
+
+        for block in data.chunks(self.block_size) {
+            self.buf
+                .reserve(max_encoded_len(block.len(), self.line_length));
+
+            if self.rules.is_active() {
+                for &byte in block {
+                    if let Some(previous) = self.pending.replace(byte) {
+                        self.encode_positioned(previous, false);
+                    }
+                }
+                if self.buf.len() >= self.block_size {
+                    self.flush(writer)?;
+                }
+                continue;
+            }
+
+            let consumed = match dispatch::select(self.backend, block.len()) {
+                #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+                backend @ (Backend::Sse2 | Backend::Avx2) => x86::encode(
+                    backend,
+                    block,
+                    &mut self.buf,
+                    &mut self.column,
+                    self.line_length,
+                ),
+                #[cfg(all(feature = "simd", target_arch = "aarch64"))]
+                Backend::Neon => {
+                    neon::encode(block, &mut self.buf, &mut self.column, self.line_length)
+                }
+                #[cfg(feature = "portable-simd")]
+                Backend::Portable => {
+                    portable::encode(block, &mut self.buf, &mut self.column, self.line_length)
+                }
+                _ => 0,
+            };
+
+            for &byte in &block[consumed..] {
+                encode_into(byte, &mut self.buf, &mut self.column, self.line_length);
+            }
+            if self.buf.len() >= self.block_size {
+                self.flush(writer)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode one byte under the position-dependent rules
+    fn encode_positioned(&mut self, byte: u8, at_end: bool) {
+        encode_into_with(
+            byte,
+            &mut self.buf,
+            &mut self.column,
+            self.line_length,
+            &self.rules,
+            at_end,
+        );
+    }
+
+    /// CRC32 of the data so far, if computed
+    pub(crate) fn crc(&self) -> Option<u32> {
+        self.hasher.clone().map(Hasher::finalize)
+    }
+
+    /// Terminate the last data line and return the CRC32, if computed
+    pub(crate) fn finish(&mut self) -> Option<u32> {
+        if let Some(byte) = self.pending.take() {
+            self.encode_positioned(byte, true);
+        }
+        if self.column > 0 {
+            self.buf.push(b'\n');
+            self.column = 0;
+        }
+        self.hasher.take().map(Hasher::finalize)
+    }
+
+    /// Write out everything queued so far
+    ///
+    /// Encoded data holds no bare LF or CR, so every LF queued ends a line.
+    pub(crate) fn flush<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        let newlines = memchr_iter(b'\n', &self.buf).count();
+        let output = match self.line_ending {
+            LineEnding::Lf => &self.buf,
+            LineEnding::CrLf => {
+                self.converted.clear();
+                self.converted.reserve(self.buf.len() + newlines);
+                let mut start = 0;
+                for end in memchr_iter(b'\n', &self.buf) {
+                    self.converted.extend_from_slice(&self.buf[start..end]);
+                    self.converted.extend_from_slice(b"\r\n");
+                    start = end + 1;
+                }
+                self.converted.extend_from_slice(&self.buf[start..]);
+                &self.converted
+            }
+        };
+        writer.write_all(output)?;
+        self.written += output.len();
+        self.newlines += newlines;
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Totals for a finished article with `keyword_lines` lines besides the data
+    fn report(&self, keyword_lines: usize, input_bytes: u64) -> EncodeReport {
+        EncodeReport {
+            input_bytes,
+            output_bytes: self.written,
+            lines: self.newlines - keyword_lines,
+            crc32: None,
+            pcrc32: None,
+        }
     }
 }
 
 /// Encoder with configurable options
+///
+/// An `Encoder` only holds configuration; all per-operation state lives on
+/// the stack of the call. It is `Send + Sync`, so one configured encoder can
+/// be shared by reference (or in an `Arc`) across a whole thread pool.
+///
+/// Instances are cheap to create and to reuse for any number of calls. To
+/// also reuse the buffers of each call, pass a `Scratch` to `encode_with_scratch`.
 #[derive(Debug, Clone)]
 pub struct Encoder {
-    line_length: usize,
+    pub(crate) line_length: usize,
     compute_crc: bool,
+    budget: Option<Arc<MemoryBudget>>,
+    text_mode: bool,
+    chunk_size: Option<usize>,
+    backend: Option<Backend>,
+    quote_names: bool,
+    multipart_framing: bool,
+    escapes: EscapeRules,
+    pub(crate) line_ending: LineEnding,
 }
 
 impl Default for Encoder {
@@ -79,6 +766,14 @@ impl Default for Encoder {
         Self {
             line_length: LINE_LENGTH,
             compute_crc: true,
+            budget: None,
+            text_mode: false,
+            chunk_size: None,
+            backend: None,
+            quote_names: false,
+            multipart_framing: false,
+            escapes: EscapeRules::default(),
+            line_ending: LineEnding::Lf,
         }
     }
 }
@@ -89,13 +784,23 @@ impl Encoder {
     /// Default settings:
     /// - Line length: 128 characters
     /// - CRC32 computation enabled
+    /// - No memory budget
+    /// - Binary mode (input is encoded as-is)
+    /// - Input read in 8 KiB chunks
+    /// - Kernels picked automatically for the CPU and input size
     pub fn new() -> Self {
         Self::default()
     }
 
     /// Set the line length for encoded output
     ///
-    /// Standard yEnc uses 128 characters per line.
+    /// Standard yEnc uses 128 characters per line. Must be between 1 and 997,
+    /// so that lines ending in an escape pair fit the NNTP line limit.
+    ///
+    /// A line ends once it holds `length` characters. An escape pair is
+    /// never split across lines: one starting in the last column is kept
+    /// whole, making that line `length + 1` characters long, as the yEnc
+    /// spec allows.
     pub fn line_length(mut self, length: usize) -> Self {
         self.line_length = length;
         self
@@ -107,75 +812,798 @@ impl Encoder {
         self
     }
 
+    /// Cap the memory used for buffering, in total across concurrent calls
+    ///
+    /// The budget is shared by this encoder, its clones and every call made
+    /// through them, such as the parts `encode_parts_parallel` encodes at
+    /// once. Each call reserves its read chunk and output block before it
+    /// starts, waiting while other calls hold the rest of the budget; a
+    /// `YencWriter` holds its output block until it is dropped. Chunks are cut
+    /// down to a quarter of the budget at most and output blocks to half,
+    /// whatever `chunk_size` says.
+    ///
+    /// Only `encode` buffers its whole input, which grows as far as the
+    /// budget has room at the time; inputs that do not fit are rejected with
+    /// `YencError::MemoryLimitExceeded` instead of being read into memory.
+    /// `encode_sized`, `encode_seekable` and `encode_part` stream the input
+    /// through their chunk.
+    ///
+    /// Calling `max_memory` again starts a new budget.
+    pub fn max_memory(mut self, bytes: usize) -> Self {
+        self.budget = Some(Arc::new(MemoryBudget::new(bytes)));
+        self
+    }
+
+    /// Encode input as text, converting bare LF line endings to CRLF
+    ///
+    /// The header size and CRC32 describe the converted data, so the decoded
+    /// file reads correctly on every platform. Only `encode` and the other
+    /// paths that buffer the whole input support it; those that take the size
+    /// up front, such as `encode_sized`, `encode_part` and `part_writer`,
+    /// reject it with `YencError::InvalidConfig`. For multi-part posts, convert
+    /// the file first so the part ranges refer to the converted data.
+    pub fn text_mode(mut self) -> Self {
+        self.text_mode = true;
+        self
+    }
+
+    /// Set the size of the chunks the input is read in
+    ///
+    /// Larger chunks mean fewer read calls, which helps with high-latency
+    /// sources; smaller chunks keep the working set in cache. Defaults to
+    /// 8 KiB. A chunk size over the memory budget fails with
+    /// `YencError::InvalidConfig`.
+    pub fn chunk_size(mut self, bytes: usize) -> Self {
+        self.chunk_size = Some(bytes.max(1));
+        self
+    }
+
+    /// Always encode with the kernels of `backend`
+    ///
+    /// By default input goes to the fastest kernels the CPU supports, with
+    /// short inputs left to the scalar code. Pinning a backend is mainly
+    /// useful for benchmarking and for ruling out a kernel when chasing a
+    /// bug. A backend the CPU does not support is ignored.
+    pub fn backend(mut self, backend: Backend) -> Self {
+        self.backend = Some(backend);
+        self
+    }
+
+    /// Put double quotes around file names that contain spaces
+    ///
+    /// Writes `name="file with spaces.bin"`, for readers that stop the name
+    /// at the first space unless it is quoted. Names without spaces are
+    /// written as they are. This crate's decoder reads either form.
+    pub fn quote_names(mut self) -> Self {
+        self.quote_names = true;
+        self
+    }
+
+    /// Frame single files as part 1 of 1
+    ///
+    /// `encode` and the other single-file methods then write `part=1
+    /// total=1` in the header, a `=ypart` line covering the whole file, and
+    /// both `pcrc32=` and `crc32=` in the trailer, as yEnc 1.2 posters do.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Encoder;
+    ///
+    /// let encoder = Encoder::new().force_multipart_framing();
+    /// let output = encoder.encode_to_vec(b"hi", "a.txt").unwrap();
+    /// assert!(output.starts_with(b"=ybegin part=1 total=1 line=128 size=2 name=a.txt\n=ypart begin=1 end=2\n"));
+    /// ```
+    pub fn force_multipart_framing(mut self) -> Self {
+        self.multipart_framing = true;
+        self
+    }
+
+    /// The part a single file is framed as
+    fn whole_file_part(size: u64) -> MultiPartInfo {
+        MultiPartInfo::new(1, 1, 1, size, size)
+    }
+
+    /// Set the terminator of header, data and trailer lines, LF by default
+    ///
+    /// Line lengths count the encoded characters only, so the terminator
+    /// does not change where lines wrap.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::{Encoder, LineEnding};
+    ///
+    /// let encoder = Encoder::new().no_crc().line_ending(LineEnding::CrLf);
+    /// let output = encoder.encode_to_vec(b"hi", "a.txt").unwrap();
+    /// assert_eq!(output, b"=ybegin line=128 size=2 name=a.txt\r\n\x92\x93\r\n=yend size=2\r\n");
+    /// ```
+    pub fn line_ending(mut self, line_ending: LineEnding) -> Self {
+        self.line_ending = line_ending;
+        self
+    }
+
+    /// Set which characters are escaped, `EscapePolicy::Paranoid` by default
+    ///
+    /// Characters added with `escape_bytes` stay escaped, as do the rules of
+    /// `escape_leading_dot` and `escape_boundary_whitespace`.
+    pub fn escape_policy(mut self, policy: EscapePolicy) -> Self {
+        self.escapes.set_policy(policy);
+        self
+    }
+
+    /// Escape a dot that would be the first character of a line
+    ///
+    /// NNTP servers take a line holding just `.` as the end of an article
+    /// and strip a leading dot from others, so posters escape one there.
+    /// The default escape set already covers every dot, wherever it lands;
+    /// this keeps lines from starting with a dot whatever else is escaped.
+    pub fn escape_leading_dot(mut self) -> Self {
+        self.escapes.leading_dot = true;
+        self
+    }
+
+    /// Also escape each of `bytes` wherever it appears in the encoded output
+    ///
+    /// This extends the set of the yEnc spec for transports that mangle
+    /// more characters. Lenient decoders take any escape, but strict ones,
+    /// this crate's included, reject escapes outside the spec's set.
+    ///
+    /// A byte whose escape pair would hold a NUL, LF or CR (`0xc0`, `0xca`
+    /// and `0xcd`), or would read as the `=y` of a keyword line (`9`), is
+    /// rejected with `YencError::InvalidConfig` when encoding.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Encoder;
+    ///
+    /// let encoder = Encoder::new().escape_bytes(b"K");
+    /// let output = encoder.encode_to_vec(b"!\"", "a.txt").unwrap();
+    /// assert!(output.windows(4).any(|w| w == b"=\x8bL\n"));
+    /// ```
+    pub fn escape_bytes(mut self, bytes: &[u8]) -> Self {
+        for &byte in bytes {
+            self.escapes.escape(byte);
+        }
+        self
+    }
+
+    /// Escape a TAB or SPACE that would start or end a line
+    ///
+    /// The yEnc spec recommends this, as mail and news gateways may strip
+    /// whitespace at the ends of lines. Like a leading dot, both are in the
+    /// default escape set already, wherever they land.
+    pub fn escape_boundary_whitespace(mut self) -> Self {
+        self.escapes.boundary_whitespace = true;
+        self
+    }
+
+    /// Quote to put on either side of `filename` in the `=ybegin` line
+    pub(crate) fn name_quote(&self, filename: &str) -> &'static str {
+        if self.quote_names && filename.contains(' ') {
+            "\""
+        } else {
+            ""
+        }
+    }
+
+    /// Validate the configuration
+    ///
+    /// Encoding methods perform the same checks, so calling this is optional;
+    /// it lets invalid settings be reported when the encoder is configured
+    /// rather than when it is first used.
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` for a line length out of bounds, a
+    /// zero memory budget, a chunk size over the memory budget, and escape
+    /// sets with a pair that cannot be written on a line.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::{Encoder, YencError};
+    ///
+    /// assert!(Encoder::new().line_length(256).try_build().is_ok());
+    /// assert!(matches!(
+    ///     Encoder::new().line_length(0).try_build(),
+    ///     Err(YencError::InvalidConfig(_))
+    /// ));
+    /// ```
+    pub fn try_build(self) -> Result<Self> {
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Check that the options are within bounds and consistent
+    pub(crate) fn validate(&self) -> Result<()> {
+        if !(1..=MAX_LINE_LENGTH).contains(&self.line_length) {
+            return Err(YencError::InvalidConfig(format!(
+                "line length must be between 1 and {}, got {}",
+                MAX_LINE_LENGTH, self.line_length
+            )));
+        }
+        let max_memory = self.budget.as_ref().map(|budget| budget.limit());
+        if max_memory == Some(0) {
+            return Err(YencError::InvalidConfig(
+                "memory budget must be greater than zero".to_string(),
+            ));
+        }
+        if let (Some(chunk_size), Some(max_memory)) = (self.chunk_size, max_memory) {
+            if chunk_size > max_memory {
+                return Err(YencError::InvalidConfig(format!(
+                    "chunk size of {} bytes exceeds the memory budget of {} bytes",
+                    chunk_size, max_memory
+                )));
+            }
+        }
+        self.escapes.validate()?;
+        Ok(())
+    }
+
+    /// Check the options for encoding an input of a size given up front
+    pub(crate) fn validate_sized(&self) -> Result<()> {
+        self.validate()?;
+        if self.text_mode {
+            return Err(YencError::InvalidConfig(
+                "text mode needs the whole input to size the header; use encode".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Chunk size used for reading input, at most a quarter of the memory budget
+    pub(crate) fn read_chunk_size(&self) -> usize {
+        let chunk_size = self.chunk_size.unwrap_or(BUFFER_SIZE);
+        match &self.budget {
+            Some(budget) => chunk_size.min((budget.limit() / 4).max(1)),
+            None => chunk_size,
+        }
+    }
+
+    /// Input bytes encoded per output block, and the memory that block takes
+    ///
+    /// The output buffer is written out once it holds a block's worth, so it
+    /// never has more than that plus one encoded block queued; with CRLF line
+    /// endings, it is copied once more on the way out. Under a memory budget,
+    /// blocks shrink until they take at most half of it.
+    fn output_block(&self) -> (usize, usize) {
+        let reserved = |block: usize| {
+            let queued = block + max_encoded_len(block, self.line_length);
+            match self.line_ending {
+                LineEnding::Lf => queued,
+                // Every line is at least a byte and its LF
+                LineEnding::CrLf => queued + queued + queued / 2,
+            }
+        };
+        let mut block = OUTPUT_BLOCK;
+        if let Some(budget) = &self.budget {
+            while block > 1 && reserved(block) > budget.limit() / 2 {
+                block /= 2;
+            }
+        }
+        (block, reserved(block))
+    }
+
+    /// Reserve the output block of one call, plus `extra` bytes
+    ///
+    /// Waits while other calls hold too much of the memory budget.
+    pub(crate) fn reserve(&self, extra: usize) -> Result<Reservation> {
+        Reservation::take(self.budget.as_ref(), self.output_block().1 + extra)
+    }
+
+    /// Measure how often bytes need escaping in a sample of the input
+    ///
+    /// Reads at most `sample_bytes` from `reader` and counts the bytes this
+    /// encoder would escape, with the same position-dependent rules as
+    /// `encoded_len` and after text mode conversion. The resulting estimate
+    /// predicts the encoded size of the whole payload, which differs a lot
+    /// between compressed and text data.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Encoder;
+    ///
+    /// let data = vec![0x41u8; 4096];
+    /// let estimate = Encoder::new().sample_overhead(&data[..], 1024).unwrap();
+    /// assert_eq!(estimate.sampled_bytes, 1024);
+    /// assert_eq!(estimate.escaped_bytes, 0);
+    /// ```
+    pub fn sample_overhead<R: Read>(
+        &self,
+        reader: R,
+        sample_bytes: u64,
+    ) -> Result<OverheadEstimate> {
+        let mut reader = reader.take(sample_bytes);
+        let chunk_size = usize::try_from(sample_bytes).unwrap_or(usize::MAX);
+        let mut buf = vec![0u8; self.read_chunk_size().min(chunk_size.max(1))];
+        let mut estimate = OverheadEstimate {
+            sampled_bytes: 0,
+            escaped_bytes: 0,
+            inserted_bytes: 0,
+            line_length: self.line_length,
+            line_ending_len: self.line_ending.as_bytes().len(),
+        };
+
+        let mut column = 0;
+        let mut count = |byte: u8, at_end: bool, estimate: &mut OverheadEstimate| {
+            let escaped = self
+                .escapes
+                .needs_escape(byte, column, self.line_length, at_end);
+            estimate.escaped_bytes += u64::from(escaped);
+            column += 1 + usize::from(escaped);
+            if column >= self.line_length {
+                column = 0;
+            }
+        };
+
+        // The last byte is held back until it is known whether it ends the
+        // sample, as some rules depend on that
+        let mut converted = Vec::with_capacity(buf.len());
+        let mut pending = None;
+        let mut prev = 0;
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            estimate.sampled_bytes += n as u64;
+
+            converted.clear();
+            converted.extend(pending.take());
+            for &byte in &buf[..n] {
+                if self.text_mode && byte == b'\n' && prev != b'\r' {
+                    converted.push(b'\r');
+                    estimate.inserted_bytes += 1;
+                }
+                converted.push(byte);
+                prev = byte;
+            }
+            pending = converted.pop();
+            for &byte in &converted {
+                count(byte, false, &mut estimate);
+            }
+        }
+        if let Some(byte) = pending {
+            count(byte, true, &mut estimate);
+        }
+
+        Ok(estimate)
+    }
+
+    /// Upper bound on the size of the encoded data lines for `input_len` bytes
+    ///
+    /// Line breaks are included, header and trailer lines are not. The bound
+    /// assumes every byte needs escaping (and, in text mode, that every byte
+    /// is a bare LF), so it is cheap and safe for preallocating output; use
+    /// `encoded_len` for the exact figure.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Encoder;
+    ///
+    /// let encoder = Encoder::new();
+    /// assert!(encoder.encoded_len_upper_bound(1000) >= encoder.encoded_len(&[b'='; 1000]));
+    /// ```
+    pub fn encoded_len_upper_bound(&self, input_len: usize) -> usize {
+        let input_len = if self.text_mode {
+            input_len.saturating_mul(2)
+        } else {
+            input_len
+        };
+        let lines = 2 * input_len / self.line_length + 1;
+        max_encoded_len(input_len, self.line_length)
+            + lines * (self.line_ending.as_bytes().len() - 1)
+    }
+
+    /// Exact size of the encoded data lines for `data`
+    ///
+    /// Line breaks are included, header and trailer lines are not. This is a
+    /// pass over the data without encoding it, for checking an article
+    /// against a size budget before doing the work.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Encoder;
+    ///
+    /// // Four bytes plus one escape, then the line break
+    /// assert_eq!(Encoder::new().encoded_len(b"ab\x13d"), 6);
+    /// ```
+    pub fn encoded_len(&self, data: &[u8]) -> usize {
+        let data = if self.text_mode {
+            Cow::Owned(to_crlf(data))
+        } else {
+            Cow::Borrowed(data)
+        };
+
+        let line_break = self.line_ending.as_bytes().len();
+        let mut len = 0;
+        let mut column = 0;
+        for (index, &byte) in data.iter().enumerate() {
+            let at_end = index + 1 == data.len();
+            column += if self
+                .escapes
+                .needs_escape(byte, column, self.line_length, at_end)
+            {
+                2
+            } else {
+                1
+            };
+            if column >= self.line_length {
+                len += column + line_break;
+                column = 0;
+            }
+        }
+        if column > 0 {
+            len + column + line_break
+        } else {
+            len
+        }
+    }
+
+    /// Read the whole input into `input`, growing `reservation` to hold it
+    fn read_input<R: Read>(
+        &self,
+        reader: R,
+        input: &mut Vec<u8>,
+        reservation: &mut Reservation,
+    ) -> Result<()> {
+        input.clear();
+        read_chunked(reader, input, self.read_chunk_size(), reservation)
+    }
+
+    /// The input as encoded: `data` itself, or in text mode a copy with CRLF
+    /// line endings, which `reservation` grows to hold
+    fn text_input<'a>(
+        &self,
+        data: &'a [u8],
+        reservation: &mut Reservation,
+    ) -> Result<Cow<'a, [u8]>> {
+        if !self.text_mode {
+            return Ok(Cow::Borrowed(data));
+        }
+        let bare_lfs = memchr_iter(b'\n', data)
+            .filter(|&pos| pos == 0 || data[pos - 1] != b'\r')
+            .count();
+        reservation.add(data.len() + bare_lfs)?;
+        Ok(Cow::Owned(to_crlf(data)))
+    }
+
     /// Encode data from a reader and write yEnc format to a writer
     ///
+    /// The whole input is read into memory first, since the header states
+    /// its size. For large inputs prefer `encode_sized` or `encode_seekable`,
+    /// which stream the data in chunks.
+    ///
     /// # Arguments
     /// * `reader` - Input reader containing raw data
     /// * `writer` - Output writer for yEnc-encoded data
     /// * `filename` - Name to use in the yEnc header
     ///
     /// # Returns
-    /// Number of bytes read from input
+    /// Sizes, line count and CRC32 of the article; see `EncodeReport`
     pub fn encode<R: Read, W: Write>(
         &self,
-        mut reader: R,
+        reader: R,
+        writer: W,
+        filename: &str,
+    ) -> Result<EncodeReport> {
+        self.encode_with_scratch(reader, writer, filename, &mut Scratch::new())
+    }
+
+    /// Encode like `encode`, reusing the buffers in `scratch`
+    ///
+    /// Once `scratch` has grown to fit the inputs at hand, encoding one
+    /// after another allocates nothing for input or output buffering. Keep
+    /// one `Scratch` per thread and share the `Encoder` itself.
+    pub fn encode_with_scratch<R: Read, W: Write>(
+        &self,
+        reader: R,
+        mut writer: W,
+        filename: &str,
+        scratch: &mut Scratch,
+    ) -> Result<EncodeReport> {
+        self.validate()?;
+        let chunk_size = self.read_chunk_size();
+        let mut reservation = self.reserve(chunk_size)?;
+        // The input grows from its first chunk as far as the budget allows
+        let mut input = reservation.split_off(chunk_size);
+        self.read_input(reader, &mut scratch.input, &mut input)?;
+        let input_data = self.text_input(&scratch.input, &mut input)?;
+
+        let size = input_data.len() as u64;
+        self.encode_single(
+            &mut writer,
+            filename,
+            size,
+            &mut scratch.output,
+            reservation,
+            |writer, state| Ok(state.write_data(writer, &input_data)?),
+        )
+    }
+
+    /// Encode a byte slice into a new vector
+    ///
+    /// Equivalent to `encode` with a slice reader and a `Vec` writer, minus
+    /// the copy of the input; the output is allocated once, sized for the
+    /// worst case.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Encoder;
+    ///
+    /// let encoded = Encoder::new().encode_to_vec(b"hello", "hello.txt").unwrap();
+    /// assert!(encoded.starts_with(b"=ybegin line=128 size=5 name=hello.txt\n"));
+    /// ```
+    pub fn encode_to_vec(&self, data: &[u8], filename: &str) -> Result<Vec<u8>> {
+        // Checked first, as sizing the output divides by the line length
+        self.validate()?;
+        let capacity =
+            self.encoded_len_upper_bound(data.len()) + filename.len() + KEYWORD_LINES_LEN;
+        let mut output = Vec::with_capacity(capacity);
+        self.encode_slice(data, &mut output, filename)?;
+        Ok(output)
+    }
+
+    /// Encode a byte slice held in memory, such as a mapped file
+    ///
+    /// Same as `encode`, minus the copy of the input into a read buffer.
+    pub(crate) fn encode_slice<W: Write>(
+        &self,
+        data: &[u8],
         mut writer: W,
         filename: &str,
-    ) -> Result<usize> {
-        let mut input_data = Vec::new();
-        reader.read_to_end(&mut input_data)?;
+    ) -> Result<EncodeReport> {
+        self.validate()?;
+        let mut reservation = self.reserve(0)?;
+        let data = self.text_input(data, &mut reservation)?;
+
+        self.encode_single(
+            &mut writer,
+            filename,
+            data.len() as u64,
+            &mut Vec::new(),
+            reservation,
+            |writer, state| Ok(state.write_data(writer, &data)?),
+        )
+    }
 
-        let size = input_data.len();
+    /// Encode exactly `size` bytes from a reader without buffering them
+    ///
+    /// Unlike `encode`, the input is read and encoded in chunks of
+    /// `chunk_size`, so memory use stays constant however large the input
+    /// is. `size` goes into the header up front; if the reader turns out to
+    /// hold fewer or more bytes, `YencError::InvalidData` is returned and the
+    /// output written so far must be discarded.
+    ///
+    /// Text mode changes the size of the data and is not supported here.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Encoder;
+    ///
+    /// let data = vec![7u8; 100_000];
+    /// let mut encoded = Vec::new();
+    /// Encoder::new()
+    ///     .encode_sized(&data[..], &mut encoded, "big.bin", data.len() as u64)
+    ///     .unwrap();
+    /// assert!(encoded.starts_with(b"=ybegin line=128 size=100000 name=big.bin\n"));
+    /// ```
+    pub fn encode_sized<R: Read, W: Write>(
+        &self,
+        reader: R,
+        mut writer: W,
+        filename: &str,
+        size: u64,
+    ) -> Result<EncodeReport> {
+        self.validate_sized()?;
+        let reservation = self.reserve(self.read_chunk_size())?;
+
+        let mut scratch = Scratch::new();
+        let (chunk, output) = (&mut scratch.input, &mut scratch.output);
+        self.encode_single(
+            &mut writer,
+            filename,
+            size,
+            output,
+            reservation,
+            |writer, state| {
+                let read = self.stream_body(reader, writer, state, chunk, size)?;
+                if read != size {
+                    return Err(YencError::InvalidData(format!(
+                        "Input size mismatch: announced {} bytes, but got {}",
+                        size,
+                        describe_read(read, size)
+                    )));
+                }
+                Ok(())
+            },
+        )
+    }
 
-        // Compute CRC32 of original data if enabled
-        let crc32 = if self.compute_crc {
-            let mut hasher = Hasher::new();
-            hasher.update(&input_data);
-            Some(hasher.finalize())
-        } else {
-            None
-        };
+    /// Encode the rest of a seekable reader without buffering it
+    ///
+    /// The size is taken from the distance between the current position and
+    /// the end of the stream; encoding then proceeds as in `encode_sized`.
+    /// This is the natural fit for large files:
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use yenc::Encoder;
+    ///
+    /// let input = File::open("big.iso")?;
+    /// let output = File::create("big.iso.yenc")?;
+    /// Encoder::new().encode_seekable(input, output, "big.iso")?;
+    /// # Ok::<(), yenc::YencError>(())
+    /// ```
+    pub fn encode_seekable<R: Read + Seek, W: Write>(
+        &self,
+        mut reader: R,
+        writer: W,
+        filename: &str,
+    ) -> Result<EncodeReport> {
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
 
-        writeln!(
-            writer,
-            "=ybegin line={} size={} name={}",
-            self.line_length, size, filename
-        )?;
+        self.encode_sized(reader, writer, filename, end.saturating_sub(start))
+    }
 
-        let mut line_length = 0;
-        for &byte in &input_data {
-            let encoded = encode_byte(byte);
+    /// Whether CRC32s are computed and written
+    pub(crate) fn computes_crc(&self) -> bool {
+        self.compute_crc
+    }
 
-            if needs_escape(byte, encoded) {
-                writer.write_all(&[ESCAPE_CHAR, encoded.wrapping_add(ESCAPE_OFFSET)])?;
-                line_length += 2;
-            } else {
-                writer.write_all(&[encoded])?;
-                line_length += 1;
-            }
+    /// Fresh per-call state with this encoder's settings
+    pub(crate) fn state(&self) -> EncodeState {
+        EncodeState::new(self.line_length, self.compute_crc, self.backend)
+            .with_rules(self.escapes.clone())
+            .with_line_ending(self.line_ending)
+            .with_block_size(self.output_block().0)
+    }
+
+    /// Write a single-part article whose body is produced by `body`
+    ///
+    /// Output is queued in `output`, which is handed back for reuse, within
+    /// the memory held in `reservation`.
+    fn encode_single<W, F>(
+        &self,
+        writer: &mut W,
+        filename: &str,
+        size: u64,
+        output: &mut Vec<u8>,
+        reservation: Reservation,
+        body: F,
+    ) -> Result<EncodeReport>
+    where
+        W: Write,
+        F: FnOnce(&mut W, &mut EncodeState) -> Result<()>,
+    {
+        let mut state = self
+            .state()
+            .with_buffer(mem::take(output))
+            .with_reservation(reservation);
+        let result = self.write_single(writer, filename, size, &mut state, body);
+        *output = state.into_buffer();
+        result
+    }
+
+    fn write_single<W, F>(
+        &self,
+        writer: &mut W,
+        filename: &str,
+        size: u64,
+        state: &mut EncodeState,
+        body: F,
+    ) -> Result<EncodeReport>
+    where
+        W: Write,
+        F: FnOnce(&mut W, &mut EncodeState) -> Result<()>,
+    {
+        self.begin_single(state, filename, size)?;
+        body(writer, state)?;
+        Ok(self.end_single(state, writer, size)?)
+    }
 
-            if line_length >= self.line_length {
-                writeln!(writer)?;
-                line_length = 0;
-            }
+    /// Queue the `=ybegin` line of a single-part article
+    pub(crate) fn begin_single(
+        &self,
+        state: &mut EncodeState,
+        filename: &str,
+        size: u64,
+    ) -> io::Result<()> {
+        // Room for the whole article when it is small, else for one block
+        let block = max_encoded_len(size.min(state.block_size as u64) as usize, self.line_length);
+        state
+            .output()
+            .reserve(block + filename.len() + KEYWORD_LINES_LEN);
+        if self.multipart_framing {
+            return self.begin_part(state, filename, &Self::whole_file_part(size));
         }
 
-        if line_length > 0 {
-            writeln!(writer)?;
+        let quote = self.name_quote(filename);
+        writeln!(
+            state.output(),
+            "=ybegin line={} size={} name={}{}{}",
+            self.line_length,
+            size,
+            quote,
+            filename,
+            quote
+        )
+    }
+
+    /// End the data of a single-part article and write out its trailer
+    pub(crate) fn end_single<W: Write>(
+        &self,
+        state: &mut EncodeState,
+        writer: &mut W,
+        size: u64,
+    ) -> io::Result<EncodeReport> {
+        if self.multipart_framing {
+            // The one part is the whole file, so both CRC32s are the same
+            let part_info = MultiPartInfo {
+                full_crc: state.crc(),
+                ..Self::whole_file_part(size)
+            };
+            return self.end_part(state, writer, &part_info);
         }
+        let crc32 = state.finish();
 
         // Write trailer with CRC32 if computed
         if let Some(crc) = crc32 {
-            writeln!(writer, "=yend size={} crc32={:08x}", size, crc)?;
+            writeln!(state.output(), "=yend size={} crc32={:08x}", size, crc)?;
         } else {
-            writeln!(writer, "=yend size={}", size)?;
+            writeln!(state.output(), "=yend size={}", size)?;
+        }
+        state.flush(writer)?;
+
+        Ok(EncodeReport {
+            crc32,
+            ..state.report(2, size)
+        })
+    }
+
+    /// Read up to `size` bytes from `reader` in chunks and encode them
+    ///
+    /// Returns the number of bytes found: `size` when the input has exactly
+    /// that many, less when it ends early and `size + 1` when it is longer.
+    /// Only the first `size` bytes are ever encoded. Chunks are read into
+    /// `chunk`.
+    fn stream_body<R: Read, W: Write>(
+        &self,
+        reader: R,
+        writer: &mut W,
+        state: &mut EncodeState,
+        chunk: &mut Vec<u8>,
+        size: u64,
+    ) -> Result<u64> {
+        chunk.clear();
+        let chunk_size = usize::try_from(size).map_or(usize::MAX, |size| size.max(1));
+        chunk.resize(self.read_chunk_size().min(chunk_size), 0);
+        let mut reader = reader.take(size);
+        let mut read = 0;
+        loop {
+            match reader.read(chunk) {
+                Ok(0) => break,
+                Ok(n) => {
+                    state.write_data(writer, &chunk[..n])?;
+                    read += n as u64;
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if read < size {
+            return Ok(read);
         }
 
-        Ok(size)
+        // Probe for one more byte, which would make the header lie
+        let longer = !at_end(&mut reader.into_inner())?;
+        Ok(size + u64::from(longer))
     }
 
     /// Encode a single part of a multi-part file
     ///
+    /// The part size is known from `part_info`, so the input is streamed in
+    /// chunks (see `chunk_size`) rather than read into memory.
+    ///
     /// # Arguments
     /// * `reader` - Input reader containing raw data for this part
     /// * `writer` - Output writer for yEnc-encoded data
@@ -183,10 +1611,15 @@ impl Encoder {
     /// * `part_info` - Multi-part configuration
     ///
     /// # Returns
-    /// Number of bytes read from input
+    /// Sizes, line count and CRCs of the part; see `EncodeReport`
     ///
     /// # Errors
-    /// Returns error if the input size doesn't match the expected part size
+    /// Returns `YencError::InvalidConfig` for text mode, which needs the
+    /// whole input to size the part, or if `part_info` describes an
+    /// impossible part (see `MultiPartInfo::validate`), and an error if the
+    /// input size doesn't match the expected part size. The mismatch is only
+    /// noticed at the end of the input, so anything already written to
+    /// `writer` should be discarded.
     ///
     /// # Example
     /// ```
@@ -204,97 +1637,268 @@ impl Encoder {
     /// ```
     pub fn encode_part<R: Read, W: Write>(
         &self,
-        mut reader: R,
+        reader: R,
+        writer: W,
+        filename: &str,
+        part_info: &MultiPartInfo,
+    ) -> Result<EncodeReport> {
+        self.encode_part_with_scratch(reader, writer, filename, part_info, &mut Scratch::new())
+    }
+
+    /// Encode a part like `encode_part`, reusing the buffers in `scratch`
+    ///
+    /// Posting a large file part by part then allocates its buffers once
+    /// rather than for every part.
+    pub fn encode_part_with_scratch<R: Read, W: Write>(
+        &self,
+        reader: R,
+        writer: W,
+        filename: &str,
+        part_info: &MultiPartInfo,
+        scratch: &mut Scratch,
+    ) -> Result<EncodeReport> {
+        self.validate_sized()?;
+        let reservation = self.reserve(self.read_chunk_size())?;
+        self.encode_part_reserved(reader, writer, filename, part_info, scratch, reservation)
+    }
+
+    /// Encode a part like `encode_part_with_scratch`, within the memory held
+    /// in `reservation`
+    fn encode_part_reserved<R: Read, W: Write>(
+        &self,
+        reader: R,
         mut writer: W,
         filename: &str,
         part_info: &MultiPartInfo,
-    ) -> Result<usize> {
-        let mut input_data = Vec::new();
-        reader.read_to_end(&mut input_data)?;
+        scratch: &mut Scratch,
+        reservation: Reservation,
+    ) -> Result<EncodeReport> {
+        let mut state = self
+            .state()
+            .with_buffer(mem::take(&mut scratch.output))
+            .with_reservation(reservation);
+        let result = self.write_part(
+            reader,
+            &mut writer,
+            filename,
+            part_info,
+            &mut state,
+            &mut scratch.input,
+        );
+        scratch.output = state.into_buffer();
+        result
+    }
 
-        let part_size = input_data.len();
-        let expected_size = part_info.expected_size();
+    /// Encode every part of `data` described by `parts` on the rayon thread pool
+    ///
+    /// `open` is called on a worker thread with each part's configuration and
+    /// returns the writer that part is encoded into, for instance a file
+    /// named after the part number. Parts are independent, so each one comes
+    /// out exactly as `encode_part` would produce it, whatever the order the
+    /// threads finish in. Returns the report of every part, in `parts` order;
+    /// if parts fail, the error of the first of them is returned instead, and
+    /// their output should be discarded.
+    ///
+    /// Under a memory budget (see `max_memory`), each part waits for its
+    /// buffers to be reserved before `open` is called, so the parts encoded
+    /// at once never hold more than the budget between them.
+    ///
+    /// Each part covers bytes `begin..=end` of `data` (1-based); parts that
+    /// reach past the end of `data` fail with `YencError::InvalidConfig`
+    /// before anything is encoded, as does text mode.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::fs::File;
+    /// use yenc::{Encoder, MultiPartInfo};
+    ///
+    /// let data = std::fs::read("big.iso")?;
+    /// let size = data.len() as u64;
+    /// let part_size = 700_000;
+    /// let total = size.div_ceil(part_size) as usize;
+    /// let parts: Vec<_> = (0..total)
+    ///     .map(|i| {
+    ///         let begin = i as u64 * part_size;
+    ///         let end = (begin + part_size).min(size);
+    ///         MultiPartInfo::new(i + 1, total, begin + 1, end, size)
+    ///     })
+    ///     .collect();
+    ///
+    /// Encoder::new().encode_parts_parallel(&data, "big.iso", &parts, |part| {
+    ///     Ok(File::create(format!("big.iso.{:03}.yenc", part.part))?)
+    /// })?;
+    /// # Ok::<(), yenc::YencError>(())
+    /// ```
+    #[cfg(feature = "rayon")]
+    pub fn encode_parts_parallel<W, F>(
+        &self,
+        data: &[u8],
+        filename: &str,
+        parts: &[MultiPartInfo],
+        open: F,
+    ) -> Result<Vec<EncodeReport>>
+    where
+        W: Write,
+        F: Fn(&MultiPartInfo) -> Result<W> + Sync,
+    {
+        use rayon::prelude::*;
+
+        self.validate_sized()?;
+        for part_info in parts {
+            part_info.validate()?;
+            if part_info.end > data.len() as u64 {
+                return Err(YencError::InvalidConfig(format!(
+                    "part {} covers bytes {}-{}, outside the {}-byte input",
+                    part_info.part,
+                    part_info.begin,
+                    part_info.end,
+                    data.len()
+                )));
+            }
+        }
+
+        let results: Vec<Result<EncodeReport>> = parts
+            .par_iter()
+            .map_init(Scratch::new, |scratch, part_info| {
+                let part = &data[part_info.begin as usize - 1..part_info.end as usize];
+                // Reserved before the writer is opened, so that parts waiting
+                // for memory hold nothing
+                let reservation = self.reserve(self.read_chunk_size())?;
+                let writer = open(part_info)?;
+                self.encode_part_reserved(part, writer, filename, part_info, scratch, reservation)
+            })
+            .collect();
+        results.into_iter().collect()
+    }
+
+    fn write_part<R: Read, W: Write>(
+        &self,
+        reader: R,
+        writer: &mut W,
+        filename: &str,
+        part_info: &MultiPartInfo,
+        state: &mut EncodeState,
+        chunk: &mut Vec<u8>,
+    ) -> Result<EncodeReport> {
+        self.write_part_body(reader, writer, filename, part_info, state, chunk)?;
+        Ok(self.end_part(state, writer, part_info)?)
+    }
 
-        // Validate that input size matches expected part size
-        if part_size != expected_size {
+    /// Write everything of a part but its trailer
+    pub(crate) fn write_part_body<R: Read, W: Write>(
+        &self,
+        reader: R,
+        writer: &mut W,
+        filename: &str,
+        part_info: &MultiPartInfo,
+        state: &mut EncodeState,
+        chunk: &mut Vec<u8>,
+    ) -> Result<()> {
+        part_info.validate()?;
+        let part_size = part_info.expected_size();
+        self.begin_part(state, filename, part_info)?;
+
+        // Encode data, streaming it since the part size is already known
+        let read = self.stream_body(reader, writer, state, chunk, part_size)?;
+        if read != part_size {
             return Err(YencError::InvalidData(format!(
-                "Part size mismatch: expected {} bytes (from begin={} end={}), but got {} bytes",
-                expected_size, part_info.begin, part_info.end, part_size
+                "Part size mismatch: expected {} bytes (from begin={} end={}), but got {}",
+                part_size,
+                part_info.begin,
+                part_info.end,
+                describe_read(read, part_size)
             )));
         }
+        Ok(())
+    }
 
-        // Compute part CRC32 if enabled
-        let part_crc = if self.compute_crc {
-            let mut hasher = Hasher::new();
-            hasher.update(&input_data);
-            Some(hasher.finalize())
-        } else {
-            None
-        };
-
+    /// Queue the `=ybegin` and `=ypart` lines of a part
+    pub(crate) fn begin_part(
+        &self,
+        state: &mut EncodeState,
+        filename: &str,
+        part_info: &MultiPartInfo,
+    ) -> io::Result<()> {
         // Write multi-part header
+        let quote = self.name_quote(filename);
         writeln!(
-            writer,
-            "=ybegin part={} total={} line={} size={} name={}",
-            part_info.part, part_info.total, self.line_length, part_info.full_size, filename
+            state.output(),
+            "=ybegin part={} total={} line={} size={} name={}{}{}",
+            part_info.part,
+            part_info.total,
+            self.line_length,
+            part_info.full_size,
+            quote,
+            filename,
+            quote
         )?;
 
         // Write part line
         writeln!(
-            writer,
+            state.output(),
             "=ypart begin={} end={}",
-            part_info.begin, part_info.end
-        )?;
-
-        // Encode data
-        let mut line_length = 0;
-        for &byte in &input_data {
-            let encoded = encode_byte(byte);
-
-            if needs_escape(byte, encoded) {
-                writer.write_all(&[ESCAPE_CHAR, encoded.wrapping_add(ESCAPE_OFFSET)])?;
-                line_length += 2;
-            } else {
-                writer.write_all(&[encoded])?;
-                line_length += 1;
-            }
-
-            if line_length >= self.line_length {
-                writeln!(writer)?;
-                line_length = 0;
-            }
-        }
+            part_info.begin,
+            part_info.end
+        )
+    }
 
-        if line_length > 0 {
-            writeln!(writer)?;
-        }
+    /// End the data of a part and write out its trailer
+    pub(crate) fn end_part<W: Write>(
+        &self,
+        state: &mut EncodeState,
+        writer: &mut W,
+        part_info: &MultiPartInfo,
+    ) -> io::Result<EncodeReport> {
+        let part_size = part_info.expected_size();
+        let part_crc = state.finish();
 
         // Write trailer
-        write!(writer, "=yend size={} part={}", part_size, part_info.part)?;
+        let trailer = state.output();
+        write!(trailer, "=yend size={} part={}", part_size, part_info.part)?;
 
         // Add part CRC if computed
         if let Some(pcrc) = part_crc {
-            write!(writer, " pcrc32={:08x}", pcrc)?;
+            write!(trailer, " pcrc32={:08x}", pcrc)?;
         }
 
         // Add full file CRC if provided
         if let Some(full_crc) = part_info.full_crc {
-            write!(writer, " crc32={:08x}", full_crc)?;
+            write!(trailer, " crc32={:08x}", full_crc)?;
         }
 
-        writeln!(writer)?;
+        writeln!(trailer)?;
+        state.flush(writer)?;
 
-        Ok(part_size)
+        Ok(EncodeReport {
+            crc32: part_info.full_crc,
+            pcrc32: part_crc,
+            ..state.report(3, part_size)
+        })
     }
 }
 
 /// Encode data with default settings
 ///
 /// This is a convenience function equivalent to `Encoder::new().encode(reader, writer, filename)`
-pub fn encode<R: Read, W: Write>(reader: R, writer: W, filename: &str) -> Result<usize> {
+pub fn encode<R: Read, W: Write>(reader: R, writer: W, filename: &str) -> Result<EncodeReport> {
     Encoder::new().encode(reader, writer, filename)
 }
 
+/// Encode a byte slice into a new vector with default settings
+///
+/// This is a convenience function equivalent to `Encoder::new().encode_to_vec(data, filename)`
+pub fn encode_to_vec(data: &[u8], filename: &str) -> Result<Vec<u8>> {
+    Encoder::new().encode_to_vec(data, filename)
+}
+
+/// Sample the escape frequency of the input with default encoder settings
+///
+/// This is a convenience function equivalent to
+/// `Encoder::new().sample_overhead(reader, sample_bytes)`
+pub fn sample_overhead<R: Read>(reader: R, sample_bytes: u64) -> Result<OverheadEstimate> {
+    Encoder::new().sample_overhead(reader, sample_bytes)
+}
+
 /// Encode a part with default encoder settings
 ///
 /// This is a convenience function equivalent to:
@@ -304,13 +1908,15 @@ pub fn encode_part<R: Read, W: Write>(
     writer: W,
     filename: &str,
     part_info: &MultiPartInfo,
-) -> Result<usize> {
+) -> Result<EncodeReport> {
     Encoder::new().encode_part(reader, writer, filename, part_info)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::thread;
+    use std::time::Duration;
 
     #[test]
     fn test_encode_byte() {
@@ -318,14 +1924,27 @@ mod tests {
         assert_eq!(encode_byte(1), 43);
     }
 
+    #[test]
+    fn test_needs_escape() {
+        let escaped: Vec<u8> = (0..=u8::MAX)
+            .filter(|&b| EscapeRules::DEFAULT.escapes(b))
+            .collect();
+        // NUL, TAB, LF, CR, SPACE, DOT and '=' once encoded, plus a literal '='
+        assert_eq!(escaped, [0x04, 0x13, b'=', 0xd6, 0xdf, 0xe0, 0xe3, 0xf6]);
+    }
+
     #[test]
     fn test_encode_simple() {
-        let input = vec![0u8, 1, 2, 3, 4];
+        let input = [0u8, 1, 2, 3, 4];
         let mut output = Vec::new();
 
-        let size = encode(&input[..], &mut output, "test.bin").unwrap();
+        let report = encode(&input[..], &mut output, "test.bin").unwrap();
 
-        assert_eq!(size, 5);
+        assert_eq!(report.input_bytes, 5);
+        assert_eq!(report.output_bytes, output.len());
+        assert_eq!(report.lines, 1);
+        assert_eq!(report.crc32, Some(0x515ad3cc));
+        assert_eq!(report.pcrc32, None);
         let output_str = String::from_utf8(output).unwrap();
         assert!(output_str.contains("=ybegin"));
         assert!(output_str.contains("name=test.bin"));
@@ -341,7 +1960,7 @@ mod tests {
 
     #[test]
     fn test_encode_no_crc() {
-        let input = vec![0u8, 1, 2, 3, 4];
+        let input = [0u8, 1, 2, 3, 4];
         let mut output = Vec::new();
 
         Encoder::new().no_crc().encode(&input[..], &mut output, "test.bin").unwrap();
@@ -352,7 +1971,7 @@ mod tests {
 
     #[test]
     fn test_encode_multipart_basic() {
-        let data = vec![0u8, 1, 2, 3, 4];
+        let data = [0u8, 1, 2, 3, 4];
         let mut output = Vec::new();
 
         let part_info = MultiPartInfo::new(1, 2, 1, 5, 10);
@@ -377,17 +1996,60 @@ mod tests {
         assert!(trailer.contains("pcrc32=515ad3cc")); // Part CRC
     }
 
+    #[test]
+    fn test_encode_quote_names() {
+        let encoder = Encoder::new().quote_names();
+        let output = encoder.encode_to_vec(b"abc", "my file.bin").unwrap();
+        assert!(output.starts_with(b"=ybegin line=128 size=3 name=\"my file.bin\"\n"));
+        let (outcome, _) = crate::Decoder::new().decode_to_vec(&output).unwrap();
+        assert_eq!(outcome.header.name, b"my file.bin");
+
+        let output = encoder.encode_to_vec(b"abc", "file.bin").unwrap();
+        assert!(output.starts_with(b"=ybegin line=128 size=3 name=file.bin\n"));
+
+        let mut output = Vec::new();
+        let part_info = MultiPartInfo::new(1, 2, 1, 3, 6);
+        encoder
+            .encode_part(&b"abc"[..], &mut output, "my file.bin", &part_info)
+            .unwrap();
+        assert!(
+            output.starts_with(b"=ybegin part=1 total=2 line=128 size=6 name=\"my file.bin\"\n")
+        );
+    }
+
+    #[test]
+    fn test_encode_report_lines() {
+        // Escapes push some lines past the line length
+        let data: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+        for line_length in [1, 64, 128, 997] {
+            let mut output = Vec::new();
+            let report = Encoder::new()
+                .line_length(line_length)
+                .encode(&data[..], &mut output, "test.bin")
+                .unwrap();
+            assert_eq!(report.output_bytes, output.len());
+            assert_eq!(report.lines, output.split(|&b| b == b'\n').count() - 3);
+        }
+
+        let report = encode(&[][..], &mut Vec::new(), "empty.bin").unwrap();
+        assert_eq!(report.lines, 0);
+    }
+
     #[test]
     fn test_encode_multipart_with_full_crc() {
-        let data = vec![5u8, 6, 7, 8, 9];
+        let data = [5u8, 6, 7, 8, 9];
         let mut output = Vec::new();
 
         let part_info = MultiPartInfo::new(2, 2, 6, 10, 10)
             .with_full_crc(0x12345678); // Full file CRC
 
-        Encoder::new()
+        let report = Encoder::new()
             .encode_part(&data[..], &mut output, "test.bin", &part_info)
             .unwrap();
+        assert_eq!(report.input_bytes, 5);
+        assert_eq!(report.output_bytes, output.len());
+        assert_eq!(report.pcrc32, Some(crc32fast::hash(&data)));
+        assert_eq!(report.crc32, Some(0x12345678));
 
         let output_str = String::from_utf8(output).unwrap();
         let trailer = output_str.lines().last().unwrap();
@@ -398,7 +2060,7 @@ mod tests {
 
     #[test]
     fn test_encode_multipart_size_mismatch() {
-        let data = vec![0u8, 1, 2]; // Only 3 bytes
+        let data = [0u8, 1, 2]; // Only 3 bytes
         let mut output = Vec::new();
 
         // Says it should be 5 bytes (begin=1 end=5)
@@ -414,11 +2076,55 @@ mod tests {
             }
             other => panic!("Expected InvalidData, got {:?}", other),
         }
+
+        // Longer input than the part range is rejected too
+        let data = [0u8; 6];
+        let result = Encoder::new().encode_part(&data[..], &mut Vec::new(), "test.bin", &part_info);
+        assert!(matches!(result, Err(YencError::InvalidData(msg)) if msg.contains("more than 5")));
+    }
+
+    #[test]
+    fn test_encode_multipart_text_mode() {
+        // Text mode would change the part size, so it is rejected up front
+        let part_info = MultiPartInfo::new(1, 2, 1, 5, 10);
+        let mut output = Vec::new();
+        let result = Encoder::new().text_mode().encode_part(
+            &b"ab\ncd"[..],
+            &mut output,
+            "test.txt",
+            &part_info,
+        );
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_encode_multipart_streams() {
+        // The part is far larger than the memory budget and the read chunks
+        let data: Vec<u8> = (0..=255).cycle().take(100_000).collect();
+        let part_info = MultiPartInfo::new(1, 1, 1, data.len() as u64, data.len() as u64);
+
+        let mut streamed = Vec::new();
+        Encoder::new()
+            .max_memory(1024)
+            .chunk_size(1000)
+            .encode_part(&data[..], &mut streamed, "test.bin", &part_info)
+            .unwrap();
+
+        let mut buffered = Vec::new();
+        Encoder::new()
+            .encode_part(&data[..], &mut buffered, "test.bin", &part_info)
+            .unwrap();
+        assert_eq!(streamed, buffered);
+
+        let mut decoded = Vec::new();
+        crate::decode(&streamed[..], &mut decoded).unwrap();
+        assert_eq!(decoded, data);
     }
 
     #[test]
     fn test_encode_multipart_no_crc() {
-        let data = vec![0u8, 1, 2, 3, 4];
+        let data = [0u8, 1, 2, 3, 4];
         let mut output = Vec::new();
 
         let part_info = MultiPartInfo::new(1, 1, 1, 5, 5);
@@ -432,6 +2138,552 @@ mod tests {
         assert!(!output_str.contains("pcrc32=")); // No CRC computed
     }
 
+    #[test]
+    fn test_encode_max_memory() {
+        // The input shares the budget with an output block of 387 bytes
+        let encoder = Encoder::new().max_memory(1024);
+        let data = [0u8; 600];
+        let mut output = Vec::new();
+        encoder.encode(&data[..], &mut output, "test.bin").unwrap();
+
+        let result = encoder.encode(&[0u8; 700][..], &mut output, "test.bin");
+        assert!(matches!(
+            result,
+            Err(YencError::MemoryLimitExceeded { limit: 1024 })
+        ));
+
+        // Streamed input only takes a chunk at a time
+        let big = vec![0u8; 100_000];
+        encoder
+            .encode_sized(&big[..], Vec::new(), "big.bin", big.len() as u64)
+            .unwrap();
+
+        // Clones share the budget: an encode waits while a writer holds
+        // more than the rest of it
+        let writer = encoder.clone().writer(Vec::new(), "test.bin", 0).unwrap();
+        thread::scope(|scope| {
+            let encoding = scope.spawn(|| encoder.encode(&data[..], Vec::new(), "test.bin"));
+            thread::sleep(Duration::from_millis(50));
+            assert!(!encoding.is_finished());
+            writer.finish().unwrap();
+            encoding.join().unwrap().unwrap();
+        });
+    }
+
+    #[test]
+    fn test_to_crlf() {
+        assert_eq!(to_crlf(b"a\nb\r\nc\n"), b"a\r\nb\r\nc\r\n");
+        assert_eq!(to_crlf(b"\n\n"), b"\r\n\r\n");
+        assert_eq!(to_crlf(b"no newline"), b"no newline");
+    }
+
+    #[test]
+    fn test_encode_text_mode() {
+        let mut output = Vec::new();
+
+        let report = Encoder::new()
+            .text_mode()
+            .encode(&b"one\ntwo\r\n"[..], &mut output, "test.txt")
+            .unwrap();
+
+        // Size and CRC refer to the converted text
+        assert_eq!(report.input_bytes, 10);
+        assert_eq!(report.crc32, Some(crc32fast::hash(b"one\r\ntwo\r\n")));
+        assert!(output.starts_with(b"=ybegin line=128 size=10 name=test.txt\n"));
+
+        let mut expected = Vec::new();
+        encode(&b"one\r\ntwo\r\n"[..], &mut expected, "test.txt").unwrap();
+        assert_eq!(output, expected);
+    }
+
+    #[test]
+    fn test_encode_to_vec() {
+        let data: Vec<u8> = (0..=255).cycle().take(5000).collect();
+        for encoder in [
+            Encoder::new(),
+            Encoder::new().line_length(1),
+            Encoder::new().no_crc(),
+            Encoder::new().text_mode(),
+        ] {
+            let mut expected = Vec::new();
+            encoder
+                .encode(&data[..], &mut expected, "test.bin")
+                .unwrap();
+
+            let output = encoder.encode_to_vec(&data, "test.bin").unwrap();
+            assert_eq!(output, expected);
+        }
+
+        let output = encode_to_vec(b"", "empty").unwrap();
+        assert_eq!(
+            output,
+            b"=ybegin line=128 size=0 name=empty\n=yend size=0 crc32=00000000\n"
+        );
+    }
+
+    #[test]
+    fn test_encoded_len() {
+        let data: Vec<u8> = (0..=255).cycle().take(3000).collect();
+        for encoder in [
+            Encoder::new(),
+            Encoder::new().line_length(1),
+            Encoder::new().line_length(61),
+            Encoder::new().text_mode(),
+        ] {
+            for len in [0, 1, 60, 61, 62, 1000, 3000] {
+                let output = encoder.encode_to_vec(&data[..len], "f").unwrap();
+                let header = output.iter().position(|&b| b == b'\n').unwrap() + 1;
+                let trailer = output.windows(6).rposition(|w| w == b"=yend ").unwrap();
+                let body = trailer - header;
+
+                assert_eq!(encoder.encoded_len(&data[..len]), body, "len={}", len);
+                assert!(encoder.encoded_len_upper_bound(len) >= body, "len={}", len);
+            }
+        }
+
+        // Escapes count twice, and the line ends before its last column
+        let encoder = Encoder::new().line_length(4);
+        assert_eq!(encoder.encoded_len(&[0; 4]), 5);
+        assert_eq!(encoder.encoded_len(&[0xd6; 4]), 10);
+        assert_eq!(encoder.encoded_len_upper_bound(4), 2 * 4 + 2 + 1);
+    }
+
+    #[test]
+    fn test_encode_escape_at_line_end() {
+        // A pair ending in the last column, and one starting there
+        let encoder = Encoder::new().line_length(4);
+        let data = [0, 0, 0xd6, 0, 0, 0, 0xd6, 0];
+        let output = encoder.encode_to_vec(&data, "a").unwrap();
+        let body = &output[output.iter().position(|&b| b == b'\n').unwrap() + 1..];
+        assert!(body.starts_with(b"**=@\n***=@\n*\n=yend"));
+
+        // Every kernel wraps the same way, wherever the escapes land
+        let data: Vec<u8> = (0..3000u32)
+            .map(|i| match (i * 7 + i / 13) % 5 {
+                0 | 1 => 0xd6,
+                _ => i as u8 | 1,
+            })
+            .collect();
+        for &backend in Backend::available() {
+            for line_length in [1, 2, 3, 4, 5, 16, 17, 31, 32, 33, 128] {
+                let encoder = Encoder::new().line_length(line_length).backend(backend);
+                let output = encoder.encode_to_vec(&data, "test.bin").unwrap();
+                let lines: Vec<&[u8]> = output.split(|&b| b == b'\n').collect();
+                let body = &lines[1..lines.len() - 2];
+
+                for (index, line) in body.iter().enumerate() {
+                    let mut pos = 0;
+                    let mut ends_in_pair = false;
+                    while pos < line.len() {
+                        ends_in_pair = line[pos] == ESCAPE_CHAR;
+                        pos += if ends_in_pair { 2 } else { 1 };
+                    }
+                    let context = (backend, line_length, index);
+                    assert_eq!(pos, line.len(), "split escape pair {:?}", context);
+                    if index + 1 < body.len() {
+                        assert!(line.len() >= line_length, "early wrap {:?}", context);
+                    }
+                    if line.len() > line_length {
+                        assert_eq!(line.len(), line_length + 1, "{:?}", context);
+                        assert!(ends_in_pair, "long line without a pair {:?}", context);
+                    }
+                }
+                let (_, decoded) = crate::Decoder::new().decode_to_vec(&output).unwrap();
+                assert_eq!(decoded, data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_leading_dot() {
+        // 0x04 encodes to '.', which lands at the start of every line
+        let data = vec![0x04; 1000];
+        for &backend in Backend::available() {
+            for line_length in [1, 2, 7, 128] {
+                let encoder = Encoder::new()
+                    .line_length(line_length)
+                    .backend(backend)
+                    .escape_leading_dot();
+                let output = encoder.encode_to_vec(&data, "test.bin").unwrap();
+                let lines: Vec<&[u8]> = output.split(|&b| b == b'\n').collect();
+                let body = &lines[1..lines.len() - 2];
+                assert!(!body.iter().any(|line| line.starts_with(b".")));
+                let body_len: usize = body.iter().map(|line| line.len() + 1).sum();
+                assert_eq!(encoder.encoded_len(&data), body_len);
+
+                let (_, decoded) = crate::Decoder::new()
+                    .strict()
+                    .decode_to_vec(&output)
+                    .unwrap();
+                assert_eq!(decoded, data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_encode_boundary_whitespace() {
+        // 0xdf and 0xf6 encode to TAB and SPACE
+        let data: Vec<u8> = (0..1001u32)
+            .map(|i| match (i * 7 + i / 11) % 4 {
+                0 => 0xdf,
+                1 => 0xf6,
+                _ => i as u8,
+            })
+            .collect();
+        for &backend in Backend::available() {
+            for line_length in [1, 2, 3, 16, 128] {
+                let encoder = Encoder::new()
+                    .line_length(line_length)
+                    .backend(backend)
+                    .escape_boundary_whitespace();
+                let output = encoder.encode_to_vec(&data, "test.bin").unwrap();
+                let lines: Vec<&[u8]> = output.split(|&b| b == b'\n').collect();
+                let body = &lines[1..lines.len() - 2];
+                for line in body {
+                    assert!(!line.starts_with(b"\t") && !line.starts_with(b" "));
+                    assert!(!line.ends_with(b"\t") && !line.ends_with(b" "));
+                }
+                let body_len: usize = body.iter().map(|line| line.len() + 1).sum();
+                assert_eq!(encoder.encoded_len(&data), body_len);
+
+                let (_, decoded) = crate::Decoder::new()
+                    .strict()
+                    .decode_to_vec(&output)
+                    .unwrap();
+                assert_eq!(decoded, data);
+            }
+        }
+    }
+
+    #[test]
+    fn test_force_multipart_framing() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i * 3) as u8).collect();
+        let encoder = Encoder::new().force_multipart_framing();
+        let mut output = Vec::new();
+        let report = encoder.encode(&data[..], &mut output, "test.bin").unwrap();
+        let crc = crc32fast::hash(&data);
+        assert_eq!((report.crc32, report.pcrc32), (Some(crc), Some(crc)));
+        assert_eq!(report.lines, memchr_iter(b'\n', &output).count() - 3);
+        assert_eq!(report.output_bytes, output.len());
+
+        let text = String::from_utf8_lossy(&output);
+        assert!(text.starts_with(
+            "=ybegin part=1 total=1 line=128 size=1000 name=test.bin\n=ypart begin=1 end=1000\n"
+        ));
+        let trailer = format!("=yend size=1000 part=1 pcrc32={0:08x} crc32={0:08x}\n", crc);
+        assert!(text.ends_with(&trailer));
+
+        let (outcome, decoded) = crate::Decoder::new()
+            .strict()
+            .decode_to_vec(&output)
+            .unwrap();
+        assert_eq!(decoded, data);
+        assert_eq!(outcome.part.unwrap().end, 1000);
+
+        // The streaming writer frames the same way
+        let mut writer = encoder.writer(Vec::new(), "test.bin", 1000).unwrap();
+        writer.write_all(&data).unwrap();
+        let (streamed, _) = writer.finish().unwrap();
+        assert_eq!(streamed, output);
+
+        let output = encoder.encode_to_vec(b"", "empty").unwrap();
+        assert!(output.starts_with(b"=ybegin part=1 total=1 line=128 size=0 name=empty\n"));
+        let (_, decoded) = crate::Decoder::new().decode_to_vec(&output).unwrap();
+        assert!(decoded.is_empty());
+    }
+
+    #[test]
+    fn test_line_ending() {
+        let data: Vec<u8> = (0..3000u32).map(|i| (i * 11 + i / 3) as u8).collect();
+        for &backend in Backend::available() {
+            let encoder = Encoder::new()
+                .line_length(61)
+                .backend(backend)
+                .line_ending(LineEnding::CrLf);
+            let mut output = Vec::new();
+            let report = encoder.encode(&data[..], &mut output, "test.bin").unwrap();
+            let lines: Vec<&[u8]> = output.split(|&b| b == b'\n').collect();
+            let (last, ended) = lines.split_last().unwrap();
+            assert!(ended.iter().all(|line| line.ends_with(b"\r")));
+            assert!(last.is_empty());
+            assert_eq!(report.output_bytes, output.len());
+            assert_eq!(report.lines, lines.len() - 3);
+            let body_len: usize = lines[1..lines.len() - 2]
+                .iter()
+                .map(|line| line.len() + 1)
+                .sum();
+            assert_eq!(encoder.encoded_len(&data), body_len);
+            assert!(encoder.encoded_len_upper_bound(data.len()) >= body_len);
+
+            // Same article as with LF, but for the line endings
+            let lf = Encoder::new().line_length(61);
+            let without_cr: Vec<u8> = output.iter().copied().filter(|&b| b != b'\r').collect();
+            assert_eq!(without_cr, lf.encode_to_vec(&data, "test.bin").unwrap());
+
+            let (_, decoded) = crate::Decoder::new()
+                .strict()
+                .decode_to_vec(&output)
+                .unwrap();
+            assert_eq!(decoded, data);
+        }
+
+        let part_info = MultiPartInfo::new(1, 2, 1, 100, 200);
+        let mut output = Vec::new();
+        Encoder::new()
+            .line_ending(LineEnding::CrLf)
+            .encode_part(&data[..100], &mut output, "test.bin", &part_info)
+            .unwrap();
+        assert_eq!(memchr_iter(b'\n', &output).count(), 4);
+        assert_eq!(memchr_iter(b'\r', &output).count(), 4);
+    }
+
+    #[test]
+    fn test_escape_policy() {
+        let data: Vec<u8> = (0..5000u32).map(|i| (i * 7 + i / 5) as u8).collect();
+        let default = Encoder::new()
+            .line_length(31)
+            .encode_to_vec(&data, "test.bin")
+            .unwrap();
+        for &backend in Backend::available() {
+            let mut sizes = Vec::new();
+            for policy in [
+                EscapePolicy::Minimal,
+                EscapePolicy::Spec,
+                EscapePolicy::Paranoid,
+            ] {
+                let encoder = Encoder::new()
+                    .line_length(31)
+                    .backend(backend)
+                    .escape_policy(policy);
+                let output = encoder.encode_to_vec(&data, "test.bin").unwrap();
+                let lines: Vec<&[u8]> = output.split(|&b| b == b'\n').collect();
+                let body = &lines[1..lines.len() - 2];
+                let body_len: usize = body.iter().map(|line| line.len() + 1).sum();
+                assert_eq!(encoder.encoded_len(&data), body_len, "{:?}", policy);
+                if policy != EscapePolicy::Minimal {
+                    for line in body {
+                        assert!(!line.starts_with(b"\t") && !line.starts_with(b" "));
+                        assert!(!line.ends_with(b"\t") && !line.ends_with(b" "));
+                        assert!(!line.starts_with(b"."));
+                    }
+                }
+                if policy == EscapePolicy::Paranoid {
+                    assert_eq!(output, default);
+                }
+
+                let (_, decoded) = crate::Decoder::new()
+                    .strict()
+                    .decode_to_vec(&output)
+                    .unwrap();
+                assert_eq!(decoded, data, "{:?}", policy);
+                sizes.push(output.len());
+            }
+            assert!(sizes[0] < sizes[1] && sizes[1] < sizes[2], "{:?}", sizes);
+        }
+
+        // Extra escapes survive a change of policy
+        let encoder = Encoder::new()
+            .escape_bytes(b"K")
+            .escape_policy(EscapePolicy::Minimal);
+        let output = encoder.encode_to_vec(b"!", "a").unwrap();
+        assert!(output.windows(2).any(|w| w == b"=\x8b"));
+    }
+
+    #[test]
+    fn test_encode_escape_bytes() {
+        let extra = b"KZz\xff";
+        let data: Vec<u8> = (0..5000u32).map(|i| (i * 13 + i / 7) as u8).collect();
+        for &backend in Backend::available() {
+            let encoder = Encoder::new()
+                .line_length(61)
+                .backend(backend)
+                .escape_bytes(extra);
+            let output = encoder.encode_to_vec(&data, "test.bin").unwrap();
+            let lines: Vec<&[u8]> = output.split(|&b| b == b'\n').collect();
+            let body = &lines[1..lines.len() - 2];
+            for line in body {
+                let mut pos = 0;
+                while pos < line.len() {
+                    if line[pos] == ESCAPE_CHAR {
+                        pos += 2;
+                    } else {
+                        assert!(!extra.contains(&line[pos]), "{:?}", backend);
+                        pos += 1;
+                    }
+                }
+            }
+            let body_len: usize = body.iter().map(|line| line.len() + 1).sum();
+            assert_eq!(encoder.encoded_len(&data), body_len);
+
+            let (_, decoded) = crate::Decoder::new().decode_to_vec(&output).unwrap();
+            assert_eq!(decoded, data);
+            let result = crate::Decoder::new().strict().decode_to_vec(&output);
+            assert!(matches!(result, Err(YencError::InvalidData(_))));
+        }
+
+        for byte in [0xc0, 0xca, 0xcd, b'9'] {
+            let encoder = Encoder::new().escape_bytes(&[byte]);
+            let result = encoder.encode_to_vec(b"a", "a");
+            assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+        }
+
+        // Data that encodes to a line reading like a trailer still round-trips
+        let data: Vec<u8> = b"9end size=}1+,-"
+            .iter()
+            .map(|&b| b.wrapping_sub(42))
+            .collect();
+        let encoder = Encoder::new().escape_policy(EscapePolicy::Minimal);
+        let output = encoder.encode_to_vec(&data, "a").unwrap();
+        let (_, decoded) = crate::Decoder::new().decode_to_vec(&output).unwrap();
+        assert_eq!(decoded, data);
+        let result = encoder.escape_bytes(b"9").encode_to_vec(&data, "a");
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_encoder_try_build() {
+        assert!(Encoder::new().try_build().is_ok());
+        assert!(Encoder::new().line_length(997).try_build().is_ok());
+
+        for encoder in [
+            Encoder::new().line_length(0),
+            Encoder::new().line_length(998),
+            Encoder::new().max_memory(0),
+            Encoder::new().chunk_size(1 << 20).max_memory(1024),
+        ] {
+            assert!(matches!(
+                encoder.try_build(),
+                Err(YencError::InvalidConfig(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_encode_invalid_config() {
+        // Invalid settings are rejected before any output is produced
+        let mut output = Vec::new();
+        let result = Encoder::new()
+            .line_length(0)
+            .encode(&b"data"[..], &mut output, "test.bin");
+
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_encode_with_scratch() {
+        let encoder = Encoder::new();
+        let mut scratch = Scratch::new();
+
+        // A large input first, so later ones land in used buffers
+        for len in [10_000, 5, 300] {
+            let data: Vec<u8> = (0..len).map(|i| (i % 256) as u8).collect();
+            let mut expected = Vec::new();
+            encoder
+                .encode(&data[..], &mut expected, "test.bin")
+                .unwrap();
+
+            let mut output = Vec::new();
+            encoder
+                .encode_with_scratch(&data[..], &mut output, "test.bin", &mut scratch)
+                .unwrap();
+            assert_eq!(output, expected);
+
+            let part_info = MultiPartInfo::new(1, 1, 1, len, len);
+            let mut expected = Vec::new();
+            encoder
+                .encode_part(&data[..], &mut expected, "test.bin", &part_info)
+                .unwrap();
+            let mut output = Vec::new();
+            encoder
+                .encode_part_with_scratch(
+                    &data[..],
+                    &mut output,
+                    "test.bin",
+                    &part_info,
+                    &mut scratch,
+                )
+                .unwrap();
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn test_encode_chunk_size() {
+        let data: Vec<u8> = (0..1000).map(|i| (i % 256) as u8).collect();
+
+        let mut expected = Vec::new();
+        encode(&data[..], &mut expected, "test.bin").unwrap();
+
+        for chunk_size in [1, 7, 4096] {
+            let mut output = Vec::new();
+            Encoder::new()
+                .chunk_size(chunk_size)
+                .encode(&data[..], &mut output, "test.bin")
+                .unwrap();
+            assert_eq!(output, expected);
+        }
+    }
+
+    #[test]
+    fn test_sample_overhead() {
+        // Every byte maps to a critical character (0x00 encodes to '*',
+        // 0xD6 encodes to NUL)
+        let data = [0xD6u8, 0xE0, 0xE3, 0x13, 0x41, 0x42, 0x43, 0x44];
+        let estimate = sample_overhead(&data[..], 1024).unwrap();
+
+        assert_eq!(estimate.sampled_bytes, 8);
+        assert_eq!(estimate.escaped_bytes, 4);
+        assert_eq!(estimate.escape_ratio(), 0.5);
+    }
+
+    #[test]
+    fn test_sample_overhead_prediction() {
+        let data: Vec<u8> = (0..=255).cycle().take(10_000).collect();
+        let encoder = Encoder::new().line_length(128);
+        let estimate = encoder.sample_overhead(&data[..], 1024).unwrap();
+        assert_eq!(estimate.sampled_bytes, 1024);
+
+        let mut encoded = Vec::new();
+        encoder
+            .no_crc()
+            .encode(&data[..], &mut encoded, "f")
+            .unwrap();
+        let body_len = encoded.len()
+            - b"=ybegin line=128 size=10000 name=f\n".len()
+            - b"=yend size=10000\n".len();
+
+        // Prediction is close to the real encoded size
+        let predicted = estimate.predict_encoded_len(data.len() as u64);
+        assert!(predicted.abs_diff(body_len as u64) < body_len as u64 / 100);
+        assert!(estimate.overhead_percent() > 0.0);
+    }
+
+    #[test]
+    fn test_sample_overhead_matches_encoded_len() {
+        // Position-dependent escapes, CRLF line endings and text mode
+        // conversion all show up in the prediction
+        let data = b"line one\n line two.\n\tend \n".repeat(40);
+        let encoders = [
+            Encoder::new().line_length(16),
+            Encoder::new().line_length(16).line_ending(LineEnding::CrLf),
+            Encoder::new().line_length(16).text_mode(),
+        ];
+        for encoder in encoders {
+            let estimate = encoder.sample_overhead(&data[..], u64::MAX).unwrap();
+            let predicted = estimate.predict_encoded_len(data.len() as u64);
+            let exact = encoder.encoded_len(&data) as u64;
+            assert!(predicted.abs_diff(exact) <= exact / 50, "{}", predicted);
+        }
+
+        let estimate = Encoder::new()
+            .text_mode()
+            .sample_overhead(&b"a\nb\r\nc\n"[..], 4)
+            .unwrap();
+        assert_eq!(estimate.sampled_bytes, 4);
+        assert_eq!(estimate.inserted_bytes, 1);
+    }
+
     #[test]
     fn test_multipart_info_expected_size() {
         let info = MultiPartInfo::new(1, 10, 1, 100, 1000);
@@ -442,5 +2694,137 @@ mod tests {
 
         let info = MultiPartInfo::new(5, 10, 400001, 500000, 500000);
         assert_eq!(info.expected_size(), 100000);
+
+        // Empty range
+        let info = MultiPartInfo::new(1, 1, 1, 0, 0);
+        assert_eq!(info.expected_size(), 0);
+
+        // A backwards range has no bytes rather than underflowing
+        let info = MultiPartInfo::new(1, 2, 10, 5, 100);
+        assert_eq!(info.expected_size(), 0);
+    }
+
+    #[test]
+    fn test_multipart_info_validate() {
+        assert!(MultiPartInfo::try_new(1, 1, 1, 0, 0).is_ok());
+        assert!(MultiPartInfo::try_new(3, 3, 201, 250, 250).is_ok());
+
+        let invalid = [
+            (0, 2, 1, 5, 10),
+            (3, 2, 1, 5, 10),
+            (1, 2, 0, 5, 10),
+            (1, 2, 10, 5, 100),
+            (2, 2, 6, 11, 10),
+        ];
+        for (part, total, begin, end, full_size) in invalid {
+            let result = MultiPartInfo::try_new(part, total, begin, end, full_size);
+            assert!(
+                matches!(result, Err(YencError::InvalidConfig(_))),
+                "{:?}",
+                (part, total, begin, end)
+            );
+
+            // Nothing is written for an impossible part
+            let part_info = MultiPartInfo::new(part, total, begin, end, full_size);
+            let mut output = Vec::new();
+            let encoder = Encoder::new();
+            let result = encoder.encode_part(&b""[..], &mut output, "a", &part_info);
+            assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+            assert!(output.is_empty());
+            assert!(encoder.part_writer(Vec::new(), "a", &part_info).is_err());
+        }
+    }
+
+    /// Writer that records the size of every write call
+    struct CountingWriter(Vec<usize>);
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_encode_batches_writes() {
+        // Header, data and trailer of a small article go out in one write
+        let mut writer = CountingWriter(Vec::new());
+        encode(&b"hello"[..], &mut writer, "hello.txt").unwrap();
+        assert_eq!(writer.0.len(), 1);
+
+        let info = MultiPartInfo::new(1, 2, 1, 5, 10).with_full_crc(0x12345678);
+        let mut writer = CountingWriter(Vec::new());
+        encode_part(&b"hello"[..], &mut writer, "hello.txt", &info).unwrap();
+        assert_eq!(writer.0.len(), 1);
+
+        // Larger inputs in blocks rather than per line or per byte
+        let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7) as u8).collect();
+        let mut writer = CountingWriter(Vec::new());
+        encode(&data[..], &mut writer, "big.bin").unwrap();
+        assert!(writer.0.len() <= 4, "{:?}", writer.0);
+    }
+
+    #[test]
+    fn test_encode_sized_matches_encode() {
+        let data: Vec<u8> = (0..50_000u32).map(|i| (i * 13) as u8).collect();
+        let encoder = Encoder::new().chunk_size(1000);
+
+        let mut expected = Vec::new();
+        encoder.encode(&data[..], &mut expected, "f.bin").unwrap();
+
+        let mut output = Vec::new();
+        let report = encoder
+            .encode_sized(&data[..], &mut output, "f.bin", data.len() as u64)
+            .unwrap();
+        assert_eq!(report.input_bytes, data.len() as u64);
+        assert_eq!(output, expected);
+
+        // The input is streamed in chunks, so only those count against the
+        // memory budget
+        let mut output = Vec::new();
+        encoder
+            .max_memory(1000)
+            .encode_sized(io::repeat(7).take(10_000), &mut output, "f.bin", 10_000)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_encode_sized_wrong_size() {
+        let data = [1u8; 100];
+
+        let result = encode_sized_default(&data[..], 101);
+        assert!(matches!(result, Err(YencError::InvalidData(msg)) if msg.contains("got 100")));
+
+        let result = encode_sized_default(&data[..], 99);
+        assert!(matches!(result, Err(YencError::InvalidData(msg)) if msg.contains("more than 99")));
+
+        let encoder = Encoder::new().text_mode();
+        let result = encoder.encode_sized(&data[..], &mut Vec::new(), "f.bin", 100);
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+    }
+
+    fn encode_sized_default(data: &[u8], size: usize) -> Result<EncodeReport> {
+        Encoder::new().encode_sized(data, &mut Vec::new(), "f.bin", size as u64)
+    }
+
+    #[test]
+    fn test_encode_seekable() {
+        let data: Vec<u8> = (0..=255).collect();
+        let mut cursor = io::Cursor::new(&data[..]);
+        cursor.set_position(56);
+
+        let mut output = Vec::new();
+        let report = Encoder::new()
+            .encode_seekable(cursor, &mut output, "tail.bin")
+            .unwrap();
+        assert_eq!(report.input_bytes, 200);
+
+        let mut expected = Vec::new();
+        encode(&data[56..], &mut expected, "tail.bin").unwrap();
+        assert_eq!(output, expected);
     }
 }
@@ -0,0 +1,230 @@
+//! NEON encoding kernel for aarch64
+//!
+//! Mirrors the SSE2 kernel: the offset is added to a whole register and the
+//! result compared against every critical character at once. Runs that need
+//! no escaping are stored in one go, sparse escapes are encoded one at a
+//! time, and escape-heavy chunks go through a branch-free byte loop. Chunks
+//! that reach the end of a line use the scalar code. NEON is part of the
+//! aarch64 baseline, so no runtime detection is needed.
+
+use std::arch::aarch64::*;
+
+use super::encode_into;
+use crate::consts::{ESCAPE_CHAR, ESCAPE_OFFSET, ESCAPING_CHARS, OFFSET};
+
+/// Bytes needing escapes per 16-byte chunk above which `encode_dense` is faster
+const DENSE_ESCAPES_PER_16: u32 = 3;
+
+/// Encode as much of `data` as the NEON kernel can handle
+///
+/// Returns the number of input bytes consumed; the caller encodes the rest
+/// with `encode_into`. `out` must have room for the worst-case encoding of
+/// `data` (see `max_encoded_len`).
+#[inline]
+pub(super) fn encode(
+    data: &[u8],
+    out: &mut Vec<u8>,
+    column: &mut usize,
+    line_length: usize,
+) -> usize {
+    debug_assert!(out.capacity() - out.len() >= super::max_encoded_len(data.len(), line_length));
+
+    // SAFETY: NEON is part of the aarch64 baseline, and the caller reserved room
+    unsafe { encode_neon(data, out, column, line_length) }
+}
+
+/// Narrow a byte-wise comparison result to 4 bits per byte, first byte lowest
+///
+/// NEON has no movemask; shifting each 16-bit lane right by 4 and narrowing
+/// keeps one nibble of every byte.
+#[inline(always)]
+unsafe fn nibble_mask(cmp: uint8x16_t) -> u64 {
+    // SAFETY: plain register operations
+    unsafe {
+        let narrowed = vshrn_n_u16::<4>(vreinterpretq_u16_u8(cmp));
+        vget_lane_u64::<0>(vreinterpret_u64_u8(narrowed))
+    }
+}
+
+/// Encode an escape-heavy chunk byte by byte
+///
+/// `hits` holds 0xff for every byte of `chunk` that needs escaping. Every
+/// byte is written as if it were escaped and the escape character is then
+/// kept or overwritten, so there is no branch per escape for the predictor
+/// to miss.
+///
+/// # Safety
+/// `out` must have at least `2 * chunk.len()` bytes of spare capacity, and
+/// the encoded chunk must fit on the current line.
+#[inline(always)]
+unsafe fn encode_dense(chunk: &[u8], hits: &[u8; 16], out: &mut Vec<u8>, column: &mut usize) {
+    let ptr = out.as_mut_ptr();
+    let start = out.len();
+    let mut len = start;
+    for (&byte, &hit) in chunk.iter().zip(hits) {
+        let escape = usize::from(hit & 1);
+        let encoded = byte.wrapping_add(OFFSET);
+        let first = if escape != 0 { ESCAPE_CHAR } else { encoded };
+        // SAFETY: at most two bytes are written per input byte, and the
+        // caller reserved `2 * chunk.len()` bytes
+        unsafe {
+            ptr.add(len).write(first);
+            ptr.add(len + 1).write(encoded.wrapping_add(ESCAPE_OFFSET));
+        }
+        len += 1 + escape;
+    }
+    // SAFETY: bytes up to `len` were initialized above
+    unsafe { out.set_len(len) };
+    *column += len - start;
+}
+
+/// Encode 16 bytes at a time
+///
+/// # Safety
+/// The CPU must support NEON, and `out` must have room for the worst-case
+/// encoding of `data`.
+#[target_feature(enable = "neon")]
+unsafe fn encode_neon(
+    data: &[u8],
+    out: &mut Vec<u8>,
+    column: &mut usize,
+    line_length: usize,
+) -> usize {
+    let mut i = 0;
+    // SAFETY: loads stay within `data`. Encoding `i` bytes appends at most
+    // twice as many plus line breaks, so with 16 or more bytes left the
+    // reserved room still fits a full store.
+    unsafe {
+        let offset = vdupq_n_u8(OFFSET);
+        let escape = vdupq_n_u8(ESCAPE_CHAR);
+        let critical = ESCAPING_CHARS.map(|c| vdupq_n_u8(c));
+
+        while i + 16 <= data.len() {
+            // The line ends inside this chunk
+            if *column + 16 > line_length {
+                encode_into(data[i], out, column, line_length);
+                i += 1;
+                continue;
+            }
+
+            let chunk = vld1q_u8(data.as_ptr().add(i));
+            let encoded = vaddq_u8(chunk, offset);
+            let mut hits = vceqq_u8(chunk, escape);
+            for c in critical {
+                hits = vorrq_u8(hits, vceqq_u8(encoded, c));
+            }
+            let mask = nibble_mask(hits);
+
+            // Escape-heavy chunks are cheaper to encode byte by byte than
+            // one escape per iteration, as long as the line cannot end
+            if mask.count_ones() / 4 > DENSE_ESCAPES_PER_16 && *column + 2 * 16 <= line_length {
+                let mut flags = [0u8; 16];
+                vst1q_u8(flags.as_mut_ptr(), hits);
+                encode_dense(&data[i..i + 16], &flags, out, column);
+                i += 16;
+                if *column >= line_length {
+                    out.push(b'\n');
+                    *column = 0;
+                }
+                continue;
+            }
+
+            let len = out.len();
+            vst1q_u8(out.as_mut_ptr().add(len), encoded);
+
+            // Keep the bytes before the first one that needs escaping
+            let run = if mask == 0 {
+                16
+            } else {
+                mask.trailing_zeros() as usize / 4
+            };
+            out.set_len(len + run);
+            *column += run;
+            i += run;
+
+            if *column >= line_length {
+                out.push(b'\n');
+                *column = 0;
+            }
+            if mask != 0 {
+                encode_into(data[i], out, column, line_length);
+                i += 1;
+            }
+        }
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::max_encoded_len;
+    use super::*;
+    use crate::consts::NEEDS_ESCAPE;
+
+    /// Input where roughly `percent`% of the bytes need escaping
+    fn sample(len: usize, percent: u32, seed: u32) -> Vec<u8> {
+        let needs_escape: Vec<u8> = (0..=u8::MAX)
+            .filter(|&byte| NEEDS_ESCAPE[byte as usize])
+            .collect();
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                if state % 100 < percent {
+                    needs_escape[(state >> 8) as usize % needs_escape.len()]
+                } else {
+                    (state >> 8) as u8
+                }
+            })
+            .collect()
+    }
+
+    fn run(data: &[u8], column: usize, line_length: usize) -> (Vec<u8>, usize) {
+        let mut out = Vec::with_capacity(max_encoded_len(data.len(), line_length));
+        let mut column = column;
+        let consumed = encode(data, &mut out, &mut column, line_length);
+        for &byte in &data[consumed..] {
+            encode_into(byte, &mut out, &mut column, line_length);
+        }
+        (out, column)
+    }
+
+    fn scalar(data: &[u8], column: usize, line_length: usize) -> (Vec<u8>, usize) {
+        let mut out = Vec::new();
+        let mut column = column;
+        for &byte in data {
+            encode_into(byte, &mut out, &mut column, line_length);
+        }
+        (out, column)
+    }
+
+    #[test]
+    fn test_neon_matches_scalar() {
+        for line_length in [1, 7, 16, 17, 33, 128, 997] {
+            for percent in [0, 2, 10, 50, 100] {
+                for len in [0, 15, 16, 31, 32, 33, 100, 1000, 4096] {
+                    let data = sample(len, percent, len as u32 * 7 + percent);
+                    for column in [0, line_length / 2, line_length - 1] {
+                        assert_eq!(
+                            run(&data, column, line_length),
+                            scalar(&data, column, line_length),
+                            "line_length={} percent={} len={} column={}",
+                            line_length,
+                            percent,
+                            len,
+                            column
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_neon_all_byte_values() {
+        let data: Vec<u8> = (0..=255).cycle().take(2048).collect();
+        assert_eq!(run(&data, 0, 128), scalar(&data, 0, 128));
+    }
+}
@@ -0,0 +1,138 @@
+//! Portable `std::simd` encoding kernel
+//!
+//! Same structure as the hand-written kernels, expressed with `std::simd`
+//! so that targets without them (RISC-V, s390x, ...) still get vectorized
+//! encoding. Requires a nightly compiler and the `portable-simd` feature.
+
+use std::simd::prelude::*;
+
+use super::encode_into;
+use crate::consts::{ESCAPE_CHAR, ESCAPING_CHARS, OFFSET};
+
+const LANES: usize = 16;
+
+/// Bytes needing escapes per chunk above which the byte loop is faster
+const DENSE_ESCAPES: u32 = 3;
+
+/// Encode as much of `data` as the portable kernel can handle
+///
+/// Returns the number of input bytes consumed; the caller encodes the rest
+/// with `encode_into`.
+pub(super) fn encode(
+    data: &[u8],
+    out: &mut Vec<u8>,
+    column: &mut usize,
+    line_length: usize,
+) -> usize {
+    let offset = Simd::splat(OFFSET);
+    let escape = Simd::splat(ESCAPE_CHAR);
+    let critical = ESCAPING_CHARS.map(Simd::splat);
+
+    let mut i = 0;
+    while i + LANES <= data.len() {
+        // The line ends inside this chunk
+        if *column + LANES > line_length {
+            encode_into(data[i], out, column, line_length);
+            i += 1;
+            continue;
+        }
+
+        let chunk = u8x16::from_slice(&data[i..i + LANES]);
+        let encoded = chunk + offset;
+        let hits = critical
+            .iter()
+            .fold(chunk.simd_eq(escape), |hits, &c| hits | encoded.simd_eq(c));
+        let mask = hits.to_bitmask();
+
+        if mask.count_ones() > DENSE_ESCAPES {
+            for &byte in &data[i..i + LANES] {
+                encode_into(byte, out, column, line_length);
+            }
+            i += LANES;
+            continue;
+        }
+
+        // Keep the bytes before the first one that needs escaping
+        let run = if mask == 0 {
+            LANES
+        } else {
+            mask.trailing_zeros() as usize
+        };
+        out.extend_from_slice(&encoded.to_array()[..run]);
+        *column += run;
+        i += run;
+
+        if *column >= line_length {
+            out.push(b'\n');
+            *column = 0;
+        }
+        if mask != 0 {
+            encode_into(data[i], out, column, line_length);
+            i += 1;
+        }
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::NEEDS_ESCAPE;
+
+    /// Input where roughly `percent`% of the bytes need escaping
+    fn sample(len: usize, percent: u32, seed: u32) -> Vec<u8> {
+        let needs_escape: Vec<u8> = (0..=u8::MAX)
+            .filter(|&byte| NEEDS_ESCAPE[byte as usize])
+            .collect();
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                if state % 100 < percent {
+                    needs_escape[(state >> 8) as usize % needs_escape.len()]
+                } else {
+                    (state >> 8) as u8
+                }
+            })
+            .collect()
+    }
+
+    /// Encode `data` with `encode` (or the scalar code alone) plus the tail
+    fn run(data: &[u8], column: usize, line_length: usize, vector: bool) -> (Vec<u8>, usize) {
+        let mut out = Vec::new();
+        let mut column = column;
+        let consumed = if vector {
+            encode(data, &mut out, &mut column, line_length)
+        } else {
+            0
+        };
+        for &byte in &data[consumed..] {
+            encode_into(byte, &mut out, &mut column, line_length);
+        }
+        (out, column)
+    }
+
+    #[test]
+    fn test_portable_matches_scalar() {
+        for line_length in [1, 7, 16, 17, 33, 128, 997] {
+            for percent in [0, 2, 10, 50, 100] {
+                for len in [0, 15, 16, 31, 32, 33, 100, 1000, 4096] {
+                    let data = sample(len, percent, len as u32 * 7 + percent);
+                    for column in [0, line_length / 2, line_length - 1] {
+                        assert_eq!(
+                            run(&data, column, line_length, true),
+                            run(&data, column, line_length, false),
+                            "line_length={} percent={} len={} column={}",
+                            line_length,
+                            percent,
+                            len,
+                            column
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
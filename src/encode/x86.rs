@@ -0,0 +1,314 @@
+//! SSE2 and AVX2 encoding kernels for x86_64
+//!
+//! Both kernels add the offset to a whole register and compare the result
+//! against every critical character at once. Runs that need no escaping are
+//! stored in one go, sparse escapes are encoded one at a time, and
+//! escape-heavy chunks go through a branch-free byte loop driven by the
+//! escape mask. Chunks that reach the end of a line use the scalar code.
+
+use std::arch::x86_64::*;
+
+use super::encode_into;
+use crate::consts::{ESCAPE_CHAR, ESCAPE_OFFSET, ESCAPING_CHARS, OFFSET};
+use crate::dispatch::Backend;
+
+/// Bytes needing escapes per 16-byte chunk above which `encode_dense` is faster
+const DENSE_ESCAPES_PER_16: u32 = 3;
+/// Bytes needing escapes per 32-byte chunk above which `encode_dense` is faster
+const DENSE_ESCAPES_PER_32: u32 = 6;
+
+/// Encode as much of `data` as the kernels of `backend` can handle
+///
+/// `backend` is `Sse2` or `Avx2` and must be available on this CPU. Returns
+/// the number of input bytes consumed; the caller encodes the rest with
+/// `encode_into`. `out` must have room for the worst-case encoding of `data`
+/// (see `max_encoded_len`).
+#[inline]
+pub(super) fn encode(
+    backend: Backend,
+    data: &[u8],
+    out: &mut Vec<u8>,
+    column: &mut usize,
+    line_length: usize,
+) -> usize {
+    debug_assert!(out.capacity() - out.len() >= super::max_encoded_len(data.len(), line_length));
+
+    debug_assert!(backend.is_available());
+
+    let mut consumed = 0;
+    if backend == Backend::Avx2 {
+        // SAFETY: the dispatcher only picks available backends, and the
+        // caller reserved room
+        consumed = unsafe { encode_avx2(data, out, column, line_length) };
+    }
+    // SAFETY: SSE2 is part of the x86_64 baseline, and the caller reserved room
+    consumed += unsafe { encode_sse2(&data[consumed..], out, column, line_length) };
+    consumed
+}
+
+/// Encode an escape-heavy chunk byte by byte
+///
+/// Bit `j` of `mask` says whether `chunk[j]` needs escaping. Every byte is
+/// written as if it were escaped and the escape character is then kept or
+/// overwritten, so there is no branch per escape for the predictor to miss.
+///
+/// # Safety
+/// `out` must have at least `2 * chunk.len()` bytes of spare capacity, and
+/// the encoded chunk must fit on the current line.
+#[inline(always)]
+unsafe fn encode_dense(chunk: &[u8], mask: u32, out: &mut Vec<u8>, column: &mut usize) {
+    let ptr = out.as_mut_ptr();
+    let start = out.len();
+    let mut len = start;
+    for (j, &byte) in chunk.iter().enumerate() {
+        let escape = ((mask >> j) & 1) as usize;
+        let encoded = byte.wrapping_add(OFFSET);
+        let first = if escape != 0 { ESCAPE_CHAR } else { encoded };
+        // SAFETY: at most two bytes are written per input byte, and the
+        // caller reserved `2 * chunk.len()` bytes
+        unsafe {
+            ptr.add(len).write(first);
+            ptr.add(len + 1).write(encoded.wrapping_add(ESCAPE_OFFSET));
+        }
+        len += 1 + escape;
+    }
+    // SAFETY: bytes up to `len` were initialized above
+    unsafe { out.set_len(len) };
+    *column += len - start;
+}
+
+/// Encode 16 bytes at a time
+///
+/// # Safety
+/// The CPU must support SSE2, and `out` must have room for the worst-case
+/// encoding of `data`.
+#[target_feature(enable = "sse2")]
+unsafe fn encode_sse2(
+    data: &[u8],
+    out: &mut Vec<u8>,
+    column: &mut usize,
+    line_length: usize,
+) -> usize {
+    let mut i = 0;
+    // SAFETY: loads stay within `data`. Encoding `i` bytes appends at most
+    // twice as many plus line breaks, so with 16 or more bytes left the
+    // reserved room still fits a full store.
+    unsafe {
+        let offset = _mm_set1_epi8(OFFSET as i8);
+        let escape = _mm_set1_epi8(ESCAPE_CHAR as i8);
+        let critical = ESCAPING_CHARS.map(|c| _mm_set1_epi8(c as i8));
+
+        while i + 16 <= data.len() {
+            // The line ends inside this chunk
+            if *column + 16 > line_length {
+                encode_into(data[i], out, column, line_length);
+                i += 1;
+                continue;
+            }
+
+            let chunk = _mm_loadu_si128(data.as_ptr().add(i).cast());
+            let encoded = _mm_add_epi8(chunk, offset);
+            let mut hits = _mm_cmpeq_epi8(chunk, escape);
+            for c in critical {
+                hits = _mm_or_si128(hits, _mm_cmpeq_epi8(encoded, c));
+            }
+            let mask = _mm_movemask_epi8(hits) as u32;
+
+            // Escape-heavy chunks are cheaper to encode byte by byte than
+            // one escape per iteration, as long as the line cannot end
+            if mask.count_ones() > DENSE_ESCAPES_PER_16 && *column + 2 * 16 <= line_length {
+                encode_dense(&data[i..i + 16], mask, out, column);
+                i += 16;
+                if *column >= line_length {
+                    out.push(b'\n');
+                    *column = 0;
+                }
+                continue;
+            }
+
+            let len = out.len();
+            _mm_storeu_si128(out.as_mut_ptr().add(len).cast(), encoded);
+
+            // Keep the bytes before the first one that needs escaping
+            let run = if mask == 0 {
+                16
+            } else {
+                mask.trailing_zeros() as usize
+            };
+            out.set_len(len + run);
+            *column += run;
+            i += run;
+
+            if *column >= line_length {
+                out.push(b'\n');
+                *column = 0;
+            }
+            if mask != 0 {
+                encode_into(data[i], out, column, line_length);
+                i += 1;
+            }
+        }
+    }
+    i
+}
+
+/// Encode 32 bytes at a time
+///
+/// # Safety
+/// The CPU must support AVX2, and `out` must have room for the worst-case
+/// encoding of `data`.
+#[target_feature(enable = "avx2")]
+unsafe fn encode_avx2(
+    data: &[u8],
+    out: &mut Vec<u8>,
+    column: &mut usize,
+    line_length: usize,
+) -> usize {
+    let mut i = 0;
+    // SAFETY: as for `encode_sse2`, with 32-byte registers
+    unsafe {
+        let offset = _mm256_set1_epi8(OFFSET as i8);
+        let escape = _mm256_set1_epi8(ESCAPE_CHAR as i8);
+        let critical = ESCAPING_CHARS.map(|c| _mm256_set1_epi8(c as i8));
+
+        while i + 32 <= data.len() {
+            if *column + 32 > line_length {
+                encode_into(data[i], out, column, line_length);
+                i += 1;
+                continue;
+            }
+
+            let chunk = _mm256_loadu_si256(data.as_ptr().add(i).cast());
+            let encoded = _mm256_add_epi8(chunk, offset);
+            let mut hits = _mm256_cmpeq_epi8(chunk, escape);
+            for c in critical {
+                hits = _mm256_or_si256(hits, _mm256_cmpeq_epi8(encoded, c));
+            }
+            let mask = _mm256_movemask_epi8(hits) as u32;
+
+            // Escape-heavy chunks are cheaper to encode byte by byte than
+            // one escape per iteration, as long as the line cannot end
+            if mask.count_ones() > DENSE_ESCAPES_PER_32 && *column + 2 * 32 <= line_length {
+                encode_dense(&data[i..i + 32], mask, out, column);
+                i += 32;
+                if *column >= line_length {
+                    out.push(b'\n');
+                    *column = 0;
+                }
+                continue;
+            }
+
+            let len = out.len();
+            _mm256_storeu_si256(out.as_mut_ptr().add(len).cast(), encoded);
+
+            let run = if mask == 0 {
+                32
+            } else {
+                mask.trailing_zeros() as usize
+            };
+            out.set_len(len + run);
+            *column += run;
+            i += run;
+
+            if *column >= line_length {
+                out.push(b'\n');
+                *column = 0;
+            }
+            if mask != 0 {
+                encode_into(data[i], out, column, line_length);
+                i += 1;
+            }
+        }
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::max_encoded_len;
+    use super::*;
+    use crate::consts::NEEDS_ESCAPE;
+
+    type Kernel = unsafe fn(&[u8], &mut Vec<u8>, &mut usize, usize) -> usize;
+
+    /// Input where roughly `percent`% of the bytes need escaping
+    fn sample(len: usize, percent: u32, seed: u32) -> Vec<u8> {
+        let needs_escape: Vec<u8> = (0..=u8::MAX)
+            .filter(|&byte| NEEDS_ESCAPE[byte as usize])
+            .collect();
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                if state % 100 < percent {
+                    needs_escape[(state >> 8) as usize % needs_escape.len()]
+                } else {
+                    (state >> 8) as u8
+                }
+            })
+            .collect()
+    }
+
+    fn run(kernel: Kernel, data: &[u8], column: usize, line_length: usize) -> (Vec<u8>, usize) {
+        let mut out = Vec::with_capacity(max_encoded_len(data.len(), line_length));
+        let mut column = column;
+        let consumed = unsafe { kernel(data, &mut out, &mut column, line_length) };
+        for &byte in &data[consumed..] {
+            encode_into(byte, &mut out, &mut column, line_length);
+        }
+        (out, column)
+    }
+
+    fn scalar(data: &[u8], column: usize, line_length: usize) -> (Vec<u8>, usize) {
+        let mut out = Vec::new();
+        let mut column = column;
+        for &byte in data {
+            encode_into(byte, &mut out, &mut column, line_length);
+        }
+        (out, column)
+    }
+
+    fn check_kernel(kernel: Kernel) {
+        for line_length in [1, 7, 16, 17, 33, 128, 997] {
+            for percent in [0, 2, 10, 50, 100] {
+                for len in [0, 15, 16, 31, 32, 33, 100, 1000, 4096] {
+                    let data = sample(len, percent, len as u32 * 7 + percent);
+                    for column in [0, line_length / 2, line_length - 1] {
+                        assert_eq!(
+                            run(kernel, &data, column, line_length),
+                            scalar(&data, column, line_length),
+                            "line_length={} percent={} len={} column={}",
+                            line_length,
+                            percent,
+                            len,
+                            column
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_sse2_matches_scalar() {
+        check_kernel(encode_sse2);
+    }
+
+    #[test]
+    fn test_avx2_matches_scalar() {
+        if Backend::Avx2.is_available() {
+            check_kernel(encode_avx2);
+        }
+    }
+
+    #[test]
+    fn test_kernels_all_byte_values() {
+        let data: Vec<u8> = (0..=255).cycle().take(2048).collect();
+        assert_eq!(run(encode_sse2, &data, 0, 128), scalar(&data, 0, 128));
+        if Backend::Avx2.is_available() {
+            assert_eq!(run(encode_avx2, &data, 0, 128), scalar(&data, 0, 128));
+        }
+    }
+}
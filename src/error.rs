@@ -16,6 +16,14 @@ pub enum YencError {
     MissingField(String),
     /// CRC mismatch
     CrcMismatch { expected: u32, actual: u32 },
+    /// Operation would exceed the configured memory budget
+    MemoryLimitExceeded { limit: usize },
+    /// Encoder or decoder options are invalid or conflict with each other
+    InvalidConfig(String),
+    /// The header `name=` value breaks the configured name limits
+    InvalidName(String),
+    /// Input ended before the `=yend` line, after `recovered` bytes of data
+    Truncated { recovered: u64 },
 }
 
 impl fmt::Display for YencError {
@@ -32,6 +40,18 @@ impl fmt::Display for YencError {
                     expected, actual
                 )
             }
+            YencError::MemoryLimitExceeded { limit } => {
+                write!(f, "Memory limit exceeded: budget is {} bytes", limit)
+            }
+            YencError::InvalidConfig(msg) => write!(f, "Invalid configuration: {}", msg),
+            YencError::InvalidName(msg) => write!(f, "Invalid file name: {}", msg),
+            YencError::Truncated { recovered } => {
+                write!(
+                    f,
+                    "Article truncated before =yend after {} bytes",
+                    recovered
+                )
+            }
         }
     }
 }
@@ -51,5 +71,16 @@ impl From<io::Error> for YencError {
     }
 }
 
+/// I/O errors are handed back as they are; anything else becomes an
+/// `io::ErrorKind::InvalidData` error wrapping the `YencError`
+impl From<YencError> for io::Error {
+    fn from(err: YencError) -> Self {
+        match err {
+            YencError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err),
+        }
+    }
+}
+
 /// A specialized `Result` type for yEnc operations
 pub type Result<T> = std::result::Result<T, YencError>;
@@ -0,0 +1,224 @@
+//! Event-driven decoding, for callers that want the metadata as it comes
+
+use std::io::Read;
+
+use crate::decode::{ArticleStream, DecodeWarning, Decoder, Event};
+use crate::error::Result;
+use crate::header::{YencHeader, YencPart, YencTrailer};
+
+/// Piece of an article reported by `YencEvents` and `Decoder::decode_events`
+#[derive(Debug, Clone, PartialEq)]
+pub enum YencEvent<'a> {
+    /// The `=ybegin` line
+    Begin(YencHeader),
+    /// The `=ypart` line of a multi-part article
+    Part(YencPart),
+    /// A block of decoded data, borrowed until the next event
+    Data(&'a [u8]),
+    /// The `=yend` line, checked against the data before it
+    End(YencTrailer),
+}
+
+/// Events of the yEnc article in a reader, pulled one at a time
+///
+/// Nothing is read until the next event is asked for, so an indexer can
+/// stop as soon as it has the header, or skip over the data without keeping
+/// any of it. The same checks as `Decoder::decode` apply; a CRC32 mismatch
+/// is reported instead of the `End` event, and whatever follows the trailer
+/// is ignored. An article without a trailer ends after its last `Data` event.
+/// Damage lenient decoding lets pass is listed by `warnings` once the
+/// article has ended.
+///
+/// Data events borrow from the decoder's buffer, so this is not an
+/// `Iterator`; loop over `next_event` instead.
+///
+/// # Example
+/// ```
+/// use yenc::{Decoder, YencEvent};
+///
+/// let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
+/// let mut events = Decoder::new().events(&input[..]).unwrap();
+///
+/// let mut size = 0;
+/// while let Some(event) = events.next_event() {
+///     match event.unwrap() {
+///         YencEvent::Begin(header) => assert_eq!(header.name, b"test.bin"),
+///         YencEvent::Data(data) => size += data.len(),
+///         _ => {}
+///     }
+/// }
+/// assert_eq!(size, 5);
+/// ```
+pub struct YencEvents<R> {
+    stream: ArticleStream<R>,
+}
+
+impl<R: Read> YencEvents<R> {
+    /// Read the events of the article in `inner` with default decoder settings
+    pub fn new(inner: R) -> Self {
+        Self {
+            stream: ArticleStream::new(&Decoder::new(), inner)
+                .expect("default decoder settings are valid"),
+        }
+    }
+
+    /// Read on to the next event, or `None` past the end of the article
+    ///
+    /// After an error, there are no more events.
+    pub fn next_event(&mut self) -> Option<Result<YencEvent<'_>>> {
+        let event = match self.stream.advance() {
+            Ok(event) => event?,
+            Err(err) => return Some(Err(err)),
+        };
+        let event = match event {
+            Event::Begin => YencEvent::Begin(self.stream.header()?.clone()),
+            Event::Part => YencEvent::Part(self.stream.part()?.clone()),
+            Event::Data => YencEvent::Data(self.stream.data()),
+            Event::End => YencEvent::End(self.stream.trailer()?.clone()),
+        };
+        Some(Ok(event))
+    }
+
+    /// Damage that lenient decoding let pass, once the article has ended
+    ///
+    /// Holds what `Decoder::decode` reports in `DecodeOutcome::warnings`,
+    /// such as a size mismatch or, with `Decoder::allow_crc_mismatch`, a
+    /// CRC32 mismatch. Complete by the time `next_event` returns `End`, or
+    /// `None` for an article without a trailer.
+    pub fn warnings(&self) -> &[DecodeWarning] {
+        self.stream.warnings()
+    }
+}
+
+impl Decoder {
+    /// Read the yEnc article in `inner` as a series of events; see `YencEvents`
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` for text mode, which is not
+    /// supported here, or if the options are invalid.
+    pub fn events<R: Read>(&self, inner: R) -> Result<YencEvents<R>> {
+        Ok(YencEvents {
+            stream: ArticleStream::new(self, inner)?,
+        })
+    }
+
+    /// Decode the article in `reader`, handing every event to `handle`
+    ///
+    /// Stops at the first error, whether found decoding or returned by
+    /// `handle`.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::{Decoder, YencEvent};
+    ///
+    /// let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
+    /// let mut names = Vec::new();
+    ///
+    /// Decoder::new()
+    ///     .decode_events(&input[..], |event| {
+    ///         if let YencEvent::Begin(header) = event {
+    ///             names.push(header.name_lossy().into_owned());
+    ///         }
+    ///         Ok(())
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(names, ["test.bin"]);
+    /// ```
+    pub fn decode_events<R, F>(&self, reader: R, mut handle: F) -> Result<()>
+    where
+        R: Read,
+        F: FnMut(YencEvent<'_>) -> Result<()>,
+    {
+        let mut events = self.events(reader)?;
+        while let Some(event) = events.next_event() {
+            handle(event?)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::YencError;
+    use crate::encode::{Encoder, MultiPartInfo};
+
+    /// Events of `input`, with the data joined up
+    fn collect(input: &[u8]) -> Result<(Vec<String>, Vec<u8>)> {
+        let mut kinds = Vec::new();
+        let mut data = Vec::new();
+        Decoder::new().decode_events(input, |event| {
+            let kind = match event {
+                YencEvent::Begin(_) => "begin",
+                YencEvent::Part(_) => "part",
+                YencEvent::Data(block) => {
+                    data.extend_from_slice(block);
+                    "data"
+                }
+                YencEvent::End(_) => "end",
+            };
+            if kinds.last().is_none_or(|last| last != kind) {
+                kinds.push(kind.to_string());
+            }
+            Ok(())
+        })?;
+        Ok((kinds, data))
+    }
+
+    #[test]
+    fn test_events() {
+        let data: Vec<u8> = (0..100_000).map(|i| (i * 13) as u8).collect();
+        let encoded = Encoder::new().encode_to_vec(&data, "test.bin").unwrap();
+        let (kinds, decoded) = collect(&encoded).unwrap();
+        assert_eq!(kinds, ["begin", "data", "end"]);
+        assert_eq!(decoded, data);
+
+        let mut encoded = Vec::new();
+        let part_info = MultiPartInfo::new(2, 2, 6, 10, 10);
+        Encoder::new()
+            .encode_part(&data[..5], &mut encoded, "test.bin", &part_info)
+            .unwrap();
+        let (kinds, decoded) = collect(&encoded).unwrap();
+        assert_eq!(kinds, ["begin", "part", "data", "end"]);
+        assert_eq!(decoded, &data[..5]);
+
+        // No trailer, no end event
+        let (kinds, _) = collect(b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n").unwrap();
+        assert_eq!(kinds, ["begin", "data"]);
+    }
+
+    #[test]
+    fn test_events_stop_early() {
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
+        let mut events = YencEvents::new(&input[..]);
+        let Some(Ok(YencEvent::Begin(header))) = events.next_event() else {
+            panic!("expected the header first");
+        };
+        assert_eq!(header.size, 5);
+
+        let result = Decoder::new().decode_events(&input[..], |_| {
+            Err(YencError::InvalidData("stop".to_string()))
+        });
+        assert!(matches!(result, Err(YencError::InvalidData(_))));
+
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5 crc32=00000000\n";
+        let result = collect(input);
+        assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+    }
+
+    #[test]
+    fn test_events_warnings() {
+        // The CRC32 matches neither the data nor the sizes
+        let input = b"=ybegin line=128 size=6 name=test.bin\nKLMNO\n=yend size=6 crc32=00000000\n";
+        let decoder = Decoder::new().allow_crc_mismatch();
+        let mut events = decoder.events(&input[..]).unwrap();
+        let mut ended = false;
+        while let Some(event) = events.next_event() {
+            ended = matches!(event.unwrap(), YencEvent::End(_));
+        }
+        assert!(ended);
+        let (outcome, _) = decoder.decode_to_vec(input).unwrap();
+        assert_eq!(outcome.warnings.len(), 2);
+        assert_eq!(events.warnings(), outcome.warnings);
+    }
+}
@@ -1,51 +1,501 @@
 //! yEnc header and trailer parsing
 
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::consts::MAX_LINE_LENGTH;
 use crate::error::{Result, YencError};
 
+/// Keywords understood by `YencHeader::parse`
+const HEADER_KEYS: &[&str] = &["line", "size", "name", "part", "total"];
+/// Keywords understood by `YencPart::parse`
+const PART_KEYS: &[&str] = &["begin", "end"];
+/// Keywords understood by `YencTrailer::parse`
+const TRAILER_KEYS: &[&str] = &["size", "part", "pcrc32", "crc32"];
+
+/// Keys on a keyword line that its parser does not recognize
+///
+/// Tokens after `name=` belong to the file name and are never reported.
+/// Lines that are not `=ybegin`, `=ypart` or `=yend` have no known keys.
+pub(crate) fn unknown_keys(line: &[u8]) -> Vec<&[u8]> {
+    let keyword = line
+        .split(u8::is_ascii_whitespace)
+        .find(|token| !token.is_empty());
+    let known = match keyword {
+        Some(b"=ybegin") => HEADER_KEYS,
+        Some(b"=ypart") => PART_KEYS,
+        Some(b"=yend") => TRAILER_KEYS,
+        _ => return Vec::new(),
+    };
+
+    fields(line)
+        .map(|(key, _)| key)
+        .take_while(|&key| key != b"name")
+        .filter(|&key| !known.iter().any(|known| known.as_bytes() == key))
+        .collect()
+}
+
+/// A `key=value` field of a keyword line
+struct Field<'a> {
+    key: &'a [u8],
+    value: &'a [u8],
+    /// Whether whitespace separated the key, `=` and value
+    spaced: bool,
+}
+
+/// The `key=value` fields of a keyword line, split at the first `=`
+///
+/// Works on the raw bytes, so nothing has to be valid UTF-8. Tokens
+/// without `=`, such as the `=ybegin` keyword itself, are skipped. Some
+/// encoders put whitespace around the `=` (`size =123`, `size = 123`); such
+/// fields are joined up again.
+fn spaced_fields(line: &[u8]) -> Vec<Field<'_>> {
+    let tokens: Vec<&[u8]> = line
+        .split(u8::is_ascii_whitespace)
+        .filter(|token| !token.is_empty())
+        .collect();
+    let bare = |index: usize| {
+        tokens
+            .get(index)
+            .copied()
+            .filter(|token| memchr::memchr(b'=', token).is_none())
+    };
+
+    let mut fields = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i];
+        i += 1;
+        let (key, mut value, mut spaced) = match memchr::memchr(b'=', token) {
+            Some(eq) if eq > 0 => (&token[..eq], &token[eq + 1..], false),
+            Some(_) => continue,
+            None => match tokens.get(i) {
+                // `key =value` or `key = value`
+                Some(next) if next.starts_with(b"=") => {
+                    i += 1;
+                    (token, &next[1..], true)
+                }
+                _ => continue,
+            },
+        };
+        // `key= value` or `key = value`
+        if value.is_empty() {
+            if let Some(next) = bare(i) {
+                value = next;
+                spaced = true;
+                i += 1;
+            }
+        }
+        fields.push(Field { key, value, spaced });
+    }
+    fields
+}
+
+/// The `key=value` fields of a keyword line, see `spaced_fields`
+fn fields(line: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    spaced_fields(line)
+        .into_iter()
+        .map(|field| (field.key, field.value))
+}
+
+/// Formatting of a keyword line that parsing tolerates but the spec does
+/// not produce
+///
+/// That is whitespace other than single spaces between tokens, whitespace
+/// around `=`, and keys given more than once (the last one counts). The
+/// name of a `=ybegin` line and whitespace around the line are not looked
+/// at. Returns a description of the first such oddity.
+pub(crate) fn formatting_issue(line: &[u8]) -> Option<String> {
+    let line = line.trim_ascii();
+    let keywords = if line.starts_with(b"=ybegin") {
+        split_name(line).0
+    } else {
+        line
+    };
+
+    let gap = keywords
+        .windows(2)
+        .any(|pair| pair[0].is_ascii_whitespace() && pair[1].is_ascii_whitespace());
+    if gap
+        || keywords
+            .iter()
+            .any(|&b| b.is_ascii_whitespace() && b != b' ')
+    {
+        return Some("Whitespace other than a single space between tokens".to_string());
+    }
+
+    let fields = spaced_fields(keywords);
+    if let Some(field) = fields.iter().find(|field| field.spaced) {
+        return Some(format!(
+            "Whitespace around '=' of {}",
+            String::from_utf8_lossy(field.key)
+        ));
+    }
+    fields.iter().enumerate().find_map(|(i, field)| {
+        fields[..i]
+            .iter()
+            .any(|earlier| earlier.key == field.key)
+            .then(|| format!("Key {} given twice", String::from_utf8_lossy(field.key)))
+    })
+}
+
+/// Parse a decimal field value
+///
+/// A value that is not a number fails rather than counting as absent, so
+/// that the error names the actual problem.
+fn parse_number<T: FromStr>(key: &str, value: &[u8]) -> Result<Option<T>> {
+    match std::str::from_utf8(value).ok().and_then(|v| v.parse().ok()) {
+        Some(number) => Ok(Some(number)),
+        None => Err(invalid_value(key, value)),
+    }
+}
+
+/// Parse a hexadecimal CRC32 field value
+fn parse_crc(key: &str, value: &[u8]) -> Result<Option<u32>> {
+    let crc = std::str::from_utf8(value)
+        .ok()
+        .and_then(|v| u32::from_str_radix(v, 16).ok());
+    match crc {
+        Some(crc) => Ok(Some(crc)),
+        None => Err(invalid_value(key, value)),
+    }
+}
+
+fn invalid_value(key: &str, value: &[u8]) -> YencError {
+    YencError::InvalidHeader(format!(
+        "Invalid value '{}' for {}=",
+        String::from_utf8_lossy(value),
+        key
+    ))
+}
+
+/// Keep a field the parser does not know, for `extra`
+///
+/// Bytes that are not UTF-8 are replaced by U+FFFD.
+fn insert_extra(extra: &mut BTreeMap<String, String>, key: &[u8], value: &[u8]) {
+    extra.insert(
+        String::from_utf8_lossy(key).into_owned(),
+        String::from_utf8_lossy(value).into_owned(),
+    );
+}
+
+/// Split the fields of a `=ybegin` line at the `name=` keyword
+///
+/// The name is the rest of the line, so it can hold spaces and `=`; only
+/// trailing whitespace and a pair of surrounding double quotes are dropped.
+/// Returns the keywords before the name and the name, if there is one.
+fn split_name(fields: &[u8]) -> (&[u8], Option<&[u8]>) {
+    let start = memchr::memmem::find_iter(fields, b"name=")
+        .find(|&index| index == 0 || fields[index - 1].is_ascii_whitespace());
+    let Some(index) = start else {
+        return (fields, None);
+    };
+
+    let name = fields[index + 5..].trim_ascii_end();
+    let name = name
+        .strip_prefix(b"\"")
+        .and_then(|name| name.strip_suffix(b"\""))
+        .unwrap_or(name);
+    (&fields[..index], Some(name))
+}
+
+/// yEnc version whose conventions an article follows
+///
+/// Told apart by the fields of the `=ybegin` line, see
+/// `YencHeader::version`. Versions are ordered, so `version >= V1_1` asks
+/// for a multi-part article.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum YencVersion {
+    /// Single-part articles: no `part=`, no `=ypart` line
+    V1_0,
+    /// Multi-part articles with `part=` but no `total=`; a `pcrc32=` on the
+    /// trailer is optional
+    V1_1,
+    /// Multi-part articles with `part=` and `total=`, and a `pcrc32=` on
+    /// every trailer
+    V1_2,
+}
+
 /// yEnc header
 #[derive(Debug, Clone, PartialEq)]
 pub struct YencHeader {
-    pub name: String,
-    pub size: usize,
+    /// Value of `name=` as it appears in the header
+    ///
+    /// Old posts often name files in Latin-1 or another legacy encoding, so
+    /// this is not necessarily UTF-8; see `name_lossy`.
+    pub name: Vec<u8>,
+    pub size: u64,
+    /// Value of `line=`; filled in with the observed line length by the
+    /// decoder when the header has none
     pub line_len: Option<usize>,
     pub part: Option<usize>,
     pub total: Option<usize>,
+    /// Keywords before the name that `parse` does not know, with their values
+    ///
+    /// Some encoders add fields of their own; they are written back by
+    /// `to_line`.
+    pub extra: BTreeMap<String, String>,
 }
 
 impl YencHeader {
     /// Parse a yEnc header line (e.g., "=ybegin line=128 size=123456 name=file.bin")
+    ///
+    /// The name runs to the end of the line, as the yEnc spec has it, so it
+    /// may contain spaces and `=`. Trailing whitespace is not part of it, and
+    /// neither are double quotes around it (`name="file with spaces.bin"`),
+    /// which some posters add.
     pub fn parse(line: &str) -> Result<Self> {
-        if !line.starts_with("=ybegin ") {
+        Self::parse_bytes(line.as_bytes())
+    }
+
+    /// Parse a yEnc header line that need not be valid UTF-8
+    ///
+    /// The line is parsed as bytes and the name is kept as found, so names
+    /// in legacy encodings or with binary garbage in them come through
+    /// untouched. Otherwise the same as `parse`; this is what the decoder
+    /// uses.
+    pub fn parse_bytes(line: &[u8]) -> Result<Self> {
+        if !line.starts_with(b"=ybegin ") {
             return Err(YencError::InvalidHeader(
                 "Header must start with '=ybegin'".to_string(),
             ));
         }
 
-        let mut name = None;
+        let (keywords, name) = split_name(&line[8..]);
         let mut size = None;
         let mut line_len = None;
         let mut part = None;
         let mut total = None;
+        let mut extra = BTreeMap::new();
 
-        for token in line[8..].split_whitespace() {
-            if let Some((key, value)) = token.split_once('=') {
-                match key {
-                    "name" => name = Some(value.to_string()),
-                    "size" => size = value.parse().ok(),
-                    "line" => line_len = value.parse().ok(),
-                    "part" => part = value.parse().ok(),
-                    "total" => total = value.parse().ok(),
-                    _ => {} // Ignore unknown fields
-                }
+        for (key, value) in fields(keywords) {
+            match key {
+                b"size" => size = parse_number("size", value)?,
+                b"line" => line_len = parse_number("line", value)?,
+                b"part" => part = parse_number("part", value)?,
+                b"total" => total = parse_number("total", value)?,
+                _ => insert_extra(&mut extra, key, value),
             }
         }
 
         Ok(YencHeader {
-            name: name.ok_or_else(|| YencError::MissingField("name".to_string()))?,
+            name: name
+                .map(<[u8]>::to_vec)
+                .ok_or_else(|| YencError::MissingField("name".to_string()))?,
             size: size.ok_or_else(|| YencError::MissingField("size".to_string()))?,
             line_len,
             part,
             total,
+            extra,
+        })
+    }
+
+    /// The header as a `=ybegin` line, without the line ending
+    ///
+    /// Fields in `extra` come after the known ones and before the name,
+    /// which ends the line. The name is quoted when `parse` would otherwise
+    /// lose part of it (trailing whitespace, or quotes of its own), so
+    /// parsing the line gives back the same header. Returned as bytes
+    /// because the name need not be UTF-8; the `Display` output is the same
+    /// line with `name_lossy`.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::YencHeader;
+    ///
+    /// let line = b"=ybegin part=1 total=4 line=128 size=8000 name=file.bin";
+    /// let header = YencHeader::parse_bytes(line).unwrap();
+    /// assert_eq!(header.to_line(), line);
+    /// assert_eq!(header.to_string().as_bytes(), line);
+    /// ```
+    pub fn to_line(&self) -> Vec<u8> {
+        let mut line = String::new();
+        self.write_keywords(&mut line)
+            .expect("writing to a String cannot fail");
+        let quote = self.name_quote().as_bytes();
+
+        let mut line = line.into_bytes();
+        line.extend_from_slice(quote);
+        line.extend_from_slice(&self.name);
+        line.extend_from_slice(quote);
+        line
+    }
+
+    /// Write the line up to and including `name=`
+    fn write_keywords<W: fmt::Write>(&self, out: &mut W) -> fmt::Result {
+        out.write_str("=ybegin")?;
+        if let Some(part) = self.part {
+            write!(out, " part={}", part)?;
+        }
+        if let Some(total) = self.total {
+            write!(out, " total={}", total)?;
+        }
+        if let Some(line_len) = self.line_len {
+            write!(out, " line={}", line_len)?;
+        }
+        write!(out, " size={}", self.size)?;
+        for (key, value) in &self.extra {
+            write!(out, " {}={}", key, value)?;
+        }
+        out.write_str(" name=")
+    }
+
+    /// Quote to put around the name so that `parse` reads it back unchanged
+    fn name_quote(&self) -> &'static str {
+        let quoted =
+            self.name.len() >= 2 && self.name.starts_with(b"\"") && self.name.ends_with(b"\"");
+        if quoted || self.name.last().is_some_and(u8::is_ascii_whitespace) {
+            "\""
+        } else {
+            ""
+        }
+    }
+
+    /// The yEnc version this header follows
+    ///
+    /// Headers without `part=` are taken as 1.0, since single-part articles
+    /// look the same in every version; with `part=`, the presence of
+    /// `total=` tells 1.2 from 1.1.
+    pub fn version(&self) -> YencVersion {
+        match (self.part, self.total) {
+            (None, _) => YencVersion::V1_0,
+            (Some(_), None) => YencVersion::V1_1,
+            (Some(_), Some(_)) => YencVersion::V1_2,
+        }
+    }
+
+    /// Start building a header field by field
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::YencHeader;
+    ///
+    /// let header = YencHeader::builder()
+    ///     .name("file.bin")
+    ///     .size(8000)
+    ///     .part(1)
+    ///     .total(4)
+    ///     .line(128)
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(header.to_line(), b"=ybegin part=1 total=4 line=128 size=8000 name=file.bin");
+    /// ```
+    pub fn builder() -> YencHeaderBuilder {
+        YencHeaderBuilder::default()
+    }
+
+    /// The file name, with bytes that are not UTF-8 replaced by U+FFFD
+    pub fn name_lossy(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.name)
+    }
+}
+
+/// Checked construction of a `YencHeader`, from `YencHeader::builder`
+///
+/// `name` and `size` are required; the other fields are left out of the
+/// header unless set.
+#[derive(Debug, Clone, Default)]
+pub struct YencHeaderBuilder {
+    name: Option<Vec<u8>>,
+    size: Option<u64>,
+    line_len: Option<usize>,
+    part: Option<usize>,
+    total: Option<usize>,
+}
+
+impl YencHeaderBuilder {
+    /// Set the file name, as text or raw bytes
+    pub fn name(mut self, name: impl AsRef<[u8]>) -> Self {
+        self.name = Some(name.as_ref().to_vec());
+        self
+    }
+
+    /// Set the size of the whole file in bytes
+    pub fn size(mut self, size: u64) -> Self {
+        self.size = Some(size);
+        self
+    }
+
+    /// Set the 1-based part number, for multi-part files
+    pub fn part(mut self, part: usize) -> Self {
+        self.part = Some(part);
+        self
+    }
+
+    /// Set the number of parts of the file
+    pub fn total(mut self, total: usize) -> Self {
+        self.total = Some(total);
+        self
+    }
+
+    /// Set the line length announced by `line=`
+    pub fn line(mut self, line_len: usize) -> Self {
+        self.line_len = Some(line_len);
+        self
+    }
+
+    /// Check the fields and build the header
+    ///
+    /// # Errors
+    /// Returns `YencError::MissingField` without a name or size,
+    /// `YencError::InvalidName` for an empty name or one holding a line
+    /// break or NUL, and `YencError::InvalidHeader` for a line length
+    /// outside 1 to 997, a part number of 0 or past the total, or a total
+    /// without a part number.
+    pub fn build(self) -> Result<YencHeader> {
+        let name = self
+            .name
+            .ok_or_else(|| YencError::MissingField("name".to_string()))?;
+        let size = self
+            .size
+            .ok_or_else(|| YencError::MissingField("size".to_string()))?;
+
+        if name.is_empty() {
+            return Err(YencError::InvalidName("name is empty".to_string()));
+        }
+        if let Some(pos) = name.iter().position(|&b| matches!(b, b'\r' | b'\n' | 0)) {
+            return Err(YencError::InvalidName(format!(
+                "line break or NUL at byte {}",
+                pos
+            )));
+        }
+        if let Some(line_len) = self.line_len {
+            if !(1..=MAX_LINE_LENGTH).contains(&line_len) {
+                return Err(YencError::InvalidHeader(format!(
+                    "line length must be between 1 and {}, got {}",
+                    MAX_LINE_LENGTH, line_len
+                )));
+            }
+        }
+        match (self.part, self.total) {
+            (Some(0), _) => {
+                return Err(YencError::InvalidHeader(
+                    "part numbers start at 1".to_string(),
+                ));
+            }
+            (Some(part), Some(total)) if part > total => {
+                return Err(YencError::InvalidHeader(format!(
+                    "part {} is past the total of {}",
+                    part, total
+                )));
+            }
+            (None, Some(_)) => {
+                return Err(YencError::InvalidHeader(
+                    "total is set without a part number".to_string(),
+                ));
+            }
+            _ => {}
+        }
+
+        Ok(YencHeader {
+            name,
+            size,
+            line_len: self.line_len,
+            part: self.part,
+            total: self.total,
+            extra: BTreeMap::new(),
         })
     }
 }
@@ -53,59 +503,88 @@ impl YencHeader {
 /// yEnc part information (for multi-part files)
 #[derive(Debug, Clone, PartialEq)]
 pub struct YencPart {
-    pub begin: usize,
-    pub end: usize,
+    pub begin: u64,
+    pub end: u64,
 }
 
 impl YencPart {
     /// Parse a yEnc part line (e.g., "=ypart begin=1 end=100000")
     pub fn parse(line: &str) -> Result<Self> {
-        if !line.starts_with("=ypart ") {
+        Self::parse_bytes(line.as_bytes())
+    }
+
+    /// Parse a yEnc part line as bytes, without checking it for UTF-8
+    pub fn parse_bytes(line: &[u8]) -> Result<Self> {
+        if !line.starts_with(b"=ypart ") {
             return Err(YencError::InvalidHeader(
                 "Part line must start with '=ypart'".to_string(),
             ));
         }
 
-        let mut begin = None;
-        let mut end = None;
+        let mut begin: Option<u64> = None;
+        let mut end: Option<u64> = None;
 
-        for token in line[7..].split_whitespace() {
-            if let Some((key, value)) = token.split_once('=') {
-                match key {
-                    "begin" => begin = value.parse().ok(),
-                    "end" => end = value.parse().ok(),
-                    _ => {}
-                }
+        for (key, value) in fields(&line[7..]) {
+            match key {
+                b"begin" => begin = parse_number("begin", value)?,
+                b"end" => end = parse_number("end", value)?,
+                _ => {}
             }
         }
 
-        Ok(YencPart {
-            begin: begin.ok_or_else(|| YencError::MissingField("begin".to_string()))?,
-            end: end.ok_or_else(|| YencError::MissingField("end".to_string()))?,
-        })
+        let begin = begin.ok_or_else(|| YencError::MissingField("begin".to_string()))?;
+        let end = end.ok_or_else(|| YencError::MissingField("end".to_string()))?;
+        // An empty part ends just before it begins, but no earlier
+        if end.saturating_add(1) < begin {
+            return Err(YencError::InvalidHeader(format!(
+                "Part range ends before it begins: begin={} end={}",
+                begin, end
+            )));
+        }
+        Ok(YencPart { begin, end })
+    }
+
+    /// The part as a `=ypart` line, without the line ending
+    ///
+    /// Same as `to_string`.
+    pub fn to_line(&self) -> String {
+        self.to_string()
     }
 
     /// Calculate the expected part size (end - begin + 1)
     ///
-    /// Note: begin and end are 1-based inclusive positions
-    pub fn size(&self) -> usize {
-        self.end - self.begin + 1
+    /// Note: begin and end are 1-based inclusive positions; an empty part
+    /// is described by `end = begin - 1`. A range that ends before that,
+    /// which `parse` rejects, has size 0.
+    pub fn size(&self) -> u64 {
+        self.end
+            .checked_sub(self.begin)
+            .map_or(0, |span| span.saturating_add(1))
     }
 }
 
 /// yEnc trailer
 #[derive(Debug, Clone, PartialEq)]
 pub struct YencTrailer {
-    pub size: usize,
+    pub size: u64,
     pub part: Option<usize>,
     pub pcrc32: Option<u32>,
     pub crc32: Option<u32>,
+    /// Keywords that `parse` does not know, with their values
+    ///
+    /// Written back by `to_line`.
+    pub extra: BTreeMap<String, String>,
 }
 
 impl YencTrailer {
     /// Parse a yEnc trailer line (e.g., "=yend size=123456 crc32=abcd1234")
     pub fn parse(line: &str) -> Result<Self> {
-        if !line.starts_with("=yend ") {
+        Self::parse_bytes(line.as_bytes())
+    }
+
+    /// Parse a yEnc trailer line as bytes, without checking it for UTF-8
+    pub fn parse_bytes(line: &[u8]) -> Result<Self> {
+        if !line.starts_with(b"=yend ") {
             return Err(YencError::InvalidHeader(
                 "Trailer must start with '=yend'".to_string(),
             ));
@@ -115,16 +594,15 @@ impl YencTrailer {
         let mut part = None;
         let mut pcrc32 = None;
         let mut crc32 = None;
+        let mut extra = BTreeMap::new();
 
-        for token in line[6..].split_whitespace() {
-            if let Some((key, value)) = token.split_once('=') {
-                match key {
-                    "size" => size = value.parse().ok(),
-                    "part" => part = value.parse().ok(),
-                    "pcrc32" => pcrc32 = u32::from_str_radix(value, 16).ok(),
-                    "crc32" => crc32 = u32::from_str_radix(value, 16).ok(),
-                    _ => {}
-                }
+        for (key, value) in fields(&line[6..]) {
+            match key {
+                b"size" => size = parse_number("size", value)?,
+                b"part" => part = parse_number("part", value)?,
+                b"pcrc32" => pcrc32 = parse_crc("pcrc32", value)?,
+                b"crc32" => crc32 = parse_crc("crc32", value)?,
+                _ => insert_extra(&mut extra, key, value),
             }
         }
 
@@ -133,8 +611,50 @@ impl YencTrailer {
             part,
             pcrc32,
             crc32,
+            extra,
         })
     }
+
+    /// The trailer as a `=yend` line, without the line ending
+    ///
+    /// Fields in `extra` come after the known ones. Same as `to_string`.
+    pub fn to_line(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for YencHeader {
+    /// The `=ybegin` line of `to_line`, with the name as in `name_lossy`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let quote = self.name_quote();
+        self.write_keywords(f)?;
+        write!(f, "{}{}{}", quote, self.name_lossy(), quote)
+    }
+}
+
+impl fmt::Display for YencPart {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "=ypart begin={} end={}", self.begin, self.end)
+    }
+}
+
+impl fmt::Display for YencTrailer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "=yend size={}", self.size)?;
+        if let Some(part) = self.part {
+            write!(f, " part={}", part)?;
+        }
+        if let Some(pcrc32) = self.pcrc32 {
+            write!(f, " pcrc32={:08x}", pcrc32)?;
+        }
+        if let Some(crc32) = self.crc32 {
+            write!(f, " crc32={:08x}", crc32)?;
+        }
+        for (key, value) in &self.extra {
+            write!(f, " {}={}", key, value)?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -145,7 +665,7 @@ mod tests {
     fn test_parse_header() {
         let line = "=ybegin line=128 size=123456 name=testfile.bin";
         let header = YencHeader::parse(line).unwrap();
-        assert_eq!(header.name, "testfile.bin");
+        assert_eq!(header.name, b"testfile.bin");
         assert_eq!(header.size, 123456);
         assert_eq!(header.line_len, Some(128));
     }
@@ -158,6 +678,130 @@ mod tests {
         assert_eq!(trailer.crc32, Some(0xabcd1234));
     }
 
+    #[test]
+    fn test_parse_header_name() {
+        let cases = [
+            (
+                "=ybegin line=128 size=10 name=My File 01.rar",
+                "My File 01.rar",
+            ),
+            (
+                "=ybegin line=128 size=10 name=a=b size=3.bin",
+                "a=b size=3.bin",
+            ),
+            (
+                "=ybegin line=128 size=10 name=  spaced out \t\r",
+                "  spaced out",
+            ),
+            ("=ybegin size=10 name=a.bin line=128", "a.bin line=128"),
+            ("=ybegin size=10 filename=x name=real name", "real name"),
+        ];
+        for (line, name) in cases {
+            let header = YencHeader::parse(line).unwrap();
+            assert_eq!(header.name, name.as_bytes(), "{}", line);
+        }
+
+        // Quotes around the whole name are dropped, others are kept
+        let quoted = [
+            (
+                "=ybegin size=10 name=\"file with spaces.bin\"",
+                "file with spaces.bin",
+            ),
+            ("=ybegin size=10 name=\"a.bin\"  ", "a.bin"),
+            ("=ybegin size=10 name=\"\"", ""),
+            ("=ybegin size=10 name=\"", "\""),
+            ("=ybegin size=10 name=a \"b\".bin", "a \"b\".bin"),
+        ];
+        for (line, name) in quoted {
+            assert_eq!(
+                YencHeader::parse(line).unwrap().name,
+                name.as_bytes(),
+                "{}",
+                line
+            );
+        }
+
+        // The keywords before the name are still read
+        let header = YencHeader::parse("=ybegin part=2 size=10 name=a b").unwrap();
+        assert_eq!(header.part, Some(2));
+        assert_eq!(header.size, 10);
+
+        let result = YencHeader::parse("=ybegin line=128 size=10 filename=a.bin");
+        assert!(matches!(result, Err(YencError::MissingField(_))));
+        let result = YencHeader::parse("=ybegin line=128 name=a size=10");
+        assert!(matches!(result, Err(YencError::MissingField(_))));
+    }
+
+    #[test]
+    fn test_parse_tolerant_keywords() {
+        let header =
+            YencHeader::parse("=ybegin line =128\tsize= 10  part = 2 name=a b.bin").unwrap();
+        assert_eq!(header.line_len, Some(128));
+        assert_eq!(header.size, 10);
+        assert_eq!(header.part, Some(2));
+        assert_eq!(header.name, b"a b.bin");
+        assert!(header.extra.is_empty());
+        let trailer = YencTrailer::parse("=yend size=4 size=5 crc32 =515ad3cc").unwrap();
+        assert_eq!(trailer.size, 5);
+        assert_eq!(trailer.crc32, Some(0x515ad3cc));
+
+        let message = |result: Result<YencTrailer>| match result {
+            Err(YencError::InvalidHeader(msg)) => msg,
+            other => panic!("expected InvalidHeader, got {:?}", other),
+        };
+        assert!(message(YencTrailer::parse("=yend size=12a")).contains("'12a' for size="));
+        assert!(message(YencTrailer::parse("=yend size=5 crc32=xyz")).contains("crc32="));
+
+        assert_eq!(
+            formatting_issue(b"=ybegin line=128 size=10 name=a  b.bin"),
+            None
+        );
+        assert_eq!(formatting_issue(b"=yend size=5 crc32=515ad3cc "), None);
+        let issue = |line: &[u8]| formatting_issue(line).unwrap();
+        assert!(issue(b"=ybegin line=128\tsize=10 name=a").contains("single space"));
+        assert!(issue(b"=ypart begin=1  end=5").contains("single space"));
+        assert!(issue(b"=yend size =5").contains("around '=' of size"));
+        assert!(issue(b"=yend size= 5").contains("around '=' of size"));
+        assert!(issue(b"=yend size=5 part=1 size=5").contains("size given twice"));
+    }
+
+    #[test]
+    fn test_parse_header_bytes() {
+        let header = YencHeader::parse_bytes(b"=ybegin size=10 name=\xc0ber \"x\".bin \r").unwrap();
+        assert_eq!(header.name, b"\xc0ber \"x\".bin");
+        assert_eq!(header.name_lossy(), "\u{fffd}ber \"x\".bin");
+        assert_eq!(header.size, 10);
+
+        // A value that is not a number is reported as such
+        let result = YencHeader::parse_bytes(b"=ybegin size=1\xff0 name=a.bin");
+        assert!(matches!(result, Err(YencError::InvalidHeader(_))));
+
+        let part = YencPart::parse_bytes(b"=ypart begin=1 end=5 \xfe=\xff").unwrap();
+        assert_eq!(part.size(), 5);
+        let trailer = YencTrailer::parse_bytes(b"=yend size=5 crc32=515ad3cc x\xff=1").unwrap();
+        assert_eq!(trailer.crc32, Some(0x515ad3cc));
+        assert_eq!(trailer.extra["x\u{fffd}"], "1");
+    }
+
+    #[test]
+    fn test_version() {
+        let cases = [
+            ("=ybegin line=128 size=10 name=a.bin", YencVersion::V1_0),
+            (
+                "=ybegin part=1 line=128 size=10 name=a.bin",
+                YencVersion::V1_1,
+            ),
+            (
+                "=ybegin part=1 total=3 line=128 size=10 name=a.bin",
+                YencVersion::V1_2,
+            ),
+        ];
+        for (line, version) in cases {
+            assert_eq!(YencHeader::parse(line).unwrap().version(), version);
+        }
+        assert!(YencVersion::V1_2 > YencVersion::V1_1);
+    }
+
     #[test]
     fn test_parse_part() {
         let line = "=ypart begin=1 end=100000";
@@ -174,11 +818,37 @@ mod tests {
         assert_eq!(part.size(), 100000);
     }
 
+    #[test]
+    fn test_parse_part_empty_range() {
+        let part = YencPart::parse("=ypart begin=101 end=100").unwrap();
+        assert_eq!(part.size(), 0);
+    }
+
+    #[test]
+    fn test_parse_part_inverted_range() {
+        for line in ["=ypart begin=10 end=5", "=ypart begin=2 end=0"] {
+            let result = YencPart::parse(line);
+            assert!(
+                matches!(result, Err(YencError::InvalidHeader(_))),
+                "{}",
+                line
+            );
+        }
+
+        let part = YencPart { begin: 10, end: 5 };
+        assert_eq!(part.size(), 0);
+        let part = YencPart {
+            begin: 2,
+            end: u64::MAX,
+        };
+        assert_eq!(part.size(), u64::MAX - 1);
+    }
+
     #[test]
     fn test_parse_multipart_header() {
         let line = "=ybegin part=1 total=10 line=128 size=500000 name=mybinary.dat";
         let header = YencHeader::parse(line).unwrap();
-        assert_eq!(header.name, "mybinary.dat");
+        assert_eq!(header.name, b"mybinary.dat");
         assert_eq!(header.size, 500000);
         assert_eq!(header.part, Some(1));
         assert_eq!(header.total, Some(10));
@@ -192,4 +862,115 @@ mod tests {
         assert_eq!(trailer.part, Some(1));
         assert_eq!(trailer.pcrc32, Some(0xabcdef12));
     }
+
+    #[test]
+    fn test_extra_fields() {
+        let line = "=ybegin part=2 total=3 line=128 size=10 zone=x01 crc=beef name=a b.bin";
+        let header = YencHeader::parse(line).unwrap();
+        assert_eq!(header.extra.len(), 2);
+        assert_eq!(header.extra["zone"], "x01");
+        assert_eq!(
+            header.to_line(),
+            b"=ybegin part=2 total=3 line=128 size=10 crc=beef zone=x01 name=a b.bin"
+        );
+        assert_eq!(YencHeader::parse_bytes(&header.to_line()).unwrap(), header);
+
+        let line = "=yend size=5 part=2 pcrc32=0515ad3c crc32=abcdef12 sha1=e5fa44f2";
+        let trailer = YencTrailer::parse(line).unwrap();
+        assert_eq!(trailer.extra["sha1"], "e5fa44f2");
+        assert_eq!(trailer.to_line(), line);
+
+        let plain = "=ybegin line=128 size=10 name=a.bin";
+        assert!(YencHeader::parse(plain).unwrap().extra.is_empty());
+        assert_eq!(
+            YencHeader::parse(plain).unwrap().to_line(),
+            plain.as_bytes()
+        );
+    }
+
+    #[test]
+    fn test_to_line() {
+        let lines = [
+            "=ybegin line=128 size=10 name=My File 01.rar",
+            "=ybegin part=3 total=9 size=0 name=a=b",
+            "=ybegin line=128 size=10 name=\"spaced \"",
+            "=ybegin line=128 size=10 name=\"\"quoted\"\"",
+        ];
+        for line in lines {
+            let header = YencHeader::parse(line).unwrap();
+            assert_eq!(header.to_line(), line.as_bytes());
+            assert_eq!(header.to_string(), line);
+            assert_eq!(YencHeader::parse_bytes(&header.to_line()).unwrap(), header);
+        }
+
+        let header = YencHeader::parse_bytes(b"=ybegin size=3 name=caf\xe9.txt").unwrap();
+        assert_eq!(header.to_line(), b"=ybegin size=3 name=caf\xe9.txt");
+        assert_eq!(header.to_string(), "=ybegin size=3 name=caf\u{fffd}.txt");
+
+        let part = YencPart::parse("=ypart begin=1 end=384000").unwrap();
+        assert_eq!(part.to_line(), "=ypart begin=1 end=384000");
+
+        // CRC32s are written with eight digits
+        let trailer = YencTrailer::parse("=yend size=3 part=1 pcrc32=abc crc32=0").unwrap();
+        assert_eq!(
+            trailer.to_string(),
+            "=yend size=3 part=1 pcrc32=00000abc crc32=00000000"
+        );
+    }
+
+    #[test]
+    fn test_header_builder() {
+        let header = YencHeader::builder()
+            .name("My File.bin")
+            .size(10)
+            .build()
+            .unwrap();
+        assert_eq!(
+            header,
+            YencHeader::parse("=ybegin size=10 name=My File.bin").unwrap()
+        );
+
+        let builder = YencHeader::builder().name("a.bin").size(10);
+        let cases = [
+            YencHeader::builder().size(10).build(),
+            YencHeader::builder().name("a.bin").build(),
+            builder.clone().name("").build(),
+            builder.clone().name("a\nb").build(),
+            builder.clone().line(0).build(),
+            builder.clone().line(998).build(),
+            builder.clone().part(0).build(),
+            builder.clone().part(3).total(2).build(),
+            builder.clone().total(2).build(),
+        ];
+        assert!(matches!(cases[0], Err(YencError::MissingField(_))));
+        assert!(matches!(cases[1], Err(YencError::MissingField(_))));
+        assert!(matches!(cases[2], Err(YencError::InvalidName(_))));
+        assert!(matches!(cases[3], Err(YencError::InvalidName(_))));
+        for result in &cases[4..] {
+            assert!(
+                matches!(result, Err(YencError::InvalidHeader(_))),
+                "{:?}",
+                result
+            );
+        }
+
+        // yEnc 1.1 parts carry no total
+        let header = builder.part(2).build().unwrap();
+        assert_eq!(header.to_line(), b"=ybegin part=2 size=10 name=a.bin");
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        assert!(unknown_keys(b"=ybegin part=1 total=2 line=128 size=10 name=a.bin").is_empty());
+        assert_eq!(
+            unknown_keys(b"=ybegin line=128 foo=1 size=10 bar=2 name=a x=y.bin"),
+            [b"foo", b"bar"]
+        );
+        assert_eq!(unknown_keys(b"=ypart begin=1 end=5 total=3"), [b"total"]);
+        assert_eq!(
+            unknown_keys(b"=yend size=5 part=1 pcrc32=515ad3cc crc=1"),
+            [b"crc"]
+        );
+        assert!(unknown_keys(b"some text=1").is_empty());
+    }
 }
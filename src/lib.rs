@@ -14,9 +14,18 @@
 //!
 //! // Decoding
 //! let mut decoded = Vec::new();
-//! let (header, _, _, _) = yenc::decode(&encoded[..], &mut decoded).unwrap();
+//! let outcome = yenc::decode(&encoded[..], &mut decoded).unwrap();
 //! assert_eq!(decoded, b"Hello, World!");
-//! assert_eq!(header.name, "hello.txt");
+//! assert_eq!(outcome.header.name, b"hello.txt");
+//! ```
+//!
+//! Data already in memory can skip the reader and writer plumbing:
+//!
+//! ```rust
+//! let encoded = yenc::encode_to_vec(b"Hello, World!", "hello.txt").unwrap();
+//! let (outcome, decoded) = yenc::decode_to_vec(&encoded).unwrap();
+//! assert_eq!(decoded, b"Hello, World!");
+//! assert_eq!(outcome.header.name, b"hello.txt");
 //! ```
 //!
 //! ## Advanced Usage
@@ -28,7 +37,7 @@
 //! let mut output = Vec::new();
 //! let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
 //!
-//! let (_header, _part, _trailer, _size) = Decoder::new()
+//! let _outcome = Decoder::new()
 //!     .strict()
 //!     .no_crc_check()
 //!     .decode(&input[..], &mut output)
@@ -43,69 +52,373 @@
 //!     .encode(&data[..], &mut encoded, "file.bin")
 //!     .unwrap();
 //! ```
+//!
+//! ## Threading
+//!
+//! `Encoder` and `Decoder` only hold configuration and are `Send + Sync`:
+//! configure one once and share it across threads. Every encode or decode
+//! call keeps its buffers and CRC state to itself.
+//!
+//! Those buffers are allocated per call. Long-running services can keep a
+//! [`Scratch`] per thread and use the `*_with_scratch` methods, which reuse
+//! its buffers from one article to the next.
+//!
+//! A single large article can also be spread over threads:
+//! `Decoder::decode_pipelined` reads and writes on threads of their own
+//! while the calling thread decodes.
+
+#![cfg_attr(feature = "portable-simd", feature(portable_simd))]
 
+mod assemble;
+mod budget;
+mod capabilities;
 mod consts;
+#[cfg(any(test, feature = "test-support"))]
+pub mod corrupt;
+pub mod crc32;
 mod decode;
+mod diff;
+mod dispatch;
 mod encode;
 pub mod error;
+mod events;
 pub mod header;
+mod lint;
+mod multipart;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod partset;
+mod pipeline;
+mod profile;
+mod reader;
+mod scan;
+mod scratch;
+mod sink;
+mod stats;
+#[cfg(all(feature = "io-uring", target_os = "linux"))]
+mod uring;
+mod verify;
+mod writer;
 
-pub use decode::{Decoder, decode};
-pub use encode::{Encoder, MultiPartInfo, encode, encode_part};
+pub use assemble::{
+    Assembler, CoverageReport, DecodeSummary, FileAssembler, extract_range, validate_coverage,
+    validate_parts,
+};
+pub use capabilities::{Capabilities, capabilities};
+pub use decode::{
+    DamageMap, DecodeOutcome, DecodeWarning, Decoder, YencMetadata, decode, decode_to_vec,
+};
+pub use diff::{DiffReport, diff};
+pub use dispatch::Backend;
+pub use encode::{
+    EncodeReport, Encoder, EscapePolicy, LineEnding, MultiPartInfo, OverheadEstimate, encode,
+    encode_part, encode_to_vec, sample_overhead,
+};
 pub use error::{Result, YencError};
-pub use header::{YencHeader, YencPart, YencTrailer};
+pub use events::{YencEvent, YencEvents};
+pub use header::{YencHeader, YencHeaderBuilder, YencPart, YencTrailer, YencVersion};
+pub use lint::{Violation, ViolationKind, lint};
+pub use multipart::{MultiPartEncoder, PartSplitter, plan_parts};
+pub use partset::PartSet;
+pub use profile::Profile;
+pub use reader::YencReader;
+pub use scan::{BlockLocation, scan};
+pub use scratch::Scratch;
+pub use sink::DecodeSink;
+pub use stats::{YencStats, analyze};
+pub use verify::VerifyReport;
+pub use writer::{EncodeSession, YencWriter};
+
+// The configuration types must stay shareable across threads
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Encoder>();
+    assert_send_sync::<Decoder>();
+};
 
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
-use std::path::Path;
+#[cfg(not(feature = "mmap"))]
+use std::io::BufWriter;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+/// Map a whole file into memory for reading
+///
+/// # Safety
+/// The mapping reflects later changes to the file, so the file must not be
+/// modified or truncated by anyone while the map is alive.
+#[cfg(feature = "mmap")]
+unsafe fn map_file(file: &File) -> std::io::Result<memmap2::Mmap> {
+    // SAFETY: upheld by the caller
+    let map = unsafe { memmap2::Mmap::map(file)? };
+    // Input is read front to back exactly once
+    #[cfg(unix)]
+    let _ = map.advise(memmap2::Advice::Sequential);
+    Ok(map)
+}
 
 /// Decode a yEnc file
 ///
 /// Opens files and decodes yEnc data.
 ///
+/// With the `io-uring` feature on Linux, reading, decoding and writing
+/// overlap, with several blocks of each file in flight. With the `mmap`
+/// feature the input file is memory-mapped instead of read through a
+/// buffer, and must then not be modified or truncated while the call runs.
+/// Where `io_uring` is unavailable, `mmap` or buffered I/O is used instead.
+///
 /// # Arguments
 /// * `input_path` - Path to the yEnc-encoded file
 /// * `output_path` - Path where decoded data will be written
 ///
 /// # Returns
-/// A tuple of (header, part, trailer, bytes_written)
-/// - For single-part files: part will be None
-/// - For multi-part files: part contains begin/end byte positions
-pub fn decode_file<P: AsRef<Path>>(
-    input_path: P,
-    output_path: P,
-) -> Result<(YencHeader, Option<YencPart>, Option<YencTrailer>, usize)> {
-    let input = BufReader::new(File::open(input_path)?);
-    let output = BufWriter::new(File::create(output_path)?);
-    decode(input, output)
+/// The same outcome as `decode`
+pub fn decode_file<P: AsRef<Path>>(input_path: P, output_path: P) -> Result<DecodeOutcome> {
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    if uring::available() {
+        let input = uring::UringReader::new(File::open(&input_path)?)?;
+        let mut output = uring::UringWriter::new(File::create(&output_path)?)?;
+        let result = decode(input, &mut output)?;
+        output.finish()?;
+        return Ok(result);
+    }
+
+    #[cfg(feature = "mmap")]
+    {
+        let input = File::open(input_path)?;
+        // SAFETY: the map is only read, and dropped before returning; the
+        // docs above rule out changes to the file in the meantime
+        let map = unsafe { map_file(&input)? };
+        // Output already goes out in large blocks, so it needs no BufWriter
+        let output = File::create(output_path)?;
+        Decoder::new().decode_slice(&map, output)
+    }
+    #[cfg(not(feature = "mmap"))]
+    {
+        let input = BufReader::new(File::open(input_path)?);
+        let output = BufWriter::new(File::create(output_path)?);
+        decode(input, output)
+    }
 }
 
 /// Encode a file to yEnc format
 ///
 /// Opens files and encodes data to yEnc.
 ///
+/// The I/O strategy follows the enabled features as for `decode_file`.
+///
 /// # Arguments
 /// * `input_path` - Path to the file to encode
 /// * `output_path` - Path where yEnc-encoded data will be written
 /// * `filename` - Filename to use in the yEnc header (defaults to input filename)
 ///
 /// # Returns
-/// Number of bytes encoded
+/// The same report as `encode`
 pub fn encode_file<P: AsRef<Path>>(
     input_path: P,
     output_path: P,
     filename: Option<&str>,
-) -> Result<usize> {
-    let input = BufReader::new(File::open(&input_path)?);
-    let output = BufWriter::new(File::create(output_path)?);
+) -> Result<EncodeReport> {
+    let name = filename.unwrap_or_else(|| header_name(input_path.as_ref()));
+
+    #[cfg(all(feature = "io-uring", target_os = "linux"))]
+    if uring::available() {
+        let input = uring::UringReader::new(File::open(&input_path)?)?;
+        let size = input.size();
+        let mut output = uring::UringWriter::new(File::create(&output_path)?)?;
+        let encoded = Encoder::new().encode_sized(input, &mut output, name, size)?;
+        output.finish()?;
+        return Ok(encoded);
+    }
+
+    #[cfg(feature = "mmap")]
+    {
+        let input = File::open(&input_path)?;
+        // SAFETY: as in `decode_file`
+        let map = unsafe { map_file(&input)? };
+        let output = File::create(output_path)?;
+        Encoder::new().encode_slice(&map, output, name)
+    }
+    #[cfg(not(feature = "mmap"))]
+    {
+        let input = BufReader::new(File::open(&input_path)?);
+        let output = BufWriter::new(File::create(output_path)?);
+        encode(input, output, name)
+    }
+}
+
+/// Name for the yEnc header of the file at `path`
+fn header_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("file.bin")
+}
+
+/// Encode a file as a multi-part post, one part file per part
+///
+/// Splits the file into parts of `part_size` bytes (the last one may be
+/// shorter) and encodes each with its `=ypart` range into
+/// `output_dir/name.part001.yenc`, `name.part002.yenc` and so on, with at
+/// least three digits. The last part carries the CRC32 of the whole file.
+/// See `MultiPartEncoder` to encode the parts anywhere else.
+///
+/// # Returns
+/// The path and report of every part file, in order
+///
+/// # Errors
+/// Returns `YencError::InvalidConfig` if `part_size` is zero, and
+/// `YencError::InvalidData` if the file changes size while it is read.
+///
+/// # Example
+/// ```no_run
+/// let parts = yenc::encode_file_multipart("big.iso", "outbox", 700_000)?;
+/// println!("{} parts, last in {}", parts.len(), parts[parts.len() - 1].0.display());
+/// # Ok::<(), yenc::YencError>(())
+/// ```
+pub fn encode_file_multipart<P: AsRef<Path>, Q: AsRef<Path>>(
+    input_path: P,
+    output_dir: Q,
+    part_size: u64,
+) -> Result<Vec<(PathBuf, EncodeReport)>> {
+    let name = header_name(input_path.as_ref());
+    let input = File::open(&input_path)?;
+    let size = input.metadata()?.len();
+    let mut input = BufReader::new(input);
 
-    let name = filename.unwrap_or_else(|| {
-        input_path
+    let mut parts = MultiPartEncoder::new(name, size, part_size)?;
+    let digits = parts.total_parts().to_string().len().max(3);
+    let mut written = Vec::with_capacity(parts.total_parts());
+    while let Some(part_info) = parts.next_part() {
+        let path = output_dir
             .as_ref()
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("file.bin")
-    });
+            .join(format!("{}.part{:0digits$}.yenc", name, part_info.part));
+        // Output already goes out in large blocks, so it needs no BufWriter
+        let output = File::create(&path)?;
+        let part = (&mut input).take(part_info.expected_size());
+        let report = parts.encode_next(part, output)?;
+        written.push((path, report));
+    }
+    Ok(written)
+}
+
+/// Decode the articles of a multi-part post into one output file
+///
+/// Articles may come in any order. Each is decoded straight into its place
+/// in the output file, with the same checks as `decode`, including the
+/// `pcrc32=` of every part. The file size is taken from the first article,
+/// and the output file is given its full size straight away (see
+/// `FileAssembler::create_unsized`); the others must agree on it and on
+/// `total=`, and together they must exactly cover the file (see
+/// `validate_coverage`). A `crc32=` of the whole file is checked against
+/// the parts combined.
+///
+/// # Errors
+/// The error of the first failing article; `YencError::InvalidData` if no
+/// articles are given, or they leave gaps or overlap; or
+/// `YencError::CrcMismatch` if the file does not match its `crc32=`.
+///
+/// # Example
+/// ```no_run
+/// use std::net::TcpStream;
+///
+/// let articles: Vec<TcpStream> = Vec::new(); // fetched segments
+/// let summary = yenc::decode_parts(articles, "big.iso")?;
+/// println!("decoded {} ({} bytes)", summary.header.name_lossy(), summary.bytes_written);
+/// # Ok::<(), yenc::YencError>(())
+/// ```
+pub fn decode_parts<R, I, P>(readers: I, output_path: P) -> Result<DecodeSummary>
+where
+    R: Read,
+    I: IntoIterator<Item = R>,
+    P: AsRef<Path>,
+{
+    assemble_parts(readers.into_iter().map(Ok), output_path)
+}
+
+/// Decode the part files of a multi-part post into one output file
+///
+/// Like `decode_parts`, opening each part file only when its turn comes.
+pub fn decode_part_paths<P, I, Q>(part_paths: I, output_path: Q) -> Result<DecodeSummary>
+where
+    P: AsRef<Path>,
+    I: IntoIterator<Item = P>,
+    Q: AsRef<Path>,
+{
+    let readers = part_paths
+        .into_iter()
+        .map(|path| Ok(BufReader::new(File::open(path)?)));
+    assemble_parts(readers, output_path)
+}
+
+fn assemble_parts<R, I, P>(readers: I, output_path: P) -> Result<DecodeSummary>
+where
+    R: Read,
+    I: Iterator<Item = Result<R>>,
+    P: AsRef<Path>,
+{
+    let mut assembler = FileAssembler::create_unsized(output_path)?;
+    let mut parts = Vec::new();
+    for reader in readers {
+        parts.push(assembler.add_part(reader?)?);
+    }
+    let Some(first) = parts.first() else {
+        return Err(YencError::InvalidData("No parts given".to_string()));
+    };
+    assembler.validate()?;
+
+    Ok(DecodeSummary {
+        header: first.header.clone(),
+        bytes_written: parts.iter().map(|outcome| outcome.bytes_written).sum(),
+        parts,
+    })
+}
+
+/// Decode the part files of a multi-part post into one output file in parallel
+///
+/// Parts are decoded on the rayon thread pool, each straight into its place
+/// in the output file, with no part file open before its turn. Once all are
+/// done, the parts must make up the whole file (see `validate_parts`).
+///
+/// # Errors
+/// The error of the first failing part file, in the order given;
+/// `YencError::InvalidData` if no files are given, the parts describe
+/// different files, or they leave gaps or overlap; or
+/// `YencError::CrcMismatch` if the file does not match its `crc32=`.
+///
+/// # Example
+/// ```no_run
+/// let parts = ["big.iso.001.yenc", "big.iso.002.yenc", "big.iso.003.yenc"];
+/// let summary = yenc::decode_part_files(&parts, "big.iso")?;
+/// println!("decoded {} ({} bytes)", summary.header.name_lossy(), summary.bytes_written);
+/// # Ok::<(), yenc::YencError>(())
+/// ```
+#[cfg(feature = "rayon")]
+pub fn decode_part_files<P: AsRef<Path>, Q: AsRef<Path>>(
+    part_paths: &[P],
+    output_path: Q,
+) -> Result<DecodeSummary> {
+    use rayon::prelude::*;
+
+    let output = File::create(output_path)?;
+
+    // Each file is opened only once its part is being decoded, as the
+    // decoder buffers its input itself
+    let decoder = Decoder::new();
+    let paths: Vec<&Path> = part_paths.iter().map(AsRef::as_ref).collect();
+    let decoded = paths
+        .par_iter()
+        .map(|path| decoder.decode_into(File::open(path)?, &mut &output))
+        .collect::<Vec<_>>()
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+    let Some(first) = decoded.first() else {
+        return Err(YencError::InvalidData("No part files given".to_string()));
+    };
+    validate_parts(&decoded)?;
 
-    encode(input, output, name)
+    Ok(DecodeSummary {
+        header: first.header.clone(),
+        bytes_written: decoded.iter().map(|outcome| outcome.bytes_written).sum(),
+        parts: decoded,
+    })
 }
@@ -0,0 +1,508 @@
+//! Spec-compliance checks for yEnc articles
+//!
+//! Unlike the decoder, which tries hard to recover data, the linter reports
+//! every deviation from the yEnc 1.3 rules it can find so that the output of
+//! other encoders can be verified.
+
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+
+use crate::consts::ESCAPE_CHAR;
+use crate::decode::trim_bytes;
+use crate::error::Result;
+use crate::header::{YencHeader, YencPart, YencTrailer, formatting_issue, unknown_keys};
+
+/// Category of a spec violation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// No `=ybegin` line was found
+    MissingHeader,
+    /// A keyword line could not be parsed
+    InvalidKeywordLine,
+    /// A keyword required by the spec is absent
+    MissingKeyword,
+    /// The header has `part=` but no `=ypart` line follows
+    MissingPartLine,
+    /// A data line is longer than the declared `line=` value allows
+    LineTooLong,
+    /// NUL or CR appears in the data without being escaped
+    UnescapedCritical,
+    /// A data line ends with a lone escape character
+    DanglingEscape,
+    /// No `=yend` line was found
+    MissingTrailer,
+    /// Declared and actual sizes disagree
+    SizeMismatch,
+    /// Part numbers or totals disagree between keyword lines
+    PartMismatch,
+    /// Lines are not terminated with CRLF
+    LineEnding,
+    /// A keyword line contains a key the spec does not define
+    UnknownKeyword,
+    /// A keyword line has stray whitespace or a key given twice
+    KeywordFormatting,
+}
+
+/// A single spec violation with its location
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    /// 1-based line number (0 if the violation concerns the article as a whole)
+    pub line: usize,
+    /// 1-based column, when the violation points at a specific byte
+    pub column: Option<usize>,
+    /// Category of the violation
+    pub kind: ViolationKind,
+    /// Human-readable description
+    pub message: String,
+}
+
+impl Violation {
+    fn new(line: usize, column: Option<usize>, kind: ViolationKind, message: String) -> Self {
+        Self {
+            line,
+            column,
+            kind,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "line {}, column {}: {}", self.line, column, self.message),
+            None => write!(f, "line {}: {}", self.line, self.message),
+        }
+    }
+}
+
+/// Split a raw line into its content and whether it was terminated by CRLF
+fn split_line_ending(raw: &[u8]) -> (&[u8], bool) {
+    if let Some(content) = raw.strip_suffix(b"\r\n") {
+        (content, true)
+    } else if let Some(content) = raw.strip_suffix(b"\n") {
+        (content, false)
+    } else {
+        (raw, false)
+    }
+}
+
+/// Check a yEnc article against the yEnc 1.3 rules
+///
+/// Only the first `=ybegin` ... `=yend` block is checked. Text before the
+/// header is ignored, as required by the spec. I/O errors are returned as
+/// errors; everything else is reported as a [`Violation`].
+///
+/// # Example
+/// ```
+/// let input = b"=ybegin line=128 size=5 name=test.bin\r\n*+,-=n\r\n=yend size=5\r\n";
+/// assert!(yenc::lint(&input[..]).unwrap().is_empty());
+/// ```
+pub fn lint<R: Read>(reader: R) -> Result<Vec<Violation>> {
+    let mut reader = BufReader::new(reader);
+    let mut violations = Vec::new();
+    let mut raw = Vec::new();
+    let mut line_no = 0;
+
+    // First line not terminated by CRLF, and how many such lines were seen
+    let mut first_bad_ending = None;
+    let mut bad_endings = 0;
+    let mut check_ending = |line_no: usize, crlf: bool| {
+        if !crlf {
+            first_bad_ending.get_or_insert(line_no);
+            bad_endings += 1;
+        }
+    };
+
+    let header = loop {
+        raw.clear();
+        if reader.read_until(b'\n', &mut raw)? == 0 {
+            violations.push(Violation::new(
+                0,
+                None,
+                ViolationKind::MissingHeader,
+                "No =ybegin line found".to_string(),
+            ));
+            return Ok(violations);
+        }
+        line_no += 1;
+
+        let trimmed = trim_bytes(&raw);
+        if trimmed.starts_with(b"=ybegin ") {
+            check_ending(line_no, split_line_ending(&raw).1);
+            match YencHeader::parse_bytes(trimmed) {
+                Ok(header) => {
+                    lint_keywords(&mut violations, line_no, trimmed);
+                    break header;
+                }
+                Err(err) => {
+                    violations.push(Violation::new(
+                        line_no,
+                        None,
+                        ViolationKind::InvalidKeywordLine,
+                        err.to_string(),
+                    ));
+                    return Ok(violations);
+                }
+            }
+        }
+    };
+
+    if header.line_len.unwrap_or(0) == 0 {
+        violations.push(Violation::new(
+            line_no,
+            None,
+            ViolationKind::MissingKeyword,
+            "Header has no usable line= keyword".to_string(),
+        ));
+    }
+
+    let mut part: Option<YencPart> = None;
+    let mut trailer: Option<(usize, YencTrailer)> = None;
+    let mut data_lines = 0;
+    let mut decoded_bytes = 0u64;
+
+    loop {
+        raw.clear();
+        if reader.read_until(b'\n', &mut raw)? == 0 {
+            break;
+        }
+        line_no += 1;
+
+        let (content, crlf) = split_line_ending(&raw);
+        check_ending(line_no, crlf);
+
+        if data_lines == 0 && part.is_none() && content.starts_with(b"=ypart ") {
+            match YencPart::parse_bytes(content) {
+                Ok(parsed) => {
+                    lint_keywords(&mut violations, line_no, content);
+                    part = Some(parsed);
+                }
+                Err(err) => violations.push(Violation::new(
+                    line_no,
+                    None,
+                    ViolationKind::InvalidKeywordLine,
+                    err.to_string(),
+                )),
+            }
+            continue;
+        }
+
+        if content.starts_with(b"=yend ") {
+            match YencTrailer::parse_bytes(content) {
+                Ok(parsed) => {
+                    lint_keywords(&mut violations, line_no, content);
+                    trailer = Some((line_no, parsed));
+                }
+                Err(err) => violations.push(Violation::new(
+                    line_no,
+                    None,
+                    ViolationKind::InvalidKeywordLine,
+                    err.to_string(),
+                )),
+            }
+            break;
+        }
+
+        if data_lines == 0 && header.part.is_some() && part.is_none() {
+            violations.push(Violation::new(
+                line_no,
+                None,
+                ViolationKind::MissingPartLine,
+                "Header has part= but no =ypart line follows".to_string(),
+            ));
+        }
+        data_lines += 1;
+
+        if let Some(line_len) = header.line_len.filter(|&n| n > 0) {
+            if content.len() > line_len + 1 {
+                violations.push(Violation::new(
+                    line_no,
+                    Some(line_len + 2),
+                    ViolationKind::LineTooLong,
+                    format!(
+                        "Line is {} characters long, limit is {} (+1 for an escape pair)",
+                        content.len(),
+                        line_len
+                    ),
+                ));
+            } else if content.len() == line_len + 1 && content[line_len - 1] != ESCAPE_CHAR {
+                violations.push(Violation::new(
+                    line_no,
+                    Some(line_len + 1),
+                    ViolationKind::LineTooLong,
+                    format!(
+                        "Line exceeds {} characters without ending in an escape pair",
+                        line_len
+                    ),
+                ));
+            }
+        }
+
+        let mut escaped = false;
+        for (i, &byte) in content.iter().enumerate() {
+            if escaped {
+                escaped = false;
+                decoded_bytes += 1;
+                continue;
+            }
+            match byte {
+                ESCAPE_CHAR => escaped = true,
+                0x00 | b'\r' => {
+                    violations.push(Violation::new(
+                        line_no,
+                        Some(i + 1),
+                        ViolationKind::UnescapedCritical,
+                        format!("Unescaped critical character {:#04x}", byte),
+                    ));
+                    decoded_bytes += 1;
+                }
+                _ => decoded_bytes += 1,
+            }
+        }
+
+        if escaped {
+            violations.push(Violation::new(
+                line_no,
+                Some(content.len()),
+                ViolationKind::DanglingEscape,
+                "Line ends with an escape character".to_string(),
+            ));
+        }
+    }
+
+    match trailer {
+        Some((trailer_line, trailer)) => {
+            lint_trailer(
+                &mut violations,
+                trailer_line,
+                &header,
+                part.as_ref(),
+                &trailer,
+                decoded_bytes,
+            );
+        }
+        None => violations.push(Violation::new(
+            line_no,
+            None,
+            ViolationKind::MissingTrailer,
+            "No =yend line found".to_string(),
+        )),
+    }
+
+    if let Some(first) = first_bad_ending {
+        violations.push(Violation::new(
+            first,
+            None,
+            ViolationKind::LineEnding,
+            format!("{} line(s) not terminated with CRLF", bad_endings),
+        ));
+    }
+
+    Ok(violations)
+}
+
+/// Report keys the spec does not define on a parsed keyword line, and
+/// formatting the spec does not allow
+fn lint_keywords(violations: &mut Vec<Violation>, line_no: usize, line: &[u8]) {
+    for key in unknown_keys(line) {
+        violations.push(Violation::new(
+            line_no,
+            None,
+            ViolationKind::UnknownKeyword,
+            format!("Unknown keyword '{}'", String::from_utf8_lossy(key)),
+        ));
+    }
+    if let Some(issue) = formatting_issue(line) {
+        violations.push(Violation::new(
+            line_no,
+            None,
+            ViolationKind::KeywordFormatting,
+            issue,
+        ));
+    }
+}
+
+/// Check the `=yend` line against the header, part line and decoded data
+fn lint_trailer(
+    violations: &mut Vec<Violation>,
+    line_no: usize,
+    header: &YencHeader,
+    part: Option<&YencPart>,
+    trailer: &YencTrailer,
+    decoded_bytes: u64,
+) {
+    let expected_size = match part {
+        Some(part) => part.size(),
+        None => header.size,
+    };
+
+    if trailer.size != expected_size {
+        violations.push(Violation::new(
+            line_no,
+            None,
+            ViolationKind::SizeMismatch,
+            format!(
+                "Trailer size={} does not match expected size {}",
+                trailer.size, expected_size
+            ),
+        ));
+    }
+
+    if decoded_bytes != trailer.size {
+        violations.push(Violation::new(
+            line_no,
+            None,
+            ViolationKind::SizeMismatch,
+            format!(
+                "Data decodes to {} bytes but trailer says size={}",
+                decoded_bytes, trailer.size
+            ),
+        ));
+    }
+
+    if let Some(header_part) = header.part {
+        if trailer.part != Some(header_part) {
+            violations.push(Violation::new(
+                line_no,
+                None,
+                ViolationKind::PartMismatch,
+                format!(
+                    "Trailer part={:?} does not match header part={}",
+                    trailer.part, header_part
+                ),
+            ));
+        }
+
+        if trailer.pcrc32.is_none() {
+            violations.push(Violation::new(
+                line_no,
+                None,
+                ViolationKind::MissingKeyword,
+                "Multi-part trailer has no pcrc32= keyword".to_string(),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(violations: &[Violation]) -> Vec<ViolationKind> {
+        violations.iter().map(|v| v.kind).collect()
+    }
+
+    #[test]
+    fn test_lint_clean_article() {
+        let input = b"junk before\r\n=ybegin line=128 size=5 name=test.bin\r\n*+,-=n\r\n=yend size=5 crc32=515ad3cc\r\n";
+        assert!(lint(&input[..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lint_bare_lf() {
+        let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5\n";
+        let violations = lint(&input[..]).unwrap();
+
+        assert_eq!(kinds(&violations), vec![ViolationKind::LineEnding]);
+        assert_eq!(violations[0].line, 1);
+        assert!(violations[0].message.contains("3 line(s)"));
+    }
+
+    #[test]
+    fn test_lint_line_too_long() {
+        let input = b"=ybegin line=4 size=6 name=test.bin\r\n*+,-./\r\n=yend size=6\r\n";
+        let violations = lint(&input[..]).unwrap();
+
+        assert_eq!(kinds(&violations), vec![ViolationKind::LineTooLong]);
+        assert_eq!(violations[0].line, 2);
+        assert_eq!(violations[0].column, Some(6));
+    }
+
+    #[test]
+    fn test_lint_escape_pair_at_boundary() {
+        // n+1 characters are allowed when the line ends with an escape pair
+        let input = b"=ybegin line=4 size=4 name=test.bin\r\n*+,=n\r\n=yend size=4\r\n";
+        assert!(lint(&input[..]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_lint_unescaped_critical() {
+        let input = b"=ybegin line=128 size=3 name=test.bin\r\n*\0+\r\n=yend size=3\r\n";
+        let violations = lint(&input[..]).unwrap();
+
+        assert_eq!(kinds(&violations), vec![ViolationKind::UnescapedCritical]);
+        assert_eq!((violations[0].line, violations[0].column), (2, Some(2)));
+    }
+
+    #[test]
+    fn test_lint_dangling_escape() {
+        let input = b"=ybegin line=128 size=3 name=test.bin\r\n*+=\r\n,\r\n=yend size=3\r\n";
+        let violations = lint(&input[..]).unwrap();
+
+        assert!(kinds(&violations).contains(&ViolationKind::DanglingEscape));
+    }
+
+    #[test]
+    fn test_lint_multipart_trailer() {
+        let input = b"=ybegin part=1 total=2 line=128 size=10 name=test.bin\r\n\
+                      =ypart begin=1 end=5\r\n\
+                      *+,-=n\r\n\
+                      =yend size=5 part=2\r\n";
+        let violations = lint(&input[..]).unwrap();
+
+        assert_eq!(
+            kinds(&violations),
+            vec![ViolationKind::PartMismatch, ViolationKind::MissingKeyword]
+        );
+        assert!(violations.iter().all(|v| v.line == 4));
+    }
+
+    #[test]
+    fn test_lint_missing_trailer_and_header() {
+        let input = b"=ybegin line=128 size=5 name=test.bin\r\n*+,-=n\r\n";
+        assert_eq!(
+            kinds(&lint(&input[..]).unwrap()),
+            vec![ViolationKind::MissingTrailer]
+        );
+
+        assert_eq!(
+            kinds(&lint(&b"no article here\r\n"[..]).unwrap()),
+            vec![ViolationKind::MissingHeader]
+        );
+    }
+
+    #[test]
+    fn test_lint_unknown_keywords() {
+        let input = b"=ybegin line=128 size=5 foo=1 name=test.bin\r\n\
+                      *+,-=n\r\n\
+                      =yend size=5 bar=2\r\n";
+        let violations = lint(&input[..]).unwrap();
+
+        assert_eq!(
+            kinds(&violations),
+            vec![ViolationKind::UnknownKeyword, ViolationKind::UnknownKeyword]
+        );
+        assert_eq!(violations[0].line, 1);
+        assert_eq!(violations[1].line, 3);
+        assert!(violations[1].message.contains("'bar'"));
+    }
+
+    #[test]
+    fn test_lint_keyword_formatting() {
+        let input = b"=ybegin line=128 size= 5 name=test.bin\r\n\
+                      *+,-=n\r\n\
+                      =yend size=5 size=5\r\n";
+        let violations = lint(&input[..]).unwrap();
+
+        assert_eq!(
+            kinds(&violations),
+            vec![
+                ViolationKind::KeywordFormatting,
+                ViolationKind::KeywordFormatting
+            ]
+        );
+        assert_eq!(violations[1].line, 3);
+        assert!(violations[1].message.contains("twice"));
+    }
+}
@@ -0,0 +1,551 @@
+//! Encoding a file as a numbered series of parts
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use crate::crc32;
+use crate::encode::{EncodeReport, Encoder, MultiPartInfo};
+use crate::error::{Result, YencError};
+
+/// Split of a file of `total_size` bytes into parts of `part_size` bytes
+#[derive(Debug, Clone, Copy)]
+struct Layout {
+    total_size: u64,
+    part_size: u64,
+}
+
+impl Layout {
+    fn new(total_size: u64, part_size: u64) -> Result<Self> {
+        if part_size == 0 {
+            return Err(YencError::InvalidConfig(
+                "part size must be greater than zero".to_string(),
+            ));
+        }
+        Ok(Self {
+            total_size,
+            part_size,
+        })
+    }
+
+    fn total_parts(&self) -> usize {
+        self.total_size.div_ceil(self.part_size).max(1) as usize
+    }
+
+    /// Range and numbering of the part at 0-based `index`
+    fn part_info(&self, index: usize) -> MultiPartInfo {
+        let begin = index as u64 * self.part_size + 1;
+        let end = (begin - 1 + self.part_size).min(self.total_size);
+        MultiPartInfo::new(index + 1, self.total_parts(), begin, end, self.total_size)
+    }
+}
+
+/// Encoder for the parts of a multi-part post, one after another
+///
+/// Splits a file of `total_size` bytes into parts of `part_size` bytes
+/// (the last one may be shorter) and numbers them. Feed it the data of each
+/// part in order with `encode_next`; it works out the `=ypart` range,
+/// accumulates the CRC32 of the whole file along the way and writes it into
+/// the trailer of the last part.
+///
+/// # Example
+/// ```
+/// use yenc::MultiPartEncoder;
+///
+/// let data: Vec<u8> = (0..=255).collect();
+/// let mut parts = MultiPartEncoder::new("file.bin", data.len() as u64, 100).unwrap();
+/// assert_eq!(parts.total_parts(), 3);
+///
+/// let mut articles = Vec::new();
+/// while let Some(part_info) = parts.next_part() {
+///     let mut article = Vec::new();
+///     parts
+///         .encode_next(&data[part_info.begin as usize - 1..part_info.end as usize], &mut article)
+///         .unwrap();
+///     articles.push(article);
+/// }
+/// let last = String::from_utf8_lossy(&articles[2]);
+/// assert!(last.contains(&format!(" crc32={:08x}", crc32fast::hash(&data))));
+/// ```
+#[derive(Debug, Clone)]
+pub struct MultiPartEncoder {
+    encoder: Encoder,
+    filename: String,
+    layout: Layout,
+    /// Parts encoded so far
+    done: usize,
+    /// CRC32 of those parts, if the encoder computes CRCs
+    crc: Option<u32>,
+}
+
+impl MultiPartEncoder {
+    /// Split a file of `total_size` bytes into parts of `part_size` bytes
+    ///
+    /// An empty file still makes one, empty, part.
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` if `part_size` is zero.
+    pub fn new(filename: &str, total_size: u64, part_size: u64) -> Result<Self> {
+        Ok(Self {
+            encoder: Encoder::new(),
+            filename: filename.to_string(),
+            layout: Layout::new(total_size, part_size)?,
+            done: 0,
+            crc: Some(0),
+        })
+    }
+
+    /// Encode the parts with `encoder` instead of the default settings
+    ///
+    /// Without CRCs (`Encoder::no_crc`), no full-file CRC32 is written either.
+    pub fn encoder(mut self, encoder: Encoder) -> Self {
+        self.crc = encoder.computes_crc().then_some(0);
+        self.encoder = encoder;
+        self
+    }
+
+    /// Number of parts the file is split into
+    pub fn total_parts(&self) -> usize {
+        self.layout.total_parts()
+    }
+
+    /// Range and numbering of the part `encode_next` encodes next
+    ///
+    /// Returns `None` once every part has been encoded. The full-file CRC32
+    /// is only known once the last part has been read, so it is not set here.
+    pub fn next_part(&self) -> Option<MultiPartInfo> {
+        if self.done == self.total_parts() {
+            return None;
+        }
+        Some(self.layout.part_info(self.done))
+    }
+
+    /// Encode the next part, reading its data from `reader`
+    ///
+    /// `reader` has to hold exactly the bytes of the range given by
+    /// `next_part`. The last part gets the CRC32 of the whole file.
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` once every part has been encoded or
+    /// if the encoder is set to text mode, and `YencError::InvalidData` if the data does not match the size of
+    /// the part. A part that fails can be encoded again: the output written
+    /// so far should be discarded, and nothing else changes.
+    pub fn encode_next<R: Read, W: Write>(
+        &mut self,
+        reader: R,
+        mut writer: W,
+    ) -> Result<EncodeReport> {
+        let Some(mut part_info) = self.next_part() else {
+            return Err(YencError::InvalidConfig(format!(
+                "all {} parts have already been encoded",
+                self.total_parts()
+            )));
+        };
+        self.encoder.validate_sized()?;
+
+        let reservation = self.encoder.reserve(self.encoder.read_chunk_size())?;
+        let mut state = self.encoder.state().with_reservation(reservation);
+        self.encoder.write_part_body(
+            reader,
+            &mut writer,
+            &self.filename,
+            &part_info,
+            &mut state,
+            &mut Vec::new(),
+        )?;
+
+        // The file CRC so far extended by the part CRC, without hashing the
+        // data twice; only kept once the part has succeeded
+        let crc = self
+            .crc
+            .zip(state.crc())
+            .map(|(crc, part_crc)| crc32::combine(crc, part_crc, part_info.expected_size()));
+        if part_info.part == part_info.total {
+            part_info.full_crc = crc;
+        }
+        let report = self.encoder.end_part(&mut state, &mut writer, &part_info)?;
+        self.crc = crc;
+        self.done += 1;
+        Ok(report)
+    }
+}
+
+/// Splitter handing out the parts of a seekable file one at a time
+///
+/// Each part comes as its `MultiPartInfo` and a reader over exactly its
+/// bytes, ready for `Encoder::encode_part` or a posting pipeline. Nothing
+/// is read ahead: the reader seeks to the start of each part when it is
+/// asked for. The full-file CRC32 is not set on the last part; use
+/// `MultiPartEncoder` to have it worked out while encoding.
+///
+/// Part readers borrow the splitter, so this is not an `Iterator`; loop
+/// over `next_part` instead.
+///
+/// # Example
+/// ```
+/// use std::io::Cursor;
+/// use yenc::{Encoder, PartSplitter};
+///
+/// let data: Vec<u8> = (0..=255).collect();
+/// let mut splitter = PartSplitter::new(Cursor::new(data), 100).unwrap();
+/// assert_eq!(splitter.total_parts(), 3);
+///
+/// while let Some(part) = splitter.next_part() {
+///     let (part_info, reader) = part.unwrap();
+///     let mut article = Vec::new();
+///     Encoder::new()
+///         .encode_part(reader, &mut article, "file.bin", &part_info)
+///         .unwrap();
+/// }
+/// ```
+pub struct PartSplitter<R> {
+    inner: R,
+    layout: Layout,
+    /// Parts handed out so far
+    done: usize,
+}
+
+impl PartSplitter<File> {
+    /// Split the file at `path` into parts of `part_size` bytes
+    ///
+    /// # Errors
+    /// As for `new`, plus I/O errors opening the file.
+    pub fn open<P: AsRef<Path>>(path: P, part_size: u64) -> Result<Self> {
+        Self::new(File::open(path)?, part_size)
+    }
+}
+
+impl<R: Read + Seek> PartSplitter<R> {
+    /// Split everything in `inner` into parts of `part_size` bytes
+    ///
+    /// The size is taken by seeking to the end. An empty input still makes
+    /// one, empty, part.
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` if `part_size` is zero.
+    pub fn new(mut inner: R, part_size: u64) -> Result<Self> {
+        let size = inner.seek(SeekFrom::End(0))?;
+        Ok(Self {
+            inner,
+            layout: Layout::new(size, part_size)?,
+            done: 0,
+        })
+    }
+
+    /// Size of the whole input in bytes
+    pub fn file_size(&self) -> u64 {
+        self.layout.total_size
+    }
+
+    /// Number of parts the input is split into
+    pub fn total_parts(&self) -> usize {
+        self.layout.total_parts()
+    }
+
+    /// The next part and a reader over its bytes, or `None` after the last
+    ///
+    /// The reader has to be done with before asking for the next part. If
+    /// the input shrinks in the meantime, it ends early, which
+    /// `Encoder::encode_part` reports as a size mismatch.
+    pub fn next_part(&mut self) -> Option<Result<(MultiPartInfo, io::Take<&mut R>)>> {
+        if self.done == self.total_parts() {
+            return None;
+        }
+        let part_info = self.layout.part_info(self.done);
+        if let Err(err) = self.inner.seek(SeekFrom::Start(part_info.begin - 1)) {
+            return Some(Err(err.into()));
+        }
+        self.done += 1;
+        let size = part_info.expected_size();
+        Some(Ok((part_info, (&mut self.inner).take(size))))
+    }
+
+    /// Return the underlying reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// Longest file name `plan_parts` leaves room for
+const PLAN_NAME_LEN: usize = 255;
+
+/// Keyword lines of a part with every number and CRC32 left out
+const PART_KEYWORDS: &str = "=ybegin part= total= line= size= name=\n\
+                             =ypart begin= end=\n\
+                             =yend size= part= pcrc32=00000000 crc32=00000000\n";
+
+/// Number of decimal digits in `n`
+fn digits(n: u64) -> usize {
+    n.checked_ilog10().map_or(1, |log| log as usize + 1)
+}
+
+impl Encoder {
+    /// Split a file into parts whose articles stay within a size limit
+    ///
+    /// Picks the largest part size for which every encoded article, keyword
+    /// lines included, is at most `max_article_bytes` long even if every
+    /// byte of the data needs escaping, and returns the parts in order.
+    /// The full-file CRC32 is not known yet, so `full_crc` is not set; see
+    /// `MultiPartEncoder`, which encodes parts of the same size.
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` if not even one byte of data fits
+    /// in an article of `max_article_bytes`.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Encoder;
+    ///
+    /// let parts = Encoder::new().plan_parts("file.bin", 1_000_000, 300_000).unwrap();
+    /// assert_eq!(parts.len(), 7);
+    /// assert_eq!(parts[6].end, 1_000_000);
+    /// ```
+    pub fn plan_parts(
+        &self,
+        filename: &str,
+        file_size: u64,
+        max_article_bytes: usize,
+    ) -> Result<Vec<MultiPartInfo>> {
+        self.validate()?;
+
+        // Every number in the keyword lines is at most the file size, or 1
+        // for an empty file; the three lines may end in CRLF
+        let overhead = PART_KEYWORDS.len()
+            + 3 * (self.line_ending.as_bytes().len() - 1)
+            + filename.len()
+            + 2 * self.name_quote(filename).len()
+            + digits(self.line_length as u64)
+            + 7 * digits(file_size.max(1));
+        let fits = |len: usize| {
+            overhead.saturating_add(self.encoded_len_upper_bound(len)) <= max_article_bytes
+        };
+        if !fits(file_size.min(1) as usize) {
+            return Err(YencError::InvalidConfig(format!(
+                "articles of {} bytes leave no room for data",
+                max_article_bytes
+            )));
+        }
+
+        // Largest part size that fits, the bound growing with the size
+        let max_part = usize::try_from(file_size).unwrap_or(usize::MAX);
+        let (mut fitting, mut too_big) = (1, max_part.max(1).saturating_add(1));
+        while too_big - fitting > 1 {
+            let mid = fitting + (too_big - fitting) / 2;
+            if fits(mid) {
+                fitting = mid;
+            } else {
+                too_big = mid;
+            }
+        }
+
+        let layout = Layout::new(file_size, fitting as u64)?;
+        Ok((0..layout.total_parts())
+            .map(|index| layout.part_info(index))
+            .collect())
+    }
+}
+
+/// Split a file into parts whose articles stay within a size limit
+///
+/// This is equivalent to `Encoder::new().plan_parts(name, file_size,
+/// max_article_bytes)` with room for a name of up to 255 bytes. Use
+/// `Encoder::plan_parts` for longer names or other settings.
+///
+/// # Example
+/// ```
+/// let parts = yenc::plan_parts(10_000_000, 750_000).unwrap();
+/// assert!(parts.iter().all(|part| part.expected_size() < 375_000));
+/// ```
+pub fn plan_parts(file_size: u64, max_article_bytes: usize) -> Result<Vec<MultiPartInfo>> {
+    Encoder::new().plan_parts(&"x".repeat(PLAN_NAME_LEN), file_size, max_article_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::LineEnding;
+
+    #[test]
+    fn test_multipart_encoder() {
+        let data: Vec<u8> = (0..2500).map(|i| (i * 7) as u8).collect();
+        let mut parts = MultiPartEncoder::new("test.bin", data.len() as u64, 1000).unwrap();
+        assert_eq!(parts.total_parts(), 3);
+
+        let mut part = 0;
+        while let Some(part_info) = parts.next_part() {
+            part += 1;
+            assert_eq!(part_info.part, part);
+            let range = (part_info.begin - 1) as usize..part_info.end as usize;
+
+            let mut expected_info = part_info.clone();
+            if part == 3 {
+                expected_info = expected_info.with_full_crc(crc32fast::hash(&data));
+                assert_eq!(range, 2000..2500);
+            }
+            let mut expected = Vec::new();
+            Encoder::new()
+                .encode_part(
+                    &data[range.clone()],
+                    &mut expected,
+                    "test.bin",
+                    &expected_info,
+                )
+                .unwrap();
+
+            let mut encoded = Vec::new();
+            let report = parts.encode_next(&data[range], &mut encoded).unwrap();
+            assert_eq!(encoded, expected);
+            assert_eq!(report.crc32, expected_info.full_crc);
+        }
+        assert_eq!(part, 3);
+
+        let result = parts.encode_next(&b""[..], Vec::new());
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_multipart_encoder_retry() {
+        let data = b"0123456789";
+        let mut parts = MultiPartEncoder::new("test.bin", data.len() as u64, 5).unwrap();
+        parts.encode_next(&data[..5], Vec::new()).unwrap();
+
+        // Too much data fails the part without counting it
+        let result = parts.encode_next(&data[4..], Vec::new());
+        assert!(matches!(result, Err(YencError::InvalidData(_))));
+        assert_eq!(parts.next_part().unwrap().part, 2);
+
+        let report = parts.encode_next(&data[5..], Vec::new()).unwrap();
+        assert_eq!(report.crc32, Some(crc32fast::hash(data)));
+
+        let mut parts = MultiPartEncoder::new("test.bin", data.len() as u64, 5)
+            .unwrap()
+            .encoder(Encoder::new().no_crc());
+        parts.encode_next(&data[..5], Vec::new()).unwrap();
+        let report = parts.encode_next(&data[5..], Vec::new()).unwrap();
+        assert_eq!(report.crc32, None);
+
+        // Text mode would change the part sizes
+        let mut parts = MultiPartEncoder::new("test.bin", data.len() as u64, 5)
+            .unwrap()
+            .encoder(Encoder::new().text_mode());
+        let result = parts.encode_next(&data[..5], Vec::new());
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+        assert_eq!(parts.next_part().unwrap().part, 1);
+
+        assert!(matches!(
+            MultiPartEncoder::new("test.bin", 10, 0),
+            Err(YencError::InvalidConfig(_))
+        ));
+    }
+
+    #[test]
+    fn test_part_splitter() {
+        let data: Vec<u8> = (0..2500).map(|i| (i * 11) as u8).collect();
+        let mut splitter = PartSplitter::new(io::Cursor::new(&data), 1000).unwrap();
+        assert_eq!(splitter.file_size(), 2500);
+
+        let mut parts = MultiPartEncoder::new("test.bin", data.len() as u64, 1000).unwrap();
+        let mut joined = Vec::new();
+        while let Some(part) = splitter.next_part() {
+            let (part_info, mut reader) = part.unwrap();
+            let expected = parts.next_part().unwrap();
+            assert_eq!(
+                (part_info.begin, part_info.end),
+                (expected.begin, expected.end)
+            );
+            assert_eq!(part_info.total, 3);
+
+            let mut part_data = Vec::new();
+            reader.read_to_end(&mut part_data).unwrap();
+            parts.encode_next(&part_data[..], io::sink()).unwrap();
+            joined.extend_from_slice(&part_data);
+        }
+        assert_eq!(joined, data);
+        assert!(parts.next_part().is_none());
+
+        // A part left half read does not throw off the next one
+        let mut splitter = PartSplitter::new(io::Cursor::new(&data), 1000).unwrap();
+        let (_, mut first) = splitter.next_part().unwrap().unwrap();
+        first.read_exact(&mut [0; 10]).unwrap();
+        let (part_info, mut second) = splitter.next_part().unwrap().unwrap();
+        let mut part_data = Vec::new();
+        second.read_to_end(&mut part_data).unwrap();
+        assert_eq!(
+            part_data,
+            &data[(part_info.begin - 1) as usize..part_info.end as usize]
+        );
+
+        let splitter = PartSplitter::new(io::Cursor::new(Vec::new()), 10).unwrap();
+        assert_eq!(splitter.total_parts(), 1);
+        let result = PartSplitter::new(io::Cursor::new(&data), 0);
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_plan_parts() {
+        // Bytes that all need escaping: NUL, LF, CR and '=' once encoded
+        let data: Vec<u8> = [214u8, 224, 227, 19].repeat(25_000);
+        let encoder = Encoder::new().line_length(100);
+        for limit in [5_000, 65_536, 250_000] {
+            let parts = encoder
+                .plan_parts("test.bin", data.len() as u64, limit)
+                .unwrap();
+            assert_eq!(parts.last().unwrap().end, data.len() as u64);
+            let mut sizes = Vec::new();
+            for (index, part_info) in parts.iter().enumerate() {
+                let mut part_info = part_info.clone();
+                if index == parts.len() - 1 {
+                    part_info = part_info.with_full_crc(0);
+                }
+                let mut article = Vec::new();
+                encoder
+                    .encode_part(
+                        &data[(part_info.begin - 1) as usize..part_info.end as usize],
+                        &mut article,
+                        "test.bin",
+                        &part_info,
+                    )
+                    .unwrap();
+                sizes.push(article.len());
+            }
+            // Within the limit, but not by much unless the file fits whole
+            let largest = *sizes.iter().max().unwrap();
+            assert!(largest <= limit, "{} > {}", largest, limit);
+            assert!(
+                parts.len() == 1 || largest > limit - 100,
+                "{} much less than {}",
+                largest,
+                limit
+            );
+        }
+
+        let parts = plan_parts(0, 1000).unwrap();
+        assert_eq!(parts.len(), 1);
+        assert_eq!(parts[0].expected_size(), 0);
+
+        let result = encoder.plan_parts("test.bin", 10, 100);
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_plan_parts_crlf() {
+        // Lines of one escape pair each, so the line breaks weigh a lot
+        let data = vec![214u8; 20_000];
+        let encoder = Encoder::new().line_length(1).line_ending(LineEnding::CrLf);
+        let parts = encoder
+            .plan_parts("test.bin", data.len() as u64, 10_000)
+            .unwrap();
+        for part_info in &parts {
+            let mut article = Vec::new();
+            encoder
+                .encode_part(
+                    &data[(part_info.begin - 1) as usize..part_info.end as usize],
+                    &mut article,
+                    "test.bin",
+                    part_info,
+                )
+                .unwrap();
+            assert!(article.len() <= 10_000, "{}", article.len());
+        }
+    }
+}
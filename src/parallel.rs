@@ -0,0 +1,44 @@
+//! Parallel decoding of multi-part files straight into the output file
+
+use std::fs::File;
+use std::io::Read;
+
+use rayon::prelude::*;
+
+use crate::decode::{DecodeOutcome, Decoder};
+use crate::error::Result;
+
+impl Decoder {
+    /// Decode many articles of a multi-part file at once on the rayon thread pool
+    ///
+    /// Every article is decoded straight into `output` at the offset given by
+    /// its `=ypart` line (single-part articles cover the whole file), so
+    /// parts can finish in any order. One result is returned per reader, in
+    /// the same order, so a broken part can be fetched again without
+    /// redoing the others.
+    ///
+    /// Under a memory budget (see `max_memory`), each part waits for its
+    /// buffers to be reserved before reading from its reader, so the parts
+    /// decoded at once never hold more than the budget between them.
+    ///
+    /// The output is not resized: if the last part is missing, the file ends
+    /// early. Use `validate_parts` on the returned outcomes to check that the
+    /// file is complete, or `decode_part_files`, which does so.
+    ///
+    /// # Errors
+    /// Every part fails with `YencError::InvalidConfig` in text mode, which
+    /// would shift the decoded bytes out of their ranges. A part fails with
+    /// `YencError::InvalidData` if its range lies outside the file size in
+    /// its header, or it decodes to more bytes than its range holds; decoding
+    /// errors are passed through.
+    pub fn decode_parts_parallel<R: Read + Send>(
+        &self,
+        readers: Vec<R>,
+        output: &File,
+    ) -> Vec<Result<DecodeOutcome>> {
+        readers
+            .into_par_iter()
+            .map(|reader| self.decode_into(reader, &mut &*output))
+            .collect()
+    }
+}
@@ -0,0 +1,226 @@
+//! Seekable reader over a file split into yEnc part files
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::assemble::{decode_part_file, index_parts};
+use crate::decode::Decoder;
+use crate::error::{Result, YencError};
+
+/// The file made up by a set of part files, read without assembling it
+///
+/// Only the keyword lines of the part files are read up front. Reads and
+/// seeks then work on the reassembled file, decoding a part (with the same
+/// checks as `decode`) the first time a read lands in it. The part read
+/// last is kept decoded, so reading straight through decodes each part once.
+///
+/// Parts may be given in any order and may overlap. A read that reaches
+/// bytes no part covers fails with `io::ErrorKind::InvalidData`, as do
+/// parts that fail to decode; the error wraps the `YencError`.
+///
+/// # Example
+/// ```no_run
+/// use std::io::{Read, Seek, SeekFrom};
+/// use yenc::PartSet;
+///
+/// let mut file = PartSet::open(["movie.mkv.part001.yenc", "movie.mkv.part002.yenc"])?;
+/// file.seek(SeekFrom::Start(1_000_000))?;
+/// let mut buf = [0u8; 4096];
+/// file.read_exact(&mut buf)?;
+/// # Ok::<(), yenc::YencError>(())
+/// ```
+#[derive(Debug)]
+pub struct PartSet {
+    decoder: Decoder,
+    full_size: u64,
+    /// Part files with the 0-based range of each, sorted by start
+    parts: Vec<(PathBuf, Range<u64>)>,
+    pos: u64,
+    /// Index in `parts` and data of the part decoded last
+    current: Option<(usize, Vec<u8>)>,
+}
+
+impl PartSet {
+    /// Index the part files at `part_paths`
+    ///
+    /// # Errors
+    /// The error of the first part file that cannot be read or parsed; or
+    /// `YencError::InvalidData` if no parts are given, or they disagree on
+    /// the file size or have ranges outside of it.
+    pub fn open<P, I>(part_paths: I) -> Result<Self>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator<Item = P>,
+    {
+        let decoder = Decoder::new();
+        let paths = part_paths
+            .into_iter()
+            .map(|path| path.as_ref().to_path_buf());
+        let (full_size, parts) = index_parts(&decoder, paths)?;
+        Ok(Self {
+            decoder,
+            full_size,
+            parts,
+            pos: 0,
+            current: None,
+        })
+    }
+
+    /// Size of the whole file in bytes
+    pub fn file_size(&self) -> u64 {
+        self.full_size
+    }
+
+    /// Whether every byte of the file is covered by some part
+    pub fn is_complete(&self) -> bool {
+        let mut next = 0;
+        for (_, part) in &self.parts {
+            if part.start > next {
+                return false;
+            }
+            next = next.max(part.end);
+        }
+        next >= self.full_size
+    }
+
+    /// Index in `parts` of a part holding byte `pos`, preferring the current one
+    fn find_part(&self, pos: u64) -> Option<usize> {
+        if let Some((index, _)) = &self.current {
+            if self.parts[*index].1.contains(&pos) {
+                return Some(*index);
+            }
+        }
+        let starting_before = self.parts.partition_point(|(_, part)| part.start <= pos);
+        (0..starting_before)
+            .rev()
+            .find(|&index| self.parts[index].1.end > pos)
+    }
+}
+
+impl Read for PartSet {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let pos = self.pos;
+        if pos >= self.full_size || out.is_empty() {
+            return Ok(0);
+        }
+        let Some(index) = self.find_part(pos) else {
+            return Err(YencError::InvalidData(format!(
+                "Gap in coverage: byte {} is missing",
+                pos + 1
+            ))
+            .into());
+        };
+
+        if self
+            .current
+            .as_ref()
+            .is_none_or(|(current, _)| *current != index)
+        {
+            let (path, part) = &self.parts[index];
+            let data = decode_part_file(&self.decoder, path, part)?;
+            self.current = Some((index, data));
+        }
+        let (_, data) = self.current.as_ref().expect("current part was just set");
+
+        let available = &data[(pos - self.parts[index].1.start) as usize..];
+        let n = available.len().min(out.len());
+        out[..n].copy_from_slice(&available[..n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for PartSet {
+    /// Move to another position in the file; nothing is decoded until a read
+    ///
+    /// Seeking past the end is allowed, and reads there return nothing.
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => {
+                self.pos = offset;
+                return Ok(offset);
+            }
+            SeekFrom::End(offset) => (self.full_size, offset),
+            SeekFrom::Current(offset) => (self.pos, offset),
+        };
+        match base.checked_add_signed(offset) {
+            Some(pos) => {
+                self.pos = pos;
+                Ok(pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::{Encoder, MultiPartInfo};
+
+    #[test]
+    fn test_part_set() {
+        let dir = std::env::temp_dir().join(format!("yenc-part-set-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i * 29 + i / 50) as u8).collect();
+
+        // Parts of 3000 bytes, plus one overlapping the first two
+        let mut paths = Vec::new();
+        let ranges = [
+            (1, 3000),
+            (3001, 6000),
+            (6001, 9000),
+            (9001, 10_000),
+            (2001, 4000),
+        ];
+        for (index, (begin, end)) in ranges.into_iter().enumerate() {
+            let part_info =
+                MultiPartInfo::new(index + 1, ranges.len(), begin, end, data.len() as u64);
+            let mut encoded = Vec::new();
+            Encoder::new()
+                .encode_part(
+                    &data[(begin - 1) as usize..end as usize],
+                    &mut encoded,
+                    "test.bin",
+                    &part_info,
+                )
+                .unwrap();
+            let path = dir.join(format!("test.bin.{}.yenc", index));
+            std::fs::write(&path, encoded).unwrap();
+            paths.push(path);
+        }
+
+        let mut set = PartSet::open(paths.iter().rev()).unwrap();
+        assert_eq!(set.file_size(), data.len() as u64);
+        assert!(set.is_complete());
+        let mut all = Vec::new();
+        set.read_to_end(&mut all).unwrap();
+        assert_eq!(all, data);
+
+        let mut buf = [0u8; 2500];
+        set.seek(SeekFrom::Start(5000)).unwrap();
+        set.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf[..], &data[5000..7500]);
+        set.seek(SeekFrom::End(-100)).unwrap();
+        assert_eq!(set.read(&mut buf).unwrap(), 100);
+        assert!(set.seek(SeekFrom::Current(-20_000)).is_err());
+
+        // Without the third part, reads stop at the gap
+        let without_third = paths
+            .iter()
+            .filter(|path| !path.ends_with("test.bin.2.yenc"));
+        let mut set = PartSet::open(without_third).unwrap();
+        assert!(!set.is_complete());
+        let err = set.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        set.seek(SeekFrom::Start(9500)).unwrap();
+        set.read_exact(&mut buf[..500]).unwrap();
+        assert_eq!(&buf[..500], &data[9500..]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
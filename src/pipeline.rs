@@ -0,0 +1,317 @@
+//! Threaded read/decode/write pipeline for large articles
+//!
+//! Reading the input, decoding it and writing the output each run on a
+//! thread of their own, handing blocks along bounded channels. While one
+//! block is being decoded, the next is already being read and the previous
+//! one written, so disk and CPU work overlap instead of taking turns.
+
+use std::io::{self, Read, Write};
+use std::mem;
+use std::sync::mpsc::{Receiver, SyncSender, sync_channel};
+use std::thread;
+
+use crate::decode::{DecodeOutcome, Decoder};
+use crate::error::Result;
+
+/// Size of each block handed between threads
+const BLOCK: usize = 256 * 1024;
+
+/// Blocks each stage may have in flight; this bounds the memory in use
+const DEPTH: usize = 4;
+
+/// Blocks in use at once: those of both channels, plus the one being filled
+/// for the writing thread
+const BLOCKS: usize = 2 * DEPTH + 1;
+
+/// Sending end of a channel between two stages
+struct Sending {
+    full: SyncSender<io::Result<Vec<u8>>>,
+    free: Receiver<Vec<u8>>,
+}
+
+/// Receiving end of a channel between two stages
+struct Receiving {
+    full: Receiver<io::Result<Vec<u8>>>,
+    free: SyncSender<Vec<u8>>,
+}
+
+/// Channel between two stages
+///
+/// Full blocks go one way and emptied buffers come back, so after the first
+/// few blocks nothing is allocated.
+fn channel(block_size: usize) -> (Sending, Receiving) {
+    let (full_tx, full_rx) = sync_channel(DEPTH);
+    let (free_tx, free_rx) = sync_channel(DEPTH);
+    for _ in 0..DEPTH {
+        free_tx.send(Vec::with_capacity(block_size)).unwrap();
+    }
+    let sending = Sending {
+        full: full_tx,
+        free: free_rx,
+    };
+    let receiving = Receiving {
+        full: full_rx,
+        free: free_tx,
+    };
+    (sending, receiving)
+}
+
+/// Read blocks from `reader` until the end of the input or the receiver hangs up
+fn read_blocks<R: Read>(mut reader: R, Sending { full, free }: Sending, block_size: usize) {
+    while let Ok(mut block) = free.recv() {
+        block.clear();
+        block.resize(block_size, 0);
+        let n = loop {
+            match reader.read(&mut block) {
+                Ok(n) => break n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    let _ = full.send(Err(err));
+                    return;
+                }
+            }
+        };
+        if n == 0 {
+            return;
+        }
+        block.truncate(n);
+        if full.send(Ok(block)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Write blocks to `writer` until the sender hangs up
+fn write_blocks<W: Write>(mut writer: W, Receiving { full, free }: Receiving) -> io::Result<()> {
+    for block in full {
+        let block = block?;
+        writer.write_all(&block)?;
+        // The decoding thread may have finished and stopped taking buffers
+        let _ = free.send(block);
+    }
+    writer.flush()
+}
+
+/// Decoder input fed by the reading thread
+struct ChannelReader {
+    channel: Receiving,
+    block: Vec<u8>,
+    pos: usize,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.block.len() {
+            let Ok(block) = self.channel.full.recv() else {
+                return Ok(0);
+            };
+            let used = mem::replace(&mut self.block, block?);
+            let _ = self.channel.free.send(used);
+            self.pos = 0;
+        }
+        let n = out.len().min(self.block.len() - self.pos);
+        out[..n].copy_from_slice(&self.block[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Decoder output drained by the writing thread
+struct ChannelWriter {
+    channel: Sending,
+    block: Vec<u8>,
+    block_size: usize,
+}
+
+impl ChannelWriter {
+    /// Hand the current block to the writing thread
+    fn send(&mut self) -> io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        // Either channel closing means the writing thread has stopped; its
+        // own error is reported once it is joined
+        let stopped = || io::Error::from(io::ErrorKind::BrokenPipe);
+        let mut next = self.channel.free.recv().map_err(|_| stopped())?;
+        next.clear();
+        let block = mem::replace(&mut self.block, next);
+        self.channel.full.send(Ok(block)).map_err(|_| stopped())
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = data.len().min(self.block_size - self.block.len());
+        self.block.extend_from_slice(&data[..n]);
+        if self.block.len() == self.block_size {
+            self.send()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send()
+    }
+}
+
+impl Decoder {
+    /// Decode like `decode`, reading and writing on threads of their own
+    ///
+    /// The input is read ahead and the output written behind in blocks of
+    /// 256 KiB, with a few blocks in flight each way, while the calling
+    /// thread decodes. Meant for large single articles coming from and going
+    /// to disk, where it overlaps the I/O with decoding; for small articles
+    /// or in-memory data the threads cost more than they save.
+    ///
+    /// Under a memory budget (see `max_memory`), the blocks in flight are
+    /// reserved together with the decoder's own buffers before anything is
+    /// read, and shrink so that they take no more than half the budget.
+    ///
+    /// `writer` is flushed before returning. If it fails, its error is
+    /// returned rather than any the decoder saw as a result.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::Decoder;
+    ///
+    /// let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
+    /// let mut output = Vec::new();
+    ///
+    /// let outcome = Decoder::new()
+    ///     .decode_pipelined(&input[..], &mut output)
+    ///     .unwrap();
+    /// assert_eq!(output, b"!\"#$%");
+    /// ```
+    pub fn decode_pipelined<R: Read + Send, W: Write + Send>(
+        &self,
+        reader: R,
+        writer: W,
+    ) -> Result<DecodeOutcome> {
+        let block_size = match self.memory_limit() {
+            Some(limit) => BLOCK.min(limit / 2 / BLOCKS).max(1),
+            None => BLOCK,
+        };
+        // Taken in one go along with the decoder's buffers, so that no
+        // memory is held while waiting for more; the blocks stay reserved
+        // until the writing thread is done with them
+        let mut reservation = self.reserve(BLOCKS * block_size)?;
+        let _blocks = reservation.split_off(BLOCKS * block_size);
+        let (read, input) = channel(block_size);
+        let (output, write) = channel(block_size);
+
+        thread::scope(|scope| {
+            scope.spawn(move || read_blocks(reader, read, block_size));
+            let written = scope.spawn(move || write_blocks(writer, write));
+
+            let input = ChannelReader {
+                channel: input,
+                block: Vec::new(),
+                pos: 0,
+            };
+            let mut output = ChannelWriter {
+                channel: output,
+                block: Vec::with_capacity(block_size),
+                block_size,
+            };
+            let result = self.decode_reserved(input, &mut output, reservation);
+            let flushed = output.flush();
+            // Hanging up lets the writing thread finish
+            drop(output);
+
+            written.join().unwrap()?;
+            let result = result?;
+            flushed?;
+            Ok(result)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::YencError;
+    use crate::encode::Encoder;
+
+    #[test]
+    fn test_decode_pipelined() {
+        // Several blocks each way, and a partial last block
+        let data: Vec<u8> = (0..DEPTH * 3 * BLOCK + 123)
+            .map(|i| (i * 31 + i / BLOCK) as u8)
+            .collect();
+        let mut encoded = Vec::new();
+        Encoder::new()
+            .encode(&data[..], &mut encoded, "test.bin")
+            .unwrap();
+
+        let mut output = Vec::new();
+        let outcome = Decoder::new()
+            .decode_pipelined(&encoded[..], &mut output)
+            .unwrap();
+        assert_eq!(outcome.header.name, b"test.bin");
+        assert!(outcome.trailer.is_some());
+        assert_eq!(outcome.bytes_written, data.len() as u64);
+        assert_eq!(output, data);
+    }
+
+    #[test]
+    fn test_decode_pipelined_max_memory() {
+        // Blocks shrink to fit the budget along with the decoder's buffers
+        let data: Vec<u8> = (0..10_000).map(|i| (i * 7) as u8).collect();
+        let mut encoded = Vec::new();
+        Encoder::new()
+            .encode(&data[..], &mut encoded, "test.bin")
+            .unwrap();
+
+        let mut output = Vec::new();
+        Decoder::new()
+            .max_memory(4096)
+            .decode_pipelined(&encoded[..], &mut output)
+            .unwrap();
+        assert_eq!(output, data);
+
+        let result = Decoder::new()
+            .max_memory(16)
+            .decode_pipelined(&encoded[..], &mut output);
+        assert!(matches!(
+            result,
+            Err(YencError::MemoryLimitExceeded { limit: 16 })
+        ));
+    }
+
+    #[test]
+    fn test_decode_pipelined_errors() {
+        struct Failing;
+
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::ConnectionReset.into())
+            }
+        }
+
+        impl Write for Failing {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::ErrorKind::StorageFull.into())
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let decoder = Decoder::new();
+        let result = decoder.decode_pipelined(Failing, Vec::new());
+        assert!(
+            matches!(result, Err(YencError::Io(err)) if err.kind() == io::ErrorKind::ConnectionReset)
+        );
+
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
+        let result = decoder.decode_pipelined(&input[..], Failing);
+        assert!(
+            matches!(result, Err(YencError::Io(err)) if err.kind() == io::ErrorKind::StorageFull)
+        );
+
+        // Decoding errors come through as usual
+        let result = decoder.decode_pipelined(&b"no header here\n"[..], Vec::new());
+        assert!(matches!(result, Err(YencError::InvalidHeader(_))));
+    }
+}
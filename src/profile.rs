@@ -0,0 +1,144 @@
+//! Preset settings for interoperating with well-known yEnc clients
+
+use crate::decode::Decoder;
+use crate::encode::{Encoder, EscapePolicy, LineEnding};
+
+/// Bundled encoder and decoder settings matching a known client
+///
+/// Each profile configures the options that client is known to rely on, so
+/// callers do not have to work out the right combination themselves. The
+/// returned `Encoder` and `Decoder` can be adjusted further with the usual
+/// builder methods.
+///
+/// # Example
+/// ```
+/// use yenc::Profile;
+///
+/// let mut encoded = Vec::new();
+/// Profile::PowerPost
+///     .encoder()
+///     .encode(&b"hello"[..], &mut encoded, "hello.txt")
+///     .unwrap();
+///
+/// let mut decoded = Vec::new();
+/// Profile::Sabnzbd.decoder().decode(&encoded[..], &mut decoded).unwrap();
+/// assert_eq!(decoded, b"hello");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// PowerPost, the reference posting client of the yEnc draft
+    ///
+    /// Posts 128-character lines ending in CRLF, ready for NNTP, and frames
+    /// single files as part 1 of 1 with both `pcrc32=` and `crc32=`, as yEnc
+    /// 1.2 posters do. Reads articles strictly, as the draft describes them.
+    PowerPost,
+    /// yydecode / yyencode
+    ///
+    /// Encodes with the escaping rules of the yEnc spec and plain LF line
+    /// endings. Validates CRCs when decoding but is tolerant of malformed
+    /// framing, such as trailers joined to the last data line or missing
+    /// `line=`.
+    Yydecode,
+    /// SABnzbd and other automated downloaders
+    ///
+    /// Decodes as much as possible: framing damage is tolerated and CRC
+    /// mismatches are reported in the outcome's warnings instead of aborting
+    /// decoding, since the downloader compares the CRC itself and repairs
+    /// damaged files with PAR2 afterwards. Encodes with the defaults.
+    Sabnzbd,
+}
+
+impl Profile {
+    /// Encoder configured for this profile
+    pub fn encoder(self) -> Encoder {
+        match self {
+            Profile::PowerPost => Encoder::new()
+                .line_length(128)
+                .line_ending(LineEnding::CrLf)
+                .force_multipart_framing(),
+            Profile::Yydecode => Encoder::new()
+                .line_length(128)
+                .escape_policy(EscapePolicy::Spec),
+            Profile::Sabnzbd => Encoder::new().line_length(128),
+        }
+    }
+
+    /// Decoder configured for this profile
+    pub fn decoder(self) -> Decoder {
+        match self {
+            Profile::PowerPost => Decoder::new().strict(),
+            Profile::Yydecode => Decoder::new(),
+            Profile::Sabnzbd => Decoder::new().allow_crc_mismatch(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::error::YencError;
+
+    const CORRUPT_CRC: &[u8] =
+        b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5 crc32=ffffffff\n";
+
+    #[test]
+    fn test_profiles_roundtrip() {
+        let data: Vec<u8> = (0..=255).collect();
+        for profile in [Profile::PowerPost, Profile::Yydecode, Profile::Sabnzbd] {
+            let mut encoded = Vec::new();
+            profile
+                .encoder()
+                .encode(&data[..], &mut encoded, "all.bin")
+                .unwrap();
+            let header = encoded.split(|&b| b == b'\n').next().unwrap();
+            assert!(header.windows(10).any(|w| w == b" line=128 "));
+            let crlf = encoded.ends_with(b"\r\n");
+            assert_eq!(crlf, profile == Profile::PowerPost, "{:?}", profile);
+            let framed = encoded.windows(7).any(|w| w == b"=ypart ");
+            assert_eq!(framed, profile == Profile::PowerPost, "{:?}", profile);
+            // Only the spec's rules leave a dot in mid-line unescaped
+            let dots = encoded
+                .split(|&b| b == b'\n')
+                .filter(|line| !line.starts_with(b"=y"))
+                .any(|line| line.contains(&b'.'));
+            assert_eq!(dots, profile == Profile::Yydecode, "{:?}", profile);
+
+            let mut decoded = Vec::new();
+            profile
+                .decoder()
+                .decode(&encoded[..], &mut decoded)
+                .unwrap();
+            assert_eq!(decoded, data, "{:?}", profile);
+        }
+    }
+
+    #[test]
+    fn test_profiles_framing_tolerance() {
+        let glued = b"=ybegin line=128 size=5 name=test.bin\nKLMNO=yend size=5\n";
+        let result = Profile::PowerPost.decoder().decode(&glued[..], io::sink());
+        assert!(matches!(result, Err(YencError::InvalidData(_))));
+        for profile in [Profile::Yydecode, Profile::Sabnzbd] {
+            let result = profile.decoder().decode(&glued[..], io::sink());
+            assert!(result.is_ok(), "{:?}", profile);
+        }
+    }
+
+    #[test]
+    fn test_profiles_crc_handling() {
+        let result = Profile::PowerPost
+            .decoder()
+            .decode(CORRUPT_CRC, &mut Vec::new());
+        assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+
+        let mut decoded = Vec::new();
+        let outcome = Profile::Sabnzbd
+            .decoder()
+            .decode(CORRUPT_CRC, &mut decoded)
+            .unwrap();
+        assert_eq!(decoded, vec![0, 1, 2, 3, 4]);
+        assert!(!outcome.crc_ok());
+        assert_eq!(outcome.crc32, Some(0x515ad3cc));
+    }
+}
@@ -0,0 +1,221 @@
+//! `Read` adapter decoding an article as it is read
+
+use std::io::{self, Read};
+
+use crate::decode::{ArticleStream, DecodeWarning, Decoder};
+use crate::error::{Result, YencError};
+use crate::header::{YencHeader, YencPart, YencTrailer};
+
+/// Reader yielding the decoded data of the yEnc article in another reader
+///
+/// Nothing is read until it is needed: `header` parses the header on its
+/// own, and reads decode the data a block at a time. The same checks as
+/// `Decoder::decode` apply, and a CRC32 mismatch shows up as an error from
+/// the read that reaches the trailer. Damage lenient decoding lets pass is
+/// listed by `warnings` once all the data has been read. Reading ends at
+/// the trailer, and whatever follows it is ignored. Errors other than I/O
+/// errors are reported as `io::ErrorKind::InvalidData`, wrapping the
+/// `YencError`.
+///
+/// # Example
+/// ```
+/// use std::io::Read;
+/// use yenc::YencReader;
+///
+/// let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
+/// let mut reader = YencReader::new(&input[..]);
+/// assert_eq!(reader.header().unwrap().name, b"test.bin");
+///
+/// let mut data = Vec::new();
+/// reader.read_to_end(&mut data).unwrap();
+/// assert_eq!(data, b"!\"#$%");
+/// ```
+pub struct YencReader<R> {
+    stream: ArticleStream<R>,
+    /// Bytes of the current block already read
+    pos: usize,
+}
+
+impl<R: Read> YencReader<R> {
+    /// Decode the article in `inner` with default decoder settings
+    pub fn new(inner: R) -> Self {
+        Self {
+            stream: ArticleStream::new(&Decoder::new(), inner)
+                .expect("default decoder settings are valid"),
+            pos: 0,
+        }
+    }
+
+    /// The article header, parsed on first use
+    ///
+    /// # Errors
+    /// Returns the error found looking for the header, such as
+    /// `YencError::InvalidHeader` if the input has none.
+    pub fn header(&mut self) -> Result<&YencHeader> {
+        while self.stream.header().is_none() {
+            if !self.advance()? {
+                return Err(YencError::InvalidHeader("No header found".to_string()));
+            }
+        }
+        Ok(self
+            .stream
+            .header()
+            .expect("loop ends once the header is parsed"))
+    }
+
+    /// The `=ypart` line, once reading has reached the data
+    pub fn part(&self) -> Option<&YencPart> {
+        self.stream.part()
+    }
+
+    /// The trailer, once all the data has been read
+    pub fn trailer(&self) -> Option<&YencTrailer> {
+        self.stream.trailer()
+    }
+
+    /// Damage that lenient decoding let pass, once all the data has been read
+    ///
+    /// Holds what `Decoder::decode` reports in `DecodeOutcome::warnings`,
+    /// such as a size mismatch or, with `Decoder::allow_crc_mismatch`, a
+    /// CRC32 mismatch. Complete once a read has returned 0.
+    pub fn warnings(&self) -> &[DecodeWarning] {
+        self.stream.warnings()
+    }
+
+    /// Move the stream on, returning whether the article went on
+    fn advance(&mut self) -> Result<bool> {
+        self.pos = 0;
+        Ok(self.stream.advance()?.is_some())
+    }
+}
+
+impl<R: Read> Read for YencReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.stream.data().len() {
+            match self.advance() {
+                Ok(true) => {}
+                Ok(false) => return Ok(0),
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let data = &self.stream.data()[self.pos..];
+        let n = data.len().min(out.len());
+        out[..n].copy_from_slice(&data[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Decoder {
+    /// Decode the article in `inner` as it is read; see `YencReader`
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` for text mode, which is not
+    /// supported here, or if the options are invalid.
+    pub fn reader<R: Read>(&self, inner: R) -> Result<YencReader<R>> {
+        Ok(YencReader {
+            stream: ArticleStream::new(self, inner)?,
+            pos: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::Encoder;
+
+    #[test]
+    fn test_reader_matches_decode() {
+        // Several blocks of data, read in pieces that do not line up with them
+        let data: Vec<u8> = (0..200_000).map(|i| (i * 7 + i / 1000) as u8).collect();
+        let encoded = Encoder::new().encode_to_vec(&data, "test.bin").unwrap();
+
+        let mut reader = Decoder::new().reader(&encoded[..]).unwrap();
+        assert_eq!(reader.header().unwrap().size, data.len() as u64);
+        let mut decoded = Vec::new();
+        let mut buf = [0u8; 1000 - 1];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            decoded.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(decoded, data);
+        assert!(reader.trailer().unwrap().crc32.is_some());
+    }
+
+    #[test]
+    fn test_reader_multipart() {
+        let input = b"=ybegin part=1 total=2 line=128 size=10 name=test.bin\n\
+                      =ypart begin=1 end=5\n\
+                      KLMNO\n\
+                      =yend size=5 part=1\n";
+        let mut reader = YencReader::new(&input[..]);
+        assert!(reader.part().is_none());
+
+        let mut decoded = Vec::new();
+        io::copy(&mut reader, &mut decoded).unwrap();
+        assert_eq!(decoded, b"!\"#$%");
+        assert_eq!(reader.part().unwrap().begin, 1);
+        assert_eq!(reader.trailer().unwrap().part, Some(1));
+    }
+
+    #[test]
+    fn test_reader_nul_padding() {
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLM\n\0\nNO\n\0\0\n=yend size=5\n";
+        let mut decoded = Vec::new();
+        YencReader::new(&input[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, b"!\"#\xd6$%");
+    }
+
+    #[test]
+    fn test_reader_warnings() {
+        // Lenient decoding lets the size mismatch and the padding pass
+        let input = b"=ybegin line=128 size=6 name=test.bin\nKLMNO\n\0\n=yend size=6\n";
+        let mut reader = YencReader::new(&input[..]);
+        let mut decoded = Vec::new();
+        reader.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, b"!\"#$%");
+        let (outcome, _) = Decoder::new().decode_to_vec(input).unwrap();
+        assert_eq!(outcome.warnings.len(), 2);
+        assert_eq!(reader.warnings(), outcome.warnings);
+
+        // Strict decoding fails on the mismatch instead
+        let input = b"=ybegin line=128 size=6 name=test.bin\nKLMNO\n=yend size=6\n";
+        let mut reader = Decoder::new().strict().reader(&input[..]).unwrap();
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_reader_trailing_garbage() {
+        let input = b"=ybegin line=128 size=2 name=a\nKL\n=yend size=2\n\
+                      -- \n=y\xff\n=ybegin size=oops\n";
+        let mut reader = YencReader::new(&input[..]);
+        let mut decoded = Vec::new();
+        reader.read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, b"!\"");
+        assert_eq!(reader.read(&mut [0; 8]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_reader_errors() {
+        let mut reader = YencReader::new(&b"no header here\n"[..]);
+        assert!(matches!(reader.header(), Err(YencError::InvalidHeader(_))));
+
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5 crc32=00000000\n";
+        let mut reader = YencReader::new(&input[..]);
+        let err = reader.read_to_end(&mut Vec::new()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let inner = err.into_inner().unwrap().downcast::<YencError>().unwrap();
+        assert!(matches!(*inner, YencError::CrcMismatch { .. }));
+
+        let result = Decoder::new().text_mode().reader(&input[..]);
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+    }
+}
@@ -0,0 +1,146 @@
+//! Locating the yEnc articles in a stream without decoding them
+
+use std::io::{BufRead, BufReader, Read};
+
+use crate::decode::{find_trailer, trim_bytes};
+use crate::error::Result;
+use crate::header::{YencHeader, YencPart, YencTrailer};
+
+/// Where a yEnc article sits in a stream, and what its keyword lines say
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockLocation {
+    /// Byte offset of the `=ybegin` line
+    pub start: u64,
+    /// Byte offset just past the `=yend` line, or past the last data line
+    /// if the article has no trailer
+    pub end: u64,
+    /// Parsed `=ybegin` fields
+    pub header: YencHeader,
+    /// Parsed `=ypart` fields (multi-part articles only)
+    pub part: Option<YencPart>,
+    /// Parsed `=yend` fields, if the trailer was found
+    pub trailer: Option<YencTrailer>,
+    /// Number of encoded data lines
+    pub lines: usize,
+}
+
+/// Find every yEnc article in `reader`, with its offsets and keyword lines
+///
+/// Only the keyword lines are parsed; data lines are counted but not
+/// decoded, so nothing is checked against the sizes or CRC32s announced.
+/// Text between articles is skipped, and a trailer glued to the last data
+/// line is found like the decoder finds it. An article without a trailer
+/// ends at the next `=ybegin` line. To decode an article found this way,
+/// seek to `start` and hand the next `end - start` bytes to a `Decoder`.
+///
+/// # Errors
+/// Returns the parse error of the first malformed keyword line.
+///
+/// # Example
+/// ```
+/// let input = b"From: poster\n\
+///               =ybegin line=128 size=2 name=a.txt\nKL\n=yend size=2\n\
+///               =ybegin line=128 size=3 name=b.txt\nMNO\n=yend size=3\n";
+///
+/// let blocks = yenc::scan(&input[..]).unwrap();
+/// assert_eq!(blocks.len(), 2);
+/// assert_eq!(blocks[0].start, 13);
+/// assert_eq!(blocks[1].header.name, b"b.txt");
+/// assert_eq!(blocks[1].end, input.len() as u64);
+/// ```
+pub fn scan<R: Read>(reader: R) -> Result<Vec<BlockLocation>> {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+    let mut blocks = Vec::new();
+    let mut current: Option<BlockLocation> = None;
+    let mut offset = 0u64;
+
+    loop {
+        line.clear();
+        let bytes_read = reader.read_until(b'\n', &mut line)?;
+        if bytes_read == 0 {
+            break;
+        }
+        let start = offset;
+        offset += bytes_read as u64;
+
+        let trimmed = trim_bytes(&line);
+        if trimmed.starts_with(b"=ybegin ") {
+            // An article without a trailer ends where the next one begins
+            blocks.extend(current.take());
+            current = Some(BlockLocation {
+                start,
+                end: offset,
+                header: YencHeader::parse_bytes(trimmed)?,
+                part: None,
+                trailer: None,
+                lines: 0,
+            });
+            continue;
+        }
+
+        let Some(block) = current.as_mut() else {
+            continue;
+        };
+        block.end = offset;
+        if block.lines == 0 && block.part.is_none() && trimmed.starts_with(b"=ypart ") {
+            block.part = Some(YencPart::parse_bytes(trimmed)?);
+        } else if let Some(pos) = find_trailer(trimmed) {
+            // The trailer may be glued to the last data line
+            if pos > 0 {
+                block.lines += 1;
+            }
+            block.trailer = Some(YencTrailer::parse_bytes(&trimmed[pos..])?);
+            blocks.extend(current.take());
+        } else {
+            block.lines += 1;
+        }
+    }
+
+    blocks.extend(current);
+    Ok(blocks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::YencError;
+
+    #[test]
+    fn test_scan() {
+        let first = &b"=ybegin part=1 total=2 line=128 size=10 name=test.bin\n\
+                       =ypart begin=1 end=5\n\
+                       *+,-=n\n\
+                       =yend size=5 part=1 pcrc32=515ad3cc\n"[..];
+        let cut = &b"=ybegin line=128 size=3 name=cut.bin\nKLM\nNOP\n"[..];
+        let last = &b"=ybegin line=128 size=2 name=last.bin\r\nNO\r\n=yend size=2\r\n"[..];
+        let input = [&b"From: poster\n\n"[..], first, b"-- \n", cut, last, b"\n"].concat();
+
+        let blocks = scan(&input[..]).unwrap();
+        assert_eq!(blocks.len(), 3);
+        for (block, article) in blocks.iter().zip([first, cut, last]) {
+            assert_eq!(&input[block.start as usize..block.end as usize], article);
+        }
+
+        assert_eq!(blocks[0].part.as_ref().unwrap().end, 5);
+        assert_eq!(blocks[0].trailer.as_ref().unwrap().pcrc32, Some(0x515ad3cc));
+        assert_eq!(blocks[0].lines, 1);
+        assert_eq!(blocks[1].header.name, b"cut.bin");
+        assert!(blocks[1].trailer.is_none());
+        assert_eq!(blocks[1].lines, 2);
+        assert_eq!(blocks[2].trailer.as_ref().unwrap().size, 2);
+
+        // A trailer glued to the last data line still ends the article
+        let glued = &b"=ybegin line=128 size=3 name=glued.bin\nKLM=yend size=3\n"[..];
+        let input = [glued, b"=ybegin line=128 size=2 name=b.bin\nKL\n"].concat();
+        let blocks = scan(&input[..]).unwrap();
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].end, glued.len() as u64);
+        assert_eq!(blocks[0].trailer.as_ref().unwrap().size, 3);
+        assert_eq!(blocks[0].lines, 1);
+
+        assert!(scan(&b"no articles here\n"[..]).unwrap().is_empty());
+        let result = scan(&b"=ybegin line=128 name=test.bin\n"[..]);
+        assert!(matches!(result, Err(YencError::MissingField(_))));
+    }
+}
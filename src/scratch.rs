@@ -0,0 +1,39 @@
+//! Reusable working buffers for encoding and decoding
+
+/// Working buffers lent to a single encode or decode call
+///
+/// Every call of `Encoder::encode` or `Decoder::decode` allocates its own
+/// input and output buffers and frees them when it returns. A process
+/// handling one article after another can instead keep a `Scratch` per
+/// thread and pass it to the `*_with_scratch` methods, so the buffers are
+/// allocated once and then reused.
+///
+/// The buffers keep the capacity of the largest article they have served;
+/// drop the `Scratch` to give that memory back.
+///
+/// # Example
+/// ```
+/// use yenc::{Decoder, Scratch};
+///
+/// let decoder = Decoder::new();
+/// let mut scratch = Scratch::new();
+/// for article in [&b"=ybegin line=128 size=2 name=a\nKL\n=yend size=2\n"[..]; 3] {
+///     let mut output = Vec::new();
+///     decoder
+///         .decode_with_scratch(article, &mut output, &mut scratch)
+///         .unwrap();
+///     assert_eq!(output, b"!\"");
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Scratch {
+    pub(crate) input: Vec<u8>,
+    pub(crate) output: Vec<u8>,
+}
+
+impl Scratch {
+    /// Create empty buffers; they grow on first use
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
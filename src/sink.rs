@@ -0,0 +1,308 @@
+//! Positioned output for decoding parts straight into their place
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+use crate::assemble::part_range;
+use crate::decode::{DecodeOutcome, Decoder};
+use crate::error::{Result, YencError};
+
+/// Storage the decoded data of a file is written into at given offsets
+///
+/// `Decoder::decode_into` writes every article at the offset its `=ypart`
+/// line gives, so the storage decides nothing about placement. Implemented
+/// for `File`, `Vec<u8>` (which grows as needed), byte slices and, with the
+/// `mmap` feature, `memmap2::MmapMut`; implement it for other backends.
+pub trait DecodeSink {
+    /// Write all of `data` starting at the 0-based `offset`
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()>;
+}
+
+/// Write all of `buf` at `offset` without moving a shared file cursor
+fn write_all_at(file: &File, buf: &[u8], offset: u64) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::FileExt::write_all_at(file, buf, offset)
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::FileExt;
+
+        let (mut buf, mut offset) = (buf, offset);
+        while !buf.is_empty() {
+            match file.seek_write(buf, offset) {
+                Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+                Ok(n) => {
+                    buf = &buf[n..];
+                    offset += n as u64;
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(())
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        let _ = (file, buf, offset);
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "positioned writes are not supported on this platform",
+        ))
+    }
+}
+
+impl DecodeSink for File {
+    /// Write at `offset` without moving the file cursor
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        write_all_at(self, data, offset)
+    }
+}
+
+impl DecodeSink for &File {
+    /// Write at `offset` without moving the file cursor, so several threads
+    /// can fill different parts of the same file
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        write_all_at(self, data, offset)
+    }
+}
+
+impl DecodeSink for Vec<u8> {
+    /// Write at `offset`, growing the vector with zeros as needed
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let start = usize::try_from(offset)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "offset out of range"))?;
+        let end = start + data.len();
+        if self.len() < end {
+            self.resize(end, 0);
+        }
+        self[start..end].copy_from_slice(data);
+        Ok(())
+    }
+}
+
+impl DecodeSink for [u8] {
+    /// Write at `offset`, failing with `io::ErrorKind::InvalidInput` past
+    /// the end of the slice
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let range = usize::try_from(offset)
+            .ok()
+            .and_then(|start| Some(start..start.checked_add(data.len())?))
+            .filter(|range| range.end <= self.len());
+        let Some(range) = range else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "writing {} bytes at offset {} goes past the end of {} bytes",
+                    data.len(),
+                    offset,
+                    self.len()
+                ),
+            ));
+        };
+        self[range].copy_from_slice(data);
+        Ok(())
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl DecodeSink for memmap2::MmapMut {
+    /// Write into the map at `offset`; see the impl for `[u8]`
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        self[..].write_at(offset, data)
+    }
+}
+
+/// Writer passing decoded bytes on to a window of a sink
+///
+/// Bytes past the end of the window are dropped and reported by `finish`.
+pub(crate) struct SinkWindowWriter<'a, S: ?Sized> {
+    sink: &'a mut S,
+    start: u64,
+    len: u64,
+    written: u64,
+    overflow: bool,
+}
+
+impl<'a, S: DecodeSink + ?Sized> SinkWindowWriter<'a, S> {
+    /// Start writing at `window.start` of `sink`
+    pub(crate) fn new(sink: &'a mut S, window: Range<u64>) -> Self {
+        Self {
+            sink,
+            start: window.start,
+            len: window.end - window.start,
+            written: 0,
+            overflow: false,
+        }
+    }
+
+    /// Number of bytes written into the window
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidData` if the part decoded to more bytes
+    /// than the window holds.
+    pub(crate) fn finish(&self) -> Result<u64> {
+        if self.overflow {
+            return Err(YencError::InvalidData(format!(
+                "Part decodes to more than the {} bytes of its range",
+                self.len
+            )));
+        }
+        Ok(self.written)
+    }
+}
+
+impl<S: DecodeSink + ?Sized> Write for SinkWindowWriter<'_, S> {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        let n = (data.len() as u64).min(self.len - self.written) as usize;
+        self.sink.write_at(self.start + self.written, &data[..n])?;
+        self.written += n as u64;
+        self.overflow |= n < data.len();
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Decoder {
+    /// Decode an article into `sink` at the offset given by its `=ypart` line
+    ///
+    /// Single-part articles go to offset 0. Parts can be decoded in any
+    /// order, each straight into its place, with the same checks as
+    /// `decode`. Nothing keeps track of which parts have been written; see
+    /// `FileAssembler` for that.
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` for text mode, which would shift
+    /// the decoded bytes out of their range, and `YencError::InvalidData`
+    /// if the part range lies outside the file size in the header, or the
+    /// part decodes to more bytes than its range holds; decoding errors and
+    /// errors of the sink are passed through.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::{Decoder, Encoder, MultiPartInfo};
+    ///
+    /// let mut second = Vec::new();
+    /// let part_info = MultiPartInfo::new(2, 2, 4, 6, 6);
+    /// Encoder::new().encode_part(&b"def"[..], &mut second, "abc.txt", &part_info).unwrap();
+    ///
+    /// let mut file = Vec::new();
+    /// Decoder::new().decode_into(&second[..], &mut file).unwrap();
+    /// assert_eq!(file, b"\0\0\0def");
+    /// ```
+    pub fn decode_into<R, S>(&self, reader: R, sink: &mut S) -> Result<DecodeOutcome>
+    where
+        R: Read,
+        S: DecodeSink + ?Sized,
+    {
+        let mut slot = None;
+        let slot_ref = &mut slot;
+
+        let result = self.decode_with(reader, move |header, part| {
+            let range = part_range(header, part, header.size)?;
+            Ok(slot_ref.insert(SinkWindowWriter::new(sink, range)))
+        })?;
+
+        if let Some(writer) = slot {
+            writer.finish()?;
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::{Encoder, MultiPartInfo};
+
+    /// The articles of `data` split into parts of `part_size` bytes
+    fn encode_parts(data: &[u8], part_size: usize) -> Vec<Vec<u8>> {
+        let total = data.len().div_ceil(part_size);
+        (0..total)
+            .map(|index| {
+                let begin = index * part_size + 1;
+                let end = (begin - 1 + part_size).min(data.len());
+                let part_info = MultiPartInfo::new(
+                    index + 1,
+                    total,
+                    begin as u64,
+                    end as u64,
+                    data.len() as u64,
+                );
+                let mut encoded = Vec::new();
+                Encoder::new()
+                    .encode_part(&data[begin - 1..end], &mut encoded, "test.bin", &part_info)
+                    .unwrap();
+                encoded
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_into() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i * 3 + i / 77) as u8).collect();
+        let parts = encode_parts(&data, 3000);
+
+        let mut output = Vec::new();
+        for part in parts.iter().rev() {
+            Decoder::new().decode_into(&part[..], &mut output).unwrap();
+        }
+        assert_eq!(output, data);
+
+        let mut buffer = vec![0u8; data.len()];
+        for part in &parts {
+            Decoder::new()
+                .decode_into(&part[..], &mut buffer[..])
+                .unwrap();
+        }
+        assert_eq!(buffer, data);
+
+        // A slice too short for the file fails the part that does not fit
+        let mut short = [0u8; 5000];
+        let result = Decoder::new().decode_into(&parts[1][..], &mut short[..]);
+        assert!(matches!(result, Err(YencError::Io(_))));
+
+        let path = std::env::temp_dir().join(format!("yenc-sink-{}", std::process::id()));
+        let mut file = File::create(&path).unwrap();
+        for part in parts.iter().rev() {
+            Decoder::new().decode_into(&part[..], &mut file).unwrap();
+        }
+        drop(file);
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_decode_into_overflow() {
+        // The part line promises 3 bytes, the data holds 5
+        let input = b"=ybegin part=1 total=2 line=128 size=6 name=test.bin\n\
+                      =ypart begin=1 end=3\n\
+                      KLMNO\n";
+        let mut output = Vec::new();
+        let result = Decoder::new().decode_into(&input[..], &mut output);
+        assert!(matches!(result, Err(YencError::InvalidData(_))));
+        assert_eq!(output, b"!\"#");
+
+        let mut output = Vec::new();
+        let result = Decoder::new()
+            .text_mode()
+            .decode_into(&input[..], &mut output);
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+        assert!(output.is_empty());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_decode_into_mmap() {
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i * 5) as u8).collect();
+        let mut map = memmap2::MmapMut::map_anon(data.len()).unwrap();
+        for part in encode_parts(&data, 4096) {
+            Decoder::new().decode_into(&part[..], &mut map).unwrap();
+        }
+        assert_eq!(&map[..], &data[..]);
+    }
+}
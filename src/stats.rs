@@ -0,0 +1,220 @@
+//! Encoding statistics for yEnc articles
+
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader, Read};
+
+use crate::consts::ESCAPE_CHAR;
+use crate::decode::{Decoder, trim_bytes};
+use crate::error::{Result, YencError};
+use crate::header::{YencHeader, YencPart, YencTrailer};
+
+/// Statistics gathered from a yEnc-encoded article
+#[derive(Debug, Clone, PartialEq)]
+pub struct YencStats {
+    /// Parsed `=ybegin` fields
+    pub header: YencHeader,
+    /// Parsed `=ypart` fields (multi-part articles only)
+    pub part: Option<YencPart>,
+    /// Parsed `=yend` fields, if the trailer was found
+    pub trailer: Option<YencTrailer>,
+    /// Number of encoded data lines
+    pub lines: u64,
+    /// Histogram of data line lengths (line length -> number of lines)
+    pub line_lengths: BTreeMap<usize, u64>,
+    /// Encoded data bytes, excluding line terminators
+    pub encoded_bytes: u64,
+    /// Number of escape sequences in the data
+    pub escapes: u64,
+    /// Number of bytes the data decodes to
+    pub decoded_bytes: u64,
+    /// Total article size from `=ybegin` to `=yend`, including keyword lines
+    pub total_bytes: u64,
+}
+
+impl YencStats {
+    /// Fraction of decoded bytes that were escaped (0.0 to 1.0)
+    pub fn escape_ratio(&self) -> f64 {
+        if self.decoded_bytes == 0 {
+            return 0.0;
+        }
+        self.escapes as f64 / self.decoded_bytes as f64
+    }
+
+    /// Size overhead of the whole article relative to the decoded data, in percent
+    pub fn overhead_percent(&self) -> f64 {
+        if self.decoded_bytes == 0 {
+            return 0.0;
+        }
+        (self.total_bytes as f64 - self.decoded_bytes as f64) * 100.0 / self.decoded_bytes as f64
+    }
+
+    /// Length of the longest data line
+    pub fn max_line_length(&self) -> Option<usize> {
+        self.line_lengths.keys().next_back().copied()
+    }
+}
+
+/// Gather encoding statistics from a yEnc article without writing decoded data
+///
+/// CRCs and sizes are not validated; the goal is to describe how an article
+/// was encoded, even if it is damaged. Data lines are delimited like a
+/// lenient `Decoder` does it: only the line ending is dropped, and a trailer
+/// glued to the last data line still ends the data. Escapes are counted per
+/// line, so a lone `=` at the end of a line counts as an escape of nothing.
+///
+/// # Example
+/// ```
+/// let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5\n";
+///
+/// let stats = yenc::analyze(&input[..]).unwrap();
+/// assert_eq!(stats.lines, 1);
+/// assert_eq!(stats.escapes, 1);
+/// assert_eq!(stats.decoded_bytes, 5);
+/// ```
+pub fn analyze<R: Read>(reader: R) -> Result<YencStats> {
+    let mut reader = BufReader::new(reader);
+    let mut line = Vec::new();
+
+    let header = loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Err(YencError::InvalidHeader("No header found".to_string()));
+        }
+
+        let trimmed = trim_bytes(&line);
+        if trimmed.starts_with(b"=ybegin ") {
+            break YencHeader::parse_bytes(trimmed)?;
+        }
+    };
+
+    let mut stats = YencStats {
+        header,
+        part: None,
+        trailer: None,
+        lines: 0,
+        line_lengths: BTreeMap::new(),
+        encoded_bytes: 0,
+        escapes: 0,
+        decoded_bytes: 0,
+        total_bytes: line.len() as u64,
+    };
+
+    let decoder = Decoder::new();
+    loop {
+        line.clear();
+        let bytes_read = reader.read_until(b'\n', &mut line)?;
+        if bytes_read == 0 {
+            break;
+        }
+        stats.total_bytes += bytes_read as u64;
+
+        let trimmed = trim_bytes(&line);
+        if stats.lines == 0 && stats.part.is_none() && trimmed.starts_with(b"=ypart ") {
+            stats.part = Some(YencPart::parse_bytes(trimmed)?);
+            continue;
+        }
+
+        let data = &line[decoder.data_range(&line)];
+        let (data, trailer) = match decoder.split_trailer(data)? {
+            Some(pos) => (&data[..pos], Some(&data[pos..])),
+            None => (data, None),
+        };
+
+        if trailer.is_none() || !data.is_empty() {
+            stats.lines += 1;
+            *stats.line_lengths.entry(data.len()).or_insert(0) += 1;
+            stats.encoded_bytes += data.len() as u64;
+
+            let mut escaped = false;
+            for &byte in data {
+                if byte == ESCAPE_CHAR && !escaped {
+                    escaped = true;
+                    stats.escapes += 1;
+                    continue;
+                }
+                escaped = false;
+                stats.decoded_bytes += 1;
+            }
+        }
+
+        if let Some(trailer) = trailer {
+            stats.trailer = Some(YencTrailer::parse_bytes(trailer)?);
+            break;
+        }
+    }
+
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_simple() {
+        let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5 crc32=515ad3cc\n";
+        let stats = analyze(&input[..]).unwrap();
+
+        assert_eq!(stats.header.name, b"test.bin");
+        assert!(stats.part.is_none());
+        assert_eq!(stats.trailer.unwrap().crc32, Some(0x515ad3cc));
+        assert_eq!(stats.lines, 1);
+        assert_eq!(stats.line_lengths.get(&6), Some(&1));
+        assert_eq!(stats.encoded_bytes, 6);
+        assert_eq!(stats.escapes, 1);
+        assert_eq!(stats.decoded_bytes, 5);
+        assert_eq!(stats.total_bytes, input.len() as u64);
+    }
+
+    #[test]
+    fn test_analyze_data_lines_like_decoder() {
+        // Spaces at the ends of data lines are data, a lone `=` does not
+        // escape the next line, and the trailer may be glued to the data
+        let input = b"=ybegin line=128 size=6 name=test.bin\r\n KL=\r\n=}M =yend size=6\r\n";
+        let stats = analyze(&input[..]).unwrap();
+
+        assert_eq!(stats.trailer.unwrap().size, 6);
+        assert_eq!(stats.lines, 2);
+        assert_eq!(stats.line_lengths.get(&4), Some(&2));
+        assert_eq!(stats.encoded_bytes, 8);
+        assert_eq!(stats.escapes, 2);
+        assert_eq!(stats.decoded_bytes, 6);
+        assert_eq!(stats.total_bytes, input.len() as u64);
+    }
+
+    #[test]
+    fn test_analyze_multipart() {
+        let input = b"=ybegin part=1 total=2 line=128 size=10 name=test.bin\n\
+                      =ypart begin=1 end=5\n\
+                      *+,-=n\n\
+                      =yend size=5 part=1 pcrc32=515ad3cc\n";
+        let stats = analyze(&input[..]).unwrap();
+
+        assert_eq!(stats.part, Some(YencPart { begin: 1, end: 5 }));
+        assert_eq!(stats.lines, 1);
+        assert_eq!(stats.decoded_bytes, 5);
+    }
+
+    #[test]
+    fn test_analyze_line_distribution() {
+        let data: Vec<u8> = (0..1000).map(|i| (i * 7 + 13) as u8).collect();
+        let mut encoded = Vec::new();
+        crate::Encoder::new()
+            .line_length(64)
+            .encode(&data[..], &mut encoded, "dist.bin")
+            .unwrap();
+
+        let stats = analyze(&encoded[..]).unwrap();
+        assert_eq!(stats.decoded_bytes, 1000);
+        assert_eq!(stats.lines, stats.line_lengths.values().sum::<u64>());
+        assert!(stats.max_line_length().unwrap() <= 65);
+        assert!(stats.escape_ratio() > 0.0);
+        assert!(stats.overhead_percent() > 0.0);
+    }
+
+    #[test]
+    fn test_analyze_missing_header() {
+        let result = analyze(&b"garbage\n"[..]);
+        assert!(matches!(result, Err(YencError::InvalidHeader(_))));
+    }
+}
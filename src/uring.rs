@@ -0,0 +1,386 @@
+//! `io_uring` file reader and writer for `encode_file` and `decode_file`
+//!
+//! Both keep several blocks in flight: the reader has the next blocks of the
+//! file already being read while the current one is encoded or decoded, and
+//! the writer hands full blocks to the kernel and carries on without waiting
+//! for them. Reading, the CPU transform and writing thus overlap instead of
+//! taking turns. Linux only, behind the `io-uring` feature.
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::fd::AsRawFd;
+use std::sync::OnceLock;
+
+use io_uring::{IoUring, opcode, types};
+
+/// Size of each block read or written
+const BLOCK: usize = 256 * 1024;
+
+/// Blocks in flight per file
+const DEPTH: usize = 4;
+
+/// Whether the kernel lets this process set up a ring
+///
+/// Old kernels lack `io_uring`, and containers often filter it out; the
+/// file functions then fall back to buffered I/O.
+pub(crate) fn available() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| IoUring::new(2).is_ok())
+}
+
+/// State of one block buffer
+enum Slot {
+    /// Not in use
+    Idle,
+    /// Waiting for the kernel
+    InFlight,
+    /// Done after `len` bytes; for reads, `pos` of them have been consumed
+    Filled { len: usize, pos: usize },
+    /// Operation failed
+    Failed(io::Error),
+}
+
+/// Block buffers with an operation each, shared by reader and writer
+struct Blocks {
+    ring: IoUring,
+    file: File,
+    /// Whether the operations are writes rather than reads
+    write: bool,
+    bufs: Vec<Box<[u8]>>,
+    slots: Vec<Slot>,
+    /// File offset and length of the operation on each block
+    ops: Vec<(u64, usize)>,
+    /// Bytes of the operation on each block already done by the kernel
+    done: Vec<usize>,
+    in_flight: usize,
+}
+
+impl Blocks {
+    fn new(file: File, write: bool) -> io::Result<Self> {
+        Ok(Self {
+            ring: IoUring::new(DEPTH as u32)?,
+            file,
+            write,
+            bufs: (0..DEPTH)
+                .map(|_| vec![0; BLOCK].into_boxed_slice())
+                .collect(),
+            slots: (0..DEPTH).map(|_| Slot::Idle).collect(),
+            ops: vec![(0, 0); DEPTH],
+            done: vec![0; DEPTH],
+            in_flight: 0,
+        })
+    }
+
+    /// Start reading or writing `len` bytes of block `index` at `offset`
+    fn submit(&mut self, index: usize, offset: u64, len: usize) -> io::Result<()> {
+        self.ops[index] = (offset, len);
+        self.done[index] = 0;
+        self.slots[index] = Slot::InFlight;
+        self.push(index)
+    }
+
+    /// Queue the rest of the operation on block `index`
+    fn push(&mut self, index: usize) -> io::Result<()> {
+        let (offset, len) = self.ops[index];
+        let done = self.done[index];
+        let fd = types::Fd(self.file.as_raw_fd());
+        // SAFETY: `done < len <= BLOCK`, so the range lies within the buffer
+        let ptr = unsafe { self.bufs[index].as_mut_ptr().add(done) };
+        let remaining = (len - done) as u32;
+        let offset = offset + done as u64;
+        let entry = if self.write {
+            opcode::Write::new(fd, ptr, remaining)
+                .offset(offset)
+                .build()
+        } else {
+            opcode::Read::new(fd, ptr, remaining).offset(offset).build()
+        }
+        .user_data(index as u64);
+
+        // SAFETY: the buffer is boxed, so it stays put, and it is neither
+        // touched nor freed until the completion for `index` has been reaped
+        // (see `Drop`). The queue has a free entry, as there are no more
+        // operations than entries.
+        unsafe { self.ring.submission().push(&entry) }
+            .map_err(|_| io::Error::other("io_uring submission queue full"))?;
+        self.in_flight += 1;
+        self.ring.submit()?;
+        Ok(())
+    }
+
+    /// Wait for at least one operation to complete and record the results
+    fn reap(&mut self) -> io::Result<()> {
+        loop {
+            match self.ring.submit_and_wait(1) {
+                Ok(_) => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        let completed: Vec<(usize, i32)> = self
+            .ring
+            .completion()
+            .map(|cqe| (cqe.user_data() as usize, cqe.result()))
+            .collect();
+        for (index, result) in completed {
+            self.in_flight -= 1;
+            let (_, len) = self.ops[index];
+            if result < 0 {
+                self.slots[index] = Slot::Failed(io::Error::from_raw_os_error(-result));
+                continue;
+            }
+
+            self.done[index] += result as usize;
+            let done = self.done[index];
+            if result == 0 && self.write {
+                self.slots[index] = Slot::Failed(io::ErrorKind::WriteZero.into());
+            } else if result == 0 || done == len {
+                // A read that comes up short ends the file early
+                self.slots[index] = Slot::Filled { len: done, pos: 0 };
+            } else {
+                // Partial transfer, queue the rest
+                self.push(index)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Wait for every operation still in flight
+    fn drain(&mut self) -> io::Result<()> {
+        while self.in_flight > 0 {
+            self.reap()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Blocks {
+    fn drop(&mut self) {
+        // The kernel may still be using the buffers
+        while self.in_flight > 0 {
+            if self.reap().is_err() {
+                // Leak the buffers rather than free them under the kernel
+                mem::forget(mem::take(&mut self.bufs));
+                return;
+            }
+        }
+    }
+}
+
+/// Reader keeping the next blocks of a file in flight
+pub(crate) struct UringReader {
+    blocks: Blocks,
+    /// Block holding the next bytes of the file
+    head: usize,
+    /// Offset of the next read to start
+    next: u64,
+    size: u64,
+}
+
+impl UringReader {
+    pub(crate) fn new(file: File) -> io::Result<Self> {
+        let size = file.metadata()?.len();
+        let mut reader = Self {
+            blocks: Blocks::new(file, false)?,
+            head: 0,
+            next: 0,
+            size,
+        };
+        for index in 0..DEPTH {
+            reader.start(index)?;
+        }
+        Ok(reader)
+    }
+
+    /// Start reading the next block of the file into block `index`, if any is left
+    fn start(&mut self, index: usize) -> io::Result<()> {
+        if self.next >= self.size {
+            self.blocks.slots[index] = Slot::Idle;
+            return Ok(());
+        }
+        let len = (self.size - self.next).min(BLOCK as u64) as usize;
+        self.blocks.submit(index, self.next, len)?;
+        self.next += len as u64;
+        Ok(())
+    }
+
+    /// Size of the file when it was opened
+    pub(crate) fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl Read for UringReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match &mut self.blocks.slots[self.head] {
+                Slot::Idle => return Ok(0),
+                Slot::InFlight => self.blocks.reap()?,
+                Slot::Failed(_) => {
+                    let failed = mem::replace(&mut self.blocks.slots[self.head], Slot::Idle);
+                    if let Slot::Failed(err) = failed {
+                        return Err(err);
+                    }
+                }
+                Slot::Filled { len, pos } => {
+                    let n = out.len().min(*len - *pos);
+                    out[..n].copy_from_slice(&self.blocks.bufs[self.head][*pos..*pos + n]);
+                    *pos += n;
+                    let short = *len < self.blocks.ops[self.head].1;
+                    if *pos == *len {
+                        if short {
+                            // The file shrank; nothing after this is valid
+                            self.next = self.size;
+                        }
+                        self.start(self.head)?;
+                        self.head = (self.head + 1) % DEPTH;
+                    }
+                    if n > 0 || out.is_empty() {
+                        return Ok(n);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Writer handing full blocks to the kernel without waiting for them
+pub(crate) struct UringWriter {
+    blocks: Blocks,
+    /// Block being filled
+    current: usize,
+    /// Bytes in the current block
+    len: usize,
+    /// Offset of the current block in the file
+    offset: u64,
+}
+
+impl UringWriter {
+    pub(crate) fn new(file: File) -> io::Result<Self> {
+        Ok(Self {
+            blocks: Blocks::new(file, true)?,
+            current: 0,
+            len: 0,
+            offset: 0,
+        })
+    }
+
+    /// Write out everything and wait for it, reporting any error
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.flush()
+    }
+
+    /// Wait until block `index` is free, returning the error of its last write
+    fn wait_for(&mut self, index: usize) -> io::Result<()> {
+        while matches!(self.blocks.slots[index], Slot::InFlight) {
+            self.blocks.reap()?;
+        }
+        match mem::replace(&mut self.blocks.slots[index], Slot::Idle) {
+            Slot::Failed(err) => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    /// Start writing the current block and move on to the next one
+    fn submit_current(&mut self) -> io::Result<()> {
+        if self.len == 0 {
+            return Ok(());
+        }
+        self.blocks.submit(self.current, self.offset, self.len)?;
+        self.offset += self.len as u64;
+        self.len = 0;
+        self.current = (self.current + 1) % DEPTH;
+        self.wait_for(self.current)
+    }
+}
+
+impl Write for UringWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(BLOCK - self.len);
+        self.blocks.bufs[self.current][self.len..self.len + n].copy_from_slice(&buf[..n]);
+        self.len += n;
+        if self.len == BLOCK {
+            self.submit_current()?;
+        }
+        Ok(n)
+    }
+
+    /// Write out the partial block and wait until everything is written
+    fn flush(&mut self) -> io::Result<()> {
+        self.submit_current()?;
+        self.blocks.drain()?;
+        for index in 0..DEPTH {
+            self.wait_for(index)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for UringWriter {
+    fn drop(&mut self) {
+        // Like `BufWriter`, write out what is left and ignore errors
+        let _ = self.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("yenc-uring-{}-{}", std::process::id(), name))
+    }
+
+    /// More blocks than are in flight at once, and a partial last block
+    fn sample() -> Vec<u8> {
+        (0..(DEPTH * 2 + 1) * BLOCK + 123)
+            .map(|i| (i * 31 + i / BLOCK) as u8)
+            .collect()
+    }
+
+    #[test]
+    fn test_reader() {
+        if !available() {
+            return;
+        }
+        let path = temp_path("reader");
+        let data = sample();
+        std::fs::write(&path, &data).unwrap();
+
+        // Reads of odd sizes straddle the block boundaries
+        let mut reader = UringReader::new(File::open(&path).unwrap()).unwrap();
+        assert_eq!(reader.size(), data.len() as u64);
+        let mut read = Vec::new();
+        let mut buf = vec![0; 100_003];
+        loop {
+            let n = reader.read(&mut buf).unwrap();
+            if n == 0 {
+                break;
+            }
+            read.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(read, data);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_writer() {
+        if !available() {
+            return;
+        }
+        let path = temp_path("writer");
+        let data = sample();
+
+        let mut writer = UringWriter::new(File::create(&path).unwrap()).unwrap();
+        for chunk in data.chunks(100_003) {
+            writer.write_all(chunk).unwrap();
+        }
+        writer.finish().unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,159 @@
+//! Integrity checks that decode an article without keeping the data
+
+use std::io::{self, Read};
+
+use crate::decode::Decoder;
+use crate::error::{Result, YencError};
+use crate::header::{YencHeader, YencPart, YencTrailer};
+
+/// What verifying an article found
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyReport {
+    /// Parsed `=ybegin` fields
+    pub header: YencHeader,
+    /// Parsed `=ypart` fields (multi-part articles only)
+    pub part: Option<YencPart>,
+    /// Parsed `=yend` fields
+    pub trailer: YencTrailer,
+    /// Number of bytes the data decodes to
+    pub decoded_bytes: u64,
+    /// CRC32 of the decoded data
+    pub crc32: u32,
+}
+
+impl VerifyReport {
+    /// Whether the trailer announced a CRC32 to check the data against
+    ///
+    /// That is `pcrc32=` for parts and `crc32=` otherwise. Without one, only
+    /// the sizes were checked.
+    pub fn crc_checked(&self) -> bool {
+        if self.part.is_some() {
+            self.trailer.pcrc32.is_some()
+        } else {
+            self.trailer.crc32.is_some()
+        }
+    }
+}
+
+impl Decoder {
+    /// Check an article for damage, decoding it without writing the data
+    ///
+    /// The data is decoded and hashed a block at a time and then dropped,
+    /// so checking a downloaded segment costs no more than the decoding
+    /// itself. The CRC32 is always computed, even with `no_crc_check`, and
+    /// text mode, repair mode and `allow_crc_mismatch` are ignored. On top
+    /// of the checks of `decode`, the decoded size has to match the trailer
+    /// and, for single-part articles, the header.
+    ///
+    /// # Errors
+    /// Returns `YencError::CrcMismatch` for damaged data, and
+    /// `YencError::InvalidData` if the sizes do not match or the article
+    /// has no trailer.
+    ///
+    /// # Example
+    /// ```
+    /// use yenc::{Decoder, YencError};
+    ///
+    /// let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5 crc32=b05f5b44\n";
+    /// let report = Decoder::new().verify(&input[..]).unwrap();
+    /// assert_eq!(report.decoded_bytes, 5);
+    /// assert!(report.crc_checked());
+    ///
+    /// let damaged = b"=ybegin line=128 size=5 name=test.bin\nKLMNP\n=yend size=5 crc32=b05f5b44\n";
+    /// let result = Decoder::new().verify(&damaged[..]);
+    /// assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+    /// ```
+    pub fn verify<R: Read>(&self, reader: R) -> Result<VerifyReport> {
+        let mut decoder = self.clone();
+        decoder.validate_crc = true;
+        decoder.text_mode = false;
+        decoder.repair = false;
+        decoder.allow_crc_mismatch = false;
+        let outcome = decoder.decode(reader, io::sink())?;
+
+        let Some(trailer) = outcome.trailer else {
+            return Err(YencError::InvalidData("No trailer found".to_string()));
+        };
+        let decoded = outcome.bytes_written;
+        if trailer.size != decoded {
+            return Err(YencError::InvalidData(format!(
+                "Size mismatch: trailer says {}, but data decodes to {}",
+                trailer.size, decoded
+            )));
+        }
+        if outcome.part.is_none() && outcome.header.size != decoded {
+            return Err(YencError::InvalidData(format!(
+                "Size mismatch: header says {}, but data decodes to {}",
+                outcome.header.size, decoded
+            )));
+        }
+
+        Ok(VerifyReport {
+            header: outcome.header,
+            part: outcome.part,
+            trailer,
+            decoded_bytes: decoded,
+            crc32: outcome.crc32.expect("CRC validation is enabled"),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode::{Encoder, MultiPartInfo};
+
+    #[test]
+    fn test_verify() {
+        let data: Vec<u8> = (0..100_000).map(|i| (i * 7 + i / 300) as u8).collect();
+        let encoded = Encoder::new().encode_to_vec(&data, "test.bin").unwrap();
+        let report = Decoder::new().no_crc_check().verify(&encoded[..]).unwrap();
+        assert_eq!(report.decoded_bytes, data.len() as u64);
+        assert_eq!(report.crc32, crc32fast::hash(&data));
+        assert!(report.crc_checked());
+
+        let mut encoded = Vec::new();
+        let part_info = MultiPartInfo::new(2, 2, 6, 10, 10);
+        Encoder::new()
+            .no_crc()
+            .encode_part(&data[..5], &mut encoded, "test.bin", &part_info)
+            .unwrap();
+        let report = Decoder::new().verify(&encoded[..]).unwrap();
+        assert_eq!(report.part.unwrap().begin, 6);
+        assert_eq!(report.crc32, crc32fast::hash(&data[..5]));
+    }
+
+    #[test]
+    fn test_verify_damage() {
+        let cases: [&[u8]; 4] = [
+            b"=ybegin line=128 size=5 name=test.bin\nKLMNP\n=yend size=5 crc32=b05f5b44\n",
+            b"=ybegin line=128 size=5 name=test.bin\nKLMN\n=yend size=5\n",
+            b"=ybegin line=128 size=6 name=test.bin\nKLMNO\n=yend size=5\n",
+            b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n",
+        ];
+        for decoder in [
+            Decoder::new().no_crc_check(),
+            Decoder::new().allow_crc_mismatch(),
+        ] {
+            let result = decoder.verify(cases[0]);
+            assert!(matches!(result, Err(YencError::CrcMismatch { .. })));
+        }
+        for input in &cases[1..] {
+            let result = Decoder::new().verify(*input);
+            assert!(
+                matches!(result, Err(YencError::InvalidData(_))),
+                "{:?}",
+                result
+            );
+        }
+
+        // Repair mode would zero-fill the damage and skip the CRC32 check
+        let input = b"=ybegin line=4 size=7 name=a\nKLMN\nK=KM\n=yend size=7 crc32=00000000\n";
+        assert!(Decoder::new().repair().verify(&input[..]).is_err());
+
+        // Without a CRC32 in the trailer, only the sizes are checked
+        let input = b"=ybegin line=128 size=5 name=test.bin\nKLMNO\n=yend size=5\n";
+        let report = Decoder::new().verify(&input[..]).unwrap();
+        assert!(!report.crc_checked());
+    }
+}
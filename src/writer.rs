@@ -0,0 +1,337 @@
+//! `Write` adapter encoding data as it is written
+
+use std::io::{self, Write};
+
+use crate::encode::{EncodeReport, EncodeState, Encoder, MultiPartInfo};
+use crate::error::{Result, YencError};
+
+/// Writer yEnc-encoding everything written to it into another writer
+///
+/// The `=ybegin` line (and `=ypart` line, for a part) is written when the
+/// writer is created, so the size of the data has to be known up front.
+/// Data can then be written in pieces of any size, for instance with
+/// `io::copy`, and `finish` writes the `=yend` trailer with the CRC32.
+///
+/// Dropping the writer without calling `finish` leaves the article without
+/// a trailer.
+///
+/// # Example
+/// ```
+/// use std::io::Write;
+/// use yenc::YencWriter;
+///
+/// let mut writer = YencWriter::new(Vec::new(), "hello.txt", 13).unwrap();
+/// writer.write_all(b"Hello, ").unwrap();
+/// writer.write_all(b"World!").unwrap();
+/// let (encoded, report) = writer.finish().unwrap();
+///
+/// assert!(encoded.starts_with(b"=ybegin line=128 size=13 name=hello.txt\n"));
+/// assert_eq!(report.input_bytes, 13);
+/// ```
+pub struct YencWriter<W: Write> {
+    inner: W,
+    encoder: Encoder,
+    state: EncodeState,
+    part: Option<MultiPartInfo>,
+    size: u64,
+    written: u64,
+}
+
+impl<W: Write> YencWriter<W> {
+    /// Start a single-part article of `size` bytes with default encoder settings
+    ///
+    /// This is equivalent to `Encoder::new().writer(inner, filename, size)`.
+    pub fn new(inner: W, filename: &str, size: u64) -> Result<Self> {
+        Encoder::new().writer(inner, filename, size)
+    }
+
+    /// Get a reference to the underlying writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Bytes of data still expected before `finish`
+    pub fn remaining(&self) -> u64 {
+        self.size - self.written
+    }
+
+    /// Write the trailer and return the underlying writer with the totals
+    ///
+    /// The underlying writer is flushed.
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidData` if fewer bytes were written than the
+    /// header announced; the output should then be discarded.
+    pub fn finish(mut self) -> Result<(W, EncodeReport)> {
+        if self.written != self.size {
+            let what = if self.part.is_some() { "Part" } else { "Input" };
+            return Err(YencError::InvalidData(format!(
+                "{} size mismatch: announced {} bytes, but got {}",
+                what, self.size, self.written
+            )));
+        }
+
+        let report = match &self.part {
+            Some(part_info) => {
+                self.encoder
+                    .end_part(&mut self.state, &mut self.inner, part_info)?
+            }
+            None => self
+                .encoder
+                .end_single(&mut self.state, &mut self.inner, self.size)?,
+        };
+        self.inner.flush()?;
+        Ok((self.inner, report))
+    }
+}
+
+impl<W: Write> Write for YencWriter<W> {
+    /// Encode `data`
+    ///
+    /// Fails with `io::ErrorKind::InvalidInput`, writing nothing, if `data`
+    /// goes past the size announced in the header.
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if data.len() as u64 > self.remaining() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "writing {} bytes goes past the {} bytes announced in the header",
+                    data.len(),
+                    self.size
+                ),
+            ));
+        }
+        self.state.write_data(&mut self.inner, data)?;
+        self.written += data.len() as u64;
+        Ok(data.len())
+    }
+
+    /// Write out the encoded data queued so far and flush the underlying writer
+    ///
+    /// The last line may be cut short; the rest of it follows with the next
+    /// write.
+    fn flush(&mut self) -> io::Result<()> {
+        self.state.flush(&mut self.inner)?;
+        self.inner.flush()
+    }
+}
+
+/// Single-part article being encoded from data handed over piece by piece
+///
+/// Created by `Encoder::begin`, which writes the header. For producers
+/// that generate data incrementally, such as decompressors or downloads:
+/// `write` each piece as it comes and `finish` to write the trailer. Unlike
+/// `YencWriter`, errors are `YencError`s and the writer is not handed back,
+/// so pass it by `&mut` to keep using it.
+///
+/// # Example
+/// ```
+/// use yenc::Encoder;
+///
+/// let mut encoded = Vec::new();
+/// let mut session = Encoder::new().begin(&mut encoded, "hello.txt", 13).unwrap();
+/// for piece in [&b"Hello, "[..], b"World!"] {
+///     session.write(piece).unwrap();
+/// }
+/// let report = session.finish().unwrap();
+///
+/// assert_eq!(report.output_bytes, encoded.len());
+/// ```
+pub struct EncodeSession<W: Write> {
+    writer: YencWriter<W>,
+}
+
+impl<W: Write> EncodeSession<W> {
+    /// Encode the next piece of data
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidData`, encoding nothing, if `data` goes
+    /// past the size given to `Encoder::begin`.
+    pub fn write(&mut self, data: &[u8]) -> Result<()> {
+        if data.len() as u64 > self.writer.remaining() {
+            return Err(YencError::InvalidData(format!(
+                "Input size mismatch: announced {0} bytes, but got more than {0} bytes",
+                self.writer.size
+            )));
+        }
+        self.writer.write_all(data)?;
+        Ok(())
+    }
+
+    /// Bytes of data still expected before `finish`
+    pub fn remaining(&self) -> u64 {
+        self.writer.remaining()
+    }
+
+    /// Write the trailer and flush the writer
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidData` if less data was written than
+    /// announced; the output should then be discarded.
+    pub fn finish(self) -> Result<EncodeReport> {
+        let (_, report) = self.writer.finish()?;
+        Ok(report)
+    }
+}
+
+impl Encoder {
+    /// Begin encoding a single-part article of `size` bytes; see `EncodeSession`
+    ///
+    /// # Errors
+    /// As for `writer`.
+    pub fn begin<W: Write>(
+        &self,
+        writer: W,
+        filename: &str,
+        size: u64,
+    ) -> Result<EncodeSession<W>> {
+        Ok(EncodeSession {
+            writer: self.writer(writer, filename, size)?,
+        })
+    }
+
+    /// Start a single-part article of `size` bytes, encoded as it is written
+    ///
+    /// Writes the `=ybegin` line to `inner` and returns a `YencWriter`
+    /// taking the data; see there.
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` for text mode, which needs the
+    /// whole input to size the header, and I/O errors writing the header.
+    pub fn writer<W: Write>(&self, inner: W, filename: &str, size: u64) -> Result<YencWriter<W>> {
+        self.validate_sized()?;
+        let mut writer = self.start_writer(inner, size, None)?;
+        self.begin_single(&mut writer.state, filename, size)?;
+        writer.state.flush(&mut writer.inner)?;
+        Ok(writer)
+    }
+
+    /// Start a part of a multi-part file, encoded as it is written
+    ///
+    /// Like `writer`, but writes the `=ybegin` and `=ypart` lines for
+    /// `part_info`; the writer then takes the bytes of that part.
+    ///
+    /// # Errors
+    /// Returns `YencError::InvalidConfig` for text mode or an impossible
+    /// `part_info`, and I/O errors writing the header lines.
+    pub fn part_writer<W: Write>(
+        &self,
+        inner: W,
+        filename: &str,
+        part_info: &MultiPartInfo,
+    ) -> Result<YencWriter<W>> {
+        self.validate_sized()?;
+        part_info.validate()?;
+        let mut writer =
+            self.start_writer(inner, part_info.expected_size(), Some(part_info.clone()))?;
+        self.begin_part(&mut writer.state, filename, part_info)?;
+        writer.state.flush(&mut writer.inner)?;
+        Ok(writer)
+    }
+
+    fn start_writer<W: Write>(
+        &self,
+        inner: W,
+        size: u64,
+        part: Option<MultiPartInfo>,
+    ) -> Result<YencWriter<W>> {
+        Ok(YencWriter {
+            inner,
+            encoder: self.clone(),
+            state: self.state().with_reservation(self.reserve(0)?),
+            part,
+            size,
+            written: 0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_writer_matches_encode() {
+        let data: Vec<u8> = (0..100_000).map(|i| (i * 7) as u8).collect();
+        for encoder in [Encoder::new(), Encoder::new().line_length(61).no_crc()] {
+            let mut expected = Vec::new();
+            let expected_report = encoder
+                .encode(&data[..], &mut expected, "test.bin")
+                .unwrap();
+
+            let mut writer = encoder
+                .writer(Vec::new(), "test.bin", data.len() as u64)
+                .unwrap();
+            for chunk in data.chunks(1000 - 1) {
+                writer.write_all(chunk).unwrap();
+            }
+            let (encoded, report) = writer.finish().unwrap();
+            assert_eq!(encoded, expected);
+            assert_eq!(report, expected_report);
+        }
+    }
+
+    #[test]
+    fn test_part_writer_matches_encode_part() {
+        let data = b"Hello, World! This is the first part.";
+        let part_info =
+            MultiPartInfo::new(1, 2, 1, data.len() as u64, 70).with_full_crc(0x1234_5678);
+
+        let mut expected = Vec::new();
+        let expected_report = Encoder::new()
+            .encode_part(&data[..], &mut expected, "file.bin", &part_info)
+            .unwrap();
+
+        let mut writer = Encoder::new()
+            .part_writer(Vec::new(), "file.bin", &part_info)
+            .unwrap();
+        io::copy(&mut &data[..], &mut writer).unwrap();
+        let (encoded, report) = writer.finish().unwrap();
+        assert_eq!(encoded, expected);
+        assert_eq!(report, expected_report);
+    }
+
+    #[test]
+    fn test_writer_size_mismatch() {
+        // The header goes out straight away
+        let writer = YencWriter::new(Vec::new(), "test.bin", 5).unwrap();
+        assert_eq!(writer.get_ref(), b"=ybegin line=128 size=5 name=test.bin\n");
+
+        let mut writer = writer;
+        writer.write_all(b"abc").unwrap();
+        assert_eq!(writer.remaining(), 2);
+        let err = writer.write(b"def").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(matches!(writer.finish(), Err(YencError::InvalidData(_))));
+
+        let result = Encoder::new().text_mode().writer(Vec::new(), "test.txt", 5);
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+        let part_info = MultiPartInfo::new(1, 2, 1, 5, 10);
+        let result = Encoder::new()
+            .text_mode()
+            .part_writer(Vec::new(), "test.txt", &part_info);
+        assert!(matches!(result, Err(YencError::InvalidConfig(_))));
+    }
+
+    #[test]
+    fn test_encode_session() {
+        let data: Vec<u8> = (0..10_000).map(|i| (i * 3) as u8).collect();
+        let expected = Encoder::new().encode_to_vec(&data, "test.bin").unwrap();
+
+        let mut encoded = Vec::new();
+        let mut session = Encoder::new()
+            .begin(&mut encoded, "test.bin", data.len() as u64)
+            .unwrap();
+        for piece in data.chunks(777) {
+            session.write(piece).unwrap();
+        }
+        assert_eq!(session.remaining(), 0);
+        assert!(matches!(
+            session.write(b"x"),
+            Err(YencError::InvalidData(_))
+        ));
+        let report = session.finish().unwrap();
+        assert_eq!(encoded, expected);
+        assert_eq!(report.input_bytes, data.len() as u64);
+    }
+}
@@ -9,14 +9,14 @@ fn test_decode_simple() {
                   =yend size=5\n";
 
     let mut output = Vec::new();
-    let (header, part, trailer, size) = decode(&input[..], &mut output).unwrap();
+    let outcome = decode(&input[..], &mut output).unwrap();
 
-    assert_eq!(header.name, "test.bin");
-    assert_eq!(header.size, 5);
-    assert_eq!(size, 5);
+    assert_eq!(outcome.header.name, b"test.bin");
+    assert_eq!(outcome.header.size, 5);
+    assert_eq!(outcome.bytes_written, 5);
     assert_eq!(output, vec![23, 24, 25, 26, 27]);
-    assert!(part.is_none()); // Single-part file
-    assert!(trailer.is_some());
+    assert!(outcome.part.is_none()); // Single-part file
+    assert!(outcome.trailer.is_some());
 }
 
 #[test]
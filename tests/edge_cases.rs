@@ -1,7 +1,5 @@
 //! Edge case tests
 
-use yenc;
-
 #[test]
 fn test_critical_characters() {
     // Test characters that need escaping
@@ -25,7 +23,76 @@ fn test_long_filename() {
     yenc::encode(&original[..], &mut encoded, &long_name).unwrap();
 
     let mut decoded = Vec::new();
-    let (header, _, _, _) = yenc::decode(&encoded[..], &mut decoded).unwrap();
+    let outcome = yenc::decode(&encoded[..], &mut decoded).unwrap();
+
+    assert_eq!(outcome.header.name, long_name.as_bytes());
+}
+
+#[test]
+fn test_trailer_without_final_newline() {
+    let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n\n=yend size=5 crc32=515ad3cc";
+
+    let mut decoded = Vec::new();
+    let outcome = yenc::decode(&input[..], &mut decoded).unwrap();
+
+    assert_eq!(decoded, vec![0, 1, 2, 3, 4]);
+    assert_eq!(outcome.bytes_written, 5);
+    assert_eq!(outcome.trailer.unwrap().crc32, Some(0x515ad3cc));
+}
+
+#[test]
+fn test_trailer_joined_to_data_line() {
+    // The newline between the last data line and =yend was lost
+    let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n=yend size=5 crc32=515ad3cc\n";
+
+    let mut decoded = Vec::new();
+    let outcome = yenc::decode(&input[..], &mut decoded).unwrap();
+
+    assert_eq!(decoded, vec![0, 1, 2, 3, 4]);
+    assert_eq!(outcome.bytes_written, 5);
+    assert_eq!(outcome.trailer.unwrap().size, 5);
+}
+
+#[test]
+fn test_trailer_joined_without_any_newline() {
+    let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n=yend size=5";
+
+    let mut decoded = Vec::new();
+    let outcome = yenc::decode(&input[..], &mut decoded).unwrap();
+
+    assert_eq!(decoded, vec![0, 1, 2, 3, 4]);
+    assert!(outcome.trailer.is_some());
+}
+
+#[test]
+fn test_trailer_joined_to_data_line_strict() {
+    let input = b"=ybegin line=128 size=5 name=test.bin\n*+,-=n=yend size=5\n";
+
+    let mut decoded = Vec::new();
+    let result = yenc::Decoder::new()
+        .strict()
+        .decode(&input[..], &mut decoded);
+
+    match result.unwrap_err() {
+        yenc::YencError::InvalidData(msg) => {
+            assert!(msg.contains("Trailer does not start on its own line"));
+        }
+        other => panic!("Expected InvalidData, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_zero_byte_file_roundtrip() {
+    let mut encoded = Vec::new();
+    yenc::encode(&[][..], &mut encoded, "empty.bin").unwrap();
+
+    let mut decoded = Vec::new();
+    let outcome = yenc::decode(&encoded[..], &mut decoded).unwrap();
 
-    assert_eq!(header.name, long_name);
+    assert_eq!(outcome.header.size, 0);
+    assert!(outcome.part.is_none());
+    assert_eq!(outcome.trailer.unwrap().crc32, Some(0));
+    assert_eq!(outcome.crc32, Some(0));
+    assert_eq!(outcome.bytes_written, 0);
+    assert!(decoded.is_empty());
 }
@@ -4,12 +4,13 @@ use yenc::encode;
 
 #[test]
 fn test_encode_simple() {
-    let input = vec![0u8, 1, 2, 3, 4];
+    let input = [0u8, 1, 2, 3, 4];
     let mut output = Vec::new();
 
-    let size = encode(&input[..], &mut output, "test.bin").unwrap();
+    let report = encode(&input[..], &mut output, "test.bin").unwrap();
 
-    assert_eq!(size, 5);
+    assert_eq!(report.input_bytes, 5);
+    assert_eq!(report.output_bytes, output.len());
 
     let output_str = String::from_utf8(output).unwrap();
     assert!(output_str.contains("=ybegin"));
@@ -37,7 +38,7 @@ fn test_encode_empty() {
     let input: Vec<u8> = Vec::new();
     let mut output = Vec::new();
 
-    let size = encode(&input[..], &mut output, "empty.bin").unwrap();
+    let report = encode(&input[..], &mut output, "empty.bin").unwrap();
 
-    assert_eq!(size, 0);
+    assert_eq!(report.input_bytes, 0);
 }
@@ -1,7 +1,5 @@
 //! Multi-part encoding/decoding tests
 
-use yenc;
-
 #[test]
 fn test_multipart_decode_single_part() {
     // Decode a single part from a multi-part file
@@ -11,10 +9,16 @@ fn test_multipart_decode_single_part() {
                   =yend size=5 part=1 pcrc32=515ad3cc\n";
     let mut output = Vec::new();
 
-    let (header, part, trailer, size) = yenc::decode(&input[..], &mut output).unwrap();
+    let yenc::DecodeOutcome {
+        header,
+        part,
+        trailer,
+        bytes_written: size,
+        ..
+    } = yenc::decode(&input[..], &mut output).unwrap();
 
     // Verify header
-    assert_eq!(header.name, "test.bin");
+    assert_eq!(header.name, b"test.bin");
     assert_eq!(header.size, 15); // Total file size
     assert_eq!(header.part, Some(1));
     assert_eq!(header.total, Some(3));
@@ -65,7 +69,12 @@ fn test_multipart_large_byte_offsets() {
                   =yend size=51200 part=100 pcrc32=ffffffff\n";
 
     let mut output = Vec::new();
-    let (header, part, trailer, _) = yenc::Decoder::new()
+    let yenc::DecodeOutcome {
+        header,
+        part,
+        trailer,
+        ..
+    } = yenc::Decoder::new()
         .no_crc_check()
         .decode(&input[..], &mut output)
         .unwrap();
@@ -89,8 +98,8 @@ fn test_multipart_with_real_crc_validation() {
     // This uses real encoded data with correct CRC values
 
     // First, encode the parts to get correct CRC values
-    let data_part1 = vec![0u8, 1, 2, 3, 4];
-    let data_part2 = vec![5u8, 6, 7, 8, 9];
+    let data_part1 = [0u8, 1, 2, 3, 4];
+    let data_part2 = [5u8, 6, 7, 8, 9];
 
     let mut encoded_part1 = Vec::new();
     let mut encoded_part2 = Vec::new();
@@ -126,26 +135,26 @@ fn test_multipart_with_real_crc_validation() {
     let mut decoded_part2 = Vec::new();
 
     // Decode with CRC validation enabled (default)
-    let (header1, part_info1, trailer1, _) = yenc::decode(part1.as_bytes(), &mut decoded_part1).unwrap();
-    let (header2, part_info2, trailer2, _) = yenc::decode(part2.as_bytes(), &mut decoded_part2).unwrap();
+    let outcome1 = yenc::decode(part1.as_bytes(), &mut decoded_part1).unwrap();
+    let outcome2 = yenc::decode(part2.as_bytes(), &mut decoded_part2).unwrap();
 
     // Verify both parts
-    assert_eq!(header1.name, "real.bin");
-    assert_eq!(header1.size, 10);
-    assert_eq!(header1.part, Some(1));
-    assert_eq!(header2.part, Some(2));
+    assert_eq!(outcome1.header.name, b"real.bin");
+    assert_eq!(outcome1.header.size, 10);
+    assert_eq!(outcome1.header.part, Some(1));
+    assert_eq!(outcome2.header.part, Some(2));
 
     // Verify part info
-    let p1 = part_info1.unwrap();
-    let p2 = part_info2.unwrap();
+    let p1 = outcome1.part.unwrap();
+    let p2 = outcome2.part.unwrap();
     assert_eq!(p1.begin, 1);
     assert_eq!(p1.end, 5);
     assert_eq!(p2.begin, 6);
     assert_eq!(p2.end, 10);
 
     // Verify CRC values are present
-    let t1 = trailer1.unwrap();
-    let t2 = trailer2.unwrap();
+    let t1 = outcome1.trailer.unwrap();
+    let t2 = outcome2.trailer.unwrap();
     assert!(t1.pcrc32.is_some());
     assert!(t2.pcrc32.is_some());
 
@@ -159,3 +168,287 @@ fn test_multipart_with_real_crc_validation() {
     full_file.extend_from_slice(&decoded_part2);
     assert_eq!(full_file, vec![0, 1, 2, 3, 4, 5, 6, 7, 8, 9]);
 }
+
+#[test]
+fn test_multipart_zero_byte_part_roundtrip() {
+    // Part 2 of 3 is empty: it covers bytes 6..=5 of a 10-byte file
+    let part_info = yenc::MultiPartInfo::new(2, 3, 6, 5, 10);
+    let mut encoded = Vec::new();
+
+    let report = yenc::encode_part(&[][..], &mut encoded, "padded.bin", &part_info).unwrap();
+    assert_eq!(report.input_bytes, 0);
+    assert_eq!(report.lines, 0);
+    assert_eq!(report.pcrc32, Some(0));
+
+    let encoded_str = String::from_utf8(encoded.clone()).unwrap();
+    assert!(encoded_str.contains("=ypart begin=6 end=5"));
+    assert!(encoded_str.contains("=yend size=0 part=2 pcrc32=00000000"));
+
+    let mut decoded = Vec::new();
+    let outcome = yenc::decode(&encoded[..], &mut decoded).unwrap();
+
+    assert_eq!(outcome.header.size, 10);
+    assert_eq!(outcome.part.unwrap().size(), 0);
+    assert_eq!(outcome.trailer.unwrap().size, 0);
+    assert_eq!(outcome.bytes_written, 0);
+    assert!(decoded.is_empty());
+}
+
+#[test]
+fn test_multipart_zero_byte_part_size_mismatch() {
+    // An empty range still rejects data
+    let part_info = yenc::MultiPartInfo::new(1, 1, 1, 0, 0);
+    let mut encoded = Vec::new();
+
+    let result = yenc::encode_part(&[0u8][..], &mut encoded, "empty.bin", &part_info);
+    assert!(matches!(result, Err(yenc::YencError::InvalidData(_))));
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_multipart_encode_parts_parallel() {
+    use std::io::Write;
+    use std::sync::Mutex;
+
+    struct Sink<'a>(&'a Mutex<Vec<u8>>);
+
+    impl Write for Sink<'_> {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7 + 13) as u8).collect();
+    let parts: Vec<_> = (0..7)
+        .map(|i| {
+            let end = ((i + 1) * 15_000).min(data.len());
+            yenc::MultiPartInfo::new(
+                i + 1,
+                7,
+                (i * 15_000 + 1) as u64,
+                end as u64,
+                data.len() as u64,
+            )
+        })
+        .collect();
+
+    let outputs: Vec<Mutex<Vec<u8>>> = parts.iter().map(|_| Mutex::new(Vec::new())).collect();
+    let encoder = yenc::Encoder::new();
+    let reports = encoder
+        .encode_parts_parallel(&data, "test.bin", &parts, |part| {
+            Ok(Sink(&outputs[part.part - 1]))
+        })
+        .unwrap();
+    assert_eq!(reports.len(), parts.len());
+
+    // Same output as encoding the parts one by one
+    for ((part, output), report) in parts.iter().zip(&outputs).zip(&reports) {
+        let part_data = &data[part.begin as usize - 1..part.end as usize];
+        let mut expected = Vec::new();
+        let expected_report = encoder
+            .encode_part(part_data, &mut expected, "test.bin", part)
+            .unwrap();
+        assert_eq!(*output.lock().unwrap(), expected);
+        assert_eq!(*report, expected_report);
+    }
+
+    // A part past the end of the input is rejected up front
+    let too_long = data.len() as u64 + 1;
+    let parts = [yenc::MultiPartInfo::new(1, 1, 1, too_long, too_long)];
+    let result = encoder.encode_parts_parallel(&data, "test.bin", &parts, |_| Ok(Vec::new()));
+    assert!(matches!(result, Err(yenc::YencError::InvalidConfig(_))));
+    let parts = [yenc::MultiPartInfo::new(1, 1, 1, u64::MAX, u64::MAX)];
+    let result = encoder.encode_parts_parallel(&data, "test.bin", &parts, |_| Ok(Vec::new()));
+    assert!(matches!(result, Err(yenc::YencError::InvalidConfig(_))));
+}
+
+#[test]
+fn test_multipart_encode_file() {
+    let dir = std::env::temp_dir().join(format!("yenc-encode-parts-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("test.bin");
+    let data: Vec<u8> = (0..25_000u32).map(|i| (i * 13 + i / 100) as u8).collect();
+    std::fs::write(&input, &data).unwrap();
+
+    let parts = yenc::encode_file_multipart(&input, &dir, 10_000).unwrap();
+    let paths: Vec<_> = parts.iter().map(|(path, _)| path.clone()).collect();
+    assert_eq!(paths[2], dir.join("test.bin.part003.yenc"));
+    assert_eq!(parts.len(), 3);
+    assert_eq!(parts[2].1.input_bytes, 5_000);
+    assert_eq!(parts[2].1.crc32, Some(crc32fast::hash(&data)));
+    assert_eq!(parts[1].1.crc32, None);
+
+    let last = std::fs::read(&paths[2]).unwrap();
+    assert!(last.starts_with(
+        b"=ybegin part=3 total=3 line=128 size=25000 name=test.bin\n=ypart begin=20001 end=25000\n"
+    ));
+
+    let output = dir.join("decoded.bin");
+    yenc::decode_part_paths(&paths, &output).unwrap();
+    assert_eq!(std::fs::read(&output).unwrap(), data);
+
+    assert!(matches!(
+        yenc::encode_file_multipart(&input, &dir, 0),
+        Err(yenc::YencError::InvalidConfig(_))
+    ));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_multipart_extract_range() {
+    let dir = std::env::temp_dir().join(format!("yenc-extract-range-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let input = dir.join("test.bin");
+    let data: Vec<u8> = (0..25_000u32).map(|i| (i * 17 + i / 64) as u8).collect();
+    std::fs::write(&input, &data).unwrap();
+    let parts = yenc::encode_file_multipart(&input, &dir, 10_000).unwrap();
+    let mut paths: Vec<_> = parts.into_iter().map(|(path, _)| path).collect();
+    paths.reverse();
+
+    for range in [
+        0..100,
+        9_990..10_010,
+        5_000..25_000,
+        24_999..25_000,
+        300..300,
+    ] {
+        let extracted = yenc::extract_range(&paths, range.clone()).unwrap();
+        assert_eq!(extracted, &data[range.start as usize..range.end as usize]);
+    }
+
+    // Only the parts covering the range have to be there
+    let extracted = yenc::extract_range(&paths[2..], 0..10_000).unwrap();
+    assert_eq!(extracted, &data[..10_000]);
+    assert!(matches!(
+        yenc::extract_range(&paths[..2], 9_000..11_000),
+        Err(yenc::YencError::InvalidData(_))
+    ));
+    assert!(matches!(
+        yenc::extract_range(&paths, 24_000..25_001),
+        Err(yenc::YencError::InvalidConfig(_))
+    ));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_multipart_decode_parts() {
+    let dir = std::env::temp_dir().join(format!("yenc-parts-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("test.bin");
+
+    let data: Vec<u8> = (0..50_000u32).map(|i| (i * 11 + 5) as u8).collect();
+    let mut articles = Vec::new();
+    for i in 0..4 {
+        let end = ((i + 1) * 15_000).min(data.len());
+        let part = yenc::MultiPartInfo::new(
+            i + 1,
+            4,
+            (i * 15_000 + 1) as u64,
+            end as u64,
+            data.len() as u64,
+        );
+        let mut encoded = Vec::new();
+        yenc::encode_part(&data[i * 15_000..end], &mut encoded, "test.bin", &part).unwrap();
+        articles.push(encoded);
+    }
+    articles.swap(0, 2);
+
+    let summary = yenc::decode_parts(articles.iter().map(|a| &a[..]), &output).unwrap();
+    assert_eq!(summary.header.name, b"test.bin");
+    assert_eq!(summary.parts.len(), 4);
+    assert_eq!(summary.parts[0].part.as_ref().unwrap().begin, 30_001);
+    assert_eq!(summary.bytes_written, data.len() as u64);
+    assert_eq!(std::fs::read(&output).unwrap(), data);
+
+    let mut paths = Vec::new();
+    for (i, article) in articles.iter().enumerate() {
+        let path = dir.join(format!("test.bin.{:03}", i + 1));
+        std::fs::write(&path, article).unwrap();
+        paths.push(path);
+    }
+    let summary = yenc::decode_part_paths(&paths, &output).unwrap();
+    assert_eq!(summary.bytes_written, data.len() as u64);
+    assert_eq!(std::fs::read(&output).unwrap(), data);
+
+    // A missing part leaves a gap
+    let result = yenc::decode_part_paths(&paths[1..], &output);
+    assert!(matches!(result, Err(yenc::YencError::InvalidData(_))));
+
+    // A corrupted part fails its pcrc32 check
+    let mut corrupted = articles[1].clone();
+    let pos = corrupted.len() / 2;
+    corrupted[pos] = corrupted[pos].wrapping_add(1);
+    let result = yenc::decode_parts([&articles[0][..], &corrupted[..]], &output);
+    assert!(matches!(result, Err(yenc::YencError::CrcMismatch { .. })));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_multipart_decode_part_files() {
+    let dir = std::env::temp_dir().join(format!("yenc-multipart-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.join("test.bin");
+
+    let data: Vec<u8> = (0..100_000u32).map(|i| (i * 7 + 13) as u8).collect();
+    let mut paths = Vec::new();
+    for i in 0..7 {
+        let end = ((i + 1) * 15_000).min(data.len());
+        let part = yenc::MultiPartInfo::new(
+            i + 1,
+            7,
+            (i * 15_000 + 1) as u64,
+            end as u64,
+            data.len() as u64,
+        );
+        let mut encoded = Vec::new();
+        yenc::encode_part(&data[i * 15_000..end], &mut encoded, "test.bin", &part).unwrap();
+
+        let path = dir.join(format!("test.bin.{:03}", i + 1));
+        std::fs::write(&path, encoded).unwrap();
+        paths.push(path);
+    }
+
+    // Order of the part files does not matter
+    paths.reverse();
+    let summary = yenc::decode_part_files(&paths, &output).unwrap();
+    assert_eq!(summary.header.name, b"test.bin");
+    assert_eq!(summary.bytes_written, data.len() as u64);
+    assert_eq!(summary.parts.len(), paths.len());
+    assert_eq!(std::fs::read(&output).unwrap(), data);
+
+    // A missing part leaves a gap
+    let result = yenc::decode_part_files(&paths[1..], &output);
+    assert!(matches!(result, Err(yenc::YencError::InvalidData(_))));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_multipart_decode_parts_parallel_bad_range() {
+    let path = std::env::temp_dir().join(format!("yenc-parallel-range-{}", std::process::id()));
+    let output = std::fs::File::create(&path).unwrap();
+
+    let input = b"=ybegin part=1 line=128 size=10 name=test.bin\n\
+                  =ypart begin=1 end=18446744073709551615\n\
+                  KLMNO\n\
+                  =yend size=5 part=1\n";
+    let results = yenc::Decoder::new().decode_parts_parallel(vec![&input[..]], &output);
+    assert!(matches!(results[0], Err(yenc::YencError::InvalidData(_))));
+
+    // Text mode would shift the decoded bytes out of their range
+    let results = yenc::Decoder::new()
+        .text_mode()
+        .decode_parts_parallel(vec![&input[..]], &output);
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(results[0], Err(yenc::YencError::InvalidConfig(_))));
+}
@@ -1,7 +1,5 @@
 //! Roundtrip tests (encode then decode)
 
-use yenc;
-
 #[test]
 fn test_roundtrip_text() {
     let original = b"The quick brown fox jumps over the lazy dog";
@@ -112,37 +110,37 @@ fn test_roundtrip_multipart_encode_decode() {
     let mut encoded_part2 = Vec::new();
 
     yenc::Encoder::new()
-        .encode_part(&part1_data[..], &mut encoded_part1, "data.bin", &part1_info)
+        .encode_part(part1_data, &mut encoded_part1, "data.bin", &part1_info)
         .unwrap();
     yenc::Encoder::new()
-        .encode_part(&part2_data[..], &mut encoded_part2, "data.bin", &part2_info)
+        .encode_part(part2_data, &mut encoded_part2, "data.bin", &part2_info)
         .unwrap();
 
     // Decode both parts
     let mut decoded_part1 = Vec::new();
     let mut decoded_part2 = Vec::new();
 
-    let (header1, p1_info, trailer1, _) = yenc::decode(&encoded_part1[..], &mut decoded_part1).unwrap();
-    let (header2, p2_info, trailer2, _) = yenc::decode(&encoded_part2[..], &mut decoded_part2).unwrap();
+    let outcome1 = yenc::decode(&encoded_part1[..], &mut decoded_part1).unwrap();
+    let outcome2 = yenc::decode(&encoded_part2[..], &mut decoded_part2).unwrap();
 
     // Verify headers
-    assert_eq!(header1.name, "data.bin");
-    assert_eq!(header1.size, 10); // Full file size
-    assert_eq!(header1.part, Some(1));
-    assert_eq!(header1.total, Some(2));
-    assert_eq!(header2.part, Some(2));
+    assert_eq!(outcome1.header.name, b"data.bin");
+    assert_eq!(outcome1.header.size, 10); // Full file size
+    assert_eq!(outcome1.header.part, Some(1));
+    assert_eq!(outcome1.header.total, Some(2));
+    assert_eq!(outcome2.header.part, Some(2));
 
     // Verify part information
-    let p1 = p1_info.unwrap();
-    let p2 = p2_info.unwrap();
+    let p1 = outcome1.part.unwrap();
+    let p2 = outcome2.part.unwrap();
     assert_eq!(p1.begin, 1);
     assert_eq!(p1.end, 5);
     assert_eq!(p2.begin, 6);
     assert_eq!(p2.end, 10);
 
     // Verify trailers
-    let t1 = trailer1.unwrap();
-    let t2 = trailer2.unwrap();
+    let t1 = outcome1.trailer.unwrap();
+    let t2 = outcome2.trailer.unwrap();
     assert_eq!(t1.size, 5); // Part size
     assert_eq!(t2.size, 5);
     assert_eq!(t1.part, Some(1));
@@ -186,18 +184,18 @@ fn test_roundtrip_multipart_with_full_crc() {
     let mut encoded_part1 = Vec::new();
     let mut encoded_part2 = Vec::new();
 
-    yenc::encode_part(&part1_data[..], &mut encoded_part1, "data.bin", &part1_info).unwrap();
-    yenc::encode_part(&part2_data[..], &mut encoded_part2, "data.bin", &part2_info).unwrap();
+    yenc::encode_part(part1_data, &mut encoded_part1, "data.bin", &part1_info).unwrap();
+    yenc::encode_part(part2_data, &mut encoded_part2, "data.bin", &part2_info).unwrap();
 
     // Decode and verify
     let mut decoded_part1 = Vec::new();
     let mut decoded_part2 = Vec::new();
 
     yenc::decode(&encoded_part1[..], &mut decoded_part1).unwrap();
-    let (_, _, trailer2, _) = yenc::decode(&encoded_part2[..], &mut decoded_part2).unwrap();
+    let outcome2 = yenc::decode(&encoded_part2[..], &mut decoded_part2).unwrap();
 
     // Verify last part has both pcrc32 and crc32
-    let t2 = trailer2.unwrap();
+    let t2 = outcome2.trailer.unwrap();
     assert!(t2.pcrc32.is_some()); // Part CRC
     assert_eq!(t2.crc32, Some(full_crc)); // Full file CRC
 
@@ -207,3 +205,93 @@ fn test_roundtrip_multipart_with_full_crc() {
     reassembled.extend_from_slice(&decoded_part2);
     assert_eq!(reassembled, full_data);
 }
+
+#[test]
+fn test_roundtrip_shared_codecs_across_threads() {
+    let encoder = yenc::Encoder::new().line_length(64);
+    let decoder = yenc::Decoder::new();
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8u8)
+            .map(|seed| {
+                let (encoder, decoder) = (&encoder, &decoder);
+                scope.spawn(move || {
+                    let data: Vec<u8> = (0..4096u32)
+                        .map(|i| (i as u8).wrapping_mul(31).wrapping_add(seed))
+                        .collect();
+
+                    let mut encoded = Vec::new();
+                    encoder
+                        .encode(&data[..], &mut encoded, "shared.bin")
+                        .unwrap();
+
+                    let mut decoded = Vec::new();
+                    decoder.decode(&encoded[..], &mut decoded).unwrap();
+                    assert_eq!(decoded, data);
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    });
+}
+
+#[test]
+fn test_roundtrip_every_backend_agrees() {
+    let data: Vec<u8> = (0..10_000u32)
+        .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+        .collect();
+
+    let mut reference = Vec::new();
+    yenc::Encoder::new()
+        .backend(yenc::Backend::Scalar)
+        .encode(&data[..], &mut reference, "backends.bin")
+        .unwrap();
+
+    for &backend in yenc::Backend::available() {
+        let mut encoded = Vec::new();
+        yenc::Encoder::new()
+            .backend(backend)
+            .encode(&data[..], &mut encoded, "backends.bin")
+            .unwrap();
+        assert_eq!(encoded, reference, "{:?}", backend);
+
+        let mut decoded = Vec::new();
+        yenc::Decoder::new()
+            .backend(backend)
+            .decode(&encoded[..], &mut decoded)
+            .unwrap();
+        assert_eq!(decoded, data, "{:?}", backend);
+    }
+}
+
+#[test]
+fn test_roundtrip_files() {
+    let dir = std::env::temp_dir().join(format!("yenc-roundtrip-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let (input, encoded, decoded) = (
+        dir.join("input.bin"),
+        dir.join("input.bin.yenc"),
+        dir.join("output.bin"),
+    );
+
+    // Empty files have nothing to map, so they get a case of their own
+    for len in [0, 300_000, 2_000_000] {
+        let data: Vec<u8> = (0..len).map(|i| (i * 7 + 13) as u8).collect();
+        std::fs::write(&input, &data).unwrap();
+
+        let report = yenc::encode_file(&input, &encoded, None).unwrap();
+        assert_eq!(report.input_bytes, len);
+        assert_eq!(report.output_bytes as u64, std::fs::metadata(&encoded).unwrap().len());
+
+        let outcome = yenc::decode_file(&encoded, &decoded).unwrap();
+        assert_eq!(outcome.header.name, b"input.bin");
+        assert!(outcome.trailer.is_some());
+        assert_eq!(outcome.bytes_written, len);
+        assert_eq!(std::fs::read(&decoded).unwrap(), data);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}